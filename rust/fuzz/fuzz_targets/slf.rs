@@ -0,0 +1,7 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate stracciatella;
+
+fuzz_target!(|data: &[u8]| {
+    stracciatella::fuzzing::fuzz_slf(data);
+});
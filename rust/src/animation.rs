@@ -0,0 +1,134 @@
+//! Structure data of the body types, see `gAnimStructureDatabase` in Animation_Data.cc.
+//!
+//! Every body type has a structure file for each structure id. The engine skips files that do not exist, so mods can
+//! leave out body types they do not use, but files that exist have to be valid.
+
+use std::ffi::CString;
+use std::path::Path;
+
+use libc::c_char;
+
+use formats::jsd::StructureFile;
+use vfs::Vfs;
+
+pub const STRUCTURE_IDS: [&str; 6] = ["S_STRUCT", "C_STRUCT", "P_STRUCT", "F_STRUCT", "FB_STRUCT", "DEFAULT_STRUCT"];
+
+const HUMAN: [&str; 6] = ["m_stand", "m_crouch", "m_prone", "m_fall", "m_fallback", "m_crouch"];
+const CREATURE: [&str; 6] = ["mn_breat", "mn_breat", "mn_breat", "mn_breat", "mn_breat", "m_crouch"];
+const KID: [&str; 6] = ["k_stand", "k_crouch", "k_crouch", "m_prone", "m_prone", "m_prone"];
+const VEHICLE: [&str; 6] = ["hmmv", "hmmv", "hmmv", "hmmv", "hmmv", "m_crouch"];
+
+/// Names of the structure files by body type and structure id, in the order of the body types of the engine.
+pub static BODY_TYPE_STRUCTURES: &[(&str, [&str; 6])] = &[
+    ("REGMALE", HUMAN),
+    ("BIGMALE", HUMAN),
+    ("STOCKYMALE", HUMAN),
+    ("REGFEMALE", HUMAN),
+    ("ADULTFEMALEMONSTER", CREATURE),
+    ("AM_MONSTER", CREATURE),
+    ("YAF_MONSTER", CREATURE),
+    ("YAM_MONSTER", CREATURE),
+    ("LARVAE_MONSTER", ["l_breath", "l_breath", "l_breath", "l_breath", "l_breath", "m_crouch"]),
+    ("INFANT_MONSTER", ["i_breath", "i_breath", "i_breath", "i_breath", "i_breath", "i_breath"]),
+    ("QUEENMONSTER", ["q_ready", "q_ready", "q_ready", "q_ready", "q_ready", "m_crouch"]),
+    ("FATCIV", HUMAN),
+    ("MANCIV", HUMAN),
+    ("MINICIV", HUMAN),
+    ("DRESSCIV", HUMAN),
+    ("HATKIDCIV", KID),
+    ("KIDCIV", KID),
+    ("CRIPPLECIV", ["m_crouch", "m_crouch", "m_crouch", "m_fall", "m_fallback", "m_crouch"]),
+    ("COW", ["cw_breath", "cw_breath", "cw_breath", "cw_breath", "cw_breath", "m_crouch"]),
+    ("CROW", ["cr_stand", "cr_crouch", "cr_prone", "cr_prone", "cr_prone", "m_crouch"]),
+    ("BLOODCAT", ["ct_breath", "ct_breath", "ct_breath", "ct_breath", "ct_breath", "m_crouch"]),
+    ("ROBOTNOWEAPON", ["j_r_bret", "j_r_bret", "j_r_bret", "j_r_bret", "j_r_bret", "j_r_bret"]),
+    ("HUMVEE", VEHICLE),
+    ("TANK_NW", ["tnk_sht", "tnk_sht", "tnk_sht", "tnk_sht", "tnk_sht", "m_crouch"]),
+    ("TANK_NE", ["tnk2_rot", "tnk2_rot", "tnk2_rot", "tnk2_rot", "tnk2_rot", "m_crouch"]),
+    ("ELDORADO", VEHICLE),
+    ("ICECREAMTRUCK", VEHICLE),
+    ("JEEP", VEHICLE),
+];
+
+pub fn structure_file_path(name: &str) -> String {
+    format!("anims/structdata/{}.jsd", name)
+}
+
+/// Reads and checks a structure file, None if it does not exist.
+pub fn load_structure_file(vfs: &Vfs, name: &str) -> Option<Result<StructureFile, String>> {
+    let path = structure_file_path(name);
+    vfs.find(Path::new(&path))?;
+    Some(vfs.read(Path::new(&path)).map_err(String::from)
+        .and_then(|b| StructureFile::parse(&b))
+        .and_then(|f| f.validate().map(|_| f))
+        .map_err(|e| format!("{}: {}", path, e)))
+}
+
+/// Returns the errors of all structure files of the body types, every file is only checked once.
+pub fn validate_body_type_structures(vfs: &Vfs) -> Vec<String> {
+    let mut names: Vec<&str> = BODY_TYPE_STRUCTURES.iter().flat_map(|(_, files)| files.iter().cloned()).collect();
+    names.sort();
+    names.dedup();
+    names.into_iter().filter_map(|n| load_structure_file(vfs, n)).filter_map(|r| r.err()).collect()
+}
+
+/// Returns the structure files of all body types as JSON object by body type and structure id.
+pub fn body_type_structures_json(vfs: &Vfs) -> serde_json::Value {
+    let mut body_types = serde_json::Map::new();
+    for (body_type, files) in BODY_TYPE_STRUCTURES {
+        let mut structures = serde_json::Map::new();
+        for (id, name) in STRUCTURE_IDS.iter().zip(files.iter()) {
+            let value = match load_structure_file(vfs, name) {
+                None => json!({ "file": structure_file_path(name), "exists": false }),
+                Some(Ok(f)) => json!({
+                    "file": structure_file_path(name),
+                    "exists": true,
+                    "number_of_structures": f.number_of_structures,
+                    "structures": f.structures,
+                }),
+                Some(Err(e)) => json!({ "file": structure_file_path(name), "exists": true, "error": e }),
+            };
+            structures.insert(String::from(*id), value);
+        }
+        body_types.insert(String::from(*body_type), serde_json::Value::Object(structures));
+    }
+    serde_json::Value::Object(body_types)
+}
+
+/// Returns the structure data of all body types as JSON, see `body_type_structures_json`.
+#[no_mangle]
+pub extern fn get_body_type_structures_json(ptr: *const Vfs) -> *mut c_char {
+    let json = body_type_structures_json(unsafe_from_ptr!(ptr)).to_string();
+    CString::new(json).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use formats::jsd::tests::structure_file;
+    use vfs::{MountSource, Vfs};
+
+    #[test]
+    fn body_type_structures_json_should_report_every_structure_file() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = temp_dir.path().join("anims/structdata");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("m_stand.jsd"), structure_file(2, &[], 0, &[(0, 1), (1, 2)])).unwrap();
+        fs::write(dir.join("hmmv.jsd"), structure_file(1, &[], 0, &[(3, 1)])).unwrap();
+        let mut vfs = Vfs::default();
+        vfs.mount(MountSource::VANILLA, PathBuf::from(temp_dir.path()), false);
+
+        let json = super::body_type_structures_json(&vfs);
+
+        assert_eq!(json.as_object().unwrap().len(), 28);
+        assert_eq!(json["STOCKYMALE"]["S_STRUCT"]["number_of_structures"], 2);
+        assert_eq!(json["STOCKYMALE"]["S_STRUCT"]["structures"][1]["tiles"].as_array().unwrap().len(), 2);
+        assert_eq!(json["STOCKYMALE"]["C_STRUCT"]["exists"], false);
+        assert_eq!(json["JEEP"]["F_STRUCT"]["error"], "anims/structdata/hmmv.jsd: Structure 3 is out of range, the file has 1 structures");
+        assert_eq!(super::validate_body_type_structures(&vfs), vec!("anims/structdata/hmmv.jsd: Structure 3 is out of range, the file has 1 structures"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Command line tool for working with the game resources.
+
+extern crate serde_json;
+extern crate stracciatella;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use stracciatella::datadiff::diff_dirs;
+use stracciatella::home::{cache_dir, find_stracciatella_home};
+use stracciatella::legacyimport::import_legacy_data;
+use stracciatella::modpackage::package_mod;
+use stracciatella::modtemplate::create_mod;
+use stracciatella::savegame::{SavePatch, SavedGame, patch_saved_game_file};
+
+const USAGE: &str = "Usage: ja2-resources <command> [<args>]
+
+Commands:
+    diff <dirA> <dirB>                  Lists the files and library entries that were added, removed or changed in dirB
+    package-mod [--dry-run] <dir> [<output dir>]
+                                        Validates a mod and packages it as zip for distribution
+    new-mod <id> [<parent dir>]         Creates the skeleton of a new mod
+    import-legacy <dir> <output dir> [<externalized dir>]
+                                        Converts the XML or INI tables of an old mod to JSON game data
+    dump-save <file>                    Prints the parsed sections of a saved game as JSON
+    patch-save <file> <patch file>      Applies whitelisted fixes to a saved game, the original is kept as <file>.bak";
+
+/// Exit code for usage and I/O errors, 1 is used by commands for a negative result like `diff` does.
+const ERROR_EXIT_CODE: i32 = 2;
+
+/// Returns the cache dir of the stracciatella home for the scratch dirs of the commands.
+fn scratch_parent_dir() -> Result<PathBuf, String> {
+    find_stracciatella_home().map(|home| cache_dir(&home))
+}
+
+fn diff(args: &[String]) -> Result<i32, String> {
+    if args.len() != 2 {
+        return Err(String::from(USAGE));
+    }
+    let diff = diff_dirs(Path::new(&args[0]), Path::new(&args[1]))?;
+    for entry in &diff {
+        println!("{}", entry);
+    }
+    Ok(if diff.is_empty() { 0 } else { 1 })
+}
+
+fn package(args: &[String]) -> Result<i32, String> {
+    let dry_run = args.first().map(|a| a == "--dry-run").unwrap_or(false);
+    let args = if dry_run { &args[1..] } else { args };
+    if args.is_empty() || args.len() > 2 {
+        return Err(String::from(USAGE));
+    }
+    let output_dir = args.get(1).map(|s| s.as_str()).unwrap_or(".");
+    let plan = package_mod(Path::new(&args[0]), Path::new(output_dir), &scratch_parent_dir()?, dry_run)?;
+    for file in &plan.files {
+        match (dry_run, file.overwrites) {
+            (true, true) => println!("Would overwrite {} with {} bytes", file.path.display(), file.size),
+            (true, false) => println!("Would create {} with {} bytes", file.path.display(), file.size),
+            (false, _) => println!("Packaged {}", file.path.display()),
+        }
+    }
+    Ok(0)
+}
+
+fn new_mod(args: &[String]) -> Result<i32, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(String::from(USAGE));
+    }
+    let parent_dir = args.get(1).map(|s| s.as_str()).unwrap_or(".");
+    let path = create_mod(Path::new(parent_dir), &args[0])?;
+    println!("Created {}", path.display());
+    Ok(0)
+}
+
+fn import_legacy(args: &[String]) -> Result<i32, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(String::from(USAGE));
+    }
+    let warnings = import_legacy_data(Path::new(&args[0]), Path::new(&args[1]), args.get(2).map(Path::new))?;
+    for warning in &warnings {
+        println!("Warning: {}", warning);
+    }
+    println!("Imported into {}", args[1]);
+    Ok(0)
+}
+
+fn dump_save(args: &[String]) -> Result<i32, String> {
+    if args.len() != 1 {
+        return Err(String::from(USAGE));
+    }
+    let bytes = fs::read(&args[0]).map_err(|s| format!("Error reading {}: {}", args[0], s.kind()))?;
+    let game = SavedGame::parse(&bytes).map_err(|s| format!("{}: {}", args[0], s))?;
+    println!("{}", serde_json::to_string_pretty(&game.to_json()).unwrap());
+    Ok(0)
+}
+
+fn patch_save(args: &[String]) -> Result<i32, String> {
+    if args.len() != 2 {
+        return Err(String::from(USAGE));
+    }
+    let json = fs::read_to_string(&args[1]).map_err(|s| format!("Error reading {}: {}", args[1], s.kind()))?;
+    let patches = SavePatch::parse_list(&json).map_err(|s| format!("{}: {}", args[1], s))?;
+    let backup_path = patch_saved_game_file(Path::new(&args[0]), &patches, &scratch_parent_dir()?)?;
+    println!("Applied {} fixes, the original game is {}", patches.len(), backup_path.display());
+    Ok(0)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let result = match args.get(1).map(|s| s.as_str()) {
+        Some("diff") => diff(&args[2..]),
+        Some("package-mod") => package(&args[2..]),
+        Some("new-mod") => new_mod(&args[2..]),
+        Some("import-legacy") => import_legacy(&args[2..]),
+        Some("dump-save") => dump_save(&args[2..]),
+        Some("patch-save") => patch_save(&args[2..]),
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            Ok(0)
+        },
+        _ => Err(String::from(USAGE)),
+    };
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(s) => {
+            eprintln!("{}", s);
+            process::exit(ERROR_EXIT_CODE);
+        }
+    }
+}
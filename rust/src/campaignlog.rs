@@ -0,0 +1,133 @@
+//! Log of the key events of a campaign, which the launcher shows as a timeline.
+//!
+//! The log is written only if `campaign_log` is enabled in ja2.json. It is stored as JSON lines in the stracciatella
+//! home, one event per line. A new campaign starts with a `CAMPAIGN_STARTED` event.
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+
+pub const CAMPAIGN_LOG_FILE_NAME: &str = "campaign-log.jsonl";
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum CampaignEventType {
+    CAMPAIGN_STARTED,
+    SECTOR_TAKEN,
+    MERC_DIED,
+    DAY_ENDED,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CampaignEvent {
+    pub event: CampaignEventType,
+    pub day: u32,
+    pub hour: u8,
+    pub minute: u8,
+    /// Name of the sector like `D13` or `D13-1` below ground, None if the event has no sector
+    pub sector: Option<String>,
+    /// E.g. the name of the merc that died
+    #[serde(default)]
+    pub details: String,
+}
+
+pub fn campaign_log_path(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join(CAMPAIGN_LOG_FILE_NAME)
+}
+
+/// Returns the name of a sector as shown by the game, None for coordinates outside of the map.
+pub fn sector_name(x: i16, y: i16, z: i8) -> Option<String> {
+    if !(1..=16).contains(&x) || !(1..=16).contains(&y) || !(0..=3).contains(&z) {
+        return None;
+    }
+    let row = (b'A' + (y - 1) as u8) as char;
+    Some(if z == 0 { format!("{}{}", row, x) } else { format!("{}{}-{}", row, x, z) })
+}
+
+pub fn append_campaign_event(path: &Path, event: &CampaignEvent) -> Result<(), String> {
+    let line = serde_json::to_string(event).map_err(|s| format!("Error writing the campaign log: {}", s))?;
+    OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut f| writeln!(f, "{}", line))
+        .map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+}
+
+/// Reads the events of the log, lines that cannot be parsed are skipped, e.g. if the game crashed while writing.
+pub fn read_campaign_log(path: &Path) -> Result<Vec<CampaignEvent>, String> {
+    if !path.exists() {
+        return Ok(vec!());
+    }
+    let contents = fs::read_to_string(path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    Ok(contents.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+}
+
+/// Appends an event to the campaign log if it is enabled. Returns false if it is disabled or writing failed.
+#[no_mangle]
+pub extern fn log_campaign_event(ptr: *const EngineOptions, event: CampaignEventType, day: u32, hour: u8, minute: u8, x: i16, y: i16, z: i8, details_ptr: *const c_char) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    if !engine_options.campaign_log {
+        return false;
+    }
+    let details = if details_ptr.is_null() { String::new() } else { unsafe { CStr::from_ptr(details_ptr) }.to_string_lossy().into_owned() };
+    let event = CampaignEvent { event, day, hour, minute, sector: sector_name(x, y, z), details };
+    append_campaign_event(&campaign_log_path(&engine_options.stracciatella_home), &event).is_ok()
+}
+
+/// Returns the events of the campaign log as JSON array.
+#[no_mangle]
+pub extern fn get_campaign_log_json(ptr: *const EngineOptions) -> *mut c_char {
+    let path = campaign_log_path(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let events = read_campaign_log(&path).unwrap_or_default();
+    CString::new(json!(events).to_string()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs::OpenOptions;
+    use std::io::prelude::*;
+    use std::ptr;
+
+    use EngineOptions;
+    use super::{CampaignEventType, campaign_log_path, log_campaign_event, read_campaign_log, sector_name};
+
+    #[test]
+    fn sector_name_should_name_sectors_like_the_game() {
+        assert_eq!(sector_name(9, 1, 0), Some(String::from("A9")));
+        assert_eq!(sector_name(13, 4, 1), Some(String::from("D13-1")));
+        assert_eq!(sector_name(0, 0, -1), None);
+    }
+
+    #[test]
+    fn log_campaign_event_should_only_write_if_enabled() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = temp_dir.path().to_path_buf();
+        let details = CString::new("Ira").unwrap();
+
+        assert!(!log_campaign_event(&engine_options, CampaignEventType::CAMPAIGN_STARTED, 1, 7, 0, 0, 0, -1, ptr::null()));
+        engine_options.campaign_log = true;
+        assert!(log_campaign_event(&engine_options, CampaignEventType::CAMPAIGN_STARTED, 1, 7, 0, 0, 0, -1, ptr::null()));
+        assert!(log_campaign_event(&engine_options, CampaignEventType::SECTOR_TAKEN, 1, 13, 5, 9, 1, 0, ptr::null()));
+        assert!(log_campaign_event(&engine_options, CampaignEventType::MERC_DIED, 2, 1, 30, 13, 4, 0, details.as_ptr()));
+        let path = campaign_log_path(temp_dir.path());
+        writeln!(OpenOptions::new().append(true).open(&path).unwrap(), "{{\"event\": \"DAY_EN").unwrap();
+
+        let events = read_campaign_log(&path).unwrap();
+
+        assert_eq!(events.iter().map(|e| e.event).collect::<Vec<CampaignEventType>>(), vec!(
+            CampaignEventType::CAMPAIGN_STARTED, CampaignEventType::SECTOR_TAKEN, CampaignEventType::MERC_DIED
+        ));
+        assert_eq!(events[0].sector, None);
+        assert_eq!(events[1].sector, Some(String::from("A9")));
+        assert_eq!((events[2].day, events[2].details.as_str()), (2, "Ira"));
+    }
+}
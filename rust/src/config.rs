@@ -0,0 +1,3 @@
+//! Configuration files besides ja2.json.
+
+pub mod legacy;
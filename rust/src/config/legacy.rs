@@ -0,0 +1,110 @@
+//! Import of the `ja2.ini` of older versions of Stracciatella, of vanilla and of JA2 1.13.
+//!
+//! If ja2.json does not exist on the first start but there is a `ja2.ini` in the home, the known settings are taken
+//! over into the new ja2.json:
+//!
+//! - the data dir from `data_dir`
+//! - the resolution from `res` or `resolution` as `WIDTHxHEIGHT`, or from `SCREEN_WIDTH` and `SCREEN_HEIGHT`
+//! - the fullscreen mode from `fullscreen`, or the opposite of `SCREEN_MODE_WINDOWED` of 1.13
+//!
+//! The sections of the INI file do not matter. Other entries are reported and the INI file is kept.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {EngineOptions, Resolution, parse_resolution};
+use datadir::resolve_case_insensitive;
+use formats::ini::IniFile;
+
+pub const LEGACY_CONFIG_FILE_NAME: &str = "ja2.ini";
+
+#[derive(Debug, PartialEq)]
+pub struct LegacyImport {
+    /// The defaults with the imported settings
+    pub engine_options: EngineOptions,
+    pub warnings: Vec<String>,
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Maps the known entries of a `ja2.ini` to engine options.
+pub fn import_legacy_ini(bytes: &[u8]) -> LegacyImport {
+    let (ini, mut warnings) = IniFile::parse_lenient(bytes);
+    let mut engine_options = EngineOptions::default();
+    let mut size = (None, None);
+    let mut ignored = vec!();
+
+    for (key, value) in ini.sections.iter().flat_map(|s| s.entries.iter()) {
+        let valid = match key.to_lowercase().as_str() {
+            "data_dir" => {
+                engine_options.vanilla_data_dir = PathBuf::from(value);
+                true
+            },
+            "res" | "resolution" => parse_resolution(value).map(|r| engine_options.resolution = r).is_ok(),
+            "screen_width" => value.parse::<u16>().map(|w| size.0 = Some(w)).is_ok(),
+            "screen_height" => value.parse::<u16>().map(|h| size.1 = Some(h)).is_ok(),
+            "fullscreen" => parse_bool(value).map(|b| engine_options.start_in_fullscreen = b).is_some(),
+            "screen_mode_windowed" => parse_bool(value).map(|b| engine_options.start_in_fullscreen = !b).is_some(),
+            _ => {
+                ignored.push(key.as_str());
+                true
+            }
+        };
+        if !valid {
+            warnings.push(format!("Invalid value '{}' of '{}'", value, key));
+        }
+    }
+    if let (Some(width), Some(height)) = size {
+        engine_options.resolution = Resolution::new(width, height);
+    }
+    if !ignored.is_empty() {
+        warnings.push(format!("Entries that are not imported: {}", ignored.join(", ")));
+    }
+    LegacyImport { engine_options, warnings }
+}
+
+/// Returns the `ja2.ini` in a dir, the name is compared case-insensitively.
+pub fn find_legacy_ini(dir: &Path) -> Option<PathBuf> {
+    resolve_case_insensitive(dir, LEGACY_CONFIG_FILE_NAME).filter(|p| p.is_file())
+}
+
+pub fn import_legacy_ini_file(path: &Path) -> Result<LegacyImport, String> {
+    let bytes = fs::read(path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    Ok(import_legacy_ini(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use Resolution;
+    use super::import_legacy_ini;
+
+    #[test]
+    fn import_legacy_ini_should_map_the_settings_of_stracciatella() {
+        let import = import_legacy_ini(b"data_dir = /home/user/ja2\nres = 1024x768\nfullscreen = 1\nplayintro = 0\n");
+
+        assert_eq!(import.engine_options.vanilla_data_dir, PathBuf::from("/home/user/ja2"));
+        assert_eq!(import.engine_options.resolution, Resolution::new(1024, 768));
+        assert!(import.engine_options.start_in_fullscreen);
+        assert_eq!(import.warnings, vec!("Entries that are not imported: playintro"));
+    }
+
+    #[test]
+    fn import_legacy_ini_should_map_the_settings_of_1_13() {
+        let ini = b"[Ja2 Settings]\r\nSCREEN_WIDTH = 800\r\nSCREEN_HEIGHT = 600 ; pixels\r\nSCREEN_MODE_WINDOWED = maybe\r\n";
+
+        let import = import_legacy_ini(ini);
+
+        assert_eq!(import.engine_options.resolution, Resolution::new(800, 600));
+        assert!(!import.engine_options.start_in_fullscreen);
+        assert_eq!(import.engine_options.vanilla_data_dir, PathBuf::from(""));
+        assert_eq!(import.warnings, vec!("Invalid value 'maybe' of 'SCREEN_MODE_WINDOWED'"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Comparison of two data directories for `ja2-resources diff`.
+//!
+//! Loose files are compared by their path relative to the compared directory, the entries of SLF libraries by the
+//! path of the library followed by their full name, e.g. `data/binarydata.slf/binarydata/credits.edt`. Paths are
+//! compared ignoring the case, as the engine does.
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::hash::Hasher;
+use std::path::Path;
+
+use dataimport::file_checksum;
+use slf::SlfLibrary;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum DiffKind {
+    ADDED,
+    REMOVED,
+    CHANGED,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    pub path: String,
+}
+
+impl Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let marker = match self.kind {
+            DiffKind::ADDED => "A",
+            DiffKind::REMOVED => "D",
+            DiffKind::CHANGED => "M",
+        };
+        write!(f, "{} {}", marker, self.path)
+    }
+}
+
+/// Returns the size and hash of the contents of all loose files and library entries in a directory.
+pub fn collect_checksums(dir: &Path) -> Result<BTreeMap<String, (u64, u64)>, String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    let mut checksums = BTreeMap::new();
+    collect_dir(dir, "", &mut checksums)?;
+    Ok(checksums)
+}
+
+fn collect_dir(dir: &Path, prefix: &str, checksums: &mut BTreeMap<String, (u64, u64)>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?.path();
+        let name = format!("{}{}", prefix, path.file_name().unwrap().to_string_lossy().to_lowercase());
+
+        if path.is_dir() {
+            collect_dir(&path, &format!("{}/", name), checksums)?;
+        } else if name.ends_with(".slf") {
+            let library = SlfLibrary::open(&path)?;
+            for entry in &library.entries {
+                let mut hasher = DefaultHasher::new();
+                hasher.write(&library.read(entry)?);
+                let entry_name = format!("{}/{}", name, library.full_name(entry).to_lowercase());
+                checksums.insert(entry_name, (u64::from(entry.length), hasher.finish()));
+            }
+        } else {
+            checksums.insert(name, file_checksum(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the entries that were added, removed or changed in `dir_b` compared to `dir_a`, ordered by path.
+pub fn diff_dirs(dir_a: &Path, dir_b: &Path) -> Result<Vec<DiffEntry>, String> {
+    let checksums_a = collect_checksums(dir_a)?;
+    let checksums_b = collect_checksums(dir_b)?;
+    let mut diff = vec!();
+
+    for (path, checksum) in &checksums_a {
+        match checksums_b.get(path) {
+            None => diff.push(DiffEntry { kind: DiffKind::REMOVED, path: path.clone() }),
+            Some(c) if c != checksum => diff.push(DiffEntry { kind: DiffKind::CHANGED, path: path.clone() }),
+            Some(_) => {}
+        }
+    }
+    for path in checksums_b.keys().filter(|p| !checksums_a.contains_key(*p)) {
+        diff.push(DiffEntry { kind: DiffKind::ADDED, path: path.clone() });
+    }
+    diff.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use slf::tests::write_library;
+    use super::{DiffEntry, DiffKind};
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    fn entry(kind: DiffKind, path: &str) -> DiffEntry {
+        DiffEntry { kind, path: String::from(path) }
+    }
+
+    #[test]
+    fn diff_dirs_should_compare_loose_files_and_library_entries() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir_a = temp_dir.path().join("a");
+        let dir_b = temp_dir.path().join("b");
+        write_file(&dir_a.join("Data/readme.txt"), b"readme");
+        write_file(&dir_a.join("Data/old.txt"), b"old");
+        write_file(&dir_a.join("Data/TILECACHE/a.sti"), b"a");
+        write_file(&dir_b.join("DATA/README.TXT"), b"readme");
+        write_file(&dir_b.join("DATA/new.txt"), b"new");
+        write_file(&dir_b.join("DATA/tilecache/A.STI"), b"b");
+        write_library(&dir_a.join("Data/binarydata.slf"), "BINARYDATA\\", &[("CREDITS.EDT", Some(b"credits")), ("OLD.EDT", Some(b"old"))]);
+        write_library(&dir_b.join("DATA/BINARYDATA.SLF"), "BINARYDATA\\", &[("credits.edt", Some(b"credits")), ("NEW.EDT", Some(b"new"))]);
+
+        assert_eq!(super::diff_dirs(&dir_a, &dir_b), Ok(vec!(
+            entry(DiffKind::ADDED, "data/binarydata.slf/binarydata/new.edt"),
+            entry(DiffKind::REMOVED, "data/binarydata.slf/binarydata/old.edt"),
+            entry(DiffKind::ADDED, "data/new.txt"),
+            entry(DiffKind::REMOVED, "data/old.txt"),
+            entry(DiffKind::CHANGED, "data/tilecache/a.sti"),
+        )));
+        assert_eq!(super::diff_dirs(&dir_a, &dir_a), Ok(vec!()));
+        assert_eq!(format!("{}", entry(DiffKind::CHANGED, "data/new.txt")), "M data/new.txt");
+    }
+
+    #[test]
+    fn diff_dirs_should_fail_for_missing_dirs() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        assert!(super::diff_dirs(temp_dir.path(), &temp_dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Layout of the vanilla data dir.
+//!
+//! The classic releases ship all resources packed into SLF libraries in the `Data` folder. The Gold re-releases
+//! ship the binary data (e.g. `ja2set.dat`) unpacked in `Data/BinaryData` instead of `BinaryData.slf`. The layout is
+//! detected from the files in the data dir, so the expected files and the mounts match the installed release.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {EngineOptions, ResourceVersion};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum DataLayout {
+    CLASSIC,
+    GOLD,
+    UNKNOWN,
+}
+
+/// Libraries that are part of every release.
+pub static COMMON_LIBRARIES: [&'static str; 19] = [
+    "ambient.slf", "anims.slf", "battlesnds.slf", "bigitems.slf", "cursors.slf", "faces.slf", "fonts.slf",
+    "interface.slf", "laptop.slf", "loadscreens.slf", "maps.slf", "mercedt.slf", "music.slf", "npc_speech.slf",
+    "npcdata.slf", "radarmaps.slf", "sounds.slf", "speech.slf", "tilesets.slf",
+];
+
+/// Libraries with the voices of the mercs and NPCs. Some downloads ship them as empty placeholders.
+pub static SPEECH_LIBRARIES: [&'static str; 2] = ["speech.slf", "npc_speech.slf"];
+
+/// Size of the header of an SLF library, smaller files cannot be libraries.
+const MIN_LIBRARY_SIZE: u64 = 532;
+
+static CLASSIC_FILES: [&'static str; 1] = ["binarydata.slf"];
+
+static GOLD_FILES: [&'static str; 1] = ["binarydata/ja2set.dat"];
+
+/// Finds an entry in a directory ignoring the case of its name.
+pub fn find_case_insensitive(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+    fs::read_dir(dir).ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().to_lowercase() == name.to_lowercase())
+        .map(|e| e.path())
+}
+
+/// Resolves a relative path with `/` as separator ignoring the case of all components.
+pub fn resolve_case_insensitive(dir: &Path, relative_path: &str) -> Option<PathBuf> {
+    relative_path.split('/').fold(Some(dir.to_path_buf()), |path, component| {
+        path.and_then(|p| find_case_insensitive(&p, component))
+    })
+}
+
+/// Returns the `Data` folder of the vanilla data dir.
+pub fn find_data_folder(vanilla_data_dir: &Path) -> Option<PathBuf> {
+    find_case_insensitive(vanilla_data_dir, "Data").and_then(|p| if p.is_dir() { Some(p) } else { None })
+}
+
+/// Returns the root of the installation for a data dir the user chose.
+///
+/// Users often choose the `Data` folder (in any case) instead of the installation root. If the chosen dir is not the
+/// root itself but its `Data` folder, the parent is returned. Otherwise the dir is kept as it is.
+pub fn normalize_data_dir(data_dir: &Path) -> PathBuf {
+    if find_data_folder(data_dir).is_some() {
+        return data_dir.to_path_buf();
+    }
+    let is_data_folder = data_dir.file_name().map(|n| n.to_string_lossy().to_lowercase() == "data").unwrap_or(false);
+    match data_dir.parent() {
+        Some(parent) if is_data_folder && data_dir.is_dir() => parent.to_path_buf(),
+        _ => data_dir.to_path_buf()
+    }
+}
+
+pub fn detect_layout(vanilla_data_dir: &Path) -> DataLayout {
+    let data_folder = match find_data_folder(vanilla_data_dir) {
+        Some(d) => d,
+        None => return DataLayout::UNKNOWN
+    };
+    let has_all = |files: &[&str]| files.iter().all(|f| resolve_case_insensitive(&data_folder, f).is_some());
+
+    if has_all(&CLASSIC_FILES) {
+        DataLayout::CLASSIC
+    } else if has_all(&GOLD_FILES) {
+        DataLayout::GOLD
+    } else {
+        DataLayout::UNKNOWN
+    }
+}
+
+/// Returns the files that are expected in the `Data` folder for a layout. The classic layout is expected if the
+/// layout is unknown.
+pub fn expected_files(layout: DataLayout) -> Vec<&'static str> {
+    let layout_files: &[&'static str] = match layout {
+        DataLayout::GOLD => &GOLD_FILES,
+        DataLayout::CLASSIC | DataLayout::UNKNOWN => &CLASSIC_FILES,
+    };
+    COMMON_LIBRARIES.iter().chain(layout_files.iter()).cloned().collect()
+}
+
+/// Returns the expected files that are missing in the data dir.
+pub fn missing_files(vanilla_data_dir: &Path) -> Vec<String> {
+    let layout = detect_layout(vanilla_data_dir);
+    let data_folder = find_data_folder(vanilla_data_dir);
+
+    expected_files(layout).into_iter()
+        .filter(|f| data_folder.as_ref().and_then(|d| resolve_case_insensitive(d, f)).is_none())
+        .map(|f| format!("Data/{}", f))
+        .collect()
+}
+
+/// Returns warnings for speech libraries that are missing or too small to be a library. Without them the mercs are
+/// silent, which looks like a bug of the game.
+pub fn check_speech_libraries(vanilla_data_dir: &Path, resource_version: ResourceVersion) -> Vec<String> {
+    let data_folder = match find_data_folder(vanilla_data_dir) {
+        Some(d) => d,
+        None => return vec!()
+    };
+    SPEECH_LIBRARIES.iter()
+        .filter_map(|l| match resolve_case_insensitive(&data_folder, l).map(|p| fs::metadata(p).map(|m| m.len())) {
+            None => Some(format!("Data/{} is missing, the mercs will be silent. Copy it from the {} release of the game.", l, resource_version)),
+            Some(Ok(size)) if size < MIN_LIBRARY_SIZE => Some(format!("Data/{} is a placeholder of {} bytes, the mercs will be silent. Copy it from the {} release of the game.", l, size, resource_version)),
+            _ => None
+        })
+        .collect()
+}
+
+#[no_mangle]
+pub extern fn get_data_layout(ptr: *const EngineOptions) -> DataLayout {
+    detect_layout(&unsafe_from_ptr!(ptr).vanilla_data_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::path::Path;
+
+    use ResourceVersion;
+    use super::DataLayout;
+
+    fn create_files(dir: &Path, files: &[&str]) {
+        for f in files {
+            let path = dir.join(f);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+    }
+
+    fn create_common_libraries(data_folder: &Path) {
+        let libraries: Vec<String> = super::COMMON_LIBRARIES.iter().map(|l| l.to_uppercase()).collect();
+        create_files(data_folder, &libraries.iter().map(|l| l.as_str()).collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn detect_layout_should_detect_classic_layout() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        create_common_libraries(&temp_dir.path().join("DATA"));
+        create_files(temp_dir.path(), &["DATA/BinaryData.slf"]);
+
+        assert_eq!(super::detect_layout(temp_dir.path()), DataLayout::CLASSIC);
+        assert_eq!(super::missing_files(temp_dir.path()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn detect_layout_should_detect_gold_layout() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        create_common_libraries(&temp_dir.path().join("Data"));
+        create_files(temp_dir.path(), &["Data/BINARYDATA/Ja2Set.dat"]);
+
+        assert_eq!(super::detect_layout(temp_dir.path()), DataLayout::GOLD);
+        assert_eq!(super::missing_files(temp_dir.path()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn missing_files_should_list_files_of_the_classic_layout_for_unknown_layouts() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        create_files(temp_dir.path(), &["Data/maps.slf"]);
+
+        let missing = super::missing_files(temp_dir.path());
+
+        assert_eq!(super::detect_layout(temp_dir.path()), DataLayout::UNKNOWN);
+        assert_eq!(missing.len(), super::COMMON_LIBRARIES.len());
+        assert!(missing.contains(&String::from("Data/binarydata.slf")));
+        assert!(!missing.contains(&String::from("Data/maps.slf")));
+    }
+
+    #[test]
+    fn check_speech_libraries_should_warn_about_missing_and_placeholder_libraries() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        create_files(temp_dir.path(), &["Data/SPEECH.SLF"]);
+
+        assert_eq!(super::check_speech_libraries(temp_dir.path(), ResourceVersion::GERMAN), vec!(
+            String::from("Data/speech.slf is a placeholder of 0 bytes, the mercs will be silent. Copy it from the GERMAN release of the game."),
+            String::from("Data/npc_speech.slf is missing, the mercs will be silent. Copy it from the GERMAN release of the game."),
+        ));
+
+        fs::write(temp_dir.path().join("Data/SPEECH.SLF"), vec!(0u8; 1024)).unwrap();
+        create_files(temp_dir.path(), &["Data/npc_speech.slf"]);
+        fs::write(temp_dir.path().join("Data/npc_speech.slf"), vec!(0u8; 532)).unwrap();
+        assert!(super::check_speech_libraries(temp_dir.path(), ResourceVersion::GERMAN).is_empty());
+    }
+
+    #[test]
+    fn normalize_data_dir_should_find_the_installation_root() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let root = temp_dir.path().join("ja2");
+        create_files(&root, &["DATA/maps.slf"]);
+
+        assert_eq!(super::normalize_data_dir(&root), root);
+        assert_eq!(super::normalize_data_dir(&root.join("DATA")), root);
+        assert_eq!(super::normalize_data_dir(temp_dir.path()), temp_dir.path());
+        assert_eq!(super::normalize_data_dir(&temp_dir.path().join("missing/Data")), temp_dir.path().join("missing/Data"));
+    }
+}
@@ -0,0 +1,401 @@
+//! Import of the vanilla data from an original installation or CD.
+//!
+//! Only the files the engine needs are copied into a data dir that is managed by stracciatella. Every copied file is
+//! verified, so a scratched CD is detected during the import and not while playing. CDs without an installed `Data`
+//! folder are read from the InstallShield cabinets of their installer, see `installshield`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use libc::c_char;
+
+use EngineOptions;
+use set_last_error;
+use datadir::{DataLayout, detect_layout, expected_files, find_data_folder, normalize_data_dir, resolve_case_insensitive};
+use fileplan::FilePlan;
+use home::cache_dir;
+use installshield::{Cabinet, find_cabinet_header};
+use logger::{self, LogLevel};
+use modpackage::CHECKSUMS_FILE_NAME;
+use sha256::sha256_hex;
+use temp::ScratchDir;
+
+/// Progress callback for the launcher, called with the copied and total bytes.
+pub type ImportProgress = extern fn(u64, u64);
+
+/// Returns all files that are imported from the `Data` folder as pairs of source path and path relative to the
+/// `Data` folder. Folders of the Gold layout (e.g. `BinaryData`) are imported completely.
+pub fn files_to_import(source_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let source_dir = normalize_data_dir(source_dir);
+    let data_folder = find_data_folder(&source_dir).ok_or_else(|| format!("{} contains no Data folder", source_dir.display()))?;
+    let mut files: Vec<(PathBuf, PathBuf)> = vec!();
+
+    for expected in expected_files(detect_layout(&source_dir)) {
+        let top_level = expected.split('/').next().unwrap();
+        let path = match resolve_case_insensitive(&data_folder, top_level) {
+            Some(p) => p,
+            None => return Err(format!("{} is missing in {}", expected, data_folder.display()))
+        };
+        if files.iter().any(|f| f.0 == path) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, &data_folder, &mut files)?;
+        } else {
+            let relative = path.strip_prefix(&data_folder).unwrap().to_path_buf();
+            files.push((path, relative));
+        }
+    }
+    Ok(files)
+}
+
+fn collect_files(dir: &Path, data_folder: &Path, files: &mut Vec<(PathBuf, PathBuf)>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?.path();
+        if path.is_dir() {
+            collect_files(&path, data_folder, files)?;
+        } else {
+            let relative = path.strip_prefix(data_folder).unwrap().to_path_buf();
+            files.push((path, relative));
+        }
+    }
+    Ok(())
+}
+
+/// Source of an imported file, a file in the `Data` folder or the index of a file in the cabinet.
+enum Source {
+    File(PathBuf),
+    Cabinet(usize),
+}
+
+/// Returns the files that are imported from the `Data` folder of a cabinet as pairs of cabinet index and path
+/// relative to the `Data` folder.
+fn cabinet_files_to_import(cabinet: &Cabinet, header_path: &Path) -> Result<Vec<(usize, PathBuf)>, String> {
+    let data_files: Vec<(usize, String)> = cabinet.files.iter().enumerate().filter_map(|(i, f)| {
+        let mut components = f.path.split('/');
+        components.by_ref().find(|c| c.to_lowercase() == "data")?;
+        Some((i, components.collect::<Vec<_>>().join("/")))
+    }).collect();
+    let has_file = |name: &str| data_files.iter().any(|f| f.1.to_lowercase() == name);
+    let layout = if has_file("binarydata.slf") {
+        DataLayout::CLASSIC
+    } else if has_file("binarydata/ja2set.dat") {
+        DataLayout::GOLD
+    } else {
+        DataLayout::UNKNOWN
+    };
+    let mut files: Vec<(usize, PathBuf)> = vec!();
+
+    for expected in expected_files(layout) {
+        let top_level = expected.split('/').next().unwrap();
+        let matching: Vec<&(usize, String)> = data_files.iter().filter(|f| {
+            let name = f.1.to_lowercase();
+            name == top_level || name.starts_with(&format!("{}/", top_level))
+        }).collect();
+        if !data_files.iter().any(|f| f.1.to_lowercase() == expected) {
+            return Err(format!("{} is missing in {}", expected, header_path.display()));
+        }
+        for &&(index, ref name) in &matching {
+            if !files.iter().any(|f| f.0 == index) {
+                files.push((index, PathBuf::from(name)));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Files to import as pairs of source and path relative to the `Data` folder, with the cabinet and its header path
+/// if they are imported from a cabinet.
+struct ImportSources {
+    cabinet: Option<(Cabinet, PathBuf)>,
+    files: Vec<(Source, PathBuf)>,
+}
+
+/// Returns the files to import from a folder or, if it has no `Data` folder, from the cabinets in it.
+fn sources_to_import(source_dir: &Path) -> Result<ImportSources, String> {
+    let error = match files_to_import(source_dir) {
+        Ok(files) => return Ok(ImportSources { cabinet: None, files: files.into_iter().map(|(path, relative)| (Source::File(path), relative)).collect() }),
+        Err(s) => s,
+    };
+    let header_path = match find_cabinet_header(source_dir) {
+        Some(p) => p,
+        None => return Err(error),
+    };
+    let cabinet = Cabinet::open(&header_path)?;
+    let files = cabinet_files_to_import(&cabinet, &header_path)?;
+    let files = files.into_iter().map(|(index, relative)| (Source::Cabinet(index), relative)).collect();
+    Ok(ImportSources { cabinet: Some((cabinet, header_path)), files })
+}
+
+/// Passes the written data on and computes the same checksum as `file_checksum`.
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: DefaultHasher,
+    size: u64,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.write(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Extracts a file of the cabinet and returns its checksum, see `file_checksum`.
+fn extract_with_checksum<W: Write>(cabinet: &Cabinet, index: usize, writer: W) -> Result<(u64, u64), String> {
+    let mut writer = ChecksumWriter { inner: writer, hasher: DefaultHasher::new(), size: 0 };
+    cabinet.extract(&cabinet.files[index], &mut writer)?;
+    writer.flush().map_err(|s| format!("Error writing {}: {}", cabinet.files[index].path, s.kind()))?;
+    Ok((writer.size, writer.hasher.finish()))
+}
+
+/// Returns the size and a hash of the contents of a file.
+pub fn file_checksum(path: &Path) -> Result<(u64, u64), String> {
+    let mut file = File::open(path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec!(0u8; 64 * 1024);
+    let mut size = 0;
+
+    loop {
+        let read = file.read(&mut buffer).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((size, hasher.finish()))
+}
+
+/// Copies the required files from an original installation or CD to `target_dir/Data` and returns the plan of the
+/// copied files. With `dry_run` nothing is written, the plan shows what would be copied.
+///
+/// Files that already exist in the target with the same contents are not copied again, so an aborted import can be
+/// resumed. Every file is copied and verified in a scratch dir in the cache dir first, so the target never contains
+/// partly copied files. The progress is reported after every file. The hashes of the imported files are written to
+/// `Data/SHA256SUMS`, see `dataverify`.
+pub fn import_data(source_dir: &Path, target_dir: &Path, cache_dir: &Path, dry_run: bool, progress: &mut dyn FnMut(u64, u64)) -> Result<FilePlan, String> {
+    let ImportSources { cabinet, files } = sources_to_import(source_dir)?;
+    let scratch = if dry_run { None } else { Some(ScratchDir::new(cache_dir, "import-data")?) };
+    let total = files.iter().map(|f| match f.0 {
+        Source::File(ref path) => fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        Source::Cabinet(index) => cabinet.as_ref().map(|c| c.0.files[index].size).unwrap_or(0),
+    }).sum();
+    let target_data_folder = target_dir.join("Data");
+    let mut plan = FilePlan::default();
+    let mut checksums = String::new();
+    let mut copied = 0;
+
+    progress(copied, total);
+    for (source, relative) in files {
+        let target = target_data_folder.join(&relative);
+        let name = relative.to_string_lossy().replace('\\', "/");
+        // Files of the cabinet are extracted to the scratch dir right away, they cannot be hashed without extracting
+        let (source_checksum, source_name) = match source {
+            Source::File(ref path) => (file_checksum(path)?, path.display().to_string()),
+            Source::Cabinet(index) => {
+                let (cabinet, header_path) = cabinet.as_ref().unwrap();
+                let checksum = match scratch {
+                    Some(ref scratch) => {
+                        let staged = scratch.join("file");
+                        let file = File::create(&staged).map_err(|s| format!("Error writing {}: {}", staged.display(), s.kind()))?;
+                        extract_with_checksum(cabinet, index, file)?
+                    },
+                    None => extract_with_checksum(cabinet, index, io::sink())?,
+                };
+                (checksum, format!("{} in {}", cabinet.files[index].path, header_path.display()))
+            },
+        };
+
+        if target.is_file() && file_checksum(&target)? == source_checksum {
+            plan.add_unchanged(&target);
+        } else {
+            plan.add(&target, source_checksum.0);
+            if let Some(ref scratch) = scratch {
+                let staged = scratch.join("file");
+                if let Source::File(ref path) = source {
+                    fs::copy(path, &staged).map_err(|s| format!("Error copying {} to {}: {}", path.display(), staged.display(), s.kind()))?;
+                }
+                if file_checksum(&staged)? != source_checksum {
+                    return Err(format!("The copy of {} differs from the original", source_name));
+                }
+                fs::create_dir_all(target.parent().unwrap()).map_err(|s| format!("Error creating {}: {}", target.display(), s.kind()))?;
+                scratch.persist("file", &target)?;
+            }
+        }
+        if scratch.is_some() {
+            let contents = fs::read(&target).map_err(|s| format!("Error reading {}: {}", target.display(), s.kind()))?;
+            checksums.push_str(&sha256_hex(&contents));
+        } else {
+            // A dry run only needs the size of the file
+            checksums.push_str(&"0".repeat(64));
+        }
+        checksums.push_str(&format!("  {}\n", name));
+        copied += source_checksum.0;
+        progress(copied, total);
+    }
+
+    let checksums_path = target_data_folder.join(CHECKSUMS_FILE_NAME);
+    plan.add(&checksums_path, checksums.len() as u64);
+    if let Some(ref scratch) = scratch {
+        fs::write(scratch.join(CHECKSUMS_FILE_NAME), &checksums)
+            .map_err(|s| format!("Error writing {}: {}", checksums_path.display(), s.kind()))?;
+        scratch.persist(CHECKSUMS_FILE_NAME, &checksums_path)?;
+    }
+    Ok(plan)
+}
+
+/// Imports the vanilla data, see `import_data`. The progress callback may be null.
+#[no_mangle]
+pub extern fn import_vanilla_data(ptr: *const EngineOptions, source_dir_ptr: *const c_char, target_dir_ptr: *const c_char, progress: Option<ImportProgress>) -> bool {
+    let cache_dir = cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let source_dir = unsafe { CStr::from_ptr(source_dir_ptr) }.to_string_lossy().into_owned();
+    let target_dir = unsafe { CStr::from_ptr(target_dir_ptr) }.to_string_lossy().into_owned();
+    let mut report = |copied, total| if let Some(f) = progress { f(copied, total) };
+
+    match import_data(Path::new(&source_dir), Path::new(&target_dir), &cache_dir, false, &mut report) {
+        Ok(_) => true,
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "dataimport", &format!("Error importing the game data: {}", s));
+            false
+        }
+    }
+}
+
+/// Returns the plan of `import_data` in a dry run as JSON, so the launcher can ask for confirmation. Returns null if
+/// the data cannot be imported, see `get_last_error`.
+#[no_mangle]
+pub extern fn get_vanilla_data_import_plan_json(ptr: *const EngineOptions, source_dir_ptr: *const c_char, target_dir_ptr: *const c_char) -> *mut c_char {
+    let cache_dir = cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let source_dir = unsafe { CStr::from_ptr(source_dir_ptr) }.to_string_lossy().into_owned();
+    let target_dir = unsafe { CStr::from_ptr(target_dir_ptr) }.to_string_lossy().into_owned();
+
+    match import_data(Path::new(&source_dir), Path::new(&target_dir), &cache_dir, true, &mut |_, _| {}) {
+        Ok(plan) => CString::new(json!(plan).to_string()).unwrap().into_raw(),
+        Err(s) => {
+            set_last_error(Some(s));
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use datadir::{COMMON_LIBRARIES, DataLayout, detect_layout};
+    use installshield::tests::write_cabinet;
+    use sha256::sha256_hex;
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    fn create_gold_installation(dir: &Path) {
+        for library in COMMON_LIBRARIES.iter() {
+            write_file(&dir.join("DATA").join(library.to_uppercase()), library.as_bytes());
+        }
+        write_file(&dir.join("DATA/BINARYDATA/JA2SET.DAT"), b"ja2set");
+        write_file(&dir.join("DATA/BINARYDATA/Prof.dat"), b"prof");
+        write_file(&dir.join("DATA/readme.txt"), b"not needed");
+    }
+
+    #[test]
+    fn import_data_should_copy_the_required_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source = temp_dir.path().join("cdrom");
+        let target = temp_dir.path().join("ja2-data");
+        create_gold_installation(&source);
+        let mut reported = vec!();
+
+        let plan = super::import_data(&source.join("DATA"), &target, &temp_dir.path().join("tmp"), false, &mut |c, t| reported.push((c, t))).unwrap();
+
+        assert_eq!(detect_layout(&target), DataLayout::GOLD);
+        assert_eq!(fs::read(target.join("Data/BINARYDATA/Prof.dat")).unwrap(), b"prof");
+        assert_eq!(fs::read(target.join("Data/MAPS.SLF")).unwrap(), b"maps.slf");
+        assert!(!target.join("Data/readme.txt").exists());
+        assert_eq!(reported.len(), COMMON_LIBRARIES.len() + 3);
+        assert_eq!(reported.first().map(|&(c, _)| c), Some(0));
+        assert!(reported.last().map(|&(c, t)| c == t).unwrap());
+        assert_eq!(fs::read_dir(temp_dir.path().join("tmp")).unwrap().count(), 0);
+        assert_eq!(plan.files.len(), COMMON_LIBRARIES.len() + 3);
+        let checksums = fs::read_to_string(target.join("Data/SHA256SUMS")).unwrap();
+        assert!(checksums.contains(&format!("{}  BINARYDATA/Prof.dat\n", sha256_hex(b"prof"))));
+    }
+
+    #[test]
+    fn import_data_should_only_plan_in_a_dry_run() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source = temp_dir.path().join("cdrom");
+        let target = temp_dir.path().join("ja2-data");
+        create_gold_installation(&source);
+        write_file(&target.join("Data/BINARYDATA/Prof.dat"), b"prof");
+        write_file(&target.join("Data/BINARYDATA/JA2SET.DAT"), b"old");
+
+        let plan = super::import_data(&source, &target, &temp_dir.path().join("tmp"), true, &mut |_, _| {}).unwrap();
+
+        assert_eq!(plan.unchanged, vec!(target.join("Data/BINARYDATA/Prof.dat")));
+        let ja2set = plan.files.iter().find(|f| f.path == target.join("Data/BINARYDATA/JA2SET.DAT")).unwrap();
+        assert_eq!((ja2set.size, ja2set.overwrites), (6, true));
+        let checksums = plan.files.last().unwrap();
+        let imported = plan.files[..plan.files.len() - 1].iter().map(|f| &f.path).chain(plan.unchanged.iter());
+        let line_lengths: usize = imported.map(|p| 64 + 2 + p.strip_prefix(target.join("Data")).unwrap().to_string_lossy().len() + 1).sum();
+        assert_eq!((&checksums.path, checksums.size), (&target.join("Data/SHA256SUMS"), line_lengths as u64));
+        assert_eq!(plan.bytes_needed, COMMON_LIBRARIES.iter().map(|l| l.len() as u64).sum::<u64>() + 3 + checksums.size);
+        assert!(!target.join("Data/MAPS.SLF").exists());
+        assert!(!temp_dir.path().join("tmp").exists());
+    }
+
+    #[test]
+    fn import_data_should_extract_the_required_files_from_cabinets() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source = temp_dir.path().join("cdrom");
+        let target = temp_dir.path().join("ja2-data");
+        fs::create_dir_all(&source).unwrap();
+        let mut files: Vec<(&str, &str, &[u8])> = COMMON_LIBRARIES.iter().map(|l| ("Program Files\\Data", *l, l.as_bytes())).collect();
+        files.push(("Program Files\\Data", "BinaryData.slf", b"binary data"));
+        files.push(("Program Files", "ja2.exe", b"not needed"));
+        write_cabinet(&source, 6, &files, true);
+        write_file(&target.join("Data/maps.slf"), b"maps.slf");
+
+        let plan = super::import_data(&source, &target, &temp_dir.path().join("tmp"), false, &mut |_, _| {}).unwrap();
+
+        assert_eq!(detect_layout(&target), DataLayout::CLASSIC);
+        assert_eq!(fs::read(target.join("Data/BinaryData.slf")).unwrap(), b"binary data");
+        assert_eq!(fs::read(target.join("Data/tilesets.slf")).unwrap(), b"tilesets.slf");
+        assert!(!target.join("Data/ja2.exe").exists());
+        assert_eq!(plan.unchanged, vec!(target.join("Data/maps.slf")));
+        assert_eq!(plan.files.len(), COMMON_LIBRARIES.len() + 1);
+    }
+
+    #[test]
+    fn import_data_should_fail_with_missing_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source = temp_dir.path().join("cdrom");
+        write_file(&source.join("Data/maps.slf"), b"maps");
+
+        assert!(super::import_data(&source, &temp_dir.path().join("ja2-data"), &temp_dir.path().join("tmp"), false, &mut |_, _| {}).is_err());
+        assert!(super::import_data(&temp_dir.path().join("missing"), &temp_dir.path().join("ja2-data"), &temp_dir.path().join("tmp"), false, &mut |_, _| {}).is_err());
+        assert!(!temp_dir.path().join("ja2-data/Data/maps.slf").exists());
+    }
+}
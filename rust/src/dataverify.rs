@@ -0,0 +1,274 @@
+//! Verification of the game data with the hashes in `SHA256SUMS`, for "Verify game data" in the launcher.
+//!
+//! The vanilla data is verified with the hashes that `dataimport` writes to `Data/SHA256SUMS`, the enabled mods with
+//! the hashes of their package, see `modpackage`. Folders without hashes are reported as unverified. The files are
+//! hashed on all cores and the verification stops early when its `CancellationToken` is cancelled.
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use libc::c_char;
+
+use EngineOptions;
+use set_last_error;
+use datadir::{find_data_folder, resolve_case_insensitive};
+use modpackage::CHECKSUMS_FILE_NAME;
+use sha256::sha256_hex;
+use vfs::find_mod_dir;
+
+/// Stops a long running operation from another thread. The operation checks the token between files.
+#[derive(Debug, Default)]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct VerificationReport {
+    pub checked_files: usize,
+    /// Missing, unreadable and damaged files
+    pub problems: Vec<String>,
+    /// Folders that have no `SHA256SUMS`
+    pub unverified: Vec<PathBuf>,
+    pub cancelled: bool,
+}
+
+impl VerificationReport {
+    fn merge(&mut self, other: VerificationReport) {
+        self.checked_files += other.checked_files;
+        self.problems.extend(other.problems);
+        self.unverified.extend(other.unverified);
+        self.cancelled |= other.cancelled;
+    }
+}
+
+/// Parses the lines of `sha256sum` into pairs of hash and name. Names marked as binary with `*` are accepted.
+pub fn parse_checksums(text: &str) -> Result<Vec<(String, String)>, String> {
+    text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()).map(|(i, line)| {
+        let hash = line.get(..64).filter(|h| h.chars().all(|c| c.is_ascii_hexdigit()));
+        let name = line.get(64..).and_then(|r| r.strip_prefix("  ").or_else(|| r.strip_prefix(" *")));
+        match (hash, name) {
+            (Some(h), Some(n)) if !n.is_empty() => Ok((h.to_lowercase(), String::from(n))),
+            _ => Err(format!("Line {} is not a valid checksum: {}", i + 1, line)),
+        }
+    }).collect()
+}
+
+fn verify_file(dir: &Path, hash: &str, name: &str) -> Option<String> {
+    let path = match resolve_case_insensitive(dir, name) {
+        Some(p) => p,
+        None => return Some(format!("{} is missing", dir.join(name).display())),
+    };
+    match fs::read(&path) {
+        Ok(contents) if sha256_hex(&contents) == hash => None,
+        Ok(_) => Some(format!("{} is damaged", path.display())),
+        Err(s) => Some(format!("Error reading {}: {}", path.display(), s.kind())),
+    }
+}
+
+/// Verifies the files listed in `SHA256SUMS` of the folder with the given number of threads.
+pub fn verify_checksums(dir: &Path, threads: usize, token: &CancellationToken) -> Result<VerificationReport, String> {
+    let checksums_path = dir.join(CHECKSUMS_FILE_NAME);
+    if !checksums_path.is_file() {
+        return Ok(VerificationReport { unverified: vec!(dir.to_path_buf()), ..VerificationReport::default() });
+    }
+    let text = fs::read_to_string(&checksums_path).map_err(|s| format!("Error reading {}: {}", checksums_path.display(), s.kind()))?;
+    let checksums = parse_checksums(&text).map_err(|s| format!("{}: {}", checksums_path.display(), s))?;
+    let next = AtomicUsize::new(0);
+    let checked = AtomicUsize::new(0);
+    let problems: Mutex<Vec<(usize, String)>> = Mutex::new(vec!());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1).min(checksums.len()) {
+            scope.spawn(|| {
+                while !token.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let (hash, name) = match checksums.get(index) {
+                        Some(c) => c,
+                        None => break,
+                    };
+                    if let Some(problem) = verify_file(dir, hash, name) {
+                        problems.lock().unwrap_or_else(|e| e.into_inner()).push((index, problem));
+                    }
+                    checked.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+    });
+
+    let mut problems = problems.into_inner().unwrap_or_else(|e| e.into_inner());
+    problems.sort();
+    Ok(VerificationReport {
+        checked_files: checked.into_inner(),
+        problems: problems.into_iter().map(|(_, p)| p).collect(),
+        unverified: vec!(),
+        cancelled: token.is_cancelled(),
+    })
+}
+
+/// Verifies up to `sample_size` files listed in `SHA256SUMS` of the folder, spread evenly over the list. This finds
+/// damaged data quickly without hashing every file.
+pub fn verify_checksum_sample(dir: &Path, sample_size: usize) -> Result<VerificationReport, String> {
+    let checksums_path = dir.join(CHECKSUMS_FILE_NAME);
+    if !checksums_path.is_file() {
+        return Ok(VerificationReport { unverified: vec!(dir.to_path_buf()), ..VerificationReport::default() });
+    }
+    let text = fs::read_to_string(&checksums_path).map_err(|s| format!("Error reading {}: {}", checksums_path.display(), s.kind()))?;
+    let checksums = parse_checksums(&text).map_err(|s| format!("{}: {}", checksums_path.display(), s))?;
+    let step = checksums.len().div_ceil(sample_size.max(1)).max(1);
+    let sample: Vec<&(String, String)> = checksums.iter().step_by(step).collect();
+
+    Ok(VerificationReport {
+        checked_files: sample.len(),
+        problems: sample.iter().filter_map(|(hash, name)| verify_file(dir, hash, name)).collect(),
+        unverified: vec!(),
+        cancelled: false,
+    })
+}
+
+/// Returns the folders that are verified: the vanilla data and the enabled mods.
+pub fn folders_to_verify(engine_options: &EngineOptions, extra_data_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = find_data_folder(&engine_options.vanilla_data_dir).into_iter().collect();
+    for mod_name in &engine_options.mods {
+        let mod_data_dir = find_mod_dir(extra_data_dir, &engine_options.stracciatella_home, mod_name);
+        dirs.push(mod_data_dir.parent().map(Path::to_path_buf).unwrap_or(mod_data_dir));
+    }
+    dirs
+}
+
+/// Verifies the folders one after the other, the progress is reported after every folder.
+pub fn verify_folders(dirs: &[PathBuf], token: &CancellationToken, progress: &mut dyn FnMut(u64, u64)) -> Result<VerificationReport, String> {
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let mut report = VerificationReport::default();
+    for (i, dir) in dirs.iter().enumerate() {
+        if token.is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+        report.merge(verify_checksums(dir, threads, token)?);
+        progress(i as u64 + 1, dirs.len() as u64);
+    }
+    Ok(report)
+}
+
+pub fn verify_game_data(engine_options: &EngineOptions, extra_data_dir: &Path, token: &CancellationToken) -> Result<VerificationReport, String> {
+    verify_folders(&folders_to_verify(engine_options, extra_data_dir), token, &mut |_, _| {})
+}
+
+#[no_mangle]
+pub extern fn create_cancellation_token() -> *mut CancellationToken {
+    Box::into_raw(Box::new(CancellationToken::default()))
+}
+
+/// Cancels the operation that uses the token, may be called from any thread.
+#[no_mangle]
+pub extern fn cancel_operation(token: *const CancellationToken) -> () {
+    unsafe_from_ptr!(token).cancel();
+}
+
+#[no_mangle]
+pub extern fn free_cancellation_token(token: *mut CancellationToken) -> () {
+    if token.is_null() { return }
+    unsafe { drop(Box::from_raw(token)); }
+}
+
+/// Verifies the game data and returns the report as JSON, null if the hashes could not be read, see
+/// `get_last_error`. Blocks until the verification is finished or cancelled with the token.
+#[no_mangle]
+pub extern fn verify_game_data_json(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char, token: *const CancellationToken) -> *mut c_char {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    match verify_game_data(unsafe_from_ptr!(ptr), Path::new(&extra_data_dir), unsafe_from_ptr!(token)) {
+        Ok(report) => CString::new(json!(report).to_string()).unwrap().into_raw(),
+        Err(s) => {
+            set_last_error(Some(s));
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::Path;
+
+    use sha256::sha256_hex;
+    use super::{CancellationToken, parse_checksums, verify_checksum_sample, verify_checksums};
+
+    fn create_files(dir: &Path) {
+        let mut checksums = String::new();
+        for i in 0..20 {
+            let contents = format!("file {}", i);
+            fs::write(dir.join(format!("{}.slf", i)), &contents).unwrap();
+            checksums.push_str(&format!("{}  {}.slf\n", sha256_hex(contents.as_bytes()), i));
+        }
+        fs::write(dir.join("SHA256SUMS"), checksums).unwrap();
+    }
+
+    #[test]
+    fn parse_checksums_should_read_the_format_of_sha256sum() {
+        let hash = sha256_hex(b"credits");
+        let text = format!("{}  data/Credits.edt\n{} *mod.json\n\n", hash, hash.to_uppercase());
+
+        assert_eq!(parse_checksums(&text), Ok(vec!((hash.clone(), String::from("data/Credits.edt")), (hash.clone(), String::from("mod.json")))));
+        assert_eq!(parse_checksums("abc  file"), Err(String::from("Line 1 is not a valid checksum: abc  file")));
+    }
+
+    #[test]
+    fn verify_checksums_should_report_missing_and_damaged_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = temp_dir.path();
+        create_files(dir);
+        fs::write(dir.join("3.slf"), "damaged").unwrap();
+        fs::remove_file(dir.join("17.slf")).unwrap();
+
+        let report = verify_checksums(dir, 4, &CancellationToken::default()).unwrap();
+
+        assert_eq!(report.checked_files, 20);
+        assert_eq!(report.problems, vec!(format!("{} is damaged", dir.join("3.slf").display()), format!("{} is missing", dir.join("17.slf").display())));
+        assert!(!report.cancelled);
+    }
+
+    #[test]
+    fn verify_checksums_should_stop_when_cancelled() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        create_files(temp_dir.path());
+        let token = CancellationToken::default();
+        token.cancel();
+
+        let report = verify_checksums(temp_dir.path(), 4, &token).unwrap();
+        let unverified = verify_checksums(&temp_dir.path().join("mods"), 4, &CancellationToken::default()).unwrap();
+
+        assert_eq!((report.checked_files, report.cancelled), (0, true));
+        assert_eq!(unverified.unverified, vec!(temp_dir.path().join("mods")));
+    }
+
+    #[test]
+    fn verify_checksum_sample_should_check_a_spread_of_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = temp_dir.path();
+        create_files(dir);
+        fs::write(dir.join("15.slf"), "damaged").unwrap();
+
+        let report = verify_checksum_sample(dir, 4).unwrap();
+
+        assert_eq!(report.checked_files, 4);
+        assert_eq!(report.problems, vec!(format!("{} is damaged", dir.join("15.slf").display())));
+        assert_eq!(verify_checksum_sample(dir, 100).unwrap().checked_files, 20);
+    }
+}
@@ -0,0 +1,76 @@
+//! Commented default configuration that is printed by `ja2 -dump-defaults`.
+//!
+//! ja2.json does not allow comments, so the output is a reference for editing ja2.json by hand and not a valid config.
+
+use EngineOptions;
+use optionregistry::find_option;
+
+fn description(key: &str) -> Option<&'static str> {
+    find_option(key).map(|o| o.description)
+}
+
+/// Returns the key of a line of pretty printed JSON and whether the line opens an object.
+fn line_key(line: &str) -> Option<(&str, bool)> {
+    let rest = line.trim_start().strip_prefix('"')?;
+    let (key, value) = rest.split_once("\":")?;
+    Some((key, value.trim() == "{"))
+}
+
+/// Returns the default ja2.json with a comment above every option.
+pub fn dump_defaults() -> String {
+    let json = serde_json::to_string_pretty(&EngineOptions::default()).expect("Default options should be serializable");
+    let mut path: Vec<&str> = vec!();
+    let mut lines = vec!();
+
+    for line in json.lines() {
+        match line_key(line) {
+            Some((key, opens_object)) => {
+                let full_key = path.iter().chain(Some(&key)).cloned().collect::<Vec<&str>>().join(".");
+                if let Some(d) = description(&full_key) {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    lines.push(format!("{}// {}", indent, d));
+                }
+                if opens_object {
+                    path.push(key);
+                }
+            },
+            None if line.trim_start().starts_with('}') => { path.pop(); },
+            None => {}
+        }
+        lines.push(String::from(line));
+    }
+    lines.join("\n")
+}
+
+/// Checks whether the defaults should be printed. The arguments are checked directly, because dumping the defaults
+/// has to work without a data dir and a valid ja2.json.
+pub fn wants_defaults_dump(args: &[String]) -> bool {
+    args.iter().skip(1).any(|a| a == "-dump-defaults" || a == "--dump-defaults")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use EngineOptions;
+    use super::{dump_defaults, wants_defaults_dump};
+
+    #[test]
+    fn dump_defaults_should_be_the_default_config_with_comments() {
+        let dump = dump_defaults();
+        let without_comments: String = dump.lines().filter(|l| !l.trim_start().starts_with("//")).collect::<Vec<&str>>().join("\n");
+
+        assert!(dump.contains("  // Start the game in the fullscreen mode\n  \"fullscreen\": false,"));
+        assert!(dump.contains("      // Fixes of bonuses\n      \"bonuses\": {"));
+        assert_eq!(serde_json::from_str::<Value>(&without_comments).unwrap(), serde_json::to_value(EngineOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn wants_defaults_dump_should_check_the_arguments() {
+        let args = |a: &[&str]| a.iter().map(|s| String::from(*s)).collect::<Vec<String>>();
+
+        assert!(wants_defaults_dump(&args(&["ja2", "--dump-defaults"])));
+        assert!(wants_defaults_dump(&args(&["ja2", "-datadir", "/ja2", "-dump-defaults"])));
+        assert!(!wants_defaults_dump(&args(&["-dump-defaults"])));
+    }
+}
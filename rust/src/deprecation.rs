@@ -0,0 +1,139 @@
+//! Deprecated options of ja2.json, so options can be renamed or removed without ignoring the settings of players.
+//!
+//! A renamed option is moved to its replacement before the config is deserialized, unless the replacement is set as
+//! well. A removed option is dropped. Both produce a `DeprecationWarning` for the launcher. The old key disappears
+//! the next time ja2.json is written. Keys of nested options are separated by dots, e.g. `gameplay.subtitles`.
+
+use std::ffi::CString;
+
+use libc::c_char;
+use serde_json::{Map, Value};
+
+use EngineOptions;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DeprecatedOption {
+    pub key: &'static str,
+    /// Key that replaces the option, None if it was removed without replacement
+    pub replacement: Option<&'static str>,
+    /// Version that stops reading the key
+    pub removed_in: &'static str,
+}
+
+/// Deprecated options, e.g. `DeprecatedOption { key: "fullscreen", replacement: Some("display.fullscreen"),
+/// removed_in: "0.19" }`. Entries are removed in the listed version.
+pub static DEPRECATED_OPTIONS: &[DeprecatedOption] = &[];
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct DeprecationWarning {
+    pub key: String,
+    pub replacement: Option<String>,
+    pub removed_in: String,
+    /// Whether the value was ignored because the replacement is set as well
+    pub ignored: bool,
+}
+
+impl DeprecationWarning {
+    pub fn message(&self) -> String {
+        match (&self.replacement, self.ignored) {
+            (Some(r), false) => format!("'{}' is deprecated and will be removed in {}, it was applied to '{}'", self.key, self.removed_in, r),
+            (Some(r), true) => format!("'{}' is deprecated and will be removed in {}, it is ignored as '{}' is set", self.key, self.removed_in, r),
+            (None, _) => format!("'{}' is no longer supported and will be removed in {}, it is ignored", self.key, self.removed_in),
+        }
+    }
+}
+
+/// Returns the object that contains the key of the path, with `create` missing objects are added.
+fn parent_object<'a>(config: &'a mut Value, path: &str, create: bool) -> Option<(&'a mut Map<String, Value>, String)> {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let key = String::from(parts.pop()?);
+    let mut current = config;
+    for part in parts {
+        let map = current.as_object_mut()?;
+        if create && !map.contains_key(part) {
+            map.insert(String::from(part), json!({}));
+        }
+        current = map.get_mut(part)?;
+    }
+    current.as_object_mut().map(|m| (m, key))
+}
+
+fn remove_key(config: &mut Value, path: &str) -> Option<Value> {
+    parent_object(config, path, false).and_then(|(map, key)| map.remove(&key))
+}
+
+fn has_key(config: &mut Value, path: &str) -> bool {
+    parent_object(config, path, false).map(|(map, key)| map.contains_key(&key)).unwrap_or(false)
+}
+
+/// Moves or removes the deprecated options in the config and returns a warning for every one that was set.
+pub fn migrate_deprecated_options(config: &mut Value, deprecated: &[DeprecatedOption]) -> Vec<DeprecationWarning> {
+    let mut warnings = vec!();
+    for option in deprecated {
+        let value = match remove_key(config, option.key) {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut ignored = true;
+        if let Some(replacement) = option.replacement {
+            if !has_key(config, replacement) {
+                if let Some((map, key)) = parent_object(config, replacement, true) {
+                    map.insert(key, value);
+                    ignored = false;
+                }
+            }
+        }
+        warnings.push(DeprecationWarning {
+            key: String::from(option.key),
+            replacement: option.replacement.map(String::from),
+            removed_in: String::from(option.removed_in),
+            ignored,
+        });
+    }
+    warnings
+}
+
+/// Returns the deprecation warnings of the loaded config as JSON array.
+#[no_mangle]
+pub extern fn get_deprecation_warnings_json(ptr: *const EngineOptions) -> *mut c_char {
+    let warnings = &unsafe_from_ptr!(ptr).validation_report.deprecations;
+    CString::new(json!(warnings).to_string()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeprecatedOption, DeprecationWarning, migrate_deprecated_options};
+
+    static DEPRECATED: &[DeprecatedOption] = &[
+        DeprecatedOption { key: "subtitles", replacement: Some("gameplay.subtitles"), removed_in: "0.19" },
+        DeprecatedOption { key: "gameplay.old_speed", replacement: Some("gameplay.speed"), removed_in: "0.19" },
+        DeprecatedOption { key: "software_mouse", replacement: None, removed_in: "0.20" },
+    ];
+
+    #[test]
+    fn migrate_deprecated_options_should_move_old_keys() {
+        let mut config = json!({ "subtitles": false, "software_mouse": true, "fullscreen": true });
+
+        let warnings = migrate_deprecated_options(&mut config, DEPRECATED);
+
+        assert_eq!(config, json!({ "gameplay": { "subtitles": false }, "fullscreen": true }));
+        assert_eq!(warnings, vec!(
+            DeprecationWarning { key: String::from("subtitles"), replacement: Some(String::from("gameplay.subtitles")), removed_in: String::from("0.19"), ignored: false },
+            DeprecationWarning { key: String::from("software_mouse"), replacement: None, removed_in: String::from("0.20"), ignored: true },
+        ));
+        assert_eq!(warnings[0].message(), "'subtitles' is deprecated and will be removed in 0.19, it was applied to 'gameplay.subtitles'");
+        assert_eq!(warnings[1].message(), "'software_mouse' is no longer supported and will be removed in 0.20, it is ignored");
+    }
+
+    #[test]
+    fn migrate_deprecated_options_should_prefer_the_replacement() {
+        let mut config = json!({ "gameplay": { "old_speed": 2, "speed": 3 } });
+
+        let warnings = migrate_deprecated_options(&mut config, DEPRECATED);
+
+        assert_eq!(config, json!({ "gameplay": { "speed": 3 } }));
+        assert!(warnings[0].ignored);
+        assert_eq!(warnings[0].message(), "'gameplay.old_speed' is deprecated and will be removed in 0.19, it is ignored as 'gameplay.speed' is set");
+        assert_eq!(migrate_deprecated_options(&mut config, DEPRECATED), vec!());
+    }
+}
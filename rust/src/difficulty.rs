@@ -0,0 +1,152 @@
+//! Difficulty settings from `difficulty.json`.
+
+use std::ffi::CStr;
+use std::path::Path;
+
+use libc::c_char;
+
+use {EngineOptions, set_last_error};
+use gamedata::{game_data_dirs, load_layered_json};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+pub struct DifficultySettings {
+    /// Number of troops the queen can send as reinforcements
+    pub queens_pool_of_troops: u32,
+    /// Size of the initial garrisons in percent of the vanilla garrisons
+    pub initial_garrison_percentage: u32,
+    pub min_enemy_group_size: u8,
+    /// Mine income in percent of the vanilla income
+    pub income_percentage: u32,
+    /// Chance of enemies dropping their items in percent of the vanilla chance
+    pub item_drop_percentage: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Difficulties {
+    pub easy: DifficultySettings,
+    pub medium: DifficultySettings,
+    pub hard: DifficultySettings,
+}
+
+impl Difficulties {
+    /// Returns the settings for a difficulty level as used by the engine (1 = easy, 2 = medium, 3 = hard).
+    pub fn get(&self, level: u8) -> Option<&DifficultySettings> {
+        match level {
+            1 => Some(&self.easy),
+            2 => Some(&self.medium),
+            3 => Some(&self.hard),
+            _ => None
+        }
+    }
+}
+
+pub fn load_difficulties(extra_data_dir: &Path, stracciatella_home: &Path, mods: &[String]) -> Result<Difficulties, String> {
+    let value = load_layered_json(&game_data_dirs(extra_data_dir, stracciatella_home, mods), "difficulty.json")?;
+    serde_json::from_value(value).map_err(|s| format!("Error parsing difficulty.json: {}", s))
+}
+
+/// Loads the settings for a difficulty level, taking the enabled mods into account.
+///
+/// Returns false and sets the last error if difficulty.json could not be loaded or the level is invalid.
+#[no_mangle]
+pub extern fn load_difficulty_settings(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char, level: u8, settings_ptr: *mut DifficultySettings) -> bool {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let settings = unsafe_from_ptr_mut!(settings_ptr);
+
+    let engine_options = unsafe_from_ptr!(ptr);
+
+    match load_difficulties(Path::new(&extra_data_dir), &engine_options.stracciatella_home, &engine_options.mods) {
+        Ok(difficulties) => match difficulties.get(level) {
+            Some(s) => {
+                *settings = *s;
+                true
+            },
+            None => {
+                set_last_error(Some(format!("Invalid difficulty level {}", level)));
+                false
+            }
+        },
+        Err(e) => {
+            set_last_error(Some(e));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use EngineOptions;
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    fn copy_vanilla_difficulty_json(extra_data_dir: &Path) {
+        let vanilla = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/externalized/difficulty.json");
+        fs::create_dir_all(extra_data_dir.join("externalized")).unwrap();
+        fs::copy(vanilla, extra_data_dir.join("externalized/difficulty.json")).unwrap();
+    }
+
+    #[test]
+    fn load_difficulties_should_load_vanilla_values() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        copy_vanilla_difficulty_json(temp_dir.path());
+
+        let difficulties = super::load_difficulties(temp_dir.path(), temp_dir.path(), &[]).unwrap();
+
+        assert_eq!(difficulties.easy.queens_pool_of_troops, 150);
+        assert_eq!(difficulties.medium.initial_garrison_percentage, 100);
+        assert_eq!(difficulties.hard.min_enemy_group_size, 6);
+        assert_eq!(difficulties.get(2), Some(&difficulties.medium));
+        assert_eq!(difficulties.get(4), None);
+    }
+
+    #[test]
+    fn load_difficulties_should_apply_mod_overrides() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        copy_vanilla_difficulty_json(temp_dir.path());
+        write_file(&temp_dir.path().join("mods/rich/data/difficulty.json"), b"{ \"hard\": { \"income_percentage\": 150 } }");
+
+        let difficulties = super::load_difficulties(temp_dir.path(), temp_dir.path(), &[String::from("rich")]).unwrap();
+
+        assert_eq!(difficulties.hard.income_percentage, 150);
+        assert_eq!(difficulties.hard.queens_pool_of_troops, 400);
+        assert_eq!(difficulties.easy.income_percentage, 100);
+    }
+
+    #[test]
+    fn load_difficulties_should_fail_with_missing_level() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write_file(&temp_dir.path().join("externalized/difficulty.json"), b"{ \"easy\": {} }");
+
+        let result = super::load_difficulties(temp_dir.path(), temp_dir.path(), &[]);
+
+        assert_eq!(result, Err(String::from("Error parsing difficulty.json: missing field `queens_pool_of_troops`")));
+    }
+
+    #[test]
+    fn load_difficulty_settings_should_fill_in_the_settings_of_the_level() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = EngineOptions::default();
+        let extra_data_dir = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let mut settings = super::DifficultySettings { queens_pool_of_troops: 0, initial_garrison_percentage: 0, min_enemy_group_size: 0, income_percentage: 0, item_drop_percentage: 0 };
+
+        assert!(!super::load_difficulty_settings(&engine_options, extra_data_dir.as_ptr(), 1, &mut settings));
+
+        copy_vanilla_difficulty_json(temp_dir.path());
+
+        assert!(super::load_difficulty_settings(&engine_options, extra_data_dir.as_ptr(), 1, &mut settings));
+        assert_eq!(settings.min_enemy_group_size, 3);
+        assert!(!super::load_difficulty_settings(&engine_options, extra_data_dir.as_ptr(), 0, &mut settings));
+    }
+}
@@ -0,0 +1,154 @@
+//! Errors with context: the layer that failed, the file it was working on and the error that caused it.
+//!
+//! An `ErrorContext` displays as the one line messages the other functions return, e.g. `Error reading
+//! /ja2/Data/maps.slf: entity not found`, and converts to `String` with `?`. Its `diagnostic` has one line per layer
+//! with the path and the location in the source code, which `get_last_error_diagnostic` returns to the launcher.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::iter;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub enum ErrorKind {
+    IO,
+    CONFIG,
+    RESOURCES,
+    MODS,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::IO => "io",
+            ErrorKind::CONFIG => "config",
+            ErrorKind::RESOURCES => "resources",
+            ErrorKind::MODS => "mods",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ErrorContext {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// File or folder the failed operation was working on
+    pub path: Option<PathBuf>,
+    /// Location in the source code that created the context
+    pub location: &'static Location<'static>,
+    pub source: Option<Box<ErrorContext>>,
+}
+
+impl ErrorContext {
+    #[track_caller]
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> ErrorContext {
+        ErrorContext { kind, message: message.into(), path: None, location: Location::caller(), source: None }
+    }
+
+    /// Returns the context of an IO error with the file it happened to.
+    #[track_caller]
+    pub fn io(error: &io::Error, path: &Path) -> ErrorContext {
+        ErrorContext::new(ErrorKind::IO, error.kind().to_string()).with_path(path)
+    }
+
+    pub fn with_path(mut self, path: &Path) -> ErrorContext {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Returns a context of a higher layer that was caused by this one.
+    #[track_caller]
+    pub fn wrap<S: Into<String>>(self, kind: ErrorKind, message: S) -> ErrorContext {
+        ErrorContext { source: Some(Box::new(self)), ..ErrorContext::new(kind, message) }
+    }
+
+    /// Returns this context and its sources, from the highest to the lowest layer.
+    pub fn chain(&self) -> impl Iterator<Item = &ErrorContext> {
+        iter::successors(Some(self), |c| c.source.as_deref())
+    }
+
+    /// Returns the message followed by one line per layer with its path and source location.
+    pub fn diagnostic(&self) -> String {
+        let mut lines = vec!(self.to_string());
+        for context in self.chain() {
+            lines.push(format!("  {}: {} ({}:{})", context.kind.name(), context.message, context.location.file(), context.location.line()));
+            if let Some(ref path) = context.path {
+                lines.push(format!("    path: {}", path.display()));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<&str> = self.chain().map(|c| c.message.as_str()).collect();
+        write!(f, "{}", messages.join(": "))
+    }
+}
+
+impl Error for ErrorContext {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|s| s as &(dyn Error + 'static))
+    }
+}
+
+impl From<ErrorContext> for String {
+    fn from(error: ErrorContext) -> String {
+        error.to_string()
+    }
+}
+
+pub trait ResultExt<T> {
+    /// Wraps the error in a context of a higher layer.
+    #[track_caller]
+    fn context<S: Into<String>>(self, kind: ErrorKind, message: S) -> Result<T, ErrorContext>;
+}
+
+impl<T> ResultExt<T> for Result<T, ErrorContext> {
+    #[track_caller]
+    fn context<S: Into<String>>(self, kind: ErrorKind, message: S) -> Result<T, ErrorContext> {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(e.wrap(kind, message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use super::{ErrorContext, ErrorKind, ResultExt};
+
+    fn read_config() -> Result<(), ErrorContext> {
+        let error = io::Error::new(io::ErrorKind::NotFound, "missing");
+        Err(ErrorContext::io(&error, Path::new("/home/.ja2/ja2.json")))
+            .context(ErrorKind::CONFIG, "Error reading ja2.json config file")
+    }
+
+    #[test]
+    fn error_context_should_display_the_messages_of_all_layers() {
+        let error = read_config().unwrap_err();
+
+        assert_eq!(error.to_string(), "Error reading ja2.json config file: entity not found");
+        assert_eq!(String::from(error.clone()), "Error reading ja2.json config file: entity not found");
+        assert_eq!(error.chain().map(|c| c.kind).collect::<Vec<_>>(), vec!(ErrorKind::CONFIG, ErrorKind::IO));
+    }
+
+    #[test]
+    fn diagnostic_should_show_paths_and_source_locations() {
+        let error = read_config().unwrap_err();
+        let line = error.location.line();
+
+        assert_eq!(error.location.file(), file!());
+        assert_eq!(error.diagnostic(), format!("Error reading ja2.json config file: entity not found\n  \
+            config: Error reading ja2.json config file ({file}:{line})\n  \
+            io: entity not found ({file}:{io_line})\n    \
+            path: /home/.ja2/ja2.json", file = file!(), line = line, io_line = line - 1));
+    }
+}
@@ -0,0 +1,123 @@
+//! Events of the library that the engine and the launcher can subscribe to.
+//!
+//! Callbacks are registered with a pointer to user data that is passed back unchanged. They are called on the thread
+//! that caused the event, after the change is complete.
+
+use std::sync::Mutex;
+
+use libc::c_void;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum EngineEvent {
+    /// The engine options were replaced, e.g. by `reload_engine_options` or `import_settings_snippet`
+    CONFIG_RELOADED,
+    MODS_CHANGED,
+    CACHE_CLEARED,
+}
+
+pub type EventCallback = extern fn(EngineEvent, *mut c_void);
+
+#[derive(Clone)]
+struct Registration {
+    id: u32,
+    event: EngineEvent,
+    callback: EventCallback,
+    /// The pointer to the user data is never dereferenced by the library
+    user_data: usize,
+}
+
+#[derive(Default)]
+pub struct EventCallbacks {
+    registrations: Vec<Registration>,
+    last_id: u32,
+}
+
+/// Callbacks that are called by `emit_event`.
+static EVENT_CALLBACKS: Mutex<Option<EventCallbacks>> = Mutex::new(None);
+
+impl EventCallbacks {
+    /// Registers a callback and returns the id to unregister it, ids are never 0.
+    pub fn register(&mut self, event: EngineEvent, callback: EventCallback, user_data: *mut c_void) -> u32 {
+        self.last_id = self.last_id.wrapping_add(1).max(1);
+        self.registrations.push(Registration { id: self.last_id, event, callback, user_data: user_data as usize });
+        self.last_id
+    }
+
+    /// Returns false if no callback was registered with the id.
+    pub fn unregister(&mut self, id: u32) -> bool {
+        let length = self.registrations.len();
+        self.registrations.retain(|r| r.id != id);
+        self.registrations.len() != length
+    }
+
+    fn callbacks_for(&self, event: EngineEvent) -> Vec<Registration> {
+        self.registrations.iter().filter(|r| r.event == event).cloned().collect()
+    }
+
+    /// Calls all callbacks that are registered for the event in the order of registration.
+    pub fn emit(&self, event: EngineEvent) {
+        for r in self.callbacks_for(event) {
+            (r.callback)(event, r.user_data as *mut c_void);
+        }
+    }
+}
+
+/// Calls the callbacks that are registered for the event. The callbacks may register and unregister callbacks.
+pub fn emit_event(event: EngineEvent) {
+    let callbacks = match *EVENT_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref c) => c.callbacks_for(event),
+        None => return
+    };
+    for r in callbacks {
+        (r.callback)(event, r.user_data as *mut c_void);
+    }
+}
+
+#[no_mangle]
+pub extern fn register_event_callback(event: EngineEvent, callback: EventCallback, user_data: *mut c_void) -> u32 {
+    let mut callbacks = EVENT_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+    callbacks.get_or_insert_with(EventCallbacks::default).register(event, callback, user_data)
+}
+
+#[no_mangle]
+pub extern fn unregister_event_callback(id: u32) -> bool {
+    let mut callbacks = EVENT_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+    callbacks.as_mut().map(|c| c.unregister(id)).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use libc::c_void;
+
+    use super::{EngineEvent, EventCallbacks};
+
+    extern fn count_event(_event: EngineEvent, user_data: *mut c_void) {
+        let counter = unsafe { &*(user_data as *const AtomicUsize) };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn emit_should_only_call_the_callbacks_of_the_event() {
+        let mut callbacks = EventCallbacks::default();
+        let mods_changed = AtomicUsize::new(0);
+        let cache_cleared = AtomicUsize::new(0);
+        let id = callbacks.register(EngineEvent::MODS_CHANGED, count_event, &mods_changed as *const _ as *mut c_void);
+        callbacks.register(EngineEvent::CACHE_CLEARED, count_event, &cache_cleared as *const _ as *mut c_void);
+
+        callbacks.emit(EngineEvent::MODS_CHANGED);
+        callbacks.emit(EngineEvent::MODS_CHANGED);
+        callbacks.emit(EngineEvent::CONFIG_RELOADED);
+
+        assert_eq!(mods_changed.load(Ordering::SeqCst), 2);
+        assert_eq!(cache_cleared.load(Ordering::SeqCst), 0);
+
+        assert!(callbacks.unregister(id));
+        assert!(!callbacks.unregister(id));
+        callbacks.emit(EngineEvent::MODS_CHANGED);
+        assert_eq!(mods_changed.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,60 @@
+//! Plan of the files an operation writes, e.g. for a dry run that the launcher shows before importing the data.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Whether an existing file is replaced
+    pub overwrites: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct FilePlan {
+    pub files: Vec<PlannedFile>,
+    /// Files that exist with the same contents and are not written
+    pub unchanged: Vec<PathBuf>,
+    /// Additional disk space for the written files, replaced files free their size
+    pub bytes_needed: u64,
+}
+
+impl FilePlan {
+    /// Adds a file that is written with the given size. Must be called before the file is written.
+    pub fn add(&mut self, path: &Path, size: u64) {
+        let existing_size = fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len());
+        self.bytes_needed += size.saturating_sub(existing_size.unwrap_or(0));
+        self.files.push(PlannedFile { path: path.to_path_buf(), size, overwrites: existing_size.is_some() });
+    }
+
+    pub fn add_unchanged(&mut self, path: &Path) {
+        self.unchanged.push(path.to_path_buf());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::{FilePlan, PlannedFile};
+
+    #[test]
+    fn add_should_detect_overwritten_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let existing = temp_dir.path().join("maps.slf");
+        fs::write(&existing, [0u8; 100]).unwrap();
+        let mut plan = FilePlan::default();
+
+        plan.add(&temp_dir.path().join("tilesets.slf"), 50);
+        plan.add(&existing, 120);
+        plan.add_unchanged(&temp_dir.path().join("sounds.slf"));
+
+        assert_eq!(plan.files[0], PlannedFile { path: temp_dir.path().join("tilesets.slf"), size: 50, overwrites: false });
+        assert!(plan.files[1].overwrites);
+        assert_eq!(plan.bytes_needed, 70);
+        assert_eq!(plan.unchanged.len(), 1);
+    }
+}
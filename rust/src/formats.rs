@@ -0,0 +1,7 @@
+//! Parsers for file formats of the game and mod data that are not needed by the engine itself, e.g. for tools that
+//! check or convert the data of mods.
+
+pub mod ini;
+pub mod jsd;
+pub mod map;
+pub mod xml;
@@ -0,0 +1,159 @@
+//! Parser for INI files like the data tables of old mods and the configuration files of vanilla and Gold.
+//!
+//! Lines are `[section]` headers or `key = value` entries, `;` and `#` start comment lines. Entries before the first
+//! section belong to a section with an empty name. Values may be enclosed in double quotes.
+//!
+//! `parse_lenient` accepts the files that the vanilla game wrote: it skips a byte order mark, reads files that are not
+//! UTF-8 as Windows-1252, strips comments after values and ignores broken lines with a warning instead of failing.
+
+use serde_json::{Map, Value};
+
+/// Characters of Windows-1252 for the bytes 0x80 to 0x9F, the other bytes are the same as in Latin-1
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes UTF-8 without byte order mark, or Windows-1252 if the bytes are not valid UTF-8.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match ::std::str::from_utf8(bytes) {
+        Ok(s) => String::from(s),
+        Err(_) => bytes.iter().map(|b| match *b {
+            0x80..=0x9F => WINDOWS_1252[(*b - 0x80) as usize],
+            _ => char::from(*b),
+        }).collect(),
+    }
+}
+
+/// Removes a comment that starts with ` ;` or ` #` outside of double quotes.
+fn strip_comment(value: &str) -> &str {
+    let mut in_quotes = false;
+    let mut previous = ' ';
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' | '#' if !in_quotes && previous.is_whitespace() => return value[..i].trim_end(),
+            _ => {}
+        }
+        previous = c;
+    }
+    value
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct IniSection {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct IniFile {
+    pub sections: Vec<IniSection>,
+}
+
+impl IniFile {
+    pub fn parse(ini: &str) -> Result<IniFile, String> {
+        IniFile::parse_lines(ini, false).map(|(ini, _)| ini)
+    }
+
+    /// Parses a file like the vanilla game and returns the warnings for the lines that were ignored.
+    pub fn parse_lenient(bytes: &[u8]) -> (IniFile, Vec<String>) {
+        IniFile::parse_lines(&decode_text(bytes), true).expect("Lenient parsing should not fail")
+    }
+
+    fn parse_lines(ini: &str, lenient: bool) -> Result<(IniFile, Vec<String>), String> {
+        let mut sections = vec!(IniSection::default());
+        let mut warnings = vec!();
+        for (i, line) in ini.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let mut problem = |message: &str| {
+                let message = format!("INI error in line {}: {}", i + 1, message);
+                if lenient { warnings.push(message); Ok(()) } else { Err(message) }
+            };
+            if let Some(name) = line.strip_prefix('[') {
+                let name = match name.strip_suffix(']') {
+                    Some(name) => name,
+                    None => {
+                        problem("] expected")?;
+                        strip_comment(name).trim_end_matches(']')
+                    }
+                };
+                sections.push(IniSection { name: String::from(name.trim()), entries: vec!() });
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some(entry) => entry,
+                None => {
+                    problem("= expected")?;
+                    continue;
+                }
+            };
+            let value = if lenient { strip_comment(value.trim()) } else { value.trim() };
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            sections.last_mut().unwrap().entries.push((String::from(key.trim()), String::from(value)));
+        }
+        if sections[0].entries.is_empty() {
+            sections.remove(0);
+        }
+        Ok((IniFile { sections }, warnings))
+    }
+
+    /// Returns the sections as JSON object of objects with string values. Sections and keys that occur more than once
+    /// are merged, later values win.
+    pub fn to_json(&self) -> Value {
+        let mut sections = Map::new();
+        for section in &self.sections {
+            let entries = sections.entry(section.name.clone()).or_insert_with(|| json!({}));
+            for (key, value) in &section.entries {
+                entries[key] = json!(value);
+            }
+        }
+        Value::Object(sections)
+    }
+
+    /// Returns the value of a key, sections and keys are compared case-insensitively.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.iter()
+            .filter(|s| s.name.eq_ignore_ascii_case(section))
+            .flat_map(|s| s.entries.iter())
+            .find(|&(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IniFile;
+
+    #[test]
+    fn parse_should_read_sections_and_entries() {
+        let ini = IniFile::parse("version = 2\n; comment\n[Weapon 1]\nszWeaponName = \"Glock 17\"\n\n# comment\n[weapon 2]\nubImpact=25\n").unwrap();
+
+        assert_eq!(ini.sections.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(), vec!("", "Weapon 1", "weapon 2"));
+        assert_eq!(ini.get("", "version"), Some("2"));
+        assert_eq!(ini.get("weapon 1", "SZWEAPONNAME"), Some("Glock 17"));
+        assert_eq!(ini.get("Weapon 2", "ubImpact"), Some("25"));
+        assert_eq!(ini.get("Weapon 2", "ubDeadliness"), None);
+    }
+
+    #[test]
+    fn parse_should_fail_for_invalid_lines() {
+        assert_eq!(IniFile::parse("[a]\nkey"), Err(String::from("INI error in line 2: = expected")));
+        assert_eq!(IniFile::parse("[a"), Err(String::from("INI error in line 1: ] expected")));
+    }
+
+    #[test]
+    fn parse_lenient_should_accept_vanilla_files() {
+        let (ini, warnings) = IniFile::parse_lenient(b"\xEF\xBB\xBF[Options]\r\nName = M\xFCller ; comment\r\nbroken\r\nText = \"a ; b\"\r\n[Shades\r\nFactor=2#x\r\n[Options]\r\nName = Ira\r\n");
+
+        assert_eq!(warnings, vec!(String::from("INI error in line 3: = expected"), String::from("INI error in line 5: ] expected")));
+        assert_eq!(ini.get("options", "name"), Some("M\u{FC}ller"));
+        assert_eq!(ini.get("Options", "Text"), Some("a ; b"));
+        assert_eq!(ini.get("Shades", "Factor"), Some("2#x"));
+        assert_eq!(ini.to_json(), json!({ "Options": { "Name": "Ira", "Text": "a ; b" }, "Shades": { "Factor": "2#x" } }));
+    }
+}
@@ -0,0 +1,245 @@
+//! Parser for the structure data in JSD files, e.g. `anims/structdata/m_stand.jsd` for the bodies of soldiers.
+//!
+//! A file starts with a header of 16 bytes. Files with auxiliary image data have an entry of 16 bytes for every
+//! structure, followed by the relative tile locations. Files with structure data have a sparse list of structures,
+//! each with 16 bytes followed by 32 bytes for every tile. All numbers are little endian.
+
+const HEADER_SIZE: usize = 16;
+const AUX_DATA_SIZE: usize = 16;
+const TILE_LOC_SIZE: usize = 2;
+const STRUCTURE_SIZE: usize = 16;
+const TILE_SIZE: usize = 32;
+const ID: &[u8] = b"J2SD";
+
+pub const STRUCTURE_FILE_CONTAINS_AUXIMAGEDATA: u8 = 0x01;
+pub const STRUCTURE_FILE_CONTAINS_STRUCTUREDATA: u8 = 0x02;
+pub const PROFILE_X_SIZE: usize = 5;
+pub const PROFILE_Y_SIZE: usize = 5;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct AuxObjectData {
+    pub wall_orientation: u8,
+    pub number_of_tiles: u8,
+    pub tile_loc_index: u16,
+    pub current_frame: u8,
+    pub number_of_frames: u8,
+    pub flags: u8,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct StructureTile {
+    pub x_pos_rel_to_base: i8,
+    pub y_pos_rel_to_base: i8,
+    /// Bits of the heights that are filled, by x and y
+    pub shape: [[u8; PROFILE_Y_SIZE]; PROFILE_X_SIZE],
+    pub flags: u8,
+    pub vehicle_hit_location: u8,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Structure {
+    pub structure_number: u16,
+    pub armour: u8,
+    pub density: u8,
+    pub flags: u32,
+    pub wall_orientation: u8,
+    /// Greater than 0 for the number of the debris plus 1, less than 0 for a partner graphic
+    pub destruction_partner: i8,
+    pub partner_delta: i8,
+    pub z_tile_offset_x: i8,
+    pub z_tile_offset_y: i8,
+    pub tiles: Vec<StructureTile>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct StructureFile {
+    /// Number of structures including the ones that are not stored
+    pub number_of_structures: u16,
+    /// Empty if the file has no auxiliary image data
+    pub aux_data: Vec<AuxObjectData>,
+    /// Tile offsets x and y of the multi-tile images
+    pub tile_locs: Vec<(i8, i8)>,
+    /// Empty if the file has no structure data
+    pub structures: Vec<Structure>,
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+}
+
+/// Returns the next `length` bytes and advances the offset, or an error if there are not enough bytes.
+fn take<'a>(bytes: &'a [u8], offset: &mut usize, length: usize, what: &str) -> Result<&'a [u8], String> {
+    let available = bytes.len() - *offset;
+    if length > available {
+        return Err(format!("Structure file is truncated: {} needs {} bytes, {} are left", what, length, available));
+    }
+    let slice = &bytes[*offset..*offset + length];
+    *offset += length;
+    Ok(slice)
+}
+
+fn parse_tile(t: &[u8]) -> StructureTile {
+    let mut shape = [[0u8; PROFILE_Y_SIZE]; PROFILE_X_SIZE];
+    for (x, row) in shape.iter_mut().enumerate() {
+        row.copy_from_slice(&t[4 + x * PROFILE_Y_SIZE..4 + (x + 1) * PROFILE_Y_SIZE]);
+    }
+    StructureTile {
+        x_pos_rel_to_base: t[2] as i8,
+        y_pos_rel_to_base: t[3] as i8,
+        shape,
+        flags: t[29],
+        vehicle_hit_location: t[30],
+    }
+}
+
+impl StructureFile {
+    pub fn parse(bytes: &[u8]) -> Result<StructureFile, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[..ID.len()] != ID {
+            return Err(String::from("Structure file has an invalid header"));
+        }
+        let number_of_structures = read_u16(&bytes[4..]);
+        let number_of_structures_stored = read_u16(&bytes[6..]);
+        let data_size = read_u16(&bytes[8..]) as usize;
+        let flags = bytes[10];
+        let number_of_tile_locs = read_u16(&bytes[14..]) as usize;
+        if number_of_structures == 0 {
+            return Err(String::from("Structure file has no structures"));
+        }
+
+        let mut offset = HEADER_SIZE;
+        let mut aux_data = vec!();
+        let mut tile_locs = vec!();
+        if flags & STRUCTURE_FILE_CONTAINS_AUXIMAGEDATA != 0 {
+            let data = take(bytes, &mut offset, AUX_DATA_SIZE * number_of_structures as usize, "auxiliary image data")?;
+            aux_data = data.chunks(AUX_DATA_SIZE).map(|a| AuxObjectData {
+                wall_orientation: a[0],
+                number_of_tiles: a[1],
+                tile_loc_index: read_u16(&a[2..]),
+                current_frame: a[7],
+                number_of_frames: a[8],
+                flags: a[9],
+            }).collect();
+            let locs = take(bytes, &mut offset, TILE_LOC_SIZE * number_of_tile_locs, "tile locations")?;
+            tile_locs = locs.chunks(TILE_LOC_SIZE).map(|l| (l[0] as i8, l[1] as i8)).collect();
+        }
+
+        let mut structures = vec!();
+        if flags & STRUCTURE_FILE_CONTAINS_STRUCTUREDATA != 0 {
+            let data = take(bytes, &mut offset, data_size, "structure data")?;
+            let mut data_offset = 0;
+            for _ in 0..number_of_structures_stored {
+                let s = take(data, &mut data_offset, STRUCTURE_SIZE, "structure")?;
+                let number_of_tiles = s[3] as usize;
+                let tiles = take(data, &mut data_offset, TILE_SIZE * number_of_tiles, "structure tiles")?;
+                structures.push(Structure {
+                    structure_number: read_u16(&s[8..]),
+                    armour: s[0],
+                    density: s[2],
+                    flags: read_u32(&s[4..]),
+                    wall_orientation: s[10],
+                    destruction_partner: s[11] as i8,
+                    partner_delta: s[12] as i8,
+                    z_tile_offset_x: s[13] as i8,
+                    z_tile_offset_y: s[14] as i8,
+                    tiles: tiles.chunks(TILE_SIZE).map(parse_tile).collect(),
+                });
+            }
+        }
+
+        Ok(StructureFile { number_of_structures, aux_data, tile_locs, structures })
+    }
+
+    /// Checks the references between the parts of the file, which the engine does not check.
+    pub fn validate(&self) -> Result<(), String> {
+        for s in &self.structures {
+            if s.structure_number >= self.number_of_structures {
+                return Err(format!("Structure {} is out of range, the file has {} structures", s.structure_number, self.number_of_structures));
+            }
+            if s.tiles.is_empty() {
+                return Err(format!("Structure {} has no tiles", s.structure_number));
+            }
+        }
+        for (i, a) in self.aux_data.iter().enumerate() {
+            if a.number_of_tiles > 0 && a.tile_loc_index as usize + a.number_of_tiles as usize > self.tile_locs.len() {
+                return Err(format!("Image {} uses tile locations that do not exist", i));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::StructureFile;
+
+    /// Writes a file with the given auxiliary data `(number of tiles, tile loc index)` and structures
+    /// `(structure number, number of tiles)`.
+    pub fn structure_file(number_of_structures: u16, aux_data: &[(u8, u16)], tile_locs: usize, structures: &[(u16, u8)]) -> Vec<u8> {
+        let data_size: usize = structures.iter().map(|&(_, t)| 16 + 32 * t as usize).sum();
+        let flags = if aux_data.is_empty() { 0 } else { 1 } | if structures.is_empty() { 0 } else { 2 };
+        let mut bytes = b"J2SD".to_vec();
+        bytes.extend_from_slice(&number_of_structures.to_le_bytes());
+        bytes.extend_from_slice(&(structures.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(data_size as u16).to_le_bytes());
+        bytes.extend_from_slice(&[flags, 0, 0, 0]);
+        bytes.extend_from_slice(&(tile_locs as u16).to_le_bytes());
+        for &(tiles, index) in aux_data {
+            let mut a = vec!(0u8; 16);
+            a[1] = tiles;
+            a[2..4].copy_from_slice(&index.to_le_bytes());
+            bytes.extend(a);
+        }
+        bytes.extend(vec!(1u8; tile_locs * 2));
+        for &(number, tiles) in structures {
+            let mut s = vec!(0u8; 16);
+            s[0] = 20;
+            s[3] = tiles;
+            s[8..10].copy_from_slice(&number.to_le_bytes());
+            bytes.extend(s);
+            for t in 0..tiles {
+                let mut tile = vec!(0u8; 32);
+                tile[2] = t;
+                tile[4] = 0x0F;
+                bytes.extend(tile);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_should_read_aux_data_and_structures() {
+        let file = StructureFile::parse(&structure_file(8, &[(0, 0); 8], 0, &[(0, 1), (5, 2)])).unwrap();
+
+        assert_eq!(file.number_of_structures, 8);
+        assert_eq!(file.aux_data.len(), 8);
+        assert_eq!(file.structures.iter().map(|s| (s.structure_number, s.tiles.len())).collect::<Vec<(u16, usize)>>(), vec!((0, 1), (5, 2)));
+        assert_eq!(file.structures[1].tiles[1].x_pos_rel_to_base, 1);
+        assert_eq!(file.structures[0].tiles[0].shape[0], [0x0F, 0, 0, 0, 0]);
+        assert_eq!(file.structures[0].armour, 20);
+        assert_eq!(file.validate(), Ok(()));
+    }
+
+    #[test]
+    fn parse_should_fail_for_invalid_files() {
+        assert_eq!(StructureFile::parse(b"J2SX"), Err(String::from("Structure file has an invalid header")));
+        assert_eq!(StructureFile::parse(&structure_file(0, &[], 0, &[])), Err(String::from("Structure file has no structures")));
+        let bytes = structure_file(2, &[], 0, &[(0, 2)]);
+        assert!(StructureFile::parse(&bytes[..bytes.len() - 1]).unwrap_err().starts_with("Structure file is truncated: structure data needs 80 bytes"));
+    }
+
+    #[test]
+    fn validate_should_detect_broken_references() {
+        let out_of_range = StructureFile::parse(&structure_file(2, &[], 0, &[(2, 1)])).unwrap();
+        assert_eq!(out_of_range.validate(), Err(String::from("Structure 2 is out of range, the file has 2 structures")));
+
+        let without_tiles = StructureFile::parse(&structure_file(2, &[], 0, &[(1, 0)])).unwrap();
+        assert_eq!(without_tiles.validate(), Err(String::from("Structure 1 has no tiles")));
+
+        let missing_tile_locs = StructureFile::parse(&structure_file(1, &[(2, 1)], 2, &[])).unwrap();
+        assert_eq!(missing_tile_locs.validate(), Err(String::from("Image 0 uses tile locations that do not exist")));
+    }
+}
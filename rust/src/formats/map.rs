@@ -0,0 +1,176 @@
+//! Parser for the header of the sector maps in `maps/*.dat`, e.g. to list the maps of a mod.
+//!
+//! A map starts with the major version as a float, the minor version since major version 4, the flags of the saved
+//! sections and the tileset. After the size of the soldiers and the heights of the tiles follow the numbers of tiles
+//! in each layer of every grid, 4 bytes with two 4 bit counts each. All numbers are little endian.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+pub const WORLD_COLS: usize = 160;
+pub const WORLD_ROWS: usize = 160;
+pub const WORLD_MAX: usize = WORLD_COLS * WORLD_ROWS;
+pub const NUM_TILESETS: i32 = 50;
+/// Latest major version, the Russian version has its own maps with major version 6
+pub const MAJOR_MAP_VERSION: f32 = 5.0;
+pub const MAJOR_MAP_VERSION_RUSSIAN: f32 = 6.0;
+
+pub const MAP_FULLSOLDIER_SAVED: u32 = 0x01;
+pub const MAP_WORLDLIGHTS_SAVED: u32 = 0x04;
+pub const MAP_WORLDITEMS_SAVED: u32 = 0x08;
+pub const MAP_EXITGRIDS_SAVED: u32 = 0x10;
+pub const MAP_DOORTABLE_SAVED: u32 = 0x20;
+pub const MAP_EDGEPOINTS_SAVED: u32 = 0x40;
+pub const MAP_AMBIENTLIGHTLEVEL_SAVED: u32 = 0x80;
+pub const MAP_NPCSCHEDULES_SAVED: u32 = 0x100;
+
+const HEIGHTS_SIZE: usize = WORLD_MAX * 2;
+const LAYER_COUNTS_SIZE: usize = WORLD_MAX * 4;
+
+/// Number of tiles in each layer of the map.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct LayerCounts {
+    pub land: u32,
+    pub objects: u32,
+    pub structs: u32,
+    pub shadows: u32,
+    pub roofs: u32,
+    pub on_roofs: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapHeader {
+    pub major_version: f32,
+    /// 0 for maps before major version 4
+    pub minor_version: u8,
+    pub flags: u32,
+    pub tileset_id: i32,
+    pub layer_counts: LayerCounts,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+}
+
+impl MapHeader {
+    /// Parses the header and the layer counts, `bytes` may be the whole map.
+    pub fn parse(bytes: &[u8]) -> Result<MapHeader, String> {
+        if bytes.len() < 4 {
+            return Err(String::from("Map is truncated: the version is missing"));
+        }
+        let major_version = f32::from_bits(read_u32(bytes));
+        let (minor_version, offset) = if major_version >= 4.0 { (bytes.get(4).cloned().unwrap_or(0), 5) } else { (0, 4) };
+        // Flags, tileset and soldier size
+        let size = offset + 12 + HEIGHTS_SIZE + LAYER_COUNTS_SIZE;
+        if bytes.len() < size {
+            return Err(format!("Map is truncated: {} of {} header bytes", bytes.len(), size));
+        }
+
+        let mut layer_counts = LayerCounts::default();
+        let counts_start = offset + 12 + HEIGHTS_SIZE;
+        for c in bytes[counts_start..size].chunks(4) {
+            layer_counts.land += u32::from(c[0] & 0x0F);
+            layer_counts.objects += u32::from(c[1] & 0x0F);
+            layer_counts.structs += u32::from(c[1] >> 4);
+            layer_counts.shadows += u32::from(c[2] & 0x0F);
+            layer_counts.roofs += u32::from(c[2] >> 4);
+            layer_counts.on_roofs += u32::from(c[3] & 0x0F);
+        }
+
+        Ok(MapHeader {
+            major_version,
+            minor_version,
+            flags: read_u32(&bytes[offset..]),
+            tileset_id: read_u32(&bytes[offset + 4..]) as i32,
+            layer_counts,
+        })
+    }
+
+    /// Checks the values the engine relies on, it does not detect all problems.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.major_version.is_finite() || self.major_version <= 0.0 || self.major_version > MAJOR_MAP_VERSION_RUSSIAN {
+            return Err(format!("Map has an invalid major version {}", self.major_version));
+        }
+        if self.tileset_id < 0 || self.tileset_id >= NUM_TILESETS {
+            return Err(format!("Map has an invalid tileset {}, expected 0 to {}", self.tileset_id, NUM_TILESETS - 1));
+        }
+        if self.layer_counts.land == 0 {
+            return Err(String::from("Map has no land tiles"));
+        }
+        Ok(())
+    }
+
+    /// Maps of an older major version are converted by the engine when they are loaded, which is slower.
+    pub fn is_outdated(&self) -> bool {
+        self.major_version < MAJOR_MAP_VERSION
+    }
+}
+
+pub fn read_map_header(path: &Path) -> Result<MapHeader, String> {
+    let mut bytes = vec!();
+    File::open(path)
+        .and_then(|f| f.take((5 + 12 + HEIGHTS_SIZE + LAYER_COUNTS_SIZE) as u64).read_to_end(&mut bytes))
+        .map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    MapHeader::parse(&bytes).map_err(|s| format!("{}: {}", path.display(), s))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::{LayerCounts, MapHeader, WORLD_MAX, read_map_header};
+
+    fn map(major_version: f32, tileset_id: i32) -> Vec<u8> {
+        let mut bytes = vec!();
+        bytes.extend_from_slice(&major_version.to_bits().to_le_bytes());
+        if major_version >= 4.0 {
+            bytes.push(25);
+        }
+        bytes.extend_from_slice(&0x18u32.to_le_bytes());
+        bytes.extend_from_slice(&tileset_id.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend(vec!(0; WORLD_MAX * 2));
+        for i in 0..WORLD_MAX {
+            // One land tile everywhere, a struct and two objects on the first grid
+            bytes.extend_from_slice(if i == 0 { &[0x31, 0x12, 0x00, 0x00] } else { &[0x01, 0x00, 0x00, 0x00] });
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_should_read_the_header_and_count_the_tiles() {
+        let header = MapHeader::parse(&map(5.0, 2)).unwrap();
+
+        assert_eq!((header.major_version, header.minor_version, header.flags, header.tileset_id), (5.0, 25, 0x18, 2));
+        assert_eq!(header.layer_counts, LayerCounts { land: WORLD_MAX as u32, objects: 2, structs: 1, shadows: 0, roofs: 0, on_roofs: 0 });
+        assert_eq!(header.validate(), Ok(()));
+        assert!(!header.is_outdated());
+
+        let old_header = MapHeader::parse(&map(3.0, 0)).unwrap();
+        assert_eq!((old_header.minor_version, old_header.flags), (0, 0x18));
+        assert!(old_header.is_outdated());
+    }
+
+    #[test]
+    fn validate_should_detect_invalid_maps() {
+        assert_eq!(MapHeader::parse(&map(5.0, 50)).unwrap().validate(), Err(String::from("Map has an invalid tileset 50, expected 0 to 49")));
+        assert!(MapHeader::parse(&map(::std::f32::NAN, 0)).unwrap().validate().is_err());
+        assert!(MapHeader::parse(&map(7.0, 0)).unwrap().validate().is_err());
+        assert_eq!(MapHeader::parse(&map(5.0, 0)[..100]), Err(format!("Map is truncated: 100 of {} header bytes", 17 + WORLD_MAX * 6)));
+    }
+
+    #[test]
+    fn read_map_header_should_only_read_the_header() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("a9.dat");
+        let mut bytes = map(6.0, 3);
+        bytes.extend(vec!(0xFF; 1000));
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(read_map_header(&path).unwrap().tileset_id, 3);
+        assert!(read_map_header(&temp_dir.path().join("b9.dat")).is_err());
+    }
+}
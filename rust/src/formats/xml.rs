@@ -0,0 +1,208 @@
+//! Parser for the subset of XML that the data tables of old mods use.
+//!
+//! Elements, attributes, text, CDATA and the predefined and numeric entities are supported. Declarations, comments
+//! and doctypes are skipped, namespaces and DTDs are not interpreted.
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlElement>,
+    /// Text of the element without the text of the children, trimmed
+    pub text: String,
+}
+
+impl XmlElement {
+    /// Parses a document and returns its root element.
+    pub fn parse(xml: &str) -> Result<XmlElement, String> {
+        let mut parser = Parser { xml, pos: 0 };
+        parser.skip_misc()?;
+        let root = parser.parse_element()?;
+        parser.skip_misc()?;
+        if parser.pos < xml.len() {
+            return Err(parser.error("content after the root element"));
+        }
+        Ok(root)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find(';').ok_or_else(|| String::from("unterminated entity"))? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse::<u32>().ok()),
+                };
+                code.and_then(::std::char::from_u32).ok_or_else(|| format!("unknown entity &{};", entity))?
+            }
+        };
+        result.push(c);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+struct Parser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        let line = self.xml[..self.pos].matches('\n').count() + 1;
+        format!("XML error in line {}: {}", line, message)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    /// Moves behind the next `end`, or fails with `what` if it does not follow.
+    fn skip_past(&mut self, end: &str, what: &str) -> Result<&'a str, String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                let skipped = &self.rest()[..i];
+                self.pos += i + end.len();
+                Ok(skipped)
+            },
+            None => Err(self.error(&format!("unterminated {}", what))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.xml.len() - self.rest().trim_start().len();
+    }
+
+    /// Skips whitespace, declarations, comments and doctypes.
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>", "declaration")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->", "comment")?;
+            } else if self.rest().starts_with("<!") {
+                self.skip_past(">", "doctype")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_name(&mut self) -> Result<&'a str, String> {
+        let length = self.rest().find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(self.rest().len());
+        if length == 0 {
+            return Err(self.error("name expected"));
+        }
+        let name = &self.rest()[..length];
+        self.pos += length;
+        Ok(name)
+    }
+
+    fn parse_element(&mut self) -> Result<XmlElement, String> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("element expected"));
+        }
+        self.pos += 1;
+        let mut element = XmlElement { name: String::from(self.read_name()?), ..XmlElement::default() };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let name = String::from(self.read_name()?);
+            self.skip_whitespace();
+            if self.rest().starts_with('=') {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return Err(self.error(&format!("value of attribute {} expected", name))),
+            };
+            self.pos += 1;
+            let value = self.skip_past(&quote.to_string(), "attribute")?;
+            let value = unescape(value).map_err(|e| self.error(&e))?;
+            element.attributes.push((name, value));
+        }
+
+        let mut text = String::new();
+        loop {
+            if self.rest().is_empty() {
+                return Err(self.error(&format!("element {} is not closed", element.name)));
+            } else if self.rest().starts_with("</") {
+                self.pos += 2;
+                let name = self.read_name()?;
+                if name != element.name {
+                    return Err(self.error(&format!("element {} is closed by {}", element.name, name)));
+                }
+                self.skip_whitespace();
+                self.skip_past(">", "closing tag")?;
+                element.text = String::from(text.trim());
+                return Ok(element);
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->", "comment")?;
+            } else if self.rest().starts_with("<![CDATA[") {
+                self.pos += 9;
+                text.push_str(self.skip_past("]]>", "CDATA section")?);
+            } else if self.rest().starts_with('<') {
+                element.children.push(self.parse_element()?);
+            } else {
+                let length = self.rest().find('<').unwrap_or(self.rest().len());
+                let raw = &self.rest()[..length];
+                text.push_str(&unescape(raw).map_err(|e| self.error(&e))?);
+                self.pos += length;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XmlElement;
+
+    #[test]
+    fn parse_should_read_elements_attributes_and_text() {
+        let xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- Weapons -->\n<WEAPONLIST>\n\t<WEAPON id='1'>\n\t\t<uiIndex>1</uiIndex>\n\t\t<szWeaponName>Glock &amp; Co &#x41;</szWeaponName>\n\t\t<empty/>\n\t\t<note><![CDATA[<raw>]]></note>\n\t</WEAPON>\n</WEAPONLIST>\n";
+
+        let root = XmlElement::parse(xml).unwrap();
+
+        assert_eq!(root.name, "WEAPONLIST");
+        let weapon = &root.children[0];
+        assert_eq!(weapon.attributes, vec!((String::from("id"), String::from("1"))));
+        assert_eq!(weapon.child("uiIndex").unwrap().text, "1");
+        assert_eq!(weapon.child("szWeaponName").unwrap().text, "Glock & Co A");
+        assert_eq!(weapon.child("empty").unwrap().text, "");
+        assert_eq!(weapon.child("note").unwrap().text, "<raw>");
+    }
+
+    #[test]
+    fn parse_should_fail_for_malformed_documents() {
+        assert_eq!(XmlElement::parse("<a>\n<b></a>"), Err(String::from("XML error in line 2: element b is closed by a")));
+        assert_eq!(XmlElement::parse("<a>"), Err(String::from("XML error in line 1: element a is not closed")));
+        assert_eq!(XmlElement::parse("<a></a><b/>"), Err(String::from("XML error in line 1: content after the root element")));
+        assert!(XmlElement::parse("<a>&unknown;</a>").is_err());
+    }
+}
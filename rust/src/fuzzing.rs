@@ -0,0 +1,109 @@
+//! Entry points for fuzzing the parsers of the binary formats, see `rust/fuzz`.
+//!
+//! Each function parses arbitrary bytes and uses the result like the game would. The parsers return errors for
+//! invalid input, so any panic is a bug.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use savegame::SavedGameHeader;
+use slf::SlfLibrary;
+use sti::StiImage;
+use text::{StringEncoding, decode_edt, string_index};
+
+pub fn fuzz_slf(data: &[u8]) {
+    if let Ok(library) = SlfLibrary::read_from(Path::new("fuzz.slf"), &mut Cursor::new(data)) {
+        for entry in &library.entries {
+            let start = entry.offset as usize;
+            let _ = &data[start..start + entry.length as usize];
+            library.find(&library.full_name(entry));
+        }
+    }
+}
+
+pub fn fuzz_sti(data: &[u8]) {
+    if let Ok(image) = StiImage::parse(data) {
+        for i in 0..image.subimages.len() {
+            let _ = image.decode_subimage(i);
+        }
+    }
+}
+
+/// The first byte selects the resource, which determines the record layout, and the encoding.
+pub fn fuzz_edt(data: &[u8]) {
+    let resources = ["binarydata/itemdesc.edt", "binarydata/help.edt", "mercedt/051.edt", "npcdata/civ01.edt", "binarydata/unknown.edt"];
+    let encodings = [StringEncoding::NORMAL, StringEncoding::ENGLISH, StringEncoding::POLISH, StringEncoding::RUSSIAN];
+    let selector = usize::from(data.first().cloned().unwrap_or(0));
+    let resource = resources[selector % resources.len()];
+    let encoding = encodings[selector / resources.len() % encodings.len()];
+
+    decode_edt(resource, data, encoding);
+    string_index(resource, data.len() as u32);
+}
+
+pub fn fuzz_save_header(data: &[u8]) {
+    if let Ok(header) = SavedGameHeader::parse_any(data) {
+        header.is_valid();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sti::tests::etrle_image;
+
+    /// Simple pseudo random numbers, so the tests are reproducible without a dependency.
+    fn pseudo_random_bytes(seed: u32, length: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+        (0..length).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect()
+    }
+
+    /// Calls a fuzz target with truncated and mutated copies of a valid input and with random bytes.
+    fn fuzz(target: fn(&[u8]), valid: &[u8]) {
+        for length in 0..valid.len() {
+            target(&valid[..length]);
+        }
+        for seed in 0..200 {
+            let mut mutated = valid.to_vec();
+            for (i, r) in pseudo_random_bytes(seed, 4).chunks(2).enumerate() {
+                let position = (usize::from(r[0]) << 8 | usize::from(r[1])) % mutated.len();
+                mutated[position] = pseudo_random_bytes(seed + 1000, i + 1)[i];
+            }
+            target(&mutated);
+            target(&pseudo_random_bytes(seed, seed as usize * 4));
+        }
+    }
+
+    #[test]
+    fn fuzz_sti_should_not_panic() {
+        fuzz(super::fuzz_sti, &etrle_image());
+    }
+
+    #[test]
+    fn fuzz_slf_should_not_panic() {
+        let mut library = vec!(0u8; 532);
+        library[512] = 1;
+        library.extend_from_slice(b"data");
+        let mut entry = vec!(0u8; 280);
+        entry[..5].copy_from_slice(b"A.DAT");
+        entry[256..260].copy_from_slice(&[0x14, 0x02, 0, 0]);
+        entry[260] = 4;
+        library.extend(entry);
+
+        fuzz(super::fuzz_slf, &library);
+    }
+
+    #[test]
+    fn fuzz_edt_should_not_panic() {
+        fuzz(super::fuzz_edt, &pseudo_random_bytes(1, 2000));
+    }
+
+    #[test]
+    fn fuzz_save_header_should_not_panic() {
+        fuzz(super::fuzz_save_header, &pseudo_random_bytes(2, 700));
+    }
+}
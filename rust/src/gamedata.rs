@@ -0,0 +1,157 @@
+//! Loading of externalized game data files.
+//!
+//! Game data is read from the `externalized` folder in the extra data dir. Mods can override single values by
+//! shipping a file with the same name in their `data` folder, the mods are layered in the order they were given.
+//! Mods are looked up in the stracciatella home first, see `vfs::find_mod_dir`.
+//!
+//! INI files of vanilla and Gold are read with the lenient INI parser and layered the same way, as object of sections.
+
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use serde_json::map::Entry;
+
+use formats::ini::IniFile;
+use vfs::find_mod_dir;
+
+/// Returns the directories that make up the game data, from lowest to highest priority.
+pub fn game_data_dirs(extra_data_dir: &Path, stracciatella_home: &Path, mods: &[String]) -> Vec<PathBuf> {
+    let mut dirs = vec!(extra_data_dir.join("externalized"));
+    for m in mods {
+        dirs.push(find_mod_dir(extra_data_dir, stracciatella_home, m));
+    }
+    dirs
+}
+
+pub fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (&mut Value::Object(ref mut base_map), Value::Object(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.entry(key) {
+                    Entry::Occupied(mut e) => merge_json(e.get_mut(), value),
+                    Entry::Vacant(e) => { e.insert(value); }
+                }
+            }
+        },
+        (base, overlay) => *base = overlay
+    }
+}
+
+/// Reads a json file from all game data dirs and merges objects key by key.
+///
+/// The file has to exist in the first (base) directory, the other layers are optional.
+pub fn load_layered_json(dirs: &[PathBuf], file_name: &str) -> Result<Value, String> {
+    let mut result: Option<Value> = None;
+
+    for (i, dir) in dirs.iter().enumerate() {
+        let path = dir.join(file_name);
+        if i > 0 && !path.is_file() {
+            continue;
+        }
+        let value: Value = File::open(&path)
+            .map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))
+            .and_then(|f| serde_json::from_reader(f).map_err(|s| format!("Error parsing {}: {}", path.display(), s)))?;
+        match result {
+            Some(ref mut base) => merge_json(base, value),
+            None => result = Some(value)
+        }
+    }
+
+    result.ok_or_else(|| format!("No game data directories to read {} from", file_name))
+}
+
+/// Reads an INI file from all game data dirs and merges the sections key by key like `load_layered_json`. Lines that
+/// cannot be parsed are ignored and returned as warnings.
+pub fn load_layered_ini(dirs: &[PathBuf], file_name: &str) -> Result<(Value, Vec<String>), String> {
+    let mut result: Option<Value> = None;
+    let mut warnings = vec!();
+
+    for (i, dir) in dirs.iter().enumerate() {
+        let path = dir.join(file_name);
+        if i > 0 && !path.is_file() {
+            continue;
+        }
+        let bytes = fs::read(&path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+        let (ini, ini_warnings) = IniFile::parse_lenient(&bytes);
+        warnings.extend(ini_warnings.into_iter().map(|w| format!("{}: {}", path.display(), w)));
+        match result {
+            Some(ref mut base) => merge_json(base, ini.to_json()),
+            None => result = Some(ini.to_json())
+        }
+    }
+
+    result.map(|r| (r, warnings)).ok_or_else(|| format!("No game data directories to read {} from", file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::{Path, PathBuf};
+
+    fn write_game_data_file(dir: &Path, file_name: &str, contents: &[u8]) {
+        fs::create_dir_all(dir).unwrap();
+        File::create(dir.join(file_name)).unwrap().write_all(contents).unwrap();
+    }
+
+    #[test]
+    fn game_data_dirs_should_put_mods_above_externalized_data() {
+        let dirs = super::game_data_dirs(Path::new("/ja2"), Path::new("/home/.ja2"), &[String::from("a"), String::from("b")]);
+
+        assert_eq!(dirs, vec!(PathBuf::from("/ja2/externalized"), PathBuf::from("/ja2/mods/a/data"), PathBuf::from("/ja2/mods/b/data")));
+    }
+
+    #[test]
+    fn load_layered_json_should_merge_objects_of_all_layers() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dirs = super::game_data_dirs(temp_dir.path(), temp_dir.path(), &[String::from("a"), String::from("b")]);
+
+        write_game_data_file(&dirs[0], "test.json", b"{ \"x\": { \"a\": 1, \"b\": 2 }, \"y\": [1, 2], \"z\": 3 }");
+        write_game_data_file(&dirs[1], "test.json", b"{ \"x\": { \"b\": 20 }, \"y\": [10] }");
+        write_game_data_file(&dirs[2], "test.json", b"{ \"x\": { \"a\": 100 } }");
+
+        let value = super::load_layered_json(&dirs, "test.json").unwrap();
+
+        assert_eq!(value, json!({ "x": { "a": 100, "b": 20 }, "y": [10], "z": 3 }));
+    }
+
+    #[test]
+    fn load_layered_json_should_require_the_base_file() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dirs = super::game_data_dirs(temp_dir.path(), temp_dir.path(), &[]);
+
+        let result = super::load_layered_json(&dirs, "test.json");
+
+        assert_eq!(result, Err(format!("Error reading {}: entity not found", dirs[0].join("test.json").display())));
+    }
+
+    #[test]
+    fn load_layered_ini_should_merge_sections_of_all_layers() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dirs = super::game_data_dirs(temp_dir.path(), temp_dir.path(), &[String::from("a")]);
+
+        write_game_data_file(&dirs[0], "shades.ini", b"[Shades]\nRed = 10\nGreen = 20\n");
+        write_game_data_file(&dirs[1], "shades.ini", b"[Shades]\nRed = 15 ; brighter\nbroken\n");
+
+        let (value, warnings) = super::load_layered_ini(&dirs, "shades.ini").unwrap();
+
+        assert_eq!(value, json!({ "Shades": { "Red": "15", "Green": "20" } }));
+        assert_eq!(warnings, vec!(format!("{}: INI error in line 3: = expected", dirs[1].join("shades.ini").display())));
+    }
+
+    #[test]
+    fn load_layered_json_should_fail_with_invalid_json_in_mod() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dirs = super::game_data_dirs(temp_dir.path(), temp_dir.path(), &[String::from("a")]);
+
+        write_game_data_file(&dirs[0], "test.json", b"{}");
+        write_game_data_file(&dirs[1], "test.json", b"{ broken");
+
+        assert!(super::load_layered_json(&dirs, "test.json").unwrap_err().starts_with("Error parsing"));
+    }
+}
@@ -0,0 +1,324 @@
+//! Gameplay options stored in the `gameplay` section of ja2.json.
+
+use std::mem;
+use std::ptr;
+
+use libc::size_t;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use EngineOptions;
+
+fn validate_speech_subtitle_duration(duration: u32) -> Result<u32, String> {
+    if (10..=1000).contains(&duration) {
+        Ok(duration)
+    } else {
+        Err(String::from("Incorrect subtitle duration, should be a value between 10 and 1000 ms."))
+    }
+}
+
+fn deserialize_speech_subtitle_duration<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let duration = u32::deserialize(deserializer)?;
+    validate_speech_subtitle_duration(duration).map_err(serde::de::Error::custom)
+}
+
+/// Fixes for vanilla bugs in the interrupt system. Each fix changes the balance and is off by default.
+#[derive(Debug, Default, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[repr(C)]
+pub struct InterruptFixes {
+    /// Enemies that are not visible to the squad can no longer trigger interrupts
+    pub no_interrupts_from_unseen_enemies: bool,
+    /// Use the remaining instead of the maximum action points of the interrupted merc
+    pub use_remaining_action_points: bool,
+}
+
+/// Fixes for vanilla bugs in bonus calculations. Each fix changes the balance and is off by default.
+#[derive(Debug, Default, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[repr(C)]
+pub struct BonusFixes {
+    /// Apply the leadership morale bonus only to mercs in the same sector as the leader
+    pub leadership_bonus_same_sector_only: bool,
+    /// Apply marksmanship training bonuses to every bullet of a burst
+    pub burst_marksmanship_bonus: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VanillaFixes {
+    pub interrupts: InterruptFixes,
+    pub bonuses: BonusFixes,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplayOptions {
+    pub subtitles: bool,
+    /// Time in milliseconds a subtitle is shown per character when there is no speech
+    #[serde(deserialize_with = "deserialize_speech_subtitle_duration")]
+    pub speech_subtitle_duration_ms: u32,
+    /// Popups that explain the laptop and other screens the first time they are opened
+    pub show_tutorials: bool,
+    /// Hints of the mercs and the help screens
+    pub show_hints: bool,
+    pub vanilla_fixes: VanillaFixes,
+}
+
+impl Default for GameplayOptions {
+    fn default() -> GameplayOptions {
+        GameplayOptions {
+            subtitles: true,
+            speech_subtitle_duration_ms: 60,
+            show_tutorials: true,
+            show_hints: true,
+            vanilla_fixes: VanillaFixes::default(),
+        }
+    }
+}
+
+/// Version of `GameplayOptionsExport`, has to be increased whenever fields are added.
+///
+/// New fields are only ever appended. The engine passes the size of the struct it was built against to
+/// `get_gameplay_options`, so it gets the fields it knows and can check the version for newer ones.
+pub const GAMEPLAY_OPTIONS_VERSION: u32 = 2;
+
+/// All gameplay options in one struct for the engine.
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[repr(C)]
+pub struct GameplayOptionsExport {
+    pub version: u32,
+    pub subtitles: bool,
+    pub speech_subtitle_duration_ms: u32,
+    pub interrupt_fixes: InterruptFixes,
+    pub bonus_fixes: BonusFixes,
+    /// Since version 2
+    pub show_tutorials: bool,
+    /// Since version 2
+    pub show_hints: bool,
+}
+
+impl<'a> From<&'a GameplayOptions> for GameplayOptionsExport {
+    fn from(options: &'a GameplayOptions) -> GameplayOptionsExport {
+        GameplayOptionsExport {
+            version: GAMEPLAY_OPTIONS_VERSION,
+            subtitles: options.subtitles,
+            speech_subtitle_duration_ms: options.speech_subtitle_duration_ms,
+            interrupt_fixes: options.vanilla_fixes.interrupts,
+            bonus_fixes: options.vanilla_fixes.bonuses,
+            show_tutorials: options.show_tutorials,
+            show_hints: options.show_hints,
+        }
+    }
+}
+
+/// Writes the fields of the export that fit completely into the first `size` bytes of `out`.
+unsafe fn write_fields(options: &GameplayOptionsExport, out: *mut u8, size: usize) {
+    macro_rules! write_field {
+        ($field:ident) => {
+            let offset = mem::offset_of!(GameplayOptionsExport, $field);
+            if offset + mem::size_of_val(&options.$field) <= size {
+                ptr::write_unaligned(out.add(offset) as *mut _, options.$field);
+            }
+        }
+    }
+    write_field!(version);
+    write_field!(subtitles);
+    write_field!(speech_subtitle_duration_ms);
+    write_field!(interrupt_fixes);
+    write_field!(bonus_fixes);
+    write_field!(show_tutorials);
+    write_field!(show_hints);
+}
+
+/// Fills the gameplay options of the engine, `size` is the size of the struct the engine was built against. Fields
+/// that are newer than the engine are not written, fields that are newer than the library are left untouched.
+#[no_mangle]
+pub extern fn get_gameplay_options(ptr: *const EngineOptions, out: *mut GameplayOptionsExport, size: size_t) -> bool {
+    if out.is_null() || size < mem::size_of::<u32>() {
+        return false;
+    }
+    let options = GameplayOptionsExport::from(&unsafe_from_ptr!(ptr).gameplay);
+    unsafe { write_fields(&options, out as *mut u8, size) };
+    true
+}
+
+#[no_mangle]
+pub extern fn should_show_subtitles(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).gameplay.subtitles
+}
+
+#[no_mangle]
+pub extern fn set_show_subtitles(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).gameplay.subtitles = val
+}
+
+#[no_mangle]
+pub extern fn get_speech_subtitle_duration_ms(ptr: *const EngineOptions) -> u32 {
+    unsafe_from_ptr!(ptr).gameplay.speech_subtitle_duration_ms
+}
+
+#[no_mangle]
+pub extern fn set_speech_subtitle_duration_ms(ptr: *mut EngineOptions, duration: u32) -> bool {
+    match validate_speech_subtitle_duration(duration) {
+        Ok(duration) => {
+            unsafe_from_ptr_mut!(ptr).gameplay.speech_subtitle_duration_ms = duration;
+            true
+        },
+        Err(_) => false
+    }
+}
+
+#[no_mangle]
+pub extern fn should_show_tutorials(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).gameplay.show_tutorials
+}
+
+#[no_mangle]
+pub extern fn set_show_tutorials(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).gameplay.show_tutorials = val
+}
+
+#[no_mangle]
+pub extern fn should_show_hints(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).gameplay.show_hints
+}
+
+#[no_mangle]
+pub extern fn set_show_hints(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).gameplay.show_hints = val
+}
+
+#[no_mangle]
+pub extern fn get_interrupt_fixes(ptr: *const EngineOptions) -> InterruptFixes {
+    unsafe_from_ptr!(ptr).gameplay.vanilla_fixes.interrupts
+}
+
+#[no_mangle]
+pub extern fn get_bonus_fixes(ptr: *const EngineOptions) -> BonusFixes {
+    unsafe_from_ptr!(ptr).gameplay.vanilla_fixes.bonuses
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use EngineOptions;
+
+    #[test]
+    fn gameplay_options_should_have_correct_defaults() {
+        let engine_options = EngineOptions::default();
+
+        assert!(super::should_show_subtitles(&engine_options));
+        assert_eq!(super::get_speech_subtitle_duration_ms(&engine_options), 60);
+        assert!(super::should_show_tutorials(&engine_options));
+        assert!(super::should_show_hints(&engine_options));
+    }
+
+    #[test]
+    fn tutorials_and_hints_should_be_switchable_individually() {
+        let mut engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"show_tutorials\": false } }").unwrap();
+
+        assert!(!super::should_show_tutorials(&engine_options));
+        assert!(super::should_show_hints(&engine_options));
+        super::set_show_hints(&mut engine_options, false);
+        super::set_show_tutorials(&mut engine_options, true);
+        let options = gameplay_options(&engine_options);
+        assert!(options.show_tutorials);
+        assert!(!options.show_hints);
+    }
+
+    #[test]
+    fn gameplay_options_should_be_read_from_gameplay_section() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"subtitles\": false, \"speech_subtitle_duration_ms\": 120 } }").unwrap();
+
+        assert!(!super::should_show_subtitles(&engine_options));
+        assert_eq!(super::get_speech_subtitle_duration_ms(&engine_options), 120);
+    }
+
+    #[test]
+    fn gameplay_options_should_keep_defaults_for_missing_keys() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"subtitles\": false } }").unwrap();
+
+        assert_eq!(super::get_speech_subtitle_duration_ms(&engine_options), 60);
+    }
+
+    #[test]
+    fn gameplay_options_should_fail_with_invalid_subtitle_duration() {
+        let result = ::serde_json::from_str::<EngineOptions>("{ \"gameplay\": { \"speech_subtitle_duration_ms\": 5000 } }");
+
+        assert_eq!(result.unwrap_err().to_string(), "Incorrect subtitle duration, should be a value between 10 and 1000 ms. at line 1 column 53");
+    }
+
+    #[test]
+    fn vanilla_fixes_should_be_off_by_default() {
+        let engine_options = EngineOptions::default();
+
+        assert_eq!(super::get_interrupt_fixes(&engine_options), super::InterruptFixes::default());
+        assert_eq!(super::get_bonus_fixes(&engine_options), super::BonusFixes::default());
+    }
+
+    #[test]
+    fn vanilla_fixes_should_be_switchable_individually() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"vanilla_fixes\": { \"interrupts\": { \"use_remaining_action_points\": true }, \"bonuses\": { \"burst_marksmanship_bonus\": true } } } }").unwrap();
+        let interrupts = super::get_interrupt_fixes(&engine_options);
+        let bonuses = super::get_bonus_fixes(&engine_options);
+
+        assert!(!interrupts.no_interrupts_from_unseen_enemies);
+        assert!(interrupts.use_remaining_action_points);
+        assert!(!bonuses.leadership_bonus_same_sector_only);
+        assert!(bonuses.burst_marksmanship_bonus);
+    }
+
+    #[test]
+    fn vanilla_fixes_should_fail_with_invalid_value() {
+        let result = ::serde_json::from_str::<EngineOptions>("{ \"gameplay\": { \"vanilla_fixes\": { \"bonuses\": { \"burst_marksmanship_bonus\": 1 } } } }");
+
+        assert!(result.is_err());
+    }
+
+    fn gameplay_options(engine_options: &EngineOptions) -> super::GameplayOptionsExport {
+        let mut options = super::GameplayOptionsExport::from(&super::GameplayOptions::default());
+        assert!(super::get_gameplay_options(engine_options, &mut options, mem::size_of::<super::GameplayOptionsExport>()));
+        options
+    }
+
+    #[test]
+    fn get_gameplay_options_should_export_all_gameplay_options() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"subtitles\": false, \"vanilla_fixes\": { \"bonuses\": { \"burst_marksmanship_bonus\": true } } } }").unwrap();
+        let options = gameplay_options(&engine_options);
+
+        assert_eq!(options.version, super::GAMEPLAY_OPTIONS_VERSION);
+        assert!(!options.subtitles);
+        assert_eq!(options.speech_subtitle_duration_ms, 60);
+        assert_eq!(options.interrupt_fixes, super::get_interrupt_fixes(&engine_options));
+        assert!(options.bonus_fixes.burst_marksmanship_bonus);
+    }
+
+    #[test]
+    fn get_gameplay_options_should_only_write_the_fields_of_older_engines() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"gameplay\": { \"subtitles\": false, \"show_hints\": false } }").unwrap();
+        let mut options = super::GameplayOptionsExport::from(&super::GameplayOptions::default());
+        options.version = 0;
+        // the struct of version 1 ends after the bonus fixes
+        let version_1_size = mem::offset_of!(super::GameplayOptionsExport, show_tutorials);
+
+        assert!(super::get_gameplay_options(&engine_options, &mut options, version_1_size));
+        assert_eq!(options.version, super::GAMEPLAY_OPTIONS_VERSION);
+        assert!(!options.subtitles);
+        assert!(options.show_hints);
+        assert!(!super::get_gameplay_options(&engine_options, ::std::ptr::null_mut(), version_1_size));
+    }
+
+    #[test]
+    fn set_speech_subtitle_duration_ms_should_reject_invalid_durations() {
+        let mut engine_options = EngineOptions::default();
+
+        assert!(!super::set_speech_subtitle_duration_ms(&mut engine_options, 0));
+        assert!(super::set_speech_subtitle_duration_ms(&mut engine_options, 80));
+        assert_eq!(super::get_speech_subtitle_duration_ms(&engine_options), 80);
+    }
+}
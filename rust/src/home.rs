@@ -0,0 +1,865 @@
+//! Location of the stracciatella home.
+//!
+//! Sandboxed builds can not write to arbitrary places in the home of the user. Flatpak apps keep their files in
+//! `~/.var/app/<app id>` and Snap apps in `~/snap/<name>/common`, so the stracciatella home is placed there when the
+//! game runs inside such a sandbox.
+//!
+//! On Unix the home of the user is `$HOME`. Some environments do not set it, e.g. services and login classes on
+//! FreeBSD and OpenBSD, so the home from the passwd database is used instead.
+//!
+//! On Linux and the BSDs the stracciatella home is `$XDG_CONFIG_HOME/ja2` and the saved games are kept in
+//! `$XDG_DATA_HOME/ja2`. On macOS everything is kept in `~/Library/Application Support/JA2 Stracciatella`. An
+//! existing `~/.ja2` from older versions is moved there once. If that fails the game keeps using `~/.ja2`.
+//!
+//! Android apps have no usable home, the Java side passes the app-private external storage with
+//! `set_android_storage_dir` before the engine options are created.
+//!
+//! `JA2_HOME` or `STRACCIATELLA_HOME` override the home on all platforms, e.g. for packages, automated runs or several
+//! users that share an account.
+//!
+//! In the portable mode the folder of the executable is the home, so the game can run from a USB stick. It is enabled
+//! with `--portable` or by a `ja2.json` next to the executable.
+//!
+//! A home that was moved with `migrate_stracciatella_home` keeps a `moved-home.txt` with the new location, which is
+//! followed as long as the old home has no ja2.json.
+
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use libc::c_char;
+
+use {EngineOptions, set_last_error};
+use events::{EngineEvent, emit_event};
+use logger::{self, LogLevel};
+use migration::migrate_legacy_home;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum Sandbox {
+    NONE,
+    FLATPAK,
+    SNAP,
+}
+
+/// Detects the sandbox from the environment variables that Flatpak and Snap set for all apps.
+pub fn detect_sandbox(env_var: &Fn(&str) -> Option<String>) -> Sandbox {
+    if env_var("FLATPAK_ID").is_some() || Path::new("/.flatpak-info").is_file() {
+        Sandbox::FLATPAK
+    } else if env_var("SNAP").is_some() && env_var("SNAP_USER_COMMON").is_some() {
+        Sandbox::SNAP
+    } else {
+        Sandbox::NONE
+    }
+}
+
+pub fn read_env_var(name: &str) -> Option<String> {
+    env::var(name).ok().and_then(|v| if v.is_empty() { None } else { Some(v) })
+}
+
+/// Returns the folder a sandboxed app can write to without extra permissions.
+pub fn sandbox_data_home(sandbox: Sandbox, env_var: &Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    match sandbox {
+        Sandbox::NONE => None,
+        Sandbox::FLATPAK => env_var("XDG_DATA_HOME").map(PathBuf::from).or_else(|| {
+            match (env_var("HOME"), env_var("FLATPAK_ID")) {
+                (Some(home), Some(id)) => Some(Path::new(&home).join(".var/app").join(id).join("data")),
+                _ => None
+            }
+        }),
+        Sandbox::SNAP => env_var("SNAP_USER_COMMON").map(PathBuf::from),
+    }
+}
+
+/// Returns the data dir that is suggested in a new ja2.json. Inside a sandbox the suggested data dir is a folder the
+/// game can always read.
+pub fn suggested_data_dir(sandbox: Sandbox, env_var: &Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    sandbox_data_home(sandbox, env_var).map(|d| d.join("ja2-data"))
+}
+
+/// Returns the home directory of the current user from the passwd database.
+#[cfg(not(windows))]
+pub fn passwd_home_dir() -> Option<PathBuf> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let suggested_size = unsafe { libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) };
+    let mut buffer_size = if suggested_size > 0 { suggested_size as usize } else { 1024 };
+    loop {
+        let mut buffer = vec!(0 as c_char; buffer_size);
+        let mut passwd: libc::passwd = unsafe { ::std::mem::zeroed() };
+        let mut result: *mut libc::passwd = ::std::ptr::null_mut();
+        let error = unsafe { libc::getpwuid_r(libc::getuid(), &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+        if error == libc::ERANGE && buffer_size < 1024 * 1024 {
+            buffer_size *= 2;
+            continue;
+        }
+        if error != 0 || result.is_null() || passwd.pw_dir.is_null() {
+            return None;
+        }
+        let dir = unsafe { CStr::from_ptr(passwd.pw_dir) }.to_bytes();
+        return if dir.is_empty() { None } else { Some(PathBuf::from(OsStr::from_bytes(dir))) };
+    }
+}
+
+/// Returns `$HOME`, the home of the passwd database if it is not set.
+#[cfg(not(windows))]
+pub fn unix_home_dir(env_var: &Fn(&str) -> Option<String>, passwd_home: &Fn() -> Option<PathBuf>) -> Option<PathBuf> {
+    env_var("HOME").map(PathBuf::from).or_else(passwd_home)
+}
+
+/// The stracciatella home and the folder with the saved games, which is the same folder on most platforms.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HomeDirs {
+    pub home: PathBuf,
+    pub saves_home: PathBuf,
+}
+
+impl HomeDirs {
+    /// Keeps the saved games in the home.
+    pub fn single(home: PathBuf) -> HomeDirs {
+        HomeDirs { saves_home: home.clone(), home }
+    }
+}
+
+/// Returns the base directories for config and data files of the XDG base directory specification. Relative paths are
+/// ignored, as the specification requires.
+#[cfg(not(windows))]
+pub fn xdg_base_dirs(env_var: &Fn(&str) -> Option<String>, user_home: &Path) -> (PathBuf, PathBuf) {
+    let base_dir = |name: &str, default: &str| {
+        env_var(name).map(PathBuf::from).filter(|p| p.is_absolute()).unwrap_or_else(|| user_home.join(default))
+    };
+    (base_dir("XDG_CONFIG_HOME", ".config"), base_dir("XDG_DATA_HOME", ".local/share"))
+}
+
+/// Moves the legacy `~/.ja2` to the new home dirs if it still has a ja2.json. If that fails the legacy home is used.
+#[cfg(not(any(windows, target_os = "android")))]
+fn migrate_legacy_home_dirs(legacy_home: PathBuf, dirs: HomeDirs) -> HomeDirs {
+    // ja2.json is deleted last, a failed migration is tried again on the next start
+    if !legacy_home.join("ja2.json").is_file() {
+        return dirs;
+    }
+
+    match migrate_legacy_home(&legacy_home, &dirs.home, &dirs.saves_home) {
+        Ok(()) => dirs,
+        Err(s) => {
+            logger::log(LogLevel::WARNING, "home", &format!("Error moving {} to {}, it is used instead: {}", legacy_home.display(), dirs.home.display(), s));
+            HomeDirs::single(legacy_home)
+        }
+    }
+}
+
+/// Returns the home dirs in the XDG base directories.
+#[cfg(not(any(windows, target_os = "macos", target_os = "android")))]
+pub fn find_xdg_home_dirs(env_var: &Fn(&str) -> Option<String>, user_home: &Path) -> HomeDirs {
+    let (config_home, data_home) = xdg_base_dirs(env_var, user_home);
+    migrate_legacy_home_dirs(user_home.join(".ja2"), HomeDirs { home: config_home.join("ja2"), saves_home: data_home.join("ja2") })
+}
+
+/// Returns the home dirs in the Application Support folder of the user.
+#[cfg(target_os = "macos")]
+pub fn find_macos_home_dirs(user_home: &Path) -> HomeDirs {
+    let home = user_home.join("Library/Application Support/JA2 Stracciatella");
+    migrate_legacy_home_dirs(user_home.join(".ja2"), HomeDirs::single(home))
+}
+
+/// Returns the home dirs in the storage of the Android app, e.g. `/storage/emulated/0/Android/data/<package>/files`.
+/// The saved games are kept there as well, so they are removed with the app.
+pub fn android_home_dirs(storage_dir: Option<PathBuf>) -> Result<HomeDirs, String> {
+    match storage_dir {
+        Some(dir) => Ok(HomeDirs::single(dir.join("ja2"))),
+        None => Err(String::from("The storage dir of the app was not set with set_android_storage_dir")),
+    }
+}
+
+#[cfg(target_os = "android")]
+static ANDROID_STORAGE_DIR: ::std::sync::Mutex<Option<PathBuf>> = ::std::sync::Mutex::new(None);
+
+/// Sets the app-private external storage from `Context.getExternalFilesDir(null)`.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern fn set_android_storage_dir(path_ptr: *const c_char) -> () {
+    let path = PathBuf::from(unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned());
+    *ANDROID_STORAGE_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+}
+
+#[cfg(target_os = "android")]
+fn find_platform_home_dirs() -> Result<HomeDirs, String> {
+    android_home_dirs(ANDROID_STORAGE_DIR.lock().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+#[cfg(not(any(windows, target_os = "android")))]
+fn find_platform_home_dirs() -> Result<HomeDirs, String> {
+    if let Some(data_home) = sandbox_data_home(detect_sandbox(&read_env_var), &read_env_var) {
+        return Ok(HomeDirs::single(data_home.join(".ja2")));
+    }
+
+    let user_home = unix_home_dir(&read_env_var, &passwd_home_dir).ok_or_else(|| String::from("Could not find home directory"))?;
+    #[cfg(target_os = "macos")]
+    return Ok(find_macos_home_dirs(&user_home));
+    #[cfg(not(target_os = "macos"))]
+    return Ok(find_xdg_home_dirs(&read_env_var, &user_home));
+}
+
+#[cfg(windows)]
+fn find_platform_home_dirs() -> Result<HomeDirs, String> {
+    find_documents_home().map(HomeDirs::single)
+}
+
+/// Environment variables that override the home, from highest to lowest priority
+const HOME_ENV_VARS: [&str; 2] = ["JA2_HOME", "STRACCIATELLA_HOME"];
+
+/// Returns the home from `JA2_HOME` or `STRACCIATELLA_HOME`, relative paths are relative to the working directory.
+pub fn env_home_dirs(env_var: &dyn Fn(&str) -> Option<String>, current_dir: &Path) -> Option<HomeDirs> {
+    HOME_ENV_VARS.iter()
+        .filter_map(|v| env_var(v))
+        .find(|h| !h.is_empty())
+        .map(|h| HomeDirs::single(current_dir.join(h)))
+}
+
+/// File in a moved home with the path of the new home
+pub const MOVED_HOME_FILE: &str = "moved-home.txt";
+
+/// Follows the `moved-home.txt` of homes without ja2.json to the home they were moved to. Saved games in a separate
+/// folder stay where they are, like `migrate_stracciatella_home` does.
+pub fn follow_moved_home(dirs: HomeDirs) -> HomeDirs {
+    let mut dirs = dirs;
+    // A home can be moved several times, a loop of moved homes must not hang the game
+    for _ in 0..8 {
+        let moved_home_file = dirs.home.join(MOVED_HOME_FILE);
+        if config_file_path(&dirs.home).is_file() || !moved_home_file.is_file() {
+            break;
+        }
+        let new_home = match fs::read_to_string(&moved_home_file) {
+            Ok(s) if !s.trim().is_empty() => PathBuf::from(s.trim()),
+            Ok(_) => break,
+            Err(s) => {
+                logger::log(LogLevel::WARNING, "home", &format!("Error reading {}: {}", moved_home_file.display(), s.kind()));
+                break;
+            }
+        };
+        logger::log(LogLevel::INFO, "home", &format!("{} was moved to {}", dirs.home.display(), new_home.display()));
+        dirs = if dirs.saves_home == dirs.home { HomeDirs::single(new_home) } else { HomeDirs { home: new_home, saves_home: dirs.saves_home } };
+    }
+    dirs
+}
+
+/// Returns the home from the environment or the default of the platform.
+pub fn find_home_dirs() -> Result<HomeDirs, String> {
+    let current_dir = env::current_dir().unwrap_or_default();
+    match env_home_dirs(&read_env_var, &current_dir) {
+        Some(dirs) => Ok(dirs),
+        None => find_platform_home_dirs(),
+    }.map(follow_moved_home)
+}
+
+#[cfg(windows)]
+fn find_documents_home() -> Result<PathBuf, String> {
+    use shell32::SHGetFolderPathW;
+    use winapi::shlobj::{CSIDL_PERSONAL, CSIDL_FLAG_CREATE};
+    use winapi::minwindef::MAX_PATH;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::ptr;
+
+    let mut home: [u16; MAX_PATH] = [0; MAX_PATH];
+
+    return match unsafe { SHGetFolderPathW(ptr::null_mut(), CSIDL_PERSONAL | CSIDL_FLAG_CREATE, ptr::null_mut(), 0, home.as_mut_ptr()) } {
+        0 => {
+            let home_trimmed: Vec<u16> = home.iter().take_while(|x| **x != 0).map(|x| *x).collect();
+
+            // the documents folder may contain any character of the user name, so it is kept as UTF-16
+            Ok(PathBuf::from(OsString::from_wide(&home_trimmed)).join("JA2"))
+        },
+        i => Err(format!("Could not get documents folder: {}", i))
+    };
+}
+
+pub fn find_stracciatella_home() -> Result<PathBuf, String> {
+    find_home_dirs().map(|d| d.home)
+}
+
+/// Checks whether the portable mode is requested on the command line. The arguments are checked directly, because
+/// the home has to be known before ja2.json is parsed.
+pub fn wants_portable_mode(args: &[String]) -> bool {
+    args.iter().skip(1).any(|a| a == "-portable" || a == "--portable")
+}
+
+pub fn executable_dir() -> Option<PathBuf> {
+    env::current_exe().ok()?.parent().map(Path::to_path_buf)
+}
+
+/// Returns the folder with the externalized data of stracciatella: `game_dir` from the config, the default of the build
+/// or the folder of the executable.
+pub fn game_dir(engine_options: &EngineOptions, build_default: &Path) -> PathBuf {
+    if !engine_options.game_dir.as_os_str().is_empty() {
+        return engine_options.game_dir.clone();
+    }
+    if !build_default.as_os_str().is_empty() {
+        return build_default.to_path_buf();
+    }
+    executable_dir().unwrap_or_default()
+}
+
+/// Returns the game dir, see `game_dir`. The engine passes `EXTRA_DATA_DIR` of the build as default.
+#[no_mangle]
+pub extern fn get_game_dir(ptr: *const EngineOptions, build_default_ptr: *const c_char) -> *mut c_char {
+    let build_default = unsafe { CStr::from_ptr(build_default_ptr) }.to_string_lossy().into_owned();
+    path_to_c_string(&game_dir(unsafe_from_ptr!(ptr), Path::new(&build_default)))
+}
+
+#[no_mangle]
+pub extern fn set_game_dir(ptr: *mut EngineOptions, game_dir_ptr: *const c_char) -> () {
+    let game_dir = unsafe { CStr::from_ptr(game_dir_ptr) }.to_string_lossy().into_owned();
+    unsafe_from_ptr_mut!(ptr).game_dir = PathBuf::from(game_dir);
+}
+
+/// Returns the home of the portable mode if it is requested or a `ja2.json` is next to the executable.
+pub fn portable_home_dirs(portable: bool, executable_dir: Option<&Path>) -> Result<Option<HomeDirs>, String> {
+    match executable_dir {
+        Some(dir) if portable || config_file_path(dir).is_file() => Ok(Some(follow_moved_home(HomeDirs::single(dir.to_path_buf())))),
+        None if portable => Err(String::from("Could not find the folder of the executable for the portable mode")),
+        _ => Ok(None),
+    }
+}
+
+pub fn config_file_path(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("ja2.json")
+}
+
+pub fn log_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("logs")
+}
+
+/// Returns the folder for saved games. Every mod has its own saved games, the first mod decides which are used.
+pub fn save_dir(saves_home: &Path, mods: &[String]) -> PathBuf {
+    match mods.first() {
+        Some(m) => saves_home.join(format!("SavedGames-{}", m)),
+        None => saves_home.join("SavedGames")
+    }
+}
+
+/// Returns the folder for saved games of the engine options. A `save_game_dir` from ja2.json replaces `SavedGames` in
+/// the saves home, relative to the home, and the saved games of mods are kept in subfolders of it.
+pub fn configured_save_dir(engine_options: &EngineOptions) -> PathBuf {
+    if engine_options.save_game_dir.as_os_str().is_empty() {
+        return save_dir(&engine_options.saves_home, &engine_options.mods);
+    }
+    let dir = engine_options.stracciatella_home.join(&engine_options.save_game_dir);
+    match engine_options.mods.first() {
+        Some(m) => dir.join(m),
+        None => dir
+    }
+}
+
+/// Returns the folder for temporary files, which is also the working directory of the engine.
+pub fn cache_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("tmp")
+}
+
+/// Returns the folder for the mods of the user, see `vfs::find_mod_dir`.
+pub fn mods_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("mods")
+}
+
+/// Folders of the user that the engine writes to.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct UserDirs {
+    /// Saved games of the first mod, see `configured_save_dir`
+    pub saves: PathBuf,
+    pub mods: PathBuf,
+    pub temp: PathBuf,
+    pub logs: PathBuf,
+}
+
+impl UserDirs {
+    pub fn new(engine_options: &EngineOptions) -> UserDirs {
+        let home = &engine_options.stracciatella_home;
+        UserDirs {
+            saves: configured_save_dir(engine_options),
+            mods: mods_dir(home),
+            temp: cache_dir(home),
+            logs: log_dir(home),
+        }
+    }
+}
+
+/// Creates the folders of the user that do not exist yet and returns them.
+pub fn ensure_user_dirs(engine_options: &EngineOptions) -> Result<UserDirs, String> {
+    let dirs = UserDirs::new(engine_options);
+    for dir in &[&dirs.saves, &dirs.mods, &dirs.temp, &dirs.logs] {
+        fs::create_dir_all(dir).map_err(|s| format!("Error creating {}: {}", dir.display(), s.kind()))?;
+    }
+    Ok(dirs)
+}
+
+/// Removes the contents of the cache dir. The dir itself is kept, as it is the working directory of the engine.
+pub fn clear_cache_dir(stracciatella_home: &Path) -> Result<(), String> {
+    let dir = cache_dir(stracciatella_home);
+    if !dir.exists() {
+        return Ok(());
+    }
+    let entries = fs::read_dir(&dir).map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?.path();
+        let result = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        result.map_err(|s| format!("Error removing {}: {}", path.display(), s.kind()))?;
+    }
+    Ok(())
+}
+
+/// Returns a path as UTF-8 for C, free with `free_rust_string`. Paths that are no valid Unicode are converted lossy.
+pub fn path_to_c_string(path: &Path) -> *mut c_char {
+    CString::new(path.to_string_lossy().into_owned()).unwrap_or_default().into_raw()
+}
+
+/// Returns the characters of a path as null-terminated UTF-16. On Windows the path is passed unchanged, so it can be
+/// opened with the wide functions of the Windows API even if it contains characters of any language.
+pub fn path_to_wide(path: &Path) -> Vec<u16> {
+    #[cfg(windows)]
+    let wide = {
+        use std::os::windows::ffi::OsStrExt;
+        path.as_os_str().encode_wide().collect::<Vec<u16>>()
+    };
+    #[cfg(not(windows))]
+    let wide = path.to_string_lossy().encode_utf16().collect::<Vec<u16>>();
+    wide.into_iter().take_while(|c| *c != 0).chain(Some(0)).collect()
+}
+
+/// Returns a path as null-terminated UTF-16 for C, free with `free_rust_wide_string`.
+pub fn path_to_wide_string(path: &Path) -> *mut u16 {
+    Box::into_raw(path_to_wide(path).into_boxed_slice()) as *mut u16
+}
+
+#[no_mangle]
+pub extern fn free_rust_wide_string(s: *mut u16) {
+    if s.is_null() {
+        return;
+    }
+    unsafe {
+        let mut len = 0;
+        while *s.add(len) != 0 {
+            len += 1;
+        }
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(s, len + 1)));
+    }
+}
+
+/// Checks whether a path points into the Flatpak document portal (`/run/user/<uid>/doc/<id>/...`).
+pub fn is_document_portal_path(path: &Path) -> bool {
+    let components: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    components.len() > 5 && components[1] == "run" && components[2] == "user" && components[4] == "doc"
+}
+
+#[cfg(target_os = "linux")]
+fn read_portal_host_path(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new("user.document-portal.host-path").unwrap();
+    let mut buffer = vec!(0u8; 4096);
+    let length = unsafe { libc::getxattr(c_path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+    if length <= 0 {
+        return None;
+    }
+    buffer.truncate(length as usize);
+    let host_path = String::from_utf8(buffer).ok()?;
+    Some(PathBuf::from(host_path.trim_end_matches('\0')))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_portal_host_path(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Translates a path that was chosen through the Flatpak document portal to the path on the host, if the game can
+/// access the host path directly. Otherwise the portal path is kept, it stays valid across restarts.
+pub fn translate_portal_path(path: &Path) -> PathBuf {
+    if !is_document_portal_path(path) {
+        return path.to_path_buf();
+    }
+    match read_portal_host_path(path) {
+        Some(ref host_path) if host_path.is_dir() || host_path.is_file() => host_path.clone(),
+        _ => path.to_path_buf()
+    }
+}
+
+#[no_mangle]
+pub extern fn get_sandbox() -> Sandbox {
+    detect_sandbox(&read_env_var)
+}
+
+/// Translates a path chosen in a file dialog, see `translate_portal_path`.
+#[no_mangle]
+pub extern fn translate_chosen_path(path_ptr: *const c_char) -> *mut c_char {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    path_to_c_string(&translate_portal_path(Path::new(&path)))
+}
+
+#[no_mangle]
+pub extern fn get_config_file_path(ptr: *const EngineOptions) -> *mut c_char {
+    path_to_c_string(&config_file_path(&unsafe_from_ptr!(ptr).stracciatella_home))
+}
+
+#[no_mangle]
+pub extern fn get_log_dir(ptr: *const EngineOptions) -> *mut c_char {
+    path_to_c_string(&log_dir(&unsafe_from_ptr!(ptr).stracciatella_home))
+}
+
+#[no_mangle]
+pub extern fn get_save_dir(ptr: *const EngineOptions) -> *mut c_char {
+    let engine_options = unsafe_from_ptr!(ptr);
+    path_to_c_string(&configured_save_dir(engine_options))
+}
+
+#[no_mangle]
+pub extern fn get_save_dir_wide(ptr: *const EngineOptions) -> *mut u16 {
+    let engine_options = unsafe_from_ptr!(ptr);
+    path_to_wide_string(&configured_save_dir(engine_options))
+}
+
+/// Returns the folder that contains the saved games of all mods.
+#[no_mangle]
+pub extern fn get_saves_home(ptr: *const EngineOptions) -> *mut c_char {
+    path_to_c_string(&unsafe_from_ptr!(ptr).saves_home)
+}
+
+#[no_mangle]
+pub extern fn get_saves_home_wide(ptr: *const EngineOptions) -> *mut u16 {
+    path_to_wide_string(&unsafe_from_ptr!(ptr).saves_home)
+}
+
+#[no_mangle]
+pub extern fn get_cache_dir(ptr: *const EngineOptions) -> *mut c_char {
+    path_to_c_string(&cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home))
+}
+
+#[no_mangle]
+pub extern fn get_mods_dir(ptr: *const EngineOptions) -> *mut c_char {
+    path_to_c_string(&mods_dir(&unsafe_from_ptr!(ptr).stracciatella_home))
+}
+
+/// Creates the folders of the user, see `ensure_user_dirs`. Returns false on errors, see `get_last_error`.
+#[no_mangle]
+pub extern fn create_user_dirs(ptr: *const EngineOptions) -> bool {
+    match ensure_user_dirs(unsafe_from_ptr!(ptr)) {
+        Ok(_) => true,
+        Err(s) => {
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern fn clear_cache(ptr: *const EngineOptions) -> bool {
+    match clear_cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home) {
+        Ok(()) => {
+            emit_event(EngineEvent::CACHE_CLEARED);
+            true
+        },
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "home", &format!("Error clearing the cache: {}", s));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use EngineOptions;
+    use super::Sandbox;
+
+    fn env_of(vars: &'static [(&'static str, &'static str)]) -> Box<Fn(&str) -> Option<String>> {
+        Box::new(move |name| vars.iter().find(|&&(k, _)| k == name).map(|&(_, v)| String::from(v)))
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn unix_home_dir_should_fall_back_to_the_passwd_database() {
+        let passwd_home = || Some(PathBuf::from("/usr/home/beastie"));
+
+        assert_eq!(super::unix_home_dir(&*env_of(&[("HOME", "/home/user")]), &passwd_home), Some(PathBuf::from("/home/user")));
+        assert_eq!(super::unix_home_dir(&*env_of(&[]), &passwd_home), Some(PathBuf::from("/usr/home/beastie")));
+        assert_eq!(super::unix_home_dir(&*env_of(&[]), &|| None), None);
+    }
+
+    #[test]
+    fn env_home_dirs_should_prefer_ja2_home() {
+        let current_dir = Path::new("/work");
+
+        assert_eq!(super::env_home_dirs(&*env_of(&[("STRACCIATELLA_HOME", "/srv/ja2"), ("JA2_HOME", "/opt/ja2")]), current_dir).map(|d| d.home), Some(PathBuf::from("/opt/ja2")));
+        assert_eq!(super::env_home_dirs(&*env_of(&[("STRACCIATELLA_HOME", "ci-home"), ("JA2_HOME", "")]), current_dir), Some(super::HomeDirs { home: PathBuf::from("/work/ci-home"), saves_home: PathBuf::from("/work/ci-home") }));
+        assert_eq!(super::env_home_dirs(&*env_of(&[("HOME", "/home/user")]), current_dir), None);
+    }
+
+    #[test]
+    fn portable_home_dirs_should_use_the_folder_of_the_executable() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let usb_stick = temp_dir.path().join("usb");
+        fs::create_dir_all(&usb_stick).unwrap();
+        let args: Vec<String> = vec!(String::from("ja2"), String::from("--portable"));
+
+        assert!(super::wants_portable_mode(&args));
+        assert!(!super::wants_portable_mode(&args[1..]));
+        assert_eq!(super::portable_home_dirs(true, Some(&usb_stick)), Ok(Some(super::HomeDirs { home: usb_stick.clone(), saves_home: usb_stick.clone() })));
+        assert_eq!(super::portable_home_dirs(false, Some(&usb_stick)), Ok(None));
+        assert!(super::portable_home_dirs(true, None).is_err());
+
+        fs::write(usb_stick.join("ja2.json"), "{}").unwrap();
+        assert_eq!(super::portable_home_dirs(false, Some(&usb_stick)).unwrap().map(|d| d.home), Some(usb_stick));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn passwd_home_dir_should_be_absolute() {
+        if let Some(home) = super::passwd_home_dir() {
+            assert!(home.is_absolute(), "{}", home.display());
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn xdg_base_dirs_should_ignore_relative_paths() {
+        let home = Path::new("/home/user");
+
+        assert_eq!(super::xdg_base_dirs(&*env_of(&[]), home), (PathBuf::from("/home/user/.config"), PathBuf::from("/home/user/.local/share")));
+        let env = env_of(&[("XDG_CONFIG_HOME", "/config"), ("XDG_DATA_HOME", "data")]);
+        assert_eq!(super::xdg_base_dirs(&*env, home), (PathBuf::from("/config"), PathBuf::from("/home/user/.local/share")));
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "macos", target_os = "android")))]
+    fn find_xdg_home_dirs_should_move_the_legacy_home_once() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let user_home = temp_dir.path();
+        fs::create_dir_all(user_home.join(".ja2/SavedGames-wildfire")).unwrap();
+        fs::create_dir_all(user_home.join(".ja2/SavedGames")).unwrap();
+        fs::write(user_home.join(".ja2/SavedGames/SaveGame01.sav"), "save").unwrap();
+        fs::write(user_home.join(".ja2/ja2.json"), "{}").unwrap();
+        let config_home = user_home.join("config");
+        let env_var = |name: &str| if name == "XDG_CONFIG_HOME" { Some(config_home.to_string_lossy().into_owned()) } else { None };
+
+        let dirs = super::find_xdg_home_dirs(&env_var, user_home);
+
+        assert_eq!(dirs, super::HomeDirs { home: user_home.join("config/ja2"), saves_home: user_home.join(".local/share/ja2") });
+        assert!(dirs.home.join("ja2.json").is_file());
+        assert_eq!(fs::read(dirs.saves_home.join("SavedGames/SaveGame01.sav")).unwrap(), b"save");
+        assert!(dirs.saves_home.join("SavedGames-wildfire").is_dir());
+        assert!(!user_home.join(".ja2").exists());
+        fs::create_dir_all(user_home.join(".ja2/logs")).unwrap();
+        assert_eq!(super::find_xdg_home_dirs(&env_var, user_home), dirs);
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "macos", target_os = "android")))]
+    fn find_xdg_home_dirs_should_keep_the_legacy_home_if_it_can_not_be_moved() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let user_home = temp_dir.path();
+        fs::create_dir_all(user_home.join(".ja2/SavedGames")).unwrap();
+        fs::write(user_home.join(".ja2/ja2.json"), "{}").unwrap();
+        fs::create_dir_all(user_home.join(".local/share/ja2/SavedGames")).unwrap();
+
+        let dirs = super::find_xdg_home_dirs(&*env_of(&[]), user_home);
+
+        assert_eq!(dirs, super::HomeDirs { home: user_home.join(".ja2"), saves_home: user_home.join(".ja2") });
+        assert!(!user_home.join(".config/ja2").exists());
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "android")))]
+    fn migrate_legacy_home_dirs_should_move_the_saves_to_a_single_home() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let legacy_home = temp_dir.path().join(".ja2");
+        fs::create_dir_all(legacy_home.join("SavedGames-wildfire")).unwrap();
+        fs::write(legacy_home.join("ja2.json"), "{}").unwrap();
+        let dirs = super::HomeDirs::single(temp_dir.path().join("Library/Application Support/JA2 Stracciatella"));
+
+        assert_eq!(super::migrate_legacy_home_dirs(legacy_home.clone(), dirs.clone()), dirs);
+        assert!(dirs.home.join("ja2.json").is_file());
+        assert!(dirs.home.join("SavedGames-wildfire").is_dir());
+        assert!(!legacy_home.exists());
+    }
+
+    #[test]
+    fn follow_moved_home_should_find_the_new_home() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        let new = temp_dir.path().join("new");
+        let saves_home = temp_dir.path().join("saves");
+        fs::create_dir_all(&old).unwrap();
+        fs::create_dir_all(&new).unwrap();
+        fs::write(old.join(super::MOVED_HOME_FILE), format!("{}\n", new.display())).unwrap();
+        fs::write(new.join("ja2.json"), "{}").unwrap();
+
+        assert_eq!(super::follow_moved_home(super::HomeDirs::single(old.clone())), super::HomeDirs::single(new.clone()));
+        let dirs = super::HomeDirs { home: old.clone(), saves_home: saves_home.clone() };
+        assert_eq!(super::follow_moved_home(dirs), super::HomeDirs { home: new.clone(), saves_home });
+        fs::write(old.join("ja2.json"), "{}").unwrap();
+        assert_eq!(super::follow_moved_home(super::HomeDirs::single(old.clone())), super::HomeDirs::single(old));
+    }
+
+    #[test]
+    fn android_home_dirs_should_use_the_storage_of_the_app() {
+        let storage_dir = PathBuf::from("/storage/emulated/0/Android/data/io.github.ja2stracciatella/files");
+
+        assert_eq!(super::android_home_dirs(Some(storage_dir.clone())), Ok(super::HomeDirs::single(storage_dir.join("ja2"))));
+        assert!(super::android_home_dirs(None).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn find_macos_home_dirs_should_use_application_support() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        let dirs = super::find_macos_home_dirs(temp_dir.path());
+
+        assert_eq!(dirs.home, temp_dir.path().join("Library/Application Support/JA2 Stracciatella"));
+        assert_eq!(dirs.saves_home, dirs.home);
+    }
+
+    #[test]
+    fn detect_sandbox_should_detect_snap() {
+        let env = env_of(&[("SNAP", "/snap/ja2/12"), ("SNAP_USER_COMMON", "/home/user/snap/ja2/common")]);
+
+        assert_eq!(super::detect_sandbox(&*env), Sandbox::SNAP);
+        assert_eq!(super::sandbox_data_home(Sandbox::SNAP, &*env), Some(PathBuf::from("/home/user/snap/ja2/common")));
+    }
+
+    #[test]
+    fn detect_sandbox_should_detect_flatpak() {
+        let env = env_of(&[("FLATPAK_ID", "io.github.ja2-stracciatella"), ("HOME", "/home/user")]);
+
+        assert_eq!(super::detect_sandbox(&*env), Sandbox::FLATPAK);
+        assert_eq!(super::sandbox_data_home(Sandbox::FLATPAK, &*env), Some(PathBuf::from("/home/user/.var/app/io.github.ja2-stracciatella/data")));
+        assert_eq!(super::suggested_data_dir(Sandbox::FLATPAK, &*env), Some(PathBuf::from("/home/user/.var/app/io.github.ja2-stracciatella/data/ja2-data")));
+    }
+
+    #[test]
+    fn sandbox_data_home_should_prefer_xdg_data_home_in_flatpak() {
+        let env = env_of(&[("FLATPAK_ID", "io.github.ja2-stracciatella"), ("HOME", "/home/user"), ("XDG_DATA_HOME", "/data")]);
+
+        assert_eq!(super::sandbox_data_home(Sandbox::FLATPAK, &*env), Some(PathBuf::from("/data")));
+        assert_eq!(super::sandbox_data_home(Sandbox::NONE, &*env), None);
+    }
+
+    #[test]
+    fn is_document_portal_path_should_detect_portal_paths() {
+        assert!(super::is_document_portal_path(Path::new("/run/user/1000/doc/a1b2c3/Jagged Alliance 2")));
+        assert!(!super::is_document_portal_path(Path::new("/run/user/1000/doc")));
+        assert!(!super::is_document_portal_path(Path::new("/home/user/ja2")));
+    }
+
+    #[test]
+    fn translate_portal_path_should_keep_other_paths() {
+        assert_eq!(super::translate_portal_path(Path::new("/home/user/ja2")), PathBuf::from("/home/user/ja2"));
+    }
+
+    #[test]
+    fn save_dir_should_depend_on_the_first_mod() {
+        let home = PathBuf::from("/home/user/.ja2");
+
+        assert_eq!(super::save_dir(&home, &[]), PathBuf::from("/home/user/.ja2/SavedGames"));
+        assert_eq!(super::save_dir(&home, &[String::from("a"), String::from("b")]), PathBuf::from("/home/user/.ja2/SavedGames-a"));
+    }
+
+    #[test]
+    fn game_dir_should_prefer_the_config_over_the_build_default() {
+        let mut engine_options = EngineOptions::default();
+
+        assert_eq!(super::game_dir(&engine_options, Path::new("/usr/share/ja2")), PathBuf::from("/usr/share/ja2"));
+        assert_eq!(super::game_dir(&engine_options, Path::new("")), super::executable_dir().unwrap());
+        engine_options.game_dir = PathBuf::from("/opt/ja2-stracciatella");
+        assert_eq!(super::game_dir(&engine_options, Path::new("/usr/share/ja2")), PathBuf::from("/opt/ja2-stracciatella"));
+    }
+
+    #[test]
+    fn configured_save_dir_should_use_the_save_game_dir_of_the_config() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from("/home/user/.ja2");
+        engine_options.saves_home = PathBuf::from("/home/user/.local/share/ja2");
+        assert_eq!(super::configured_save_dir(&engine_options), PathBuf::from("/home/user/.local/share/ja2/SavedGames"));
+
+        engine_options.save_game_dir = PathBuf::from("/home/user/Dropbox/JA2");
+        assert_eq!(super::configured_save_dir(&engine_options), PathBuf::from("/home/user/Dropbox/JA2"));
+        engine_options.save_game_dir = PathBuf::from("cloud");
+        engine_options.mods = vec!(String::from("wildfire"));
+        assert_eq!(super::configured_save_dir(&engine_options), PathBuf::from("/home/user/.ja2/cloud/wildfire"));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn path_helpers_should_be_relative_to_the_home() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from("/home/user/.ja2");
+        engine_options.saves_home = PathBuf::from("/home/user/.local/share/ja2");
+
+        unsafe {
+            assert_eq!(CString::from_raw(super::get_config_file_path(&engine_options)), CString::new("/home/user/.ja2/ja2.json").unwrap());
+            assert_eq!(CString::from_raw(super::get_log_dir(&engine_options)), CString::new("/home/user/.ja2/logs").unwrap());
+            assert_eq!(CString::from_raw(super::get_save_dir(&engine_options)), CString::new("/home/user/.local/share/ja2/SavedGames").unwrap());
+            assert_eq!(CString::from_raw(super::get_saves_home(&engine_options)), CString::new("/home/user/.local/share/ja2").unwrap());
+            assert_eq!(CString::from_raw(super::get_cache_dir(&engine_options)), CString::new("/home/user/.ja2/tmp").unwrap());
+        }
+    }
+
+    #[test]
+    fn clear_cache_dir_should_keep_the_dir() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let cache_dir = super::cache_dir(temp_dir.path());
+        fs::create_dir_all(cache_dir.join("tilecache")).unwrap();
+        fs::write(cache_dir.join("tilecache/a.sti"), b"a").unwrap();
+        fs::write(cache_dir.join("b.tmp"), b"b").unwrap();
+
+        assert_eq!(super::clear_cache_dir(temp_dir.path()), Ok(()));
+
+        assert!(cache_dir.is_dir());
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+        assert_eq!(super::clear_cache_dir(&temp_dir.path().join("missing")), Ok(()));
+    }
+
+    #[test]
+    fn path_getters_should_keep_non_ascii_user_names() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from("/home/Пользователь/Документы/JA2");
+        engine_options.saves_home = PathBuf::from("/home/用户/JA2");
+
+        unsafe {
+            assert_eq!(CString::from_raw(::get_stracciatella_home(&engine_options)).to_str(), Ok("/home/Пользователь/Документы/JA2"));
+            let wide = ::get_stracciatella_home_wide(&engine_options);
+            let len = (0..).take_while(|i| *wide.add(*i) != 0).count();
+            assert_eq!(String::from_utf16(std::slice::from_raw_parts(wide, len)).unwrap(), "/home/Пользователь/Документы/JA2");
+            super::free_rust_wide_string(wide);
+        }
+        assert_eq!(super::path_to_wide(&engine_options.saves_home), "/home/用户/JA2\u{0}".encode_utf16().collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn ensure_user_dirs_should_create_the_folders_of_the_user() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = temp_dir.path().join("config");
+        engine_options.saves_home = temp_dir.path().join("data");
+        engine_options.mods = vec!(String::from("wildfire"));
+
+        let dirs = super::ensure_user_dirs(&engine_options).unwrap();
+
+        assert_eq!(dirs, super::UserDirs {
+            saves: temp_dir.path().join("data/SavedGames-wildfire"),
+            mods: temp_dir.path().join("config/mods"),
+            temp: temp_dir.path().join("config/tmp"),
+            logs: temp_dir.path().join("config/logs"),
+        });
+        assert!(dirs.saves.is_dir() && dirs.mods.is_dir() && dirs.temp.is_dir() && dirs.logs.is_dir());
+        assert_eq!(super::ensure_user_dirs(&engine_options), Ok(dirs));
+    }
+}
@@ -0,0 +1,250 @@
+//! Decoder for raw DEFLATE streams (RFC 1951), e.g. the compressed chunks of InstallShield cabinets.
+//!
+//! The decoder follows `puff.c` of zlib: it is small and checks every code, so damaged data from a scratched CD is
+//! reported as an error instead of producing garbage. It is not fast, which does not matter for a one-time import.
+
+const MAX_BITS: usize = 15;
+const MAX_LENGTH_CODES: usize = 286;
+const MAX_DISTANCE_CODES: usize = 30;
+const FIXED_LENGTH_CODES: usize = 288;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order of the code length codes in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.input.get(self.position).ok_or_else(|| String::from("The compressed data ends unexpectedly"))?;
+            self.buffer |= u32::from(byte) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code as the number of codes per length and the symbols ordered by their code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(String::from("The compressed data has an invalid Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec!(0u16; lengths.len());
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("The compressed data has an invalid code"))
+    }
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), String> {
+    reader.align_to_byte();
+    let header = reader.input.get(reader.position..reader.position + 4).ok_or_else(|| String::from("The compressed data ends unexpectedly"))?;
+    let length = usize::from(header[0]) | usize::from(header[1]) << 8;
+    if header[2] != !header[0] || header[3] != !header[1] {
+        return Err(String::from("The compressed data has an invalid stored block"));
+    }
+    let start = reader.position + 4;
+    let contents = reader.input.get(start..start + length).ok_or_else(|| String::from("The compressed data ends unexpectedly"))?;
+    output.extend_from_slice(contents);
+    reader.position = start + length;
+    Ok(())
+}
+
+fn inflate_codes(reader: &mut BitReader, output: &mut Vec<u8>, lengths: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(reader)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(String::from("The compressed data has an invalid length"));
+        }
+        let length = LENGTH_BASE[symbol] as usize + reader.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+        let symbol = distances.decode(reader)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err(String::from("The compressed data has an invalid distance"));
+        }
+        let distance = DISTANCE_BASE[symbol] as usize + reader.bits(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+        if distance > output.len() {
+            return Err(String::from("The compressed data refers to data before its start"));
+        }
+        // The copy may overlap with the bytes it produces
+        let start = output.len() - distance;
+        for i in 0..length {
+            let byte = output[start + i];
+            output.push(byte);
+        }
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; FIXED_LENGTH_CODES];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; MAX_DISTANCE_CODES])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let length_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if length_count > MAX_LENGTH_CODES || distance_count > MAX_DISTANCE_CODES {
+        return Err(String::from("The compressed data has too many codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec!();
+    while lengths.len() < length_count + distance_count {
+        let (length, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + reader.bits(2)?),
+                None => return Err(String::from("The compressed data repeats a missing length")),
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > length_count + distance_count {
+        return Err(String::from("The compressed data has too many lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(String::from("The compressed data has no end of block code"));
+    }
+    Ok((Huffman::new(&lengths[..length_count])?, Huffman::new(&lengths[length_count..])?))
+}
+
+/// Decompresses a raw DEFLATE stream. Data after the final block is ignored.
+pub fn inflate(input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { input, position: 0, buffer: 0, count: 0 };
+    let mut output = vec!();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, &mut output)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                inflate_codes(&mut reader, &mut output, &lengths, &distances)?;
+            },
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut reader)?;
+                inflate_codes(&mut reader, &mut output, &lengths, &distances)?;
+            },
+            _ => return Err(String::from("The compressed data has an invalid block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inflate;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn inflate_should_decode_stored_and_fixed_blocks() {
+        assert_eq!(inflate(&from_hex("011100eeff4a616767656420416c6c69616e63652032")), Ok(b"Jagged Alliance 2".to_vec()));
+        assert_eq!(inflate(&from_hex("f34a4c4f4f4d5170ccc9c94ccc4b4e55300200")), Ok(b"Jagged Alliance 2".to_vec()));
+    }
+
+    #[test]
+    fn inflate_should_decode_dynamic_blocks() {
+        let compressed = from_hex("edcdc10d44410802d056acc18e089983c693d27fbedbc116a00937e1a5290aad6e81a5926f3258640550c030cb85bdd43378203ca7cadff36dfa88922131052538bd3310dd7f1d37600792fb34340bf5233c8f3df6d8638f3df6d8638f3df6d8ffd90f");
+        let expected: Vec<u8> = (0..3000usize).map(|i| b"ja2 stracciatella"[(i * i * 3 + i / 7) % 17]).collect();
+
+        assert_eq!(inflate(&compressed), Ok(expected));
+    }
+
+    #[test]
+    fn inflate_should_fail_for_damaged_data() {
+        let compressed = from_hex("f34a4c4f4f4d5170ccc9c94ccc4b4e55300200");
+
+        assert!(inflate(&compressed[..10]).is_err());
+        assert!(inflate(&from_hex("011100eefe4a61")).is_err());
+        assert!(inflate(&[0x07]).is_err());
+    }
+}
@@ -0,0 +1,511 @@
+//! Reader for the InstallShield cabinets of the original CDs, e.g. `data1.hdr` with `data1.cab`, `data2.cab`, ...
+//!
+//! The header lists the directories and files, the volumes contain the data. Files are stored in chunks of raw
+//! DEFLATE data that start with their 16 bit length and may continue in the next volume. Versions 5 and later are
+//! supported, the layout of the file descriptors changed with version 6. The format is described by `unshield`.
+
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use datadir::find_case_insensitive;
+use errorcontext::{ErrorContext, ErrorKind};
+use inflate::inflate;
+
+const SIGNATURE: u32 = 0x2863_5349;
+const COMMON_HEADER_SIZE: usize = 20;
+const VOLUME_HEADER_SIZE_V5: usize = 0x28;
+const VOLUME_HEADER_SIZE_V6: usize = 0x40;
+const FILE_DESCRIPTOR_SIZE_V6: usize = 0x57;
+
+const FILE_SPLIT: u16 = 1;
+const FILE_OBFUSCATED: u16 = 2;
+const FILE_COMPRESSED: u16 = 4;
+const FILE_INVALID: u16 = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CabinetFile {
+    /// Path of the file with `/` as separator, e.g. `Data/BinaryData/ja2set.dat`
+    pub path: String,
+    index: u32,
+    flags: u16,
+    pub size: u64,
+    compressed_size: u64,
+    data_offset: u64,
+    /// Volume with the start of the file, counted from 1
+    volume: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cabinet {
+    /// Folder and name prefix of the volumes, e.g. `data` for `data1.cab`
+    dir: PathBuf,
+    prefix: String,
+    major_version: u32,
+    pub files: Vec<CabinetFile>,
+}
+
+fn invalid(path: &Path) -> ErrorContext {
+    ErrorContext::new(ErrorKind::RESOURCES, format!("{} is not a valid InstallShield cabinet", path.display())).with_path(path)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from(b[0]) | u16::from(b[1]) << 8)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| b.iter().rev().fold(0, |v, &b| v << 8 | u32::from(b)))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| b.iter().rev().fold(0, |v, &b| v << 8 | u64::from(b)))
+}
+
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().position(|&b| b == 0)?;
+    Some(bytes[..end].iter().map(|&b| char::from(b)).collect())
+}
+
+/// Returns the major version from the version field of the common header.
+fn major_version(version: u32) -> u32 {
+    match version >> 24 {
+        1 => (version >> 12) & 0xf,
+        2 | 4 => (version & 0xffff) / 100,
+        _ => 0,
+    }
+}
+
+/// Reverses the obfuscation of some cabinets. The seed is the number of bytes of the file that were read before.
+fn deobfuscate(buffer: &mut [u8], seed: &mut u32) {
+    for b in buffer.iter_mut() {
+        *b = (*b ^ 0xd5).rotate_right(2).wrapping_sub((*seed % 0x47) as u8);
+        *seed = seed.wrapping_add(1);
+    }
+}
+
+/// Returns the header of the cabinets in a folder, `data1.hdr` or `data1.cab` if the header is part of the first
+/// volume.
+pub fn find_cabinet_header(dir: &Path) -> Option<PathBuf> {
+    find_case_insensitive(dir, "data1.hdr").or_else(|| find_case_insensitive(dir, "data1.cab")).filter(|p| p.is_file())
+}
+
+impl Cabinet {
+    pub fn open(header_path: &Path) -> Result<Cabinet, ErrorContext> {
+        let data = fs::read(header_path)
+            .map_err(|s| ErrorContext::io(&s, header_path).wrap(ErrorKind::RESOURCES, format!("Error reading {}", header_path.display())))?;
+        Cabinet::parse(header_path, &data)
+    }
+
+    /// Reads the file list of a header, `header_path` is used to find the volumes.
+    pub fn parse(header_path: &Path, data: &[u8]) -> Result<Cabinet, ErrorContext> {
+        let name = header_path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let prefix = match name.strip_suffix('1') {
+            Some(p) => String::from(p),
+            None => return Err(invalid(header_path)),
+        };
+        if read_u32(data, 0) != Some(SIGNATURE) {
+            return Err(invalid(header_path));
+        }
+        let major_version = read_u32(data, 4).map(major_version).unwrap_or(0);
+        if major_version < 5 {
+            return Err(ErrorContext::new(ErrorKind::RESOURCES, format!("{} has an unsupported InstallShield version", header_path.display())).with_path(header_path));
+        }
+        let files = read_files(data, major_version).ok_or_else(|| invalid(header_path))?;
+        Ok(Cabinet { dir: header_path.parent().map(Path::to_path_buf).unwrap_or_default(), prefix, major_version, files })
+    }
+
+    fn volume_path(&self, volume: u32) -> Result<PathBuf, ErrorContext> {
+        let name = format!("{}{}.cab", self.prefix, volume);
+        find_case_insensitive(&self.dir, &name).ok_or_else(|| {
+            ErrorContext::new(ErrorKind::RESOURCES, format!("The volume {} is missing in {}", name, self.dir.display())).with_path(&self.dir.join(&name))
+        })
+    }
+
+    /// Writes the contents of a file and returns its size.
+    pub fn extract<W: Write>(&self, file: &CabinetFile, writer: &mut W) -> Result<u64, ErrorContext> {
+        let mut reader = VolumeReader::open(self, file)?;
+        let mut written = 0u64;
+        let write_error = |s: ::std::io::Error| ErrorContext::new(ErrorKind::IO, s.kind().to_string()).wrap(ErrorKind::RESOURCES, format!("Error writing {}", file.path));
+
+        if file.flags & FILE_COMPRESSED != 0 {
+            let mut left = file.compressed_size;
+            while left > 0 {
+                let mut length = [0u8; 2];
+                reader.read(&mut length)?;
+                let length = u64::from(u16::from(length[0]) | u16::from(length[1]) << 8);
+                let mut chunk = vec!(0u8; length as usize);
+                reader.read(&mut chunk)?;
+                let contents = inflate(&chunk).map_err(|s| ErrorContext::new(ErrorKind::RESOURCES, format!("{} is damaged: {}", file.path, s)))?;
+                writer.write_all(&contents).map_err(write_error)?;
+                written += contents.len() as u64;
+                left = left.checked_sub(2 + length).ok_or_else(|| ErrorContext::new(ErrorKind::RESOURCES, format!("{} is damaged", file.path)))?;
+            }
+        } else {
+            let mut buffer = vec!(0u8; 64 * 1024);
+            while written < file.size {
+                let size = buffer.len().min((file.size - written) as usize);
+                reader.read(&mut buffer[..size])?;
+                writer.write_all(&buffer[..size]).map_err(write_error)?;
+                written += size as u64;
+            }
+        }
+
+        if written != file.size {
+            return Err(ErrorContext::new(ErrorKind::RESOURCES, format!("{} is damaged, it has {} instead of {} bytes", file.path, written, file.size)));
+        }
+        Ok(written)
+    }
+}
+
+/// Reads the directories and valid files of the cabinet descriptor.
+fn read_files(data: &[u8], major_version: u32) -> Option<Vec<CabinetFile>> {
+    let descriptor = read_u32(data, 12)? as usize;
+    let file_table_offset = read_u32(data, descriptor + 0x0c)? as usize;
+    let directory_count = read_u32(data, descriptor + 0x1c)? as usize;
+    let file_count = read_u32(data, descriptor + 0x28)? as usize;
+    let file_table_offset2 = read_u32(data, descriptor + 0x2c)? as usize;
+    let file_table = descriptor + file_table_offset;
+    let table_entry = |i: usize| read_u32(data, file_table + 4 * i).map(|o| o as usize);
+
+    let directories = (0..directory_count)
+        .map(|i| read_string(data, file_table + table_entry(i)?))
+        .collect::<Option<Vec<String>>>()?;
+
+    let mut files = vec!();
+    for index in 0..file_count {
+        let (name_offset, directory_index, file) = if major_version == 5 {
+            let d = file_table + table_entry(directory_count + index)?;
+            let file = CabinetFile {
+                path: String::new(),
+                index: index as u32,
+                flags: read_u16(data, d + 8)?,
+                size: u64::from(read_u32(data, d + 10)?),
+                compressed_size: u64::from(read_u32(data, d + 14)?),
+                data_offset: u64::from(read_u32(data, d + 0x26)?),
+                volume: 1,
+            };
+            (read_u32(data, d)? as usize, read_u32(data, d + 4)? as usize, file)
+        } else {
+            let d = file_table + file_table_offset2 + index * FILE_DESCRIPTOR_SIZE_V6;
+            let file = CabinetFile {
+                path: String::new(),
+                index: index as u32,
+                flags: read_u16(data, d)?,
+                size: read_u64(data, d + 2)?,
+                compressed_size: read_u64(data, d + 10)?,
+                data_offset: read_u64(data, d + 18)?,
+                volume: u32::from(read_u16(data, d + 0x55)?),
+            };
+            (read_u32(data, d + 0x3a)? as usize, usize::from(read_u16(data, d + 0x3e)?), file)
+        };
+        if file.flags & FILE_INVALID != 0 || name_offset == 0 {
+            continue;
+        }
+        let name = read_string(data, file_table + name_offset)?;
+        let directory = directories.get(directory_index)?.replace('\\', "/");
+        let path = if directory.is_empty() { name } else { format!("{}/{}", directory.trim_end_matches('/'), name) };
+        files.push(CabinetFile { path, ..file });
+    }
+    Some(files)
+}
+
+/// Location of a file in a volume as the data offset and the compressed and expanded size.
+struct VolumeRange {
+    first_file_index: u32,
+    last_file_index: u32,
+    first: (u64, u64, u64),
+    last: (u64, u64, u64),
+}
+
+/// Reads the data of a file, continuing in the next volume where the file is split.
+struct VolumeReader<'a> {
+    cabinet: &'a Cabinet,
+    file: &'a CabinetFile,
+    volume: u32,
+    handle: File,
+    bytes_left: u64,
+    seed: u32,
+}
+
+impl<'a> VolumeReader<'a> {
+    fn open(cabinet: &'a Cabinet, file: &'a CabinetFile) -> Result<VolumeReader<'a>, ErrorContext> {
+        let mut volume = file.volume.max(1);
+        loop {
+            let (handle, range) = VolumeReader::open_volume(cabinet, volume)?;
+            // Version 5 does not store the volume of a file, it is searched from the first volume
+            if cabinet.major_version == 5 && file.index > range.last_file_index {
+                volume += 1;
+                continue;
+            }
+            let mut reader = VolumeReader { cabinet, file, volume, handle, bytes_left: 0, seed: 0 };
+            reader.start(range)?;
+            return Ok(reader);
+        }
+    }
+
+    fn open_volume(cabinet: &Cabinet, volume: u32) -> Result<(File, VolumeRange), ErrorContext> {
+        let path = cabinet.volume_path(volume)?;
+        let error = |s: ::std::io::Error| ErrorContext::io(&s, &path).wrap(ErrorKind::RESOURCES, format!("Error reading {}", path.display()));
+        let mut handle = File::open(&path).map_err(&error)?;
+        let size = if cabinet.major_version == 5 { VOLUME_HEADER_SIZE_V5 } else { VOLUME_HEADER_SIZE_V6 };
+        let mut header = vec!(0u8; COMMON_HEADER_SIZE + size);
+        handle.read_exact(&mut header).map_err(&error)?;
+        if read_u32(&header, 0) != Some(SIGNATURE) {
+            return Err(invalid(&path));
+        }
+
+        let h = &header[COMMON_HEADER_SIZE..];
+        let u32_at = |i: usize| u64::from(read_u32(h, 4 * i).unwrap_or(0));
+        let range = if cabinet.major_version == 5 {
+            VolumeRange {
+                first_file_index: u32_at(2) as u32,
+                last_file_index: u32_at(3) as u32,
+                first: (u32_at(4), u32_at(6), u32_at(5)),
+                last: (u32_at(7), u32_at(9), u32_at(8)),
+            }
+        } else {
+            let u64_at = |i: usize| u32_at(i) | u32_at(i + 1) << 32;
+            VolumeRange {
+                first_file_index: u32_at(2) as u32,
+                last_file_index: u32_at(3) as u32,
+                first: (u64_at(4), u64_at(8), u64_at(6)),
+                last: (u64_at(10), u64_at(14), u64_at(12)),
+            }
+        };
+        Ok((handle, range))
+    }
+
+    /// Seeks to the part of the file in the current volume. The first and last file of a volume may be split.
+    fn start(&mut self, range: VolumeRange) -> Result<(), ErrorContext> {
+        let compressed = self.file.flags & FILE_COMPRESSED != 0;
+        let part = if self.file.index == range.last_file_index && range.last.1 != 0 {
+            Some(range.last)
+        } else if self.file.index == range.first_file_index && range.first.1 != 0 {
+            Some(range.first)
+        } else {
+            None
+        };
+        let (offset, size) = match part {
+            Some((offset, compressed_size, size)) if self.file.flags & FILE_SPLIT != 0 => (offset, if compressed { compressed_size } else { size }),
+            _ => (self.file.data_offset, if compressed { self.file.compressed_size } else { self.file.size }),
+        };
+        self.bytes_left = size;
+        self.handle.seek(SeekFrom::Start(offset))
+            .map(|_| ())
+            .map_err(|s| ErrorContext::new(ErrorKind::IO, s.kind().to_string()).wrap(ErrorKind::RESOURCES, format!("Error reading {}", self.file.path)))
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), ErrorContext> {
+        let mut done = 0;
+        while done < buffer.len() {
+            if self.bytes_left == 0 {
+                let (handle, range) = VolumeReader::open_volume(self.cabinet, self.volume + 1)?;
+                self.volume += 1;
+                self.handle = handle;
+                self.start(range)?;
+                if self.bytes_left == 0 {
+                    return Err(ErrorContext::new(ErrorKind::RESOURCES, format!("{} is damaged", self.file.path)));
+                }
+            }
+            let size = (buffer.len() - done).min(self.bytes_left as usize);
+            self.handle.read_exact(&mut buffer[done..done + size])
+                .map_err(|s| ErrorContext::new(ErrorKind::IO, s.kind().to_string()).wrap(ErrorKind::RESOURCES, format!("Error reading {}", self.file.path)))?;
+            done += size;
+            self.bytes_left -= size as u64;
+        }
+        if self.file.flags & FILE_OBFUSCATED != 0 {
+            deobfuscate(buffer, &mut self.seed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::Path;
+
+    use super::{Cabinet, FILE_COMPRESSED, FILE_SPLIT, find_cabinet_header};
+
+    fn put_u32(data: &mut Vec<u8>, offset: usize, value: u32) {
+        if data.len() < offset + 4 {
+            data.resize(offset + 4, 0);
+        }
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Compresses the contents into chunks of stored DEFLATE blocks.
+    fn compress(contents: &[u8]) -> Vec<u8> {
+        let mut data = vec!();
+        for chunk in contents.chunks(7) {
+            let n = chunk.len() as u8;
+            data.extend_from_slice(&[5 + n, 0, 1, n, 0, !n, 0xff]);
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    /// Writes `data1.hdr` and the volumes of a cabinet with the files as directory, name and contents. With
+    /// `split_last` the last file continues in a second volume.
+    pub fn write_cabinet(dir: &Path, major_version: u32, files: &[(&str, &str, &[u8])], split_last: bool) {
+        let mut directories: Vec<&str> = vec!();
+        for file in files {
+            if !directories.contains(&file.0) {
+                directories.push(file.0);
+            }
+        }
+        let volume_header_size = 20 + if major_version == 5 { 0x28 } else { 0x40 };
+        let compressed: Vec<Vec<u8>> = files.iter().map(|f| compress(f.2)).collect();
+        let last = files.len() - 1;
+
+        let mut table = vec!();
+        let table_entries = directories.len() + if major_version == 5 { files.len() } else { 0 };
+        table.resize(table_entries * 4, 0);
+        for (i, directory) in directories.iter().enumerate() {
+            let offset = table.len() as u32;
+            put_u32(&mut table, 4 * i, offset);
+            table.extend_from_slice(directory.as_bytes());
+            table.push(0);
+        }
+        let mut name_offsets = vec!();
+        for file in files {
+            name_offsets.push(table.len() as u32);
+            table.extend_from_slice(file.1.as_bytes());
+            table.push(0);
+        }
+        let file_table_offset2 = table.len();
+        let mut data_offset = volume_header_size as u32;
+        for (i, file) in files.iter().enumerate() {
+            let directory_index = directories.iter().position(|d| *d == file.0).unwrap() as u32;
+            let flags = FILE_COMPRESSED | if split_last && i == last { FILE_SPLIT } else { 0 };
+            let (size, compressed_size) = (file.2.len() as u32, compressed[i].len() as u32);
+            let start = table.len();
+            if major_version == 5 {
+                put_u32(&mut table, 4 * (directories.len() + i), start as u32);
+                put_u32(&mut table, start, name_offsets[i]);
+                put_u32(&mut table, start + 4, directory_index);
+                table.extend_from_slice(&flags.to_le_bytes());
+                table.extend_from_slice(&size.to_le_bytes());
+                table.extend_from_slice(&compressed_size.to_le_bytes());
+                put_u32(&mut table, start + 0x26, data_offset);
+                table.resize(start + 0x3a, 0);
+            } else {
+                table.extend_from_slice(&flags.to_le_bytes());
+                table.extend_from_slice(&u64::from(size).to_le_bytes());
+                table.extend_from_slice(&u64::from(compressed_size).to_le_bytes());
+                table.extend_from_slice(&u64::from(data_offset).to_le_bytes());
+                put_u32(&mut table, start + 0x3a, name_offsets[i]);
+                table.extend_from_slice(&(directory_index as u16).to_le_bytes());
+                table.resize(start + 0x55, 0);
+                table.extend_from_slice(&1u16.to_le_bytes());
+            }
+            data_offset += compressed_size;
+        }
+
+        let version = if major_version == 5 { 0x0100_5000 } else { 0x0400_0000 | major_version * 100 };
+        let common_header = |header: &mut Vec<u8>| {
+            put_u32(header, 0, super::SIGNATURE);
+            put_u32(header, 4, version);
+        };
+        let mut header = vec!();
+        common_header(&mut header);
+        put_u32(&mut header, 12, 0x200);
+        put_u32(&mut header, 0x200 + 0x0c, 0x100);
+        put_u32(&mut header, 0x200 + 0x1c, directories.len() as u32);
+        put_u32(&mut header, 0x200 + 0x28, files.len() as u32);
+        put_u32(&mut header, 0x200 + 0x2c, file_table_offset2 as u32);
+        header.resize(0x300, 0);
+        header.extend_from_slice(&table);
+        fs::write(dir.join("data1.hdr"), header).unwrap();
+
+        // The volume header has the index, offset, expanded and compressed size of the first and last file
+        let write_volume = |name: &str, first: (u32, u32, u32, u32), last: (u32, u32, u32, u32), data: &[u8]| {
+            let mut volume = vec!();
+            common_header(&mut volume);
+            let fields = [volume_header_size as u32, 0, first.0, last.0, first.1, first.2, first.3, last.1, last.2, last.3];
+            for (i, &field) in fields.iter().enumerate() {
+                if major_version == 5 {
+                    put_u32(&mut volume, 20 + 4 * i, field);
+                } else {
+                    // Version 6 stores the offsets and sizes with 64 bits
+                    let index = if i < 4 { i } else { 4 + 2 * (i - 4) };
+                    put_u32(&mut volume, 20 + 4 * index, field);
+                }
+            }
+            volume.resize(volume_header_size, 0);
+            volume.extend_from_slice(data);
+            fs::write(dir.join(name), volume).unwrap();
+        };
+        let data: Vec<u8> = compressed.concat();
+        let last_offset = data_offset - compressed[last].len() as u32;
+        let last_size = files[last].2.len() as u32;
+        if split_last {
+            let split = data.len() - compressed[last].len() / 2;
+            let in_first = (split - (last_offset as usize - volume_header_size)) as u32;
+            write_volume("data1.cab", (0, 0, 0, 0), (last as u32, last_offset, last_size, in_first), &data[..split]);
+            let rest = (data.len() - split) as u32;
+            write_volume("data2.cab", (last as u32, volume_header_size as u32, last_size, rest), (last as u32, 0, 0, 0), &data[split..]);
+        } else {
+            write_volume("data1.cab", (0, 0, 0, 0), (last as u32, 0, 0, 0), &data);
+        }
+    }
+
+    #[test]
+    fn cabinets_of_version_5_should_be_extracted() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let files: [(&str, &str, &[u8]); 3] = [
+            ("Data", "maps.slf", b"maps library"),
+            ("Data\\BinaryData", "ja2set.dat", b"structure data of all tilesets"),
+            ("", "readme.txt", b"readme"),
+        ];
+        write_cabinet(temp_dir.path(), 5, &files, false);
+
+        let cabinet = Cabinet::open(&find_cabinet_header(temp_dir.path()).unwrap()).unwrap();
+        let paths: Vec<&str> = cabinet.files.iter().map(|f| f.path.as_str()).collect();
+        let mut contents = vec!();
+        let size = cabinet.extract(&cabinet.files[1], &mut contents).unwrap();
+
+        assert_eq!(paths, vec!("Data/maps.slf", "Data/BinaryData/ja2set.dat", "readme.txt"));
+        assert_eq!((size, contents), (30, b"structure data of all tilesets".to_vec()));
+    }
+
+    #[test]
+    fn cabinets_of_version_6_should_continue_split_files_in_the_next_volume() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let files: [(&str, &str, &[u8]); 2] = [
+            ("Data", "binarydata.slf", b"binary data"),
+            ("Data", "speech.slf", b"all the voices of the mercs and the npcs"),
+        ];
+        write_cabinet(temp_dir.path(), 6, &files, true);
+
+        let cabinet = Cabinet::open(&temp_dir.path().join("data1.hdr")).unwrap();
+        let mut first = vec!();
+        let mut split = vec!();
+        cabinet.extract(&cabinet.files[0], &mut first).unwrap();
+        cabinet.extract(&cabinet.files[1], &mut split).unwrap();
+
+        assert_eq!(first, b"binary data");
+        assert_eq!(split, b"all the voices of the mercs and the npcs");
+    }
+
+    #[test]
+    fn cabinets_should_fail_when_damaged_or_incomplete() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let files: [(&str, &str, &[u8]); 1] = [("Data", "speech.slf", b"all the voices of the mercs")];
+        write_cabinet(temp_dir.path(), 6, &files, true);
+        fs::remove_file(temp_dir.path().join("data2.cab")).unwrap();
+        let header_path = temp_dir.path().join("data1.hdr");
+        let cabinet = Cabinet::open(&header_path).unwrap();
+
+        let missing = cabinet.extract(&cabinet.files[0], &mut vec!()).unwrap_err();
+
+        assert!(missing.to_string().starts_with("The volume data2.cab is missing in"));
+        assert!(Cabinet::parse(&header_path, b"MSCF").is_err());
+        assert!(Cabinet::parse(&header_path, &fs::read(&header_path).unwrap()[..0x210]).is_err());
+    }
+}
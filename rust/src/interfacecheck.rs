@@ -0,0 +1,198 @@
+//! Checks of the interface images and radar maps before the game is started.
+//!
+//! The engine stops with an error when an image is missing while a screen is loaded, which can be long after the start.
+//! The problems found here are added to the validation report of the engine options instead.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use EngineOptions;
+use Resolution;
+use sti::StiImage;
+use theme::find_themed_file;
+use vfs::Vfs;
+
+pub const RADAR_WINDOW_WIDTH: u16 = 88;
+pub const RADAR_WINDOW_HEIGHT: u16 = 44;
+pub const MAPS_DIR: &str = "maps";
+pub const RADAR_MAPS_DIR: &str = "radarmaps";
+
+/// Images of the tactical interface that are loaded for every sector.
+pub static REQUIRED_INTERFACE_IMAGES: &[&str] = &[
+    "interface/bottom_bar.sti",
+    "interface/bottom_bar_buttons.sti",
+    "interface/button_frame.sti",
+    "interface/gold_front.sti",
+    "interface/inventory_bottom_panel.sti",
+    "interface/inventory_buttons.sti",
+    "interface/inventory_gold_front.sti",
+    "interface/squadpanel.sti",
+];
+
+/// Returns the size of the first subimage, or of the whole image if it is not compressed.
+fn image_size(bytes: &[u8]) -> Result<(u16, u16), String> {
+    let image = StiImage::parse(bytes)?;
+    Ok(match image.subimages.first() {
+        Some(s) => (s.width, s.height),
+        None => (image.width, image.height),
+    })
+}
+
+fn read_image_size(vfs: &Vfs, theme_id: &str, path: &Path) -> Option<Result<(u16, u16), String>> {
+    let file = find_themed_file(vfs, theme_id, path)?;
+    Some(file.read().map_err(String::from).and_then(|b| image_size(&b)).map_err(|e| format!("{}: {}", path.display(), e)))
+}
+
+/// Checks that an interface image exists, fits the screen and, if the theme replaces it, has the height of the image
+/// it replaces, as the positions of the panels depend on it.
+pub fn check_interface_image(vfs: &Vfs, theme_id: &str, path: &Path, resolution: &Resolution) -> Option<String> {
+    let (width, height) = match read_image_size(vfs, theme_id, path) {
+        None => return Some(format!("{} is missing", path.display())),
+        Some(Err(e)) => return Some(e),
+        Some(Ok(size)) => size,
+    };
+    if width > resolution.width || height > resolution.height {
+        return Some(format!("{} is {}x{}, larger than the resolution {}x{}", path.display(), width, height, resolution.width, resolution.height));
+    }
+    if !theme_id.is_empty() {
+        if let Some(Ok((_, original_height))) = read_image_size(vfs, "", path) {
+            if height != original_height {
+                return Some(format!("{} of theme {} has a height of {}, expected {}", path.display(), theme_id, height, original_height));
+            }
+        }
+    }
+    None
+}
+
+/// Collects the names of the maps in all mounts without extension, in lowercase.
+pub fn find_map_names(vfs: &Vfs) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for mount in &vfs.mounts {
+        match mount.library {
+            Some(ref library) => {
+                let prefix = format!("{}/", MAPS_DIR);
+                for entry in &library.entries {
+                    let full_name = library.full_name(entry).to_lowercase();
+                    if let Some(name) = full_name.strip_prefix(&prefix).and_then(|n| n.strip_suffix(".dat")) {
+                        if !name.contains('/') {
+                            names.insert(String::from(name));
+                        }
+                    }
+                }
+            },
+            None => if let Ok(entries) = fs::read_dir(mount.path.join(MAPS_DIR)) {
+                names.extend(entries.filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_lowercase())
+                    .filter_map(|n| n.strip_suffix(".dat").map(String::from)));
+            }
+        }
+    }
+    names
+}
+
+/// Checks that every map has a radar map that fits the radar window.
+pub fn check_radar_maps(vfs: &Vfs) -> Vec<String> {
+    let mut problems = vec!();
+    for name in find_map_names(vfs) {
+        let path = Path::new(RADAR_MAPS_DIR).join(format!("{}.sti", name));
+        match read_image_size(vfs, "", &path) {
+            None => problems.push(format!("{} is missing for map {}", path.display(), name)),
+            Some(Err(e)) => problems.push(e),
+            Some(Ok((width, height))) if width > RADAR_WINDOW_WIDTH || height > RADAR_WINDOW_HEIGHT =>
+                problems.push(format!("{} is {}x{}, larger than the radar window {}x{}", path.display(), width, height, RADAR_WINDOW_WIDTH, RADAR_WINDOW_HEIGHT)),
+            Some(Ok(_)) => {}
+        }
+    }
+    problems
+}
+
+/// Runs all checks for the resolution and the theme of the engine options.
+pub fn check_interface(engine_options: &EngineOptions, vfs: &Vfs) -> Vec<String> {
+    let mut problems: Vec<String> = REQUIRED_INTERFACE_IMAGES.iter()
+        .filter_map(|p| check_interface_image(vfs, &engine_options.ui_theme, Path::new(p), &engine_options.resolution))
+        .collect();
+    problems.extend(check_radar_maps(vfs));
+    problems
+}
+
+/// Adds the problems of the interface images to the validation report and returns their number.
+#[no_mangle]
+pub extern fn validate_interface_images(ptr: *mut EngineOptions, vfs_ptr: *const Vfs) -> u32 {
+    let engine_options = unsafe_from_ptr_mut!(ptr);
+    let problems = check_interface(engine_options, unsafe_from_ptr!(vfs_ptr));
+    let count = problems.len() as u32;
+    engine_options.validation_report.warnings.extend(problems);
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use {EngineOptions, Resolution};
+    use sti::tests::etrle_image;
+    use vfs::{MountSource, Vfs};
+
+    /// Returns an image with one subimage of the given size, the pixels are not valid for the size.
+    fn image(width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = etrle_image();
+        bytes[82..84].copy_from_slice(&height.to_le_bytes());
+        bytes[84..86].copy_from_slice(&width.to_le_bytes());
+        bytes
+    }
+
+    fn write(dir: &Path, path: &str, contents: &[u8]) {
+        let full_path = dir.join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, contents).unwrap();
+    }
+
+    fn vfs(dir: &Path) -> Vfs {
+        let mut vfs = Vfs::default();
+        vfs.mount(MountSource::VANILLA, PathBuf::from(dir), false);
+        vfs
+    }
+
+    #[test]
+    fn check_interface_image_should_check_the_size() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write(temp_dir.path(), "interface/bottom_bar.sti", &image(640, 120));
+        write(temp_dir.path(), "themes/wide/interface/bottom_bar.sti", &image(1280, 120));
+        write(temp_dir.path(), "themes/tall/interface/bottom_bar.sti", &image(640, 140));
+        write(temp_dir.path(), "interface/gold_front.sti", b"STCI");
+        let vfs = vfs(temp_dir.path());
+        let check = |theme: &str, path: &str, width: u16| super::check_interface_image(&vfs, theme, Path::new(path), &Resolution::new(width, 480));
+
+        assert_eq!(check("", "interface/bottom_bar.sti", 640), None);
+        assert_eq!(check("wide", "interface/bottom_bar.sti", 1280), None);
+        assert_eq!(check("wide", "interface/bottom_bar.sti", 1024), Some(String::from("interface/bottom_bar.sti is 1280x120, larger than the resolution 1024x480")));
+        assert_eq!(check("tall", "interface/bottom_bar.sti", 640), Some(String::from("interface/bottom_bar.sti of theme tall has a height of 140, expected 120")));
+        assert_eq!(check("", "interface/squadpanel.sti", 640), Some(String::from("interface/squadpanel.sti is missing")));
+        assert_eq!(check("", "interface/gold_front.sti", 640), Some(String::from("interface/gold_front.sti: STI image has an invalid header")));
+    }
+
+    #[test]
+    fn validate_interface_images_should_report_radar_maps_of_all_maps() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        for path in super::REQUIRED_INTERFACE_IMAGES {
+            write(temp_dir.path(), path, &image(640, 120));
+        }
+        write(temp_dir.path(), "maps/A9.dat", b"");
+        write(temp_dir.path(), "maps/b9.dat", b"");
+        write(temp_dir.path(), "maps/c9.dat", b"");
+        write(temp_dir.path(), "radarmaps/a9.sti", &image(88, 44));
+        write(temp_dir.path(), "radarmaps/b9.sti", &image(90, 44));
+        let vfs = vfs(temp_dir.path());
+        let mut engine_options = EngineOptions::default();
+
+        assert_eq!(super::validate_interface_images(&mut engine_options, &vfs), 2);
+        assert_eq!(engine_options.validation_report.warnings, vec!(
+            String::from("radarmaps/b9.sti is 90x44, larger than the radar window 88x44"),
+            String::from("radarmaps/c9.sti is missing for map c9"),
+        ));
+    }
+}
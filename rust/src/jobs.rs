@@ -0,0 +1,291 @@
+//! Background jobs for long operations, so the launcher does not block its UI thread.
+//!
+//! Jobs run on a small pool of worker threads. The launcher starts a job, polls its status and progress with the
+//! returned handle and reads the result as JSON when it is finished. Cancelling is cooperative, jobs that do not
+//! check their `CancellationToken` run to the end. Freeing a handle does not stop the job.
+
+use std::ffi::{CStr, CString};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+use libc::c_char;
+use serde_json::Value;
+
+use EngineOptions;
+use dataimport::import_data;
+use dataverify::{CancellationToken, folders_to_verify, verify_folders};
+use events::{EngineEvent, emit_event};
+use home::{cache_dir, clear_cache_dir};
+use modpackage::package_mod;
+
+/// Number of jobs that run at the same time, further jobs wait in a queue
+const WORKER_THREADS: usize = 2;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum JobStatus {
+    QUEUED,
+    RUNNING,
+    SUCCEEDED,
+    FAILED,
+    CANCELLED,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        self != JobStatus::QUEUED && self != JobStatus::RUNNING
+    }
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct ThreadPool {
+    sender: Mutex<Sender<Task>>,
+}
+
+impl ThreadPool {
+    fn new(threads: usize) -> ThreadPool {
+        let (sender, receiver) = channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver: Arc<Mutex<Receiver<Task>>> = Arc::clone(&receiver);
+            thread::Builder::new().name(format!("job-worker-{}", i)).spawn(move || loop {
+                let task = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                match task {
+                    Ok(task) => task(),
+                    Err(_) => break,
+                }
+            }).expect("Job worker threads should start");
+        }
+        ThreadPool { sender: Mutex::new(sender) }
+    }
+
+    fn execute(&self, task: Task) {
+        self.sender.lock().unwrap_or_else(|e| e.into_inner()).send(task).expect("Job workers should be running");
+    }
+}
+
+fn pool() -> &'static ThreadPool {
+    static POOL: OnceLock<ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| ThreadPool::new(WORKER_THREADS))
+}
+
+struct JobState {
+    /// Status with the result as JSON on success or the error message on failure
+    status: Mutex<(JobStatus, Option<String>)>,
+    finished: Condvar,
+    progress: Mutex<(u64, u64)>,
+    token: CancellationToken,
+}
+
+/// Passed to the function of a job to report progress and check for cancellation.
+pub struct JobContext {
+    state: Arc<JobState>,
+}
+
+impl JobContext {
+    pub fn report_progress(&self, done: u64, total: u64) {
+        *self.state.progress.lock().unwrap_or_else(|e| e.into_inner()) = (done, total);
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.state.token
+    }
+}
+
+pub struct Job {
+    state: Arc<JobState>,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        self.state.status.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    /// Returns the done and total units of work, e.g. bytes.
+    pub fn progress(&self) -> (u64, u64) {
+        *self.state.progress.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the result as JSON or the error message, None while the job runs.
+    pub fn result(&self) -> Option<String> {
+        self.state.status.lock().unwrap_or_else(|e| e.into_inner()).1.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.state.token.cancel();
+    }
+
+    /// Blocks until the job is finished.
+    pub fn wait(&self) -> JobStatus {
+        let mut status = self.state.status.lock().unwrap_or_else(|e| e.into_inner());
+        while !status.0.is_finished() {
+            status = self.state.finished.wait(status).unwrap_or_else(|e| e.into_inner());
+        }
+        status.0
+    }
+}
+
+/// Queues a job. Its function returns the result that the launcher reads as JSON.
+pub fn start_job<F>(f: F) -> Job where F: FnOnce(&JobContext) -> Result<Value, String> + Send + 'static {
+    let state = Arc::new(JobState {
+        status: Mutex::new((JobStatus::QUEUED, None)),
+        finished: Condvar::new(),
+        progress: Mutex::new((0, 0)),
+        token: CancellationToken::default(),
+    });
+    let context = JobContext { state: Arc::clone(&state) };
+    pool().execute(Box::new(move || {
+        let state = Arc::clone(&context.state);
+        state.status.lock().unwrap_or_else(|e| e.into_inner()).0 = JobStatus::RUNNING;
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| f(&context)))
+            .unwrap_or_else(|_| Err(String::from("The job failed unexpectedly")));
+        let finished = match result {
+            Ok(_) if state.token.is_cancelled() => (JobStatus::CANCELLED, None),
+            Ok(value) => (JobStatus::SUCCEEDED, Some(value.to_string())),
+            Err(s) => (JobStatus::FAILED, Some(s)),
+        };
+        *state.status.lock().unwrap_or_else(|e| e.into_inner()) = finished;
+        state.finished.notify_all();
+    }));
+    Job { state }
+}
+
+fn c_string_arg(ptr: *const c_char) -> String {
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// Verifies the game data in the background, see `dataverify`. The result is the verification report.
+#[no_mangle]
+pub extern fn start_verification_job(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> *mut Job {
+    let dirs = folders_to_verify(unsafe_from_ptr!(ptr), Path::new(&c_string_arg(extra_data_dir_ptr)));
+    let job = start_job(move |context| {
+        let report = verify_folders(&dirs, context.token(), &mut |done, total| context.report_progress(done, total))?;
+        Ok(json!(report))
+    });
+    Box::into_raw(Box::new(job))
+}
+
+/// Imports the vanilla data in the background, see `dataimport`. The result is the plan of the copied files.
+#[no_mangle]
+pub extern fn start_data_import_job(ptr: *const EngineOptions, source_dir_ptr: *const c_char, target_dir_ptr: *const c_char) -> *mut Job {
+    let cache_dir = cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let source_dir = PathBuf::from(c_string_arg(source_dir_ptr));
+    let target_dir = PathBuf::from(c_string_arg(target_dir_ptr));
+    let job = start_job(move |context| {
+        let plan = import_data(&source_dir, &target_dir, &cache_dir, false, &mut |done, total| context.report_progress(done, total))?;
+        Ok(json!(plan))
+    });
+    Box::into_raw(Box::new(job))
+}
+
+/// Packages a mod in the background, see `modpackage`. The result is the plan of the written package.
+#[no_mangle]
+pub extern fn start_mod_package_job(ptr: *const EngineOptions, mod_dir_ptr: *const c_char, output_dir_ptr: *const c_char) -> *mut Job {
+    let cache_dir = cache_dir(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let mod_dir = PathBuf::from(c_string_arg(mod_dir_ptr));
+    let output_dir = PathBuf::from(c_string_arg(output_dir_ptr));
+    let job = start_job(move |_| {
+        let plan = package_mod(&mod_dir, &output_dir, &cache_dir, false)?;
+        Ok(json!(plan))
+    });
+    Box::into_raw(Box::new(job))
+}
+
+/// Clears the cache dir in the background, see `home::clear_cache_dir`.
+#[no_mangle]
+pub extern fn start_clear_cache_job(ptr: *const EngineOptions) -> *mut Job {
+    let stracciatella_home = unsafe_from_ptr!(ptr).stracciatella_home.clone();
+    let job = start_job(move |_| {
+        clear_cache_dir(&stracciatella_home)?;
+        emit_event(EngineEvent::CACHE_CLEARED);
+        Ok(Value::Null)
+    });
+    Box::into_raw(Box::new(job))
+}
+
+#[no_mangle]
+pub extern fn get_job_status(job: *const Job) -> JobStatus {
+    unsafe_from_ptr!(job).status()
+}
+
+#[no_mangle]
+pub extern fn get_job_progress(job: *const Job, done_ptr: *mut u64, total_ptr: *mut u64) -> () {
+    let (done, total) = unsafe_from_ptr!(job).progress();
+    unsafe {
+        *done_ptr = done;
+        *total_ptr = total;
+    }
+}
+
+/// Returns the result as JSON if the job succeeded, the error message if it failed and null otherwise.
+#[no_mangle]
+pub extern fn get_job_result(job: *const Job) -> *mut c_char {
+    match unsafe_from_ptr!(job).result() {
+        Some(s) => CString::new(s).unwrap().into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern fn cancel_job(job: *const Job) -> () {
+    unsafe_from_ptr!(job).cancel();
+}
+
+/// Frees the handle, a running job continues in the background.
+#[no_mangle]
+pub extern fn free_job(job: *mut Job) -> () {
+    if job.is_null() { return }
+    unsafe { drop(Box::from_raw(job)); }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::{JobStatus, start_job};
+
+    #[test]
+    fn jobs_should_report_progress_and_result() {
+        let job = start_job(|context| {
+            context.report_progress(3, 3);
+            Ok(json!({ "checked_files": 3 }))
+        });
+
+        assert_eq!(job.wait(), JobStatus::SUCCEEDED);
+        assert_eq!(job.progress(), (3, 3));
+        assert_eq!(job.result(), Some(String::from(r#"{"checked_files":3}"#)));
+    }
+
+    #[test]
+    fn jobs_should_report_errors_and_panics() {
+        let failed = start_job(|_| Err(String::from("Error reading /ja2/Data")));
+        let panicked = start_job(|_| panic!("unexpected"));
+
+        assert_eq!(failed.wait(), JobStatus::FAILED);
+        assert_eq!(failed.result(), Some(String::from("Error reading /ja2/Data")));
+        assert_eq!(panicked.wait(), JobStatus::FAILED);
+    }
+
+    #[test]
+    fn jobs_should_stop_when_cancelled() {
+        let (sender, receiver) = channel::<()>();
+        let job = start_job(move |context| {
+            receiver.recv().unwrap();
+            assert!(context.token().is_cancelled());
+            Ok(json!(null))
+        });
+
+        assert!(!job.status().is_finished());
+        job.cancel();
+        sender.send(()).unwrap();
+
+        assert_eq!(job.wait(), JobStatus::CANCELLED);
+        assert_eq!(job.result(), None);
+    }
+}
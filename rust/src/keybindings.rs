@@ -0,0 +1,162 @@
+//! Key bindings that replace the built-in keys of the engine.
+//!
+//! The bindings are kept in `keybindings.json` in the stracciatella home and map an action to a key, e.g.
+//! `"save_game": "Ctrl+S"`. Actions without binding use the built-in key, so resetting to the defaults removes all
+//! bindings. Binding sets can be exported to and imported from standalone files in the same format to share them.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+
+pub const KEYBINDINGS_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyBindings {
+    pub version: u32,
+    pub bindings: BTreeMap<String, String>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings { version: KEYBINDINGS_VERSION, bindings: BTreeMap::new() }
+    }
+}
+
+impl KeyBindings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != KEYBINDINGS_VERSION {
+            return Err(format!("Unsupported key bindings version {}, expected {}", self.version, KEYBINDINGS_VERSION));
+        }
+        for (action, key) in &self.bindings {
+            if action.is_empty() || !action.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+                return Err(format!("Invalid action {:?}, actions consist of lowercase letters, digits and underscores", action));
+            }
+            if key.trim().is_empty() {
+                return Err(format!("Action {} has no key", action));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a binding set, e.g. one that was exported by another player.
+    pub fn read(path: &Path) -> Result<KeyBindings, String> {
+        let contents = fs::read(path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+        let key_bindings: KeyBindings = serde_json::from_slice(&contents).map_err(|s| format!("Error parsing {}: {}", path.display(), s))?;
+        key_bindings.validate()?;
+        Ok(key_bindings)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|s| format!("Error creating contents of {}: {}", path.display(), s))?;
+        fs::write(path, json).map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+    }
+}
+
+pub fn keybindings_file_path(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("keybindings.json")
+}
+
+/// Returns the bindings of the player, no bindings if there is no keybindings.json.
+pub fn load_keybindings(stracciatella_home: &Path) -> Result<KeyBindings, String> {
+    let path = keybindings_file_path(stracciatella_home);
+    if !path.is_file() {
+        return Ok(KeyBindings::default());
+    }
+    KeyBindings::read(&path)
+}
+
+pub fn export_keybindings_to(stracciatella_home: &Path, path: &Path) -> Result<(), String> {
+    load_keybindings(stracciatella_home)?.write(path)
+}
+
+/// Replaces the bindings of the player with the binding set in the file. Invalid files change nothing.
+pub fn import_keybindings_from(stracciatella_home: &Path, path: &Path) -> Result<(), String> {
+    KeyBindings::read(path)?.write(&keybindings_file_path(stracciatella_home))
+}
+
+pub fn reset_keybindings_in(stracciatella_home: &Path) -> Result<(), String> {
+    let path = keybindings_file_path(stracciatella_home);
+    match fs::remove_file(&path) {
+        Err(ref s) if s.kind() != ErrorKind::NotFound => Err(format!("Error removing {}: {}", path.display(), s.kind())),
+        _ => Ok(()),
+    }
+}
+
+#[no_mangle]
+pub extern fn export_keybindings(ptr: *const EngineOptions, path_ptr: *const c_char) -> bool {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    export_keybindings_to(&unsafe_from_ptr!(ptr).stracciatella_home, Path::new(&path)).is_ok()
+}
+
+#[no_mangle]
+pub extern fn import_keybindings(ptr: *const EngineOptions, path_ptr: *const c_char) -> bool {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    import_keybindings_from(&unsafe_from_ptr!(ptr).stracciatella_home, Path::new(&path)).is_ok()
+}
+
+#[no_mangle]
+pub extern fn reset_keybindings(ptr: *const EngineOptions) -> bool {
+    reset_keybindings_in(&unsafe_from_ptr!(ptr).stracciatella_home).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::{KeyBindings, export_keybindings_to, import_keybindings_from, keybindings_file_path, load_keybindings, reset_keybindings_in};
+
+    #[test]
+    fn import_and_export_should_share_binding_sets() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let home = temp_dir.path().join("home");
+        let other_home = temp_dir.path().join("other");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&other_home).unwrap();
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bindings.insert(String::from("save_game"), String::from("Ctrl+S"));
+        key_bindings.write(&keybindings_file_path(&home)).unwrap();
+
+        let exported = temp_dir.path().join("scheme.json");
+        export_keybindings_to(&home, &exported).unwrap();
+        import_keybindings_from(&other_home, &exported).unwrap();
+
+        assert_eq!(load_keybindings(&other_home), Ok(key_bindings));
+    }
+
+    #[test]
+    fn import_should_keep_the_bindings_if_the_file_is_invalid() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bindings.insert(String::from("quick_save"), String::from("Alt+S"));
+        key_bindings.write(&keybindings_file_path(temp_dir.path())).unwrap();
+        let invalid = temp_dir.path().join("invalid.json");
+
+        for contents in &["{ \"version\": 2, \"bindings\": {} }", "{ \"version\": 1, \"bindings\": { \"Save Game\": \"S\" } }", "{ \"version\": 1, \"bindings\": { \"save_game\": \" \" } }", "[]"] {
+            fs::write(&invalid, contents).unwrap();
+            assert!(import_keybindings_from(temp_dir.path(), &invalid).is_err(), "{}", contents);
+        }
+        assert_eq!(load_keybindings(temp_dir.path()), Ok(key_bindings));
+    }
+
+    #[test]
+    fn reset_should_remove_all_bindings() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bindings.insert(String::from("quick_save"), String::from("Alt+S"));
+        key_bindings.write(&keybindings_file_path(temp_dir.path())).unwrap();
+
+        reset_keybindings_in(temp_dir.path()).unwrap();
+        reset_keybindings_in(temp_dir.path()).unwrap();
+
+        assert_eq!(load_keybindings(temp_dir.path()), Ok(KeyBindings::default()));
+    }
+}
@@ -0,0 +1,320 @@
+//! Import of the data tables of old mods, in the XML format of JA2 1.13 or as INI files with one section per record.
+//!
+//! Only the common subsets are converted: the guns of `Weapons.xml` with their item data from `Items.xml` into
+//! `weapons.json`, and the stats of `MercProfiles.xml` into `merc-profiles.json`. Fields without an equivalent are
+//! reported, so they can be ported by hand. `merc-profiles.json` is not read by the engine yet, it keeps the stats
+//! until the merc profiles are externalized.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use datadir::resolve_case_insensitive;
+use formats::ini::IniFile;
+use formats::xml::XmlElement;
+
+/// Fields and values of a record, e.g. of a `<WEAPON>` element.
+pub type Record = Vec<(String, String)>;
+
+/// Internal types by the `ubWeaponType` of 1.13, 0 are items that are not guns.
+const WEAPON_TYPES: [&str; 9] = ["", "PISTOL", "M_PISTOL", "SMG", "RIFLE", "SN_RIFLE", "ASRIFLE", "LMG", "SHOTGUN"];
+/// Names of the vanilla calibres by their index, see `calibres.json`.
+const CALIBRES: [&str; 17] = [
+    "NOAMMO", "AMMO38", "AMMO9", "AMMO45", "AMMO357", "AMMO12G", "AMMOCAWS", "AMMO545", "AMMO556", "AMMO762N",
+    "AMMO762W", "AMMO47", "AMMO57", "AMMOMONST", "AMMOROCKET", "AMMODART", "AMMOFLAME",
+];
+const WEAPON_NUMBER_FIELDS: &[&str] = &[
+    "ubReadyTime", "ubShotsPer4Turns", "ubShotsPerBurst", "ubBurstPenalty", "ubBulletSpeed", "ubImpact",
+    "ubDeadliness", "ubMagSize", "usRange", "usReloadDelay", "ubAttackVolume", "ubHitVolume",
+];
+const ITEM_NUMBER_FIELDS: &[&str] = &[
+    "ubGraphicType", "ubGraphicNum", "ubWeight", "ubPerPocket", "usPrice", "ubCoolness", "bReliability", "bRepairEase",
+];
+/// Fields that are used by the conversion without being copied.
+const CONVERTED_FIELDS: &[&str] = &[
+    "uiIndex", "szWeaponName", "szItemName", "szLongItemName", "ubWeaponType", "ubWeaponClass", "usItemClass",
+    "ubCalibre", "NotBuyable",
+];
+/// Fields of `weapons.json` that are not in the data tables of 1.13.
+const REQUIRED_WEAPON_FIELDS: &[&str] = &["calibre", "Sound"];
+pub const MERC_STAT_FIELDS: &[&str] = &[
+    "bLifeMax", "bLife", "bAgility", "bDexterity", "bStrength", "bLeadership", "bWisdom", "bExpLevel",
+    "bMarksmanship", "bMechanical", "bExplosive", "bMedical",
+];
+
+fn field<'a>(record: &'a Record, name: &str) -> Option<&'a str> {
+    record.iter().find(|&(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+fn number(record: &Record, name: &str) -> Option<i64> {
+    field(record, name).and_then(|v| v.trim().parse::<f64>().ok()).map(|v| v as i64)
+}
+
+/// Converts a display name like `Glock 17` to an internal name like `GLOCK_17`.
+pub fn internal_name(name: &str) -> String {
+    let upper: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { ' ' }).collect();
+    upper.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+/// Returns the records of an XML table, the fields are the children of the record elements.
+pub fn records_from_xml(xml: &str, record_name: &str) -> Result<Vec<Record>, String> {
+    let root = XmlElement::parse(xml)?;
+    Ok(root.children.iter()
+        .filter(|e| e.name.eq_ignore_ascii_case(record_name))
+        .map(|e| e.children.iter().map(|c| (c.name.clone(), c.text.clone())).collect())
+        .collect())
+}
+
+/// Returns the sections of an INI file as records. Without an `uiIndex` entry the number at the end of the section
+/// name is the index, e.g. 3 for `[Weapon 3]`.
+pub fn records_from_ini(ini: &str) -> Result<Vec<Record>, String> {
+    let ini = IniFile::parse(ini)?;
+    Ok(ini.sections.into_iter().filter(|s| !s.name.is_empty()).map(|s| {
+        let mut record = s.entries;
+        if field(&record, "uiIndex").is_none() {
+            let digits: String = s.name.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+            if !digits.is_empty() {
+                record.push((String::from("uiIndex"), digits.chars().rev().collect()));
+            }
+        }
+        record
+    }).collect())
+}
+
+fn collect_unsupported(record: &Record, known: &[&[&str]], unsupported: &mut BTreeSet<String>) {
+    for (key, _) in record {
+        if !known.iter().any(|fields| fields.iter().any(|f| f.eq_ignore_ascii_case(key))) {
+            unsupported.insert(key.clone());
+        }
+    }
+}
+
+/// Converts the guns to the entries of `weapons.json`. The entries replace the ones of `base` with the same item
+/// index, fields that the tables do not have are kept from there.
+pub fn import_weapons(weapons: &[Record], items: &[Record], base: &[Value]) -> (Vec<Value>, Vec<String>) {
+    let mut entries: BTreeMap<i64, Value> = base.iter()
+        .filter_map(|w| w["itemIndex"].as_i64().map(|i| (i, w.clone())))
+        .collect();
+    let mut warnings = vec!();
+    let mut unsupported = BTreeSet::new();
+
+    for weapon in weapons {
+        let index = match number(weapon, "uiIndex") {
+            Some(i) => i,
+            None => {
+                warnings.push(String::from("A weapon without uiIndex was skipped"));
+                continue;
+            }
+        };
+        let weapon_type = number(weapon, "ubWeaponType").unwrap_or(0);
+        let internal_type = match WEAPON_TYPES.get(weapon_type as usize) {
+            Some(t) if !t.is_empty() => *t,
+            _ => {
+                warnings.push(format!("Item {}: weapon type {} is not supported, the item was skipped", index, weapon_type));
+                continue;
+            }
+        };
+        let item = items.iter().find(|i| number(i, "uiIndex") == Some(index));
+
+        let mut entry = match entries.get(&index) {
+            Some(Value::Object(e)) => e.clone(),
+            _ => Map::new(),
+        };
+        entry.insert(String::from("itemIndex"), json!(index));
+        let name = field(weapon, "szWeaponName").or_else(|| item.and_then(|i| field(i, "szItemName")));
+        if let Some(n) = name {
+            entry.insert(String::from("internalName"), json!(internal_name(n)));
+        } else if !entry.contains_key("internalName") {
+            entry.insert(String::from("internalName"), json!(format!("ITEM_{}", index)));
+        }
+        entry.insert(String::from("internalType"), json!(internal_type));
+        if let Some(c) = number(weapon, "ubCalibre") {
+            match CALIBRES.get(c as usize) {
+                Some(calibre) => { entry.insert(String::from("calibre"), json!(calibre)); },
+                None => warnings.push(format!("Item {}: calibre {} is not a vanilla calibre", index, c)),
+            }
+        }
+        for f in WEAPON_NUMBER_FIELDS {
+            if let Some(v) = number(weapon, f) {
+                entry.insert(String::from(*f), json!(v));
+            }
+        }
+        collect_unsupported(weapon, &[WEAPON_NUMBER_FIELDS, CONVERTED_FIELDS], &mut unsupported);
+
+        if let Some(item) = item {
+            for f in ITEM_NUMBER_FIELDS {
+                if let Some(v) = number(item, f) {
+                    entry.insert(String::from(*f), json!(v));
+                }
+            }
+            if let Some(v) = field(item, "NotBuyable") {
+                entry.insert(String::from("bNotBuyable"), json!(v == "1" || v.eq_ignore_ascii_case("true")));
+            }
+            collect_unsupported(item, &[ITEM_NUMBER_FIELDS, CONVERTED_FIELDS], &mut unsupported);
+        }
+
+        let missing: Vec<&str> = REQUIRED_WEAPON_FIELDS.iter().cloned().filter(|f| !entry.contains_key(*f)).collect();
+        if !missing.is_empty() {
+            warnings.push(format!("Item {}: {} has to be added by hand", index, missing.join(", ")));
+        }
+        entries.insert(index, Value::Object(entry));
+    }
+
+    if !unsupported.is_empty() {
+        warnings.push(format!("Unsupported fields were not imported: {}", unsupported.into_iter().collect::<Vec<String>>().join(", ")));
+    }
+    (entries.into_values().collect(), warnings)
+}
+
+/// Converts the stats of the mercs to the entries of `merc-profiles.json`.
+pub fn import_merc_profiles(profiles: &[Record]) -> (Vec<Value>, Vec<String>) {
+    let mut entries = vec!();
+    let mut warnings = vec!();
+    let mut unsupported = BTreeSet::new();
+
+    for profile in profiles {
+        let index = match number(profile, "uiIndex") {
+            Some(i) => i,
+            None => {
+                warnings.push(String::from("A merc profile without uiIndex was skipped"));
+                continue;
+            }
+        };
+        let mut entry = Map::new();
+        entry.insert(String::from("profileIndex"), json!(index));
+        if let Some(n) = field(profile, "zNickname") {
+            entry.insert(String::from("zNickname"), json!(n));
+        }
+        for f in MERC_STAT_FIELDS {
+            if let Some(v) = number(profile, f) {
+                entry.insert(String::from(*f), json!(v));
+            }
+        }
+        collect_unsupported(profile, &[MERC_STAT_FIELDS, &["uiIndex", "zNickname"]], &mut unsupported);
+        entries.push(Value::Object(entry));
+    }
+
+    if !unsupported.is_empty() {
+        warnings.push(format!("Unsupported fields were not imported: {}", unsupported.into_iter().collect::<Vec<String>>().join(", ")));
+    }
+    (entries, warnings)
+}
+
+/// Reads the records of `<name>.xml` or `<name>.ini` in the dir, None if neither exists.
+fn read_table(dir: &Path, name: &str, record_name: &str) -> Option<Result<Vec<Record>, String>> {
+    let xml = resolve_case_insensitive(dir, &format!("{}.xml", name));
+    let path = xml.clone().or_else(|| resolve_case_insensitive(dir, &format!("{}.ini", name)))?;
+    let contents = match fs::read(&path) {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
+        Err(s) => return Some(Err(format!("Error reading {}: {}", path.display(), s.kind()))),
+    };
+    let records = if xml.is_some() { records_from_xml(&contents, record_name) } else { records_from_ini(&contents) };
+    Some(records.map_err(|s| format!("{}: {}", path.display(), s)))
+}
+
+fn write_json(path: &Path, entries: &[Value]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(entries).expect("Entries should be serializable");
+    fs::write(path, json).map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+}
+
+/// Converts the tables in `source_dir` to JSON files in `target_dir` and returns the warnings. The `weapons.json` in
+/// `base_dir`, e.g. the externalized dir, provides the weapons that are not in the tables.
+pub fn import_legacy_data(source_dir: &Path, target_dir: &Path, base_dir: Option<&Path>) -> Result<Vec<String>, String> {
+    let weapons = read_table(source_dir, "weapons", "WEAPON").transpose()?;
+    let profiles = read_table(source_dir, "mercprofiles", "PROFILE").transpose()?;
+    if weapons.is_none() && profiles.is_none() {
+        return Err(format!("{} contains no weapons or merc profiles in XML or INI format", source_dir.display()));
+    }
+    fs::create_dir_all(target_dir).map_err(|s| format!("Error creating {}: {}", target_dir.display(), s.kind()))?;
+    let mut warnings = vec!();
+
+    if let Some(weapons) = weapons {
+        let items = read_table(source_dir, "items", "ITEM").transpose()?.unwrap_or_default();
+        let base: Vec<Value> = match base_dir {
+            Some(dir) => {
+                let path = dir.join("weapons.json");
+                let contents = fs::read(&path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+                serde_json::from_slice(&contents).map_err(|s| format!("Error parsing {}: {}", path.display(), s))?
+            },
+            None => vec!(),
+        };
+        let (entries, w) = import_weapons(&weapons, &items, &base);
+        write_json(&target_dir.join("weapons.json"), &entries)?;
+        warnings.extend(w);
+    }
+    if let Some(profiles) = profiles {
+        let (entries, w) = import_merc_profiles(&profiles);
+        write_json(&target_dir.join("merc-profiles.json"), &entries)?;
+        warnings.extend(w);
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use serde_json::Value;
+
+    use super::{import_legacy_data, import_weapons, internal_name, records_from_ini, records_from_xml};
+
+    const WEAPONS_XML: &str = "<WEAPONLIST>
+        <WEAPON><uiIndex>1</uiIndex><szWeaponName>Glock 17</szWeaponName><ubWeaponType>1</ubWeaponType><ubCalibre>2</ubCalibre><ubImpact>23</ubImpact><bRecoilX>2</bRecoilX></WEAPON>
+        <WEAPON><uiIndex>400</uiIndex><szWeaponName>H&amp;K G11</szWeaponName><ubWeaponType>6</ubWeaponType><ubCalibre>40</ubCalibre></WEAPON>
+        <WEAPON><uiIndex>401</uiIndex><ubWeaponType>12</ubWeaponType></WEAPON>
+    </WEAPONLIST>";
+
+    #[test]
+    fn internal_name_should_convert_display_names() {
+        assert_eq!(internal_name("Glock 17"), "GLOCK_17");
+        assert_eq!(internal_name("H&K G11 (caseless)"), "H_K_G11_CASELESS");
+    }
+
+    #[test]
+    fn import_weapons_should_merge_the_tables_into_the_base_weapons() {
+        let weapons = records_from_xml(WEAPONS_XML, "WEAPON").unwrap();
+        let items = records_from_ini("[Item 1]\nubWeight = 7\nNotBuyable = 1\nusOverheatingJamThreshold = 5\n").unwrap();
+        let base = vec!(
+            json!({ "itemIndex": 0, "internalName": "NOTHING", "internalType": "NOWEAPON", "usRange": 0 }),
+            json!({ "itemIndex": 1, "internalName": "GLOCK_17", "internalType": "PISTOL", "ubImpact": 21, "Sound": "glock.wav" }),
+        );
+
+        let (entries, warnings) = import_weapons(&weapons, &items, &base);
+
+        assert_eq!(entries.iter().map(|e| e["itemIndex"].as_i64().unwrap()).collect::<Vec<i64>>(), vec!(0, 1, 400));
+        assert_eq!(entries[1], json!({
+            "itemIndex": 1, "internalName": "GLOCK_17", "internalType": "PISTOL", "calibre": "AMMO9", "ubImpact": 23,
+            "Sound": "glock.wav", "ubWeight": 7, "bNotBuyable": true
+        }));
+        assert_eq!(entries[2]["internalName"], "H_K_G11");
+        assert_eq!(warnings, vec!(
+            String::from("Item 400: calibre 40 is not a vanilla calibre"),
+            String::from("Item 400: calibre, Sound has to be added by hand"),
+            String::from("Item 401: weapon type 12 is not supported, the item was skipped"),
+            String::from("Unsupported fields were not imported: bRecoilX, usOverheatingJamThreshold"),
+        ));
+    }
+
+    #[test]
+    fn import_legacy_data_should_write_the_json_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source_dir = temp_dir.path().join("TableData");
+        let target_dir = temp_dir.path().join("mod/data");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("Weapons.xml"), WEAPONS_XML).unwrap();
+        fs::write(source_dir.join("MercProfiles.ini"), "[Profile 7]\nzNickname = Ivan\nbMarksmanship = 92\nbAgility = 80\n").unwrap();
+
+        let warnings = import_legacy_data(&source_dir, &target_dir, None).unwrap();
+
+        assert_eq!(warnings.len(), 5);
+        let weapons: Value = serde_json::from_slice(&fs::read(target_dir.join("weapons.json")).unwrap()).unwrap();
+        assert_eq!(weapons.as_array().unwrap().len(), 2);
+        let profiles: Value = serde_json::from_slice(&fs::read(target_dir.join("merc-profiles.json")).unwrap()).unwrap();
+        assert_eq!(profiles, json!([{ "profileIndex": 7, "zNickname": "Ivan", "bAgility": 80, "bMarksmanship": 92 }]));
+        assert!(import_legacy_data(&target_dir, &source_dir, None).is_err());
+    }
+}
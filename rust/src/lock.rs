@@ -0,0 +1,237 @@
+//! Lock file that marks a running game.
+//!
+//! The game writes its process id to `ja2.lock` in the stracciatella home while it runs, so the launcher can tell
+//! that it should not start a second game or write ja2.json. A lock file of a process that does not exist anymore
+//! was left behind by a crash and is ignored.
+//!
+//! Files that are shared by the launcher and the game, like ja2.json, are protected with advisory locks so a read
+//! never sees a partial write.
+
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+
+use libc::c_char;
+
+use EngineOptions;
+use logger::{self, LogLevel};
+
+/// Home of the lock that was acquired by `lock_game`.
+static LOCKED_HOME: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn lock_file_path(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("ja2.lock")
+}
+
+fn read_lock_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok().and_then(|s| s.trim().parse().ok())
+}
+
+#[cfg(not(windows))]
+pub fn is_process_running(pid: u32) -> bool {
+    // pid 0 and negative pids address process groups
+    if pid == 0 || pid > i32::MAX as u32 {
+        return false;
+    }
+    if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+        return true;
+    }
+    // The process exists but belongs to another user
+    ::std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn is_process_running(pid: u32) -> bool {
+    use kernel32::{CloseHandle, GetExitCodeProcess, OpenProcess};
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const STILL_ACTIVE: u32 = 259;
+
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if handle.is_null() {
+        return false;
+    }
+    let mut exit_code: u32 = 0;
+    let result = unsafe { GetExitCodeProcess(handle, &mut exit_code) };
+    unsafe { CloseHandle(handle) };
+    result != 0 && exit_code == STILL_ACTIVE
+}
+
+/// Blocks until the lock on the file is acquired, a shared lock if `exclusive` is false. The lock is released when
+/// the file is closed.
+#[cfg(not(windows))]
+pub fn lock_file(file: &File, exclusive: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if exclusive { libc::LOCK_EX } else { libc::LOCK_SH };
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn lock_file(file: &File, exclusive: bool) -> io::Result<()> {
+    use kernel32::LockFileEx;
+    use std::os::windows::io::AsRawHandle;
+    use winapi::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, OVERLAPPED};
+
+    let flags = if exclusive { LOCKFILE_EXCLUSIVE_LOCK } else { 0 };
+    let mut overlapped: OVERLAPPED = unsafe { ::std::mem::zeroed() };
+    // Locks the whole file, even if it grows
+    if unsafe { LockFileEx(file.as_raw_handle() as _, flags, 0, !0, !0, &mut overlapped) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the process id of the running game, None if there is no lock file or it is stale.
+pub fn find_running_game(stracciatella_home: &Path) -> Option<u32> {
+    read_lock_pid(&lock_file_path(stracciatella_home)).filter(|pid| is_process_running(*pid))
+}
+
+fn running_game_message(stracciatella_home: &Path, pid: u32) -> String {
+    format!("JA2 Stracciatella is already running with process id {} and the home {}. Two games that use the same home overwrite each others saved games.", pid, stracciatella_home.display())
+}
+
+/// Writes the lock file for the current process, a stale lock file is replaced.
+pub fn acquire_lock(stracciatella_home: &Path) -> Result<(), String> {
+    let own_pid = process::id();
+    match find_running_game(stracciatella_home) {
+        Some(pid) if pid != own_pid => return Err(running_game_message(stracciatella_home, pid)),
+        _ => {}
+    }
+    let path = lock_file_path(stracciatella_home);
+    fs::write(&path, own_pid.to_string()).map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+}
+
+/// Removes the lock file if it belongs to the current process.
+pub fn release_lock(stracciatella_home: &Path) -> Result<(), String> {
+    let path = lock_file_path(stracciatella_home);
+    if read_lock_pid(&path) != Some(process::id()) {
+        return Ok(());
+    }
+    match fs::remove_file(&path) {
+        Err(ref s) if s.kind() != ErrorKind::NotFound => Err(format!("Error removing {}: {}", path.display(), s.kind())),
+        _ => Ok(()),
+    }
+}
+
+#[no_mangle]
+pub extern fn is_game_running(ptr: *const EngineOptions) -> bool {
+    find_running_game(&unsafe_from_ptr!(ptr).stracciatella_home).is_some()
+}
+
+/// Returns the message to show if another game is running, null if there is none.
+#[no_mangle]
+pub extern fn get_running_game_message(ptr: *const EngineOptions) -> *mut c_char {
+    let home = &unsafe_from_ptr!(ptr).stracciatella_home;
+    match find_running_game(home) {
+        Some(pid) if pid != process::id() => CString::new(running_game_message(home, pid)).unwrap().into_raw(),
+        _ => ::std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern fn lock_game(ptr: *const EngineOptions) -> bool {
+    let home = &unsafe_from_ptr!(ptr).stracciatella_home;
+    match acquire_lock(home) {
+        Ok(()) => {
+            *LOCKED_HOME.lock().unwrap_or_else(|e| e.into_inner()) = Some(home.clone());
+            true
+        },
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern fn unlock_game() -> () {
+    if let Some(home) = LOCKED_HOME.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        if let Err(s) = release_lock(&home) {
+            logger::log(LogLevel::WARNING, "lock", &s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::process;
+
+    use super::{acquire_lock, find_running_game, lock_file, lock_file_path, release_lock};
+
+    /// Larger than the maximum process id of Linux and Windows
+    const DEAD_PID: u32 = 0x7FFF_FFF0;
+
+    #[test]
+    fn acquire_lock_should_mark_the_game_as_running_until_it_is_released() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        assert_eq!(find_running_game(dir.path()), None);
+
+        acquire_lock(dir.path()).unwrap();
+        assert_eq!(find_running_game(dir.path()), Some(process::id()));
+        acquire_lock(dir.path()).unwrap();
+
+        release_lock(dir.path()).unwrap();
+        assert_eq!(find_running_game(dir.path()), None);
+        assert!(!lock_file_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn stale_locks_should_be_ignored_and_replaced() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        fs::write(lock_file_path(dir.path()), DEAD_PID.to_string()).unwrap();
+        assert_eq!(find_running_game(dir.path()), None);
+
+        fs::write(lock_file_path(dir.path()), "garbage").unwrap();
+        assert_eq!(find_running_game(dir.path()), None);
+
+        acquire_lock(dir.path()).unwrap();
+        assert_eq!(find_running_game(dir.path()), Some(process::id()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn acquire_lock_should_fail_if_another_game_is_running() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        // init always runs
+        fs::write(lock_file_path(dir.path()), "1").unwrap();
+
+        assert_eq!(find_running_game(dir.path()), Some(1));
+        assert!(acquire_lock(dir.path()).unwrap_err().contains("already running with process id 1"));
+        assert_eq!(fs::read_to_string(lock_file_path(dir.path())).unwrap(), "1");
+    }
+
+    #[test]
+    fn release_lock_should_keep_the_lock_of_other_processes() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        fs::write(lock_file_path(dir.path()), DEAD_PID.to_string()).unwrap();
+
+        release_lock(dir.path()).unwrap();
+        assert!(lock_file_path(dir.path()).exists());
+    }
+
+    #[test]
+    fn lock_file_should_allow_several_shared_locks() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = dir.path().join("ja2.json");
+        fs::write(&path, "{}").unwrap();
+
+        let first = fs::File::open(&path).unwrap();
+        let second = fs::File::open(&path).unwrap();
+        lock_file(&first, false).unwrap();
+        lock_file(&second, false).unwrap();
+        drop(first);
+        drop(second);
+
+        let writer = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        lock_file(&writer, true).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Logger that can be used from rust and from the engine through `log_message`.
+//!
+//! Log lines are written to stderr and optionally to a log file, either as plain text or as JSON lines. The engine
+//! routes the messages of SLOG through `log_message` once the logger is initialized.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fmt;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::c_char;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use EngineOptions;
+
+#[derive(Debug, PartialEq, PartialOrd, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum LogLevel {
+    ERROR,
+    WARNING,
+    INFO,
+    DEBUG,
+    TRACE,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ERROR" => Ok(LogLevel::ERROR),
+            "WARNING" | "WARN" => Ok(LogLevel::WARNING),
+            "INFO" => Ok(LogLevel::INFO),
+            "DEBUG" => Ok(LogLevel::DEBUG),
+            "TRACE" => Ok(LogLevel::TRACE),
+            _ => Err(format!("Log level {} is unknown", s))
+        }
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            LogLevel::ERROR => "ERROR",
+            LogLevel::WARNING => "WARNING",
+            LogLevel::INFO => "INFO",
+            LogLevel::DEBUG => "DEBUG",
+            LogLevel::TRACE => "TRACE",
+        })
+    }
+}
+
+/// Deserializes log filters from a map of module names to level names, e.g. `{"vfs": "trace", "config": "warn"}`.
+pub fn deserialize_log_filters<'de, D>(deserializer: D) -> Result<BTreeMap<String, LogLevel>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let filters = BTreeMap::<String, String>::deserialize(deserializer)?;
+    filters.iter()
+        .map(|(module, level)| LogLevel::from_str(level).map(|l| (module.to_lowercase(), l)))
+        .collect::<Result<BTreeMap<String, LogLevel>, String>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum LogFormat {
+    TEXT,
+    JSON,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "TEXT" => Ok(LogFormat::TEXT),
+            "JSON" => Ok(LogFormat::JSON),
+            _ => Err(format!("Log format {} is unknown", s))
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            LogFormat::TEXT => "TEXT",
+            LogFormat::JSON => "JSON",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LogRecord<'a> {
+    pub timestamp: String,
+    pub level: LogLevel,
+    pub module: &'a str,
+    pub message: &'a str,
+}
+
+/// Formats seconds since the unix epoch as an ISO 8601 UTC timestamp.
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let seconds_of_day = seconds % 86400;
+
+    // Convert days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60)
+}
+
+impl<'a> LogRecord<'a> {
+    pub fn new(level: LogLevel, module: &'a str, message: &'a str) -> LogRecord<'a> {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        LogRecord { timestamp: format_timestamp(seconds), level, module, message }
+    }
+
+    pub fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::TEXT => format!("{} {:<7} [{:<16}] {}", self.timestamp, self.level, self.module, self.message),
+            LogFormat::JSON => serde_json::to_string(self).expect("Log records should be serializable"),
+        }
+    }
+}
+
+struct Logger {
+    format: LogFormat,
+    level: LogLevel,
+    filters: BTreeMap<String, LogLevel>,
+    file: Option<File>,
+}
+
+impl Logger {
+    /// Modules without a filter use the level of the logger. Module names are compared case insensitively.
+    fn is_enabled(&self, level: LogLevel, module: &str) -> bool {
+        level <= *self.filters.get(&module.to_lowercase()).unwrap_or(&self.level)
+    }
+}
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// Initializes the global logger, logging to stderr and to the log file if one is given.
+pub fn init(format: LogFormat, level: LogLevel, filters: BTreeMap<String, LogLevel>, log_file: Option<&Path>) -> Result<(), String> {
+    let file = match log_file {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|s| format!("Error opening log file {}: {}", path.display(), s.kind()))?),
+        None => None
+    };
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    *logger = Some(Logger { format, level, filters, file });
+    Ok(())
+}
+
+/// Logs a message. Warnings and errors are written to stderr as text if the logger was not initialized yet, e.g.
+/// while the engine options are built.
+pub fn log(level: LogLevel, module: &str, message: &str) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    let logger = match *logger {
+        Some(ref mut l) => l,
+        None => {
+            if level <= LogLevel::WARNING {
+                eprintln!("{}", LogRecord::new(level, module, message.trim_end()).format(LogFormat::TEXT));
+            }
+            return
+        }
+    };
+    if !logger.is_enabled(level, module) {
+        return;
+    }
+
+    let line = LogRecord::new(level, module, message.trim_end()).format(logger.format);
+    eprintln!("{}", line);
+    if let Some(ref mut f) = logger.file {
+        let _ = writeln!(f, "{}", line);
+    }
+}
+
+#[no_mangle]
+pub extern fn get_log_format(ptr: *const EngineOptions) -> LogFormat {
+    unsafe_from_ptr!(ptr).log_format
+}
+
+/// Initializes the logger with the format and filters of the engine options. The log file is optional and can be null.
+#[no_mangle]
+pub extern fn init_logger(ptr: *const EngineOptions, log_file_ptr: *const c_char) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let level = if engine_options.start_in_debug_mode { LogLevel::DEBUG } else { LogLevel::WARNING };
+    let log_file = if log_file_ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(log_file_ptr) }.to_string_lossy().into_owned())
+    };
+
+    init(engine_options.log_format, level, engine_options.log_filters.clone(), log_file.as_ref().map(Path::new)).is_ok()
+}
+
+#[no_mangle]
+pub extern fn log_message(level: LogLevel, tag_ptr: *const c_char, message_ptr: *const c_char) -> () {
+    let tag = unsafe { CStr::from_ptr(tag_ptr) }.to_string_lossy();
+    let message = unsafe { CStr::from_ptr(message_ptr) }.to_string_lossy();
+    log(level, &tag, &message);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    use EngineOptions;
+    use super::{LogFormat, LogLevel, LogRecord, Logger};
+
+    fn logger_with_filters(filters: BTreeMap<String, LogLevel>) -> Logger {
+        Logger { format: LogFormat::TEXT, level: LogLevel::WARNING, filters, file: None }
+    }
+
+    #[test]
+    fn format_timestamp_should_format_iso_8601_timestamps() {
+        assert_eq!(super::format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(super::format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(super::format_timestamp(1520165045), "2018-03-04T12:04:05Z");
+    }
+
+    #[test]
+    fn format_should_create_text_lines() {
+        let record = LogRecord { timestamp: String::from("2018-03-04T12:04:05Z"), level: LogLevel::WARNING, module: "vfs", message: "Missing file" };
+
+        assert_eq!(record.format(LogFormat::TEXT), "2018-03-04T12:04:05Z WARNING [vfs             ] Missing file");
+    }
+
+    #[test]
+    fn format_should_create_json_lines() {
+        let record = LogRecord { timestamp: String::from("2018-03-04T12:04:05Z"), level: LogLevel::INFO, module: "config", message: "Quote \" and\nnewline" };
+
+        assert_eq!(record.format(LogFormat::JSON), r#"{"timestamp":"2018-03-04T12:04:05Z","level":"INFO","module":"config","message":"Quote \" and\nnewline"}"#);
+    }
+
+    #[test]
+    fn log_format_should_be_parsed_from_string() {
+        assert_eq!(LogFormat::from_str("JSON"), Ok(LogFormat::JSON));
+        assert_eq!(LogFormat::from_str("XML"), Err(String::from("Log format XML is unknown")));
+    }
+
+    #[test]
+    fn log_level_should_be_parsed_case_insensitively() {
+        assert_eq!(LogLevel::from_str("trace"), Ok(LogLevel::TRACE));
+        assert_eq!(LogLevel::from_str("warn"), Ok(LogLevel::WARNING));
+        assert_eq!(LogLevel::from_str("Warning"), Ok(LogLevel::WARNING));
+        assert_eq!(LogLevel::from_str("verbose"), Err(String::from("Log level verbose is unknown")));
+    }
+
+    #[test]
+    fn is_enabled_should_use_level_of_the_module_filter() {
+        let mut filters = BTreeMap::new();
+        filters.insert(String::from("vfs"), LogLevel::TRACE);
+        filters.insert(String::from("config"), LogLevel::ERROR);
+        let logger = logger_with_filters(filters);
+
+        assert!(logger.is_enabled(LogLevel::TRACE, "vfs"));
+        assert!(logger.is_enabled(LogLevel::DEBUG, "VFS"));
+        assert!(!logger.is_enabled(LogLevel::WARNING, "config"));
+        assert!(logger.is_enabled(LogLevel::ERROR, "config"));
+        assert!(logger.is_enabled(LogLevel::WARNING, "Sound"));
+        assert!(!logger.is_enabled(LogLevel::INFO, "Sound"));
+    }
+
+    #[test]
+    fn log_filters_should_be_read_from_json() {
+        let engine_options: EngineOptions = ::serde_json::from_str("{ \"log_filters\": { \"VFS\": \"trace\", \"config\": \"warn\" } }").unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(String::from("vfs"), LogLevel::TRACE);
+        expected.insert(String::from("config"), LogLevel::WARNING);
+
+        assert_eq!(engine_options.log_filters, expected);
+    }
+
+    #[test]
+    fn log_filters_should_fail_with_unknown_level() {
+        let result = ::serde_json::from_str::<EngineOptions>("{ \"log_filters\": { \"vfs\": \"loud\" } }");
+
+        assert_eq!(result.unwrap_err().to_string(), "Log level loud is unknown at line 1 column 36");
+    }
+
+    #[test]
+    fn log_levels_should_be_ordered_by_verbosity() {
+        assert!(LogLevel::ERROR < LogLevel::WARNING);
+        assert!(LogLevel::DEBUG < LogLevel::TRACE);
+    }
+}
@@ -0,0 +1,295 @@
+//! Catalog of the error messages about the config and the command line.
+//!
+//! Messages are identified by a key and translated to the language of the launcher, `{0}`, `{1}`, ... in a
+//! translation are replaced by the parameters of the message. Missing translations fall back to English. The
+//! languages are the ones of the vanilla game.
+
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Message {
+    pub key: &'static str,
+    pub params: Vec<String>,
+}
+
+static ENGLISH: &[(&str, &str)] = &[
+    ("config_read_error", "Error reading ja2.json config file: {0}"),
+    ("config_parse_error", "Error parsing ja2.json config file: {0}"),
+    ("data_dir_not_set", "Vanilla data directory has to be set either in config file or per command line switch"),
+    ("nonexistent_data_dir", "Please specify an existing datadir."),
+    ("nonexistent_game_dir", "The game dir {0} does not exist"),
+    ("save_game_dir_error", "Could not use {0} for the saved games"),
+    ("unknown_arguments", "Unknown arguments: '{0}'."),
+    ("unrecognized_option", "Unrecognized option: '{0}'"),
+    ("argument_missing", "Argument to option '{0}' missing"),
+    ("unexpected_argument", "Option '{0}' does not take an argument"),
+    ("option_duplicated", "Option '{0}' given more than once"),
+    ("option_missing", "Required option '{0}' missing"),
+    ("incorrect_resolution", "Incorrect resolution format, should be WIDTHxHEIGHT or WIDTHxHEIGHT@REFRESHRATE."),
+    ("unknown_resource_version", "Resource version {0} is unknown"),
+    ("unknown_text_language", "Text language {0} is unknown"),
+    ("unknown_scaling", "Scaling {0} is unknown, use LINEAR, NEAR_PERFECT or PERFECT"),
+    ("incorrect_sample_rate", "Incorrect sample rate, should be a value between 8000 and 192000 Hz."),
+    ("incorrect_brightness", "Incorrect brightness, should be a value between 0.5 and 2.0."),
+    ("incorrect_max_fps", "Incorrect frame rate limit, should be 0 for no limit or a value between 15 and 240."),
+    ("incorrect_display_index", "Incorrect display index {0}, should be a number like 0 or 1."),
+    ("incorrect_window_position", "Incorrect window position {0}, should be X,Y."),
+    ("incorrect_seed", "Incorrect seed, should be a positive number."),
+    ("unknown_log_format", "Log format {0} is unknown"),
+];
+
+static GERMAN: &[(&str, &str)] = &[
+    ("config_read_error", "Fehler beim Lesen der Konfigurationsdatei ja2.json: {0}"),
+    ("config_parse_error", "Fehler beim Verarbeiten der Konfigurationsdatei ja2.json: {0}"),
+    ("data_dir_not_set", "Das Verzeichnis des Originalspiels muss in der Konfigurationsdatei oder per Kommandozeilenparameter angegeben werden"),
+    ("nonexistent_data_dir", "Bitte ein existierendes Datenverzeichnis angeben."),
+    ("nonexistent_game_dir", "Das Spielverzeichnis {0} existiert nicht"),
+    ("save_game_dir_error", "{0} kann nicht für die Spielstände verwendet werden"),
+    ("unknown_arguments", "Unbekannte Argumente: '{0}'."),
+    ("unrecognized_option", "Unbekannte Option: '{0}'"),
+    ("argument_missing", "Der Option '{0}' fehlt ein Wert"),
+    ("unexpected_argument", "Die Option '{0}' erwartet keinen Wert"),
+    ("option_duplicated", "Die Option '{0}' wurde mehrfach angegeben"),
+    ("option_missing", "Die Option '{0}' fehlt"),
+    ("incorrect_resolution", "Ungültiges Format der Auflösung, erwartet wird BREITExHÖHE oder BREITExHÖHE@BILDWIEDERHOLRATE."),
+    ("unknown_resource_version", "Unbekannte Ressourcenversion {0}"),
+    ("unknown_text_language", "Unbekannte Sprache der Texte {0}"),
+    ("unknown_scaling", "Unbekannte Skalierung {0}, erlaubt sind LINEAR, NEAR_PERFECT und PERFECT"),
+    ("incorrect_sample_rate", "Ungültige Abtastrate, erlaubt sind Werte zwischen 8000 und 192000 Hz."),
+    ("incorrect_brightness", "Ungültige Helligkeit, erlaubt sind Werte zwischen 0.5 und 2.0."),
+    ("incorrect_max_fps", "Ungültige Begrenzung der Bildrate, erlaubt sind 0 für keine Begrenzung oder Werte zwischen 15 und 240."),
+    ("incorrect_display_index", "Ungültiger Bildschirm {0}, erlaubt sind Zahlen wie 0 oder 1."),
+    ("incorrect_window_position", "Ungültige Fensterposition {0}, erwartet wird X,Y."),
+    ("incorrect_seed", "Ungültiger Startwert, erwartet wird eine positive Zahl."),
+    ("unknown_log_format", "Unbekanntes Logformat {0}"),
+];
+
+static DUTCH: &[(&str, &str)] = &[
+    ("config_read_error", "Fout bij het lezen van configuratiebestand ja2.json: {0}"),
+    ("config_parse_error", "Fout bij het verwerken van configuratiebestand ja2.json: {0}"),
+    ("data_dir_not_set", "De map van het originele spel moet in het configuratiebestand of via de opdrachtregel worden opgegeven"),
+    ("nonexistent_data_dir", "Geef een bestaande datamap op."),
+    ("nonexistent_game_dir", "De spelmap {0} bestaat niet"),
+    ("save_game_dir_error", "{0} kan niet worden gebruikt voor de opgeslagen spellen"),
+    ("unknown_arguments", "Onbekende argumenten: '{0}'."),
+    ("unrecognized_option", "Onbekende optie: '{0}'"),
+    ("argument_missing", "Waarde voor optie '{0}' ontbreekt"),
+    ("unexpected_argument", "Optie '{0}' verwacht geen waarde"),
+    ("option_duplicated", "Optie '{0}' is meer dan één keer opgegeven"),
+    ("option_missing", "Verplichte optie '{0}' ontbreekt"),
+    ("incorrect_resolution", "Onjuiste resolutie, gebruik BREEDTExHOOGTE of BREEDTExHOOGTE@VERVERSINGSFREQUENTIE."),
+    ("unknown_resource_version", "Onbekende versie van de bronbestanden {0}"),
+    ("unknown_text_language", "Onbekende taal van de teksten {0}"),
+    ("unknown_scaling", "Onbekende schaling {0}, gebruik LINEAR, NEAR_PERFECT of PERFECT"),
+    ("incorrect_sample_rate", "Onjuiste samplefrequentie, gebruik een waarde tussen 8000 en 192000 Hz."),
+    ("incorrect_brightness", "Onjuiste helderheid, gebruik een waarde tussen 0.5 en 2.0."),
+    ("incorrect_max_fps", "Onjuiste limiet voor de framerate, gebruik 0 voor geen limiet of een waarde tussen 15 en 240."),
+    ("incorrect_display_index", "Onjuiste beeldschermindex {0}, gebruik een getal zoals 0 of 1."),
+    ("incorrect_window_position", "Onjuiste vensterpositie {0}, gebruik X,Y."),
+    ("incorrect_seed", "Onjuiste seed, gebruik een positief getal."),
+    ("unknown_log_format", "Onbekend logformaat {0}"),
+];
+
+static FRENCH: &[(&str, &str)] = &[
+    ("config_read_error", "Erreur lors de la lecture du fichier de configuration ja2.json : {0}"),
+    ("config_parse_error", "Erreur d'analyse du fichier de configuration ja2.json : {0}"),
+    ("data_dir_not_set", "Le dossier du jeu original doit être indiqué dans le fichier de configuration ou en ligne de commande"),
+    ("nonexistent_data_dir", "Veuillez indiquer un dossier de données existant."),
+    ("nonexistent_game_dir", "Le dossier du jeu {0} n'existe pas"),
+    ("save_game_dir_error", "Impossible d'utiliser {0} pour les parties sauvegardées"),
+    ("unknown_arguments", "Arguments inconnus : '{0}'."),
+    ("unrecognized_option", "Option inconnue : '{0}'"),
+    ("argument_missing", "Valeur manquante pour l'option '{0}'"),
+    ("unexpected_argument", "L'option '{0}' n'accepte pas de valeur"),
+    ("option_duplicated", "L'option '{0}' est indiquée plusieurs fois"),
+    ("option_missing", "L'option obligatoire '{0}' est manquante"),
+    ("incorrect_resolution", "Format de résolution incorrect, attendu LARGEURxHAUTEUR ou LARGEURxHAUTEUR@FRÉQUENCE."),
+    ("unknown_resource_version", "Version des ressources {0} inconnue"),
+    ("unknown_text_language", "Langue des textes {0} inconnue"),
+    ("unknown_scaling", "Mise à l'échelle {0} inconnue, utilisez LINEAR, NEAR_PERFECT ou PERFECT"),
+    ("incorrect_sample_rate", "Fréquence d'échantillonnage incorrecte, la valeur doit être comprise entre 8000 et 192000 Hz."),
+    ("incorrect_brightness", "Luminosité incorrecte, la valeur doit être comprise entre 0.5 et 2.0."),
+    ("incorrect_max_fps", "Limite d'images par seconde incorrecte, utilisez 0 pour aucune limite ou une valeur entre 15 et 240."),
+    ("incorrect_display_index", "Index d'écran {0} incorrect, utilisez un nombre comme 0 ou 1."),
+    ("incorrect_window_position", "Position de fenêtre {0} incorrecte, utilisez X,Y."),
+    ("incorrect_seed", "Graine incorrecte, un nombre positif est attendu."),
+    ("unknown_log_format", "Format de journal {0} inconnu"),
+];
+
+static ITALIAN: &[(&str, &str)] = &[
+    ("config_read_error", "Errore durante la lettura del file di configurazione ja2.json: {0}"),
+    ("config_parse_error", "Errore di analisi del file di configurazione ja2.json: {0}"),
+    ("data_dir_not_set", "La cartella del gioco originale deve essere indicata nel file di configurazione o dalla riga di comando"),
+    ("nonexistent_data_dir", "Specifica una cartella dei dati esistente."),
+    ("nonexistent_game_dir", "La cartella del gioco {0} non esiste"),
+    ("save_game_dir_error", "Impossibile usare {0} per le partite salvate"),
+    ("unknown_arguments", "Argomenti sconosciuti: '{0}'."),
+    ("unrecognized_option", "Opzione sconosciuta: '{0}'"),
+    ("argument_missing", "Manca il valore dell'opzione '{0}'"),
+    ("unexpected_argument", "L'opzione '{0}' non accetta valori"),
+    ("option_duplicated", "L'opzione '{0}' è indicata più volte"),
+    ("option_missing", "Manca l'opzione obbligatoria '{0}'"),
+    ("incorrect_resolution", "Formato della risoluzione non valido, deve essere LARGHEZZAxALTEZZA o LARGHEZZAxALTEZZA@FREQUENZA."),
+    ("unknown_resource_version", "Versione delle risorse {0} sconosciuta"),
+    ("unknown_text_language", "Lingua dei testi {0} sconosciuta"),
+    ("unknown_scaling", "Ridimensionamento {0} sconosciuto, usa LINEAR, NEAR_PERFECT o PERFECT"),
+    ("incorrect_sample_rate", "Frequenza di campionamento non valida, deve essere un valore tra 8000 e 192000 Hz."),
+    ("incorrect_brightness", "Luminosità non valida, deve essere un valore tra 0.5 e 2.0."),
+    ("incorrect_max_fps", "Limite dei fotogrammi al secondo non valido, usa 0 per nessun limite o un valore tra 15 e 240."),
+    ("incorrect_display_index", "Indice dello schermo {0} non valido, usa un numero come 0 o 1."),
+    ("incorrect_window_position", "Posizione della finestra {0} non valida, usa X,Y."),
+    ("incorrect_seed", "Seme non valido, deve essere un numero positivo."),
+    ("unknown_log_format", "Formato del log {0} sconosciuto"),
+];
+
+static POLISH: &[(&str, &str)] = &[
+    ("config_read_error", "Błąd odczytu pliku konfiguracyjnego ja2.json: {0}"),
+    ("config_parse_error", "Błąd przetwarzania pliku konfiguracyjnego ja2.json: {0}"),
+    ("data_dir_not_set", "Katalog oryginalnej gry musi być podany w pliku konfiguracyjnym lub w wierszu poleceń"),
+    ("nonexistent_data_dir", "Podaj istniejący katalog danych."),
+    ("nonexistent_game_dir", "Katalog gry {0} nie istnieje"),
+    ("save_game_dir_error", "Nie można użyć {0} do zapisanych gier"),
+    ("unknown_arguments", "Nieznane argumenty: '{0}'."),
+    ("unrecognized_option", "Nieznana opcja: '{0}'"),
+    ("argument_missing", "Brak wartości opcji '{0}'"),
+    ("unexpected_argument", "Opcja '{0}' nie przyjmuje wartości"),
+    ("option_duplicated", "Opcja '{0}' została podana więcej niż raz"),
+    ("option_missing", "Brak wymaganej opcji '{0}'"),
+    ("incorrect_resolution", "Nieprawidłowy format rozdzielczości, oczekiwano SZEROKOŚĆxWYSOKOŚĆ lub SZEROKOŚĆxWYSOKOŚĆ@ODŚWIEŻANIE."),
+    ("unknown_resource_version", "Nieznana wersja zasobów {0}"),
+    ("unknown_text_language", "Nieznany język tekstów {0}"),
+    ("unknown_scaling", "Nieznane skalowanie {0}, użyj LINEAR, NEAR_PERFECT lub PERFECT"),
+    ("incorrect_sample_rate", "Nieprawidłowa częstotliwość próbkowania, dozwolone są wartości od 8000 do 192000 Hz."),
+    ("incorrect_brightness", "Nieprawidłowa jasność, dozwolone są wartości od 0.5 do 2.0."),
+    ("incorrect_max_fps", "Nieprawidłowy limit klatek na sekundę, użyj 0 dla braku limitu lub wartości od 15 do 240."),
+    ("incorrect_display_index", "Nieprawidłowy indeks ekranu {0}, użyj liczby jak 0 lub 1."),
+    ("incorrect_window_position", "Nieprawidłowa pozycja okna {0}, użyj X,Y."),
+    ("incorrect_seed", "Nieprawidłowe ziarno, oczekiwano liczby dodatniej."),
+    ("unknown_log_format", "Nieznany format logu {0}"),
+];
+
+static RUSSIAN: &[(&str, &str)] = &[
+    ("config_read_error", "Ошибка чтения файла конфигурации ja2.json: {0}"),
+    ("config_parse_error", "Ошибка разбора файла конфигурации ja2.json: {0}"),
+    ("data_dir_not_set", "Папку оригинальной игры нужно указать в файле конфигурации или в командной строке"),
+    ("nonexistent_data_dir", "Укажите существующую папку с данными."),
+    ("nonexistent_game_dir", "Папка игры {0} не существует"),
+    ("save_game_dir_error", "Невозможно использовать {0} для сохранённых игр"),
+    ("unknown_arguments", "Неизвестные аргументы: '{0}'."),
+    ("unrecognized_option", "Неизвестный параметр: '{0}'"),
+    ("argument_missing", "Не указано значение параметра '{0}'"),
+    ("unexpected_argument", "Параметр '{0}' не принимает значение"),
+    ("option_duplicated", "Параметр '{0}' указан несколько раз"),
+    ("option_missing", "Не указан обязательный параметр '{0}'"),
+    ("incorrect_resolution", "Неверный формат разрешения, ожидается ШИРИНАxВЫСОТА или ШИРИНАxВЫСОТА@ЧАСТОТА."),
+    ("unknown_resource_version", "Неизвестная версия ресурсов {0}"),
+    ("unknown_text_language", "Неизвестный язык текстов {0}"),
+    ("unknown_scaling", "Неизвестное масштабирование {0}, используйте LINEAR, NEAR_PERFECT или PERFECT"),
+    ("incorrect_sample_rate", "Неверная частота дискретизации, допустимы значения от 8000 до 192000 Гц."),
+    ("incorrect_brightness", "Неверная яркость, допустимы значения от 0.5 до 2.0."),
+    ("incorrect_max_fps", "Неверное ограничение частоты кадров, используйте 0 без ограничения или значение от 15 до 240."),
+    ("incorrect_display_index", "Неверный номер экрана {0}, используйте число, например 0 или 1."),
+    ("incorrect_window_position", "Неверная позиция окна {0}, используйте X,Y."),
+    ("incorrect_seed", "Неверное начальное значение, ожидается положительное число."),
+    ("unknown_log_format", "Неизвестный формат журнала {0}"),
+];
+
+/// Translations by language code.
+static CATALOG: &[(&str, &[(&str, &str)])] = &[
+    ("en", ENGLISH),
+    ("de", GERMAN),
+    ("nl", DUTCH),
+    ("fr", FRENCH),
+    ("it", ITALIAN),
+    ("pl", POLISH),
+    ("ru", RUSSIAN),
+];
+
+/// Returns the language code of a language or locale, e.g. `de` for `de_DE.UTF-8`.
+fn language_code(language: &str) -> String {
+    language.split(&['_', '-', '.', '@'][..]).next().unwrap_or("").to_lowercase()
+}
+
+fn lookup(language: &str, key: &str) -> Option<&'static str> {
+    let code = language_code(language);
+    CATALOG.iter()
+        .find(|&&(c, _)| c == code)
+        .and_then(|&(_, messages)| messages.iter().find(|&&(k, _)| k == key))
+        .map(|&(_, m)| m)
+}
+
+/// Returns the language of the system from the locale variables, `en` if they are not set.
+pub fn system_language(env_var: &Fn(&str) -> Option<String>) -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|v| env_var(v))
+        .next()
+        .map(|l| language_code(&l))
+        .filter(|l| !l.is_empty() && l != "c" && l != "posix")
+        .unwrap_or_else(|| String::from("en"))
+}
+
+/// Returns the language of the messages, the language of the system if no launcher language is configured.
+pub fn message_language(launcher_language: &str, env_var: &Fn(&str) -> Option<String>) -> String {
+    if launcher_language.is_empty() {
+        return system_language(env_var);
+    }
+    language_code(launcher_language)
+}
+
+impl Message {
+    pub fn new(key: &'static str, params: Vec<String>) -> Message {
+        Message { key, params }
+    }
+
+    pub fn localize(&self, language: &str) -> String {
+        let template = lookup(language, self.key).or_else(|| lookup("en", self.key)).unwrap_or(self.key);
+        self.params.iter().enumerate()
+            .fold(String::from(template), |s, (i, p)| s.replace(&format!("{{{}}}", i), p))
+    }
+}
+
+/// Displays the English message.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.localize("en"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CATALOG, ENGLISH, Message, message_language, system_language};
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| vars.iter().find(|&&(n, _)| n == name).map(|&(_, v)| String::from(v))
+    }
+
+    #[test]
+    fn every_language_should_translate_every_message() {
+        for &(language, messages) in CATALOG {
+            let keys: Vec<&str> = messages.iter().map(|&(k, _)| k).collect();
+            assert_eq!(keys, ENGLISH.iter().map(|&(k, _)| k).collect::<Vec<&str>>(), "{}", language);
+            for (&(key, translation), &(_, english)) in messages.iter().zip(ENGLISH.iter()) {
+                assert_eq!(translation.contains("{0}"), english.contains("{0}"), "{} {}", language, key);
+            }
+        }
+    }
+
+    #[test]
+    fn localize_should_replace_the_parameters() {
+        let message = Message::new("unknown_resource_version", vec!(String::from("KLINGON")));
+
+        assert_eq!(message.localize("de"), "Unbekannte Ressourcenversion KLINGON");
+        assert_eq!(message.localize("pl_PL.UTF-8"), "Nieznana wersja zasobów KLINGON");
+        assert_eq!(message.localize("pt_BR"), "Resource version KLINGON is unknown");
+        assert_eq!(message.to_string(), "Resource version KLINGON is unknown");
+    }
+
+    #[test]
+    fn message_language_should_prefer_the_launcher_language() {
+        assert_eq!(message_language("fr", &env(&[("LANG", "de_DE.UTF-8")])), "fr");
+        assert_eq!(message_language("", &env(&[("LANG", "de_DE.UTF-8")])), "de");
+        assert_eq!(system_language(&env(&[("LC_ALL", "ru_RU.UTF-8"), ("LANG", "de_DE.UTF-8")])), "ru");
+        assert_eq!(system_language(&env(&[("LANG", "C.UTF-8")])), "en");
+        assert_eq!(system_language(&env(&[])), "en");
+    }
+}
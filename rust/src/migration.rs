@@ -0,0 +1,354 @@
+//! Migration of the stracciatella home to a new location.
+//!
+//! All files are copied and verified before anything is deleted in the old home, so an interrupted migration never
+//! loses data. If the migration fails, the copies are removed again. Comments in ja2.json are not kept.
+
+use std::ffi::{CStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+use serde_json::Value;
+
+use {EngineOptions, set_last_error, strip_json_comments};
+use home::MOVED_HOME_FILE;
+
+/// Files and folders in the home that belong to the player besides the saved games. ja2.json is deleted last, so the
+/// old home stays usable until the migration is complete.
+static MIGRATED_ENTRIES: [&'static str; 4] = ["mods", "music-packs", "replays", "ja2.json"];
+
+/// Returns the saved game folders of all mods in the home, e.g. `SavedGames` and `SavedGames-wildfire`.
+fn saved_games_folders(home: &Path) -> Result<Vec<OsString>, String> {
+    let entries = fs::read_dir(home).map_err(|s| format!("Error reading {}: {}", home.display(), s.kind()))?;
+    let mut folders = vec!();
+    for entry in entries {
+        let name = entry.map_err(|s| format!("Error reading {}: {}", home.display(), s.kind()))?.file_name();
+        if name.to_string_lossy().starts_with("SavedGames") {
+            folders.push(name);
+        }
+    }
+    folders.sort();
+    Ok(folders)
+}
+
+/// Returns the moves of the other player files from the old to the new home.
+fn player_file_moves(old: &Path, new: &Path) -> Vec<(PathBuf, PathBuf)> {
+    MIGRATED_ENTRIES.iter()
+        .filter(|e| old.join(e).exists())
+        .map(|e| (old.join(e), new.join(e)))
+        .collect()
+}
+
+fn copy_recursively(from: &Path, to: &Path) -> Result<(), String> {
+    if from.is_dir() {
+        fs::create_dir_all(to).map_err(|s| format!("Error creating {}: {}", to.display(), s.kind()))?;
+        let entries = fs::read_dir(from).map_err(|s| format!("Error reading {}: {}", from.display(), s.kind()))?;
+        for entry in entries {
+            let entry = entry.map_err(|s| format!("Error reading {}: {}", from.display(), s.kind()))?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ()).map_err(|s| format!("Error copying {} to {}: {}", from.display(), to.display(), s.kind()))
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let mut contents = vec!();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut contents))
+        .map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    Ok(contents)
+}
+
+/// Checks that the copy has the same files with the same contents as the original.
+fn verify_copy(from: &Path, to: &Path) -> Result<(), String> {
+    if from.is_dir() {
+        let entries = fs::read_dir(from).map_err(|s| format!("Error reading {}: {}", from.display(), s.kind()))?;
+        for entry in entries {
+            let entry = entry.map_err(|s| format!("Error reading {}: {}", from.display(), s.kind()))?;
+            verify_copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if read_file(from)? != read_file(to)? {
+        Err(format!("{} differs from {}", to.display(), from.display()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Replaces the old home at the start of all paths in the config with the new home. Paths are compared by their
+/// components, so a sibling like `~/.ja2-backup` is not inside `~/.ja2`.
+fn update_paths(value: &mut Value, old: &Path, new: &Path) {
+    match *value {
+        Value::String(ref mut s) => {
+            let updated = match Path::new(s.as_str()).strip_prefix(old) {
+                Ok(rest) if rest.as_os_str().is_empty() => new.to_path_buf(),
+                Ok(rest) => new.join(rest),
+                Err(_) => return
+            };
+            *s = updated.to_string_lossy().into_owned();
+        },
+        Value::Array(ref mut a) => for v in a { update_paths(v, old, new) },
+        Value::Object(ref mut m) => for (_, v) in m.iter_mut() { update_paths(v, old, new) },
+        _ => {}
+    }
+}
+
+/// Returns the contents of ja2.json of the old home with the paths in the new home, or `None` if there is no ja2.json.
+fn updated_json_config(old: &Path, new: &Path) -> Result<Option<String>, String> {
+    let path = old.join("ja2.json");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = String::from_utf8(read_file(&path)?).map_err(|s| format!("Error parsing {}: {}", path.display(), s))?;
+    let mut config: Value = serde_json::from_str(&strip_json_comments(&contents)).map_err(|s| format!("Error parsing {}: {}", path.display(), s))?;
+    update_paths(&mut config, old, new);
+    serde_json::to_string_pretty(&config).map(Some).map_err(|s| format!("Error creating contents of {}: {}", path.display(), s))
+}
+
+fn write_json_config(new: &Path, json: &str) -> Result<(), String> {
+    let path = new.join("ja2.json");
+    File::create(&path)
+        .and_then(|mut f| f.write_all(json.as_bytes()))
+        .map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+}
+
+fn copy_entries(new: &Path, moves: &[(PathBuf, PathBuf)], config: Option<&String>) -> Result<(), String> {
+    for (from, to) in moves {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).map_err(|s| format!("Error creating {}: {}", parent.display(), s.kind()))?;
+        }
+        copy_recursively(from, to)?;
+    }
+    for (from, to) in moves {
+        verify_copy(from, to)?;
+    }
+    match config {
+        Some(json) => write_json_config(new, json),
+        None => Ok(())
+    }
+}
+
+/// Removes the copies of a failed migration. None of them existed before the migration.
+fn remove_copies(moves: &[(PathBuf, PathBuf)]) {
+    for (_, to) in moves {
+        let _ = if to.is_dir() { fs::remove_dir_all(to) } else { fs::remove_file(to) };
+    }
+}
+
+fn check_homes(old: &Path, new: &Path) -> Result<(), String> {
+    if new.starts_with(old) || old.starts_with(new) {
+        return Err(format!("{} and {} must not contain each other", old.display(), new.display()));
+    }
+    Ok(())
+}
+
+/// Copies the entries of the old home to their new paths and deletes them after all copies were verified. Nothing is
+/// overwritten.
+fn move_entries(old: &Path, new: &Path, moves: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    if let Some((_, existing)) = moves.iter().find(|(_, to)| to.exists()) {
+        return Err(format!("{} already exists", existing.display()));
+    }
+
+    // The config is checked before anything is copied
+    let config = updated_json_config(old, new)?;
+
+    fs::create_dir_all(new).map_err(|s| format!("Error creating {}: {}", new.display(), s.kind()))?;
+    if let Err(s) = copy_entries(new, moves, config.as_ref()) {
+        remove_copies(moves);
+        return Err(s);
+    }
+
+    for (from, _) in moves {
+        let result = if from.is_dir() { fs::remove_dir_all(from) } else { fs::remove_file(from) };
+        result.map_err(|s| format!("Error deleting {}: {}", from.display(), s.kind()))?;
+    }
+    // Other files like logs stay in the old home, the folder is only removed if it is empty
+    let _ = fs::remove_dir(old);
+    Ok(())
+}
+
+/// Moves the config, saves and mods of the old home to the new home.
+///
+/// Nothing is overwritten in the new home. The old files are only deleted after all copies were verified. The old home
+/// keeps a `moved-home.txt` with the absolute path of the new home, so the next start finds it, see
+/// `home::follow_moved_home`.
+pub fn migrate_home(old: &Path, new: &Path) -> Result<(), String> {
+    if !old.is_dir() {
+        return Err(format!("{} is not a directory", old.display()));
+    }
+    if !new.is_absolute() {
+        return Err(format!("{} is not an absolute path", new.display()));
+    }
+    check_homes(old, new)?;
+
+    let mut moves: Vec<(PathBuf, PathBuf)> = saved_games_folders(old)?.iter()
+        .map(|name| (old.join(name), new.join(name)))
+        .collect();
+    moves.extend(player_file_moves(old, new));
+
+    // The pointer is only followed once ja2.json is gone, so it is written first and never points to a partial home
+    let moved_home_file = old.join(MOVED_HOME_FILE);
+    File::create(&moved_home_file)
+        .and_then(|mut f| f.write_all(new.to_string_lossy().as_bytes()))
+        .map_err(|s| format!("Error writing {}: {}", moved_home_file.display(), s.kind()))?;
+    let result = move_entries(old, new, &moves);
+    if result.is_err() {
+        let _ = fs::remove_file(&moved_home_file);
+    }
+    result
+}
+
+/// Moves the legacy home `~/.ja2` to the XDG base directories. The saved games of all mods go to the saves home, the
+/// other files of the player to the new home.
+pub fn migrate_legacy_home(old: &Path, new: &Path, saves_home: &Path) -> Result<(), String> {
+    if !old.is_dir() {
+        return Err(format!("{} is not a directory", old.display()));
+    }
+    check_homes(old, new)?;
+    check_homes(old, saves_home)?;
+
+    let mut moves: Vec<(PathBuf, PathBuf)> = saved_games_folders(old)?.iter()
+        .map(|name| (old.join(name), saves_home.join(name)))
+        .collect();
+    moves.extend(player_file_moves(old, new));
+    move_entries(old, new, &moves)
+}
+
+/// Migrates the home of the engine options to a new location and uses the new home afterwards.
+#[no_mangle]
+pub extern fn migrate_stracciatella_home(ptr: *mut EngineOptions, new_home_ptr: *const c_char) -> bool {
+    let engine_options = unsafe_from_ptr_mut!(ptr);
+    let new_home = PathBuf::from(unsafe { CStr::from_ptr(new_home_ptr) }.to_string_lossy().into_owned());
+
+    match migrate_home(&engine_options.stracciatella_home, &new_home) {
+        Ok(()) => {
+            // Saved games in the XDG data home stay where they are
+            if engine_options.saves_home == engine_options.stracciatella_home {
+                engine_options.saves_home = new_home.clone();
+            }
+            engine_options.stracciatella_home = new_home;
+            true
+        },
+        Err(s) => {
+            set_last_error(Some(format!("Error migrating the home dir: {}", s)));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    #[test]
+    fn migrate_home_should_move_all_player_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        let new = temp_dir.path().join("new");
+        let config = format!("{{ \"data_dir\": \"/ja2\", \"mods\": [\"{}/mods/x\"] }}", old.display());
+        write_file(&old.join("ja2.json"), config.as_bytes());
+        write_file(&old.join("SavedGames/SaveGame01.sav"), b"save");
+        write_file(&old.join("SavedGames-wildfire/SaveGame02.sav"), b"wildfire save");
+        write_file(&old.join("mods/x/data/test.json"), b"{}");
+        write_file(&old.join("ja2.log"), b"log");
+
+        assert_eq!(super::migrate_home(&old, &new), Ok(()));
+
+        assert_eq!(fs::read(new.join("SavedGames/SaveGame01.sav")).unwrap(), b"save");
+        assert_eq!(fs::read(new.join("SavedGames-wildfire/SaveGame02.sav")).unwrap(), b"wildfire save");
+        assert!(!old.join("SavedGames-wildfire").exists());
+        assert!(new.join("mods/x/data/test.json").is_file());
+        let config: ::serde_json::Value = ::serde_json::from_slice(&fs::read(new.join("ja2.json")).unwrap()).unwrap();
+        assert_eq!(config, json!({ "data_dir": "/ja2", "mods": [format!("{}/mods/x", new.display())] }));
+        assert!(!old.join("SavedGames").exists());
+        assert!(!old.join("ja2.json").exists());
+        assert!(old.join("ja2.log").exists());
+        assert_eq!(fs::read_to_string(old.join(super::MOVED_HOME_FILE)).unwrap(), new.to_string_lossy());
+    }
+
+    #[test]
+    fn migrate_home_should_not_overwrite_existing_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        let new = temp_dir.path().join("new");
+        write_file(&old.join("ja2.json"), b"{}");
+        write_file(&old.join("SavedGames/SaveGame01.sav"), b"old save");
+        write_file(&new.join("SavedGames/SaveGame01.sav"), b"new save");
+
+        assert_eq!(super::migrate_home(&old, &new), Err(format!("{} already exists", new.join("SavedGames").display())));
+        assert!(old.join("ja2.json").exists());
+        assert!(!old.join(super::MOVED_HOME_FILE).exists());
+        assert!(!new.join("ja2.json").exists());
+    }
+
+    #[test]
+    fn migrate_home_should_accept_comments_in_the_config() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join(".ja2");
+        let new = temp_dir.path().join("new");
+        let backup = temp_dir.path().join(".ja2-backup");
+        let config = format!("{{\n  // my mods\n  \"mods\": [\"{}/mods/x\", \"{}/x\"]\n}}", old.display(), backup.display());
+        write_file(&old.join("ja2.json"), config.as_bytes());
+        write_file(&old.join("SavedGames/SaveGame01.sav"), b"save");
+
+        assert_eq!(super::migrate_home(&old, &new), Ok(()));
+
+        let config: ::serde_json::Value = ::serde_json::from_slice(&fs::read(new.join("ja2.json")).unwrap()).unwrap();
+        assert_eq!(config, json!({ "mods": [new.join("mods/x").to_string_lossy(), backup.join("x").to_string_lossy()] }));
+    }
+
+    #[test]
+    fn migrate_home_should_not_copy_anything_with_an_invalid_config() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        let new = temp_dir.path().join("new");
+        write_file(&old.join("ja2.json"), b"{ invalid");
+        write_file(&old.join("SavedGames/SaveGame01.sav"), b"save");
+
+        assert!(super::migrate_home(&old, &new).is_err());
+        assert!(!new.join("SavedGames").exists());
+        assert!(old.join("SavedGames/SaveGame01.sav").exists());
+    }
+
+    #[test]
+    fn remove_copies_should_roll_back_a_failed_migration() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        let new = temp_dir.path().join("new");
+        write_file(&old.join("SavedGames/SaveGame01.sav"), b"save");
+        write_file(&old.join("ja2.json"), b"{}");
+        let moves = vec!((old.join("SavedGames"), new.join("SavedGames")), (old.join("ja2.json"), new.join("ja2.json")));
+
+        assert!(super::copy_entries(&new, &moves, None).is_ok());
+        super::remove_copies(&moves);
+
+        assert!(!new.join("SavedGames").exists());
+        assert!(!new.join("ja2.json").exists());
+        assert_eq!(super::migrate_home(&old, &new), Ok(()));
+    }
+
+    #[test]
+    fn migrate_home_should_fail_with_nested_homes() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let old = temp_dir.path().join("old");
+        fs::create_dir_all(&old).unwrap();
+
+        assert!(super::migrate_home(&old, &old.join("new")).is_err());
+        assert!(super::migrate_home(&old, Path::new("relative")).is_err());
+        assert!(super::migrate_home(&temp_dir.path().join("missing"), &old).is_err());
+    }
+}
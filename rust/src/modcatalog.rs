@@ -0,0 +1,235 @@
+//! Catalog of known community mods, e.g. for update hints in the launcher.
+//!
+//! The catalog is optional. The launcher downloads it and `refresh_mod_catalog` stores a copy in the home:
+//!
+//! ```json
+//! { "mods": [{ "id": "o-fortuna", "name": "O Fortuna", "version": "1.1", "homepage": "https://example.com",
+//!   "download_url": "https://example.com/o-fortuna-1.1.zip", "engine_versions": ["0.16", "0.17"] }] }
+//! ```
+//!
+//! An engine version is compatible if it equals a listed version or starts with it followed by a `.`, e.g. `0.17.2`
+//! matches `0.17`. Mods without `engine_versions` are compatible with every version.
+
+use std::cmp::Ordering;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+use set_last_error;
+use modmanifest::{ModManifest, is_valid_mod_id};
+use modwatcher::mods_dirs;
+
+pub const CATALOG_FILE_NAME: &str = "mod-catalog.json";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub homepage: String,
+    pub download_url: String,
+    #[serde(default)]
+    pub engine_versions: Vec<String>,
+}
+
+impl CatalogEntry {
+    pub fn is_compatible(&self, engine_version: &str) -> bool {
+        self.engine_versions.is_empty() || self.engine_versions.iter().any(|v| {
+            engine_version == v || engine_version.strip_prefix(v.as_str()).map(|r| r.starts_with('.')).unwrap_or(false)
+        })
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ModCatalog {
+    pub mods: Vec<CatalogEntry>,
+}
+
+/// Newer version of an installed mod that is in the catalog.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct UpdateHint {
+    pub id: String,
+    pub installed_version: String,
+    pub available_version: String,
+    pub download_url: String,
+    pub homepage: String,
+    /// Whether the new version supports the running engine
+    pub compatible: bool,
+}
+
+pub fn catalog_path(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join(CATALOG_FILE_NAME)
+}
+
+/// Compares versions by their numeric parts, e.g. `1.10` is newer than `1.9`. Parts that are not numbers are
+/// compared as text.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| v.split(['.', '-', '+']).map(String::from).collect::<Vec<String>>();
+    let (a_parts, b_parts) = (parts(a), parts(b));
+    for (pa, pb) in a_parts.iter().zip(b_parts.iter()) {
+        let ordering = match (pa.parse::<u64>(), pb.parse::<u64>()) {
+            (Ok(na), Ok(nb)) => na.cmp(&nb),
+            _ => pa.cmp(pb),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_parts.len().cmp(&b_parts.len())
+}
+
+impl ModCatalog {
+    pub fn parse(json: &str) -> Result<ModCatalog, String> {
+        let catalog: ModCatalog = serde_json::from_str(json).map_err(|s| format!("Error parsing the mod catalog: {}", s))?;
+        if let Some(e) = catalog.mods.iter().find(|e| !is_valid_mod_id(&e.id)) {
+            return Err(format!("The mod catalog has an invalid mod id '{}'", e.id));
+        }
+        Ok(catalog)
+    }
+
+    /// Loads the cached catalog, an empty catalog if there is none.
+    pub fn load(stracciatella_home: &Path) -> Result<ModCatalog, String> {
+        let path = catalog_path(stracciatella_home);
+        if !path.exists() {
+            return Ok(ModCatalog::default());
+        }
+        let contents = fs::read_to_string(&path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+        ModCatalog::parse(&contents).map_err(|s| format!("{}: {}", path.display(), s))
+    }
+
+    /// Replaces the cached catalog if the new one is valid. The file is replaced at once, so a failed write never
+    /// leaves a broken catalog behind.
+    pub fn refresh(stracciatella_home: &Path, json: &str) -> Result<ModCatalog, String> {
+        let catalog = ModCatalog::parse(json)?;
+        let path = catalog_path(stracciatella_home);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, json)
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))?;
+        Ok(catalog)
+    }
+
+    pub fn find(&self, id: &str) -> Option<&CatalogEntry> {
+        self.mods.iter().find(|e| e.id == id)
+    }
+
+    /// Returns the installed mods that have a newer version in the catalog.
+    pub fn update_hints(&self, installed: &[ModManifest], engine_version: &str) -> Vec<UpdateHint> {
+        installed.iter().filter_map(|m| {
+            let entry = self.find(&m.id)?;
+            if compare_versions(&entry.version, &m.version) != Ordering::Greater {
+                return None;
+            }
+            Some(UpdateHint {
+                id: m.id.clone(),
+                installed_version: m.version.clone(),
+                available_version: entry.version.clone(),
+                download_url: entry.download_url.clone(),
+                homepage: entry.homepage.clone(),
+                compatible: entry.is_compatible(engine_version),
+            })
+        }).collect()
+    }
+}
+
+/// Returns the manifests of the mods in the mods dirs, mods without a valid manifest are left out.
+pub fn installed_mods(extra_data_dir: &Path, stracciatella_home: &Path) -> Vec<ModManifest> {
+    let mut manifests: Vec<ModManifest> = vec!();
+    for dir in mods_dirs(extra_data_dir, stracciatella_home) {
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for manifest in entries.filter_map(|e| e.ok()).filter_map(|e| ModManifest::load(&e.path()).ok()) {
+                // Mods in the home take precedence, see `vfs::find_mod_dir`
+                if !manifests.iter().any(|m| m.id == manifest.id) {
+                    manifests.push(manifest);
+                }
+            }
+        }
+    }
+    manifests
+}
+
+/// Stores a downloaded catalog in the home. False if it is invalid, see `get_last_error`.
+#[no_mangle]
+pub extern fn refresh_mod_catalog(ptr: *const EngineOptions, path_ptr: *const c_char) -> bool {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    let result = fs::read_to_string(&path)
+        .map_err(|s| format!("Error reading {}: {}", path, s.kind()))
+        .and_then(|json| ModCatalog::refresh(&unsafe_from_ptr!(ptr).stracciatella_home, &json));
+    let success = result.is_ok();
+    set_last_error(result.err());
+    success
+}
+
+/// Returns the update hints for the installed mods as JSON array, empty if there is no valid catalog.
+#[no_mangle]
+pub extern fn get_mod_update_hints_json(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char, engine_version_ptr: *const c_char) -> *mut c_char {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let engine_version = unsafe { CStr::from_ptr(engine_version_ptr) }.to_string_lossy().into_owned();
+    let catalog = ModCatalog::load(&engine_options.stracciatella_home).unwrap_or_default();
+    let hints = catalog.update_hints(&installed_mods(Path::new(&extra_data_dir), &engine_options.stracciatella_home), &engine_version);
+    CString::new(json!(hints).to_string()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::cmp::Ordering;
+    use std::fs;
+
+    use super::{ModCatalog, catalog_path, compare_versions, installed_mods};
+
+    const CATALOG: &str = r#"{ "mods": [
+        { "id": "o-fortuna", "version": "1.10", "download_url": "https://example.com/o-fortuna.zip", "engine_versions": ["0.17"] },
+        { "id": "old-mod", "version": "1.0", "download_url": "https://example.com/old-mod.zip" }
+    ] }"#;
+
+    #[test]
+    fn compare_versions_should_compare_numeric_parts() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.0-beta", "2.0-alpha"), Ordering::Greater);
+    }
+
+    #[test]
+    fn refresh_should_only_replace_the_catalog_with_a_valid_one() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let home = temp_dir.path();
+
+        assert_eq!(ModCatalog::load(home), Ok(ModCatalog::default()));
+        assert_eq!(ModCatalog::refresh(home, CATALOG).unwrap().mods.len(), 2);
+        assert!(ModCatalog::refresh(home, r#"{ "mods": [{ "id": "Bad Id", "version": "1", "download_url": "" }] }"#).is_err());
+        assert!(ModCatalog::refresh(home, "{").is_err());
+
+        assert_eq!(ModCatalog::load(home).unwrap().find("o-fortuna").unwrap().version, "1.10");
+        assert_eq!(fs::read_to_string(catalog_path(home)).unwrap(), CATALOG);
+    }
+
+    #[test]
+    fn update_hints_should_list_installed_mods_with_newer_versions() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let home = temp_dir.path().join("home");
+        let extra_data_dir = temp_dir.path().join("extra");
+        for &(dir, id, version) in &[(&home, "o-fortuna", "1.9"), (&extra_data_dir, "old-mod", "1.0"), (&extra_data_dir, "o-fortuna", "1.0")] {
+            let mod_dir = dir.join("mods").join(format!("{}-{}", id, version));
+            fs::create_dir_all(&mod_dir).unwrap();
+            fs::write(mod_dir.join("mod.json"), format!(r#"{{ "id": "{}", "name": "{}", "version": "{}" }}"#, id, id, version)).unwrap();
+        }
+        let catalog = ModCatalog::parse(CATALOG).unwrap();
+
+        let hints = catalog.update_hints(&installed_mods(&extra_data_dir, &home), "0.17.1");
+
+        assert_eq!(hints.len(), 1);
+        assert_eq!((hints[0].id.as_str(), hints[0].installed_version.as_str(), hints[0].available_version.as_str()), ("o-fortuna", "1.9", "1.10"));
+        assert!(hints[0].compatible);
+        assert!(!catalog.mods[0].is_compatible("0.16.9"));
+        assert!(!catalog.mods[0].is_compatible("0.170"));
+    }
+}
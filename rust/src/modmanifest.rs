@@ -0,0 +1,146 @@
+//! Manifest of a mod.
+//!
+//! The manifest is the `mod.json` in the mod folder next to the `data` folder:
+//!
+//! ```json
+//! { "id": "o-fortuna", "name": "O Fortuna", "version": "1.0", "files": ["readme.txt", "license.txt"] }
+//! ```
+//!
+//! `files` lists the files outside of the `data` folder that belong to the mod, e.g. a readme or a preview image.
+
+use std::fs::File;
+use std::path::{Component, Path};
+
+use datadir::find_data_folder;
+use errorcontext::{ErrorContext, ErrorKind};
+use pathcheck::is_reserved_windows_name;
+use slf::SlfLibrary;
+use vfs::find_mod_libraries;
+
+pub const MANIFEST_FILE_NAME: &str = "mod.json";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ModManifest {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+}
+
+/// Mod ids are used as folder names, so they are restricted to lowercase ASCII letters, digits, `-` and `_` and must
+/// not be reserved on Windows.
+pub fn is_valid_mod_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') && !is_reserved_windows_name(id)
+}
+
+/// Versions are part of the file name of packaged mods.
+pub fn is_valid_mod_version(version: &str) -> bool {
+    !version.is_empty() && version.chars().all(|c| c.is_ascii_alphanumeric() || ".-_+".contains(c))
+}
+
+impl ModManifest {
+    pub fn load(mod_dir: &Path) -> Result<ModManifest, ErrorContext> {
+        let path = mod_dir.join(MANIFEST_FILE_NAME);
+        File::open(&path)
+            .map_err(|s| ErrorContext::io(&s, &path).wrap(ErrorKind::MODS, format!("Error reading {}", path.display())))
+            .and_then(|f| serde_json::from_reader(f).map_err(|s| ErrorContext::new(ErrorKind::MODS, format!("Error parsing {}: {}", path.display(), s)).with_path(&path)))
+    }
+
+    /// Returns all problems of the manifest and the mod folder it belongs to.
+    pub fn validate(&self, mod_dir: &Path) -> Vec<String> {
+        let mut problems = vec!();
+
+        if !is_valid_mod_id(&self.id) {
+            problems.push(format!("id '{}' may only contain lowercase letters, digits, '-' and '_'", self.id));
+        }
+        if self.name.trim().is_empty() {
+            problems.push(String::from("name must not be empty"));
+        }
+        if !is_valid_mod_version(&self.version) {
+            problems.push(format!("version '{}' may only contain letters, digits, '.', '-', '_' and '+'", self.version));
+        }
+        for file in &self.files {
+            let path = Path::new(file);
+            if path.components().any(|c| !matches!(c, Component::Normal(_))) {
+                problems.push(format!("file {} has to be a path relative to the mod", file));
+            } else if !mod_dir.join(path).is_file() {
+                problems.push(format!("file {} does not exist", file));
+            }
+        }
+        match find_data_folder(mod_dir) {
+            Some(data_folder) => for library in find_mod_libraries(&data_folder) {
+                if let Err(s) = SlfLibrary::open(&library) {
+                    problems.push(s.to_string());
+                }
+            },
+            None => problems.push(format!("{} contains no data folder", mod_dir.display()))
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    use super::ModManifest;
+
+    #[test]
+    fn is_valid_mod_id_should_only_allow_folder_names() {
+        assert!(super::is_valid_mod_id("from-russia-with-love"));
+        assert!(super::is_valid_mod_id("mod_2"));
+        assert!(!super::is_valid_mod_id(""));
+        assert!(!super::is_valid_mod_id("My Mod"));
+        assert!(!super::is_valid_mod_id("../mod"));
+        assert!(!super::is_valid_mod_id("con"));
+    }
+
+    #[test]
+    fn is_valid_mod_version_should_only_allow_file_name_characters() {
+        assert!(super::is_valid_mod_version("1.0.2-beta+1"));
+        assert!(!super::is_valid_mod_version(""));
+        assert!(!super::is_valid_mod_version("1.0/2"));
+    }
+
+    #[test]
+    fn validate_should_report_all_problems() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mod_dir = temp_dir.path();
+        File::create(mod_dir.join("mod.json")).unwrap()
+            .write_all(br#"{ "id": "My Mod", "name": "My mod", "version": "", "files": ["readme.txt", "../secret.txt"] }"#).unwrap();
+
+        let manifest = ModManifest::load(mod_dir).unwrap();
+
+        assert_eq!(manifest.validate(mod_dir), vec!(
+            String::from("id 'My Mod' may only contain lowercase letters, digits, '-' and '_'"),
+            String::from("version '' may only contain letters, digits, '.', '-', '_' and '+'"),
+            String::from("file readme.txt does not exist"),
+            String::from("file ../secret.txt has to be a path relative to the mod"),
+            format!("{} contains no data folder", mod_dir.display()),
+        ));
+
+        fs::create_dir(mod_dir.join("Data")).unwrap();
+        File::create(mod_dir.join("readme.txt")).unwrap();
+        let manifest = ModManifest { id: String::from("my-mod"), version: String::from("1.0"), files: vec!(String::from("readme.txt")), ..manifest };
+        assert_eq!(manifest.validate(mod_dir), Vec::<String>::new());
+
+        File::create(mod_dir.join("Data/broken.slf")).unwrap().write_all(b"not a library").unwrap();
+        assert_eq!(manifest.validate(mod_dir).len(), 1);
+    }
+
+    #[test]
+    fn load_should_fail_for_invalid_manifests() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        assert!(ModManifest::load(temp_dir.path()).is_err());
+
+        File::create(temp_dir.path().join("mod.json")).unwrap().write_all(br#"{ "id": "my-mod" }"#).unwrap();
+        assert!(ModManifest::load(temp_dir.path()).is_err());
+    }
+}
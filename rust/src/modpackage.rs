@@ -0,0 +1,221 @@
+//! Packaging of mods for distribution, see `ja2-resources package-mod`.
+//!
+//! The zip contains a single folder named after the mod id with the manifest, the files it references and the `data`
+//! folder. Hidden files and the metadata of file managers are left out. `SHA256SUMS` in the folder lists the hashes
+//! of all other files in the format of `sha256sum`, so downloads can be verified.
+
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use datadir::find_data_folder;
+use fileplan::FilePlan;
+use modmanifest::{MANIFEST_FILE_NAME, ModManifest};
+use pathcheck::check_relative_path;
+use sha256::sha256_hex;
+use temp::ScratchDir;
+use zip::ZipWriter;
+
+pub const CHECKSUMS_FILE_NAME: &str = "SHA256SUMS";
+
+/// Files that file managers create in every folder, compared in lowercase.
+static IGNORED_FILE_NAMES: [&str; 3] = ["thumbs.db", "desktop.ini", "__macosx"];
+
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('.') || IGNORED_FILE_NAMES.contains(&name.to_lowercase().as_str())
+}
+
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<(PathBuf, String)>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if is_ignored(&file_name) {
+            continue;
+        }
+        let name = format!("{}/{}", prefix, file_name);
+        if path.is_dir() {
+            collect_files(&path, &name, files)?;
+        } else {
+            files.push((path, name));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files of a mod as pairs of source path and path in the zip, ordered by the path in the zip.
+///
+/// The `data` folder is always named in lowercase in the zip. Paths that only differ in case are an error, as they
+/// can not be extracted on case-insensitive file systems, as are paths that cannot be created on Windows.
+pub fn files_to_package(mod_dir: &Path, manifest: &ModManifest) -> Result<Vec<(PathBuf, String)>, String> {
+    let data_folder = find_data_folder(mod_dir).ok_or_else(|| format!("{} contains no data folder", mod_dir.display()))?;
+    let mut files = vec!((mod_dir.join(MANIFEST_FILE_NAME), format!("{}/{}", manifest.id, MANIFEST_FILE_NAME)));
+
+    for file in &manifest.files {
+        files.push((mod_dir.join(file), format!("{}/{}", manifest.id, file.replace('\\', "/"))));
+    }
+    collect_files(&data_folder, &format!("{}/data", manifest.id), &mut files)?;
+    files.sort_by(|a, b| a.1.to_lowercase().cmp(&b.1.to_lowercase()).then_with(|| a.1.cmp(&b.1)));
+    files.dedup_by(|a, b| a.1 == b.1);
+
+    if let Some(pair) = files.windows(2).find(|p| p[0].1.to_lowercase() == p[1].1.to_lowercase()) {
+        return Err(format!("{} and {} only differ in case", pair[0].0.display(), pair[1].0.display()));
+    }
+    for (_, name) in &files {
+        check_relative_path(name)?;
+    }
+    Ok(files)
+}
+
+fn write_package<W: Write>(writer: W, path: &Path, manifest: &ModManifest, files: &[(PathBuf, String)]) -> Result<W, String> {
+    let write_error = |s: ::std::io::Error| format!("Error writing {}: {}", path.display(), s.kind());
+    let mut zip = ZipWriter::new(writer);
+    let mut checksums = String::new();
+
+    for (source, name) in files {
+        let contents = fs::read(source).map_err(|s| format!("Error reading {}: {}", source.display(), s.kind()))?;
+        checksums.push_str(&format!("{}  {}\n", sha256_hex(&contents), &name[manifest.id.len() + 1..]));
+        zip.add_file(name, &contents).map_err(&write_error)?;
+    }
+    zip.add_file(&format!("{}/{}", manifest.id, CHECKSUMS_FILE_NAME), checksums.as_bytes()).map_err(&write_error)?;
+    zip.finish().and_then(|mut w| w.flush().map(|_| w)).map_err(&write_error)
+}
+
+/// Counts the bytes of a package in a dry run.
+#[derive(Default)]
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Validates a mod and packages it as `<id>-<version>.zip` in the output dir. Returns the plan with the zip. With
+/// `dry_run` the zip is not written, the plan shows its path and size.
+///
+/// The zip is written in a scratch dir in the cache dir, so the output dir never contains an incomplete package.
+pub fn package_mod(mod_dir: &Path, output_dir: &Path, cache_dir: &Path, dry_run: bool) -> Result<FilePlan, String> {
+    let manifest = ModManifest::load(mod_dir)?;
+    let problems = manifest.validate(mod_dir);
+    if !problems.is_empty() {
+        return Err(format!("{} is not a valid mod:\n{}", mod_dir.display(), problems.join("\n")));
+    }
+    let files = files_to_package(mod_dir, &manifest)?;
+    let file_name = format!("{}-{}.zip", manifest.id, manifest.version);
+    let path = output_dir.join(&file_name);
+    let mut plan = FilePlan::default();
+
+    if dry_run {
+        let size = write_package(ByteCounter::default(), &path, &manifest, &files)?.0;
+        plan.add(&path, size);
+        return Ok(plan);
+    }
+    let scratch = ScratchDir::new(cache_dir, "package-mod")?;
+    let scratch_path = scratch.join(&file_name);
+    let file = File::create(&scratch_path).map_err(|s| format!("Error writing {}: {}", scratch_path.display(), s.kind()))?;
+    write_package(BufWriter::new(file), &scratch_path, &manifest, &files)?;
+    let size = fs::metadata(&scratch_path).map(|m| m.len()).unwrap_or(0);
+    plan.add(&path, size);
+    scratch.persist(&file_name, &path)?;
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use modmanifest::ModManifest;
+    use sha256::sha256_hex;
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    fn create_mod(mod_dir: &Path) {
+        write_file(&mod_dir.join("mod.json"), br#"{ "id": "my-mod", "name": "My mod", "version": "1.0", "files": ["readme.txt"] }"#);
+        write_file(&mod_dir.join("readme.txt"), b"readme");
+        write_file(&mod_dir.join("notes.txt"), b"not packaged");
+        write_file(&mod_dir.join("Data/BinaryData/Credits.edt"), b"credits");
+        write_file(&mod_dir.join("Data/.DS_Store"), b"");
+        write_file(&mod_dir.join("Data/Thumbs.db"), b"");
+    }
+
+    #[test]
+    fn files_to_package_should_normalize_the_layout() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mod_dir = temp_dir.path().join("My Mod");
+        create_mod(&mod_dir);
+        let manifest = ModManifest::load(&mod_dir).unwrap();
+
+        let names: Vec<String> = super::files_to_package(&mod_dir, &manifest).unwrap().into_iter().map(|f| f.1).collect();
+
+        assert_eq!(names, vec!("my-mod/data/BinaryData/Credits.edt", "my-mod/mod.json", "my-mod/readme.txt"));
+
+        // Paths that only differ in case can only be created on case-sensitive file systems
+        write_file(&mod_dir.join("Data/binarydata/credits.edt"), b"credits");
+        if !mod_dir.join("Data/BinaryData/credits.edt").exists() {
+            assert!(super::files_to_package(&mod_dir, &manifest).is_err());
+        }
+    }
+
+    #[test]
+    fn files_to_package_should_reject_paths_that_cannot_be_installed_on_windows() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mod_dir = temp_dir.path().join("my-mod");
+        create_mod(&mod_dir);
+        write_file(&mod_dir.join("Data/Sounds/aux.wav"), b"");
+        let manifest = ModManifest::load(&mod_dir).unwrap();
+
+        let result = super::files_to_package(&mod_dir, &manifest);
+
+        assert_eq!(result.err(), Some(String::from("my-mod/data/Sounds/aux.wav cannot be installed on Windows: aux.wav is a reserved name on Windows, rename it")));
+    }
+
+    #[test]
+    fn package_mod_should_write_a_zip_with_checksums() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mod_dir = temp_dir.path().join("my-mod");
+        create_mod(&mod_dir);
+
+        let planned = super::package_mod(&mod_dir, temp_dir.path(), &temp_dir.path().join("tmp"), true).unwrap();
+        assert!(!temp_dir.path().join("my-mod-1.0.zip").exists());
+        let plan = super::package_mod(&mod_dir, temp_dir.path(), &temp_dir.path().join("tmp"), false).unwrap();
+
+        assert_eq!(plan, planned);
+        let path = &plan.files[0].path;
+        assert_eq!(path, &temp_dir.path().join("my-mod-1.0.zip"));
+        let zip = fs::read(path).unwrap();
+        assert_eq!(zip.len() as u64, plan.files[0].size);
+        let checksums = format!("{}  data/BinaryData/Credits.edt\n", sha256_hex(b"credits"));
+        assert!(zip.windows(checksums.len()).any(|w| w == checksums.as_bytes()));
+        assert!(!zip.windows(12).any(|w| w == b"not packaged"));
+        assert_eq!(fs::read_dir(temp_dir.path().join("tmp")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn package_mod_should_fail_for_invalid_mods() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mod_dir = temp_dir.path().join("my-mod");
+        create_mod(&mod_dir);
+        fs::remove_file(mod_dir.join("readme.txt")).unwrap();
+
+        let error = super::package_mod(&mod_dir, temp_dir.path(), &temp_dir.path().join("tmp"), false).unwrap_err();
+
+        assert!(error.ends_with("file readme.txt does not exist"), "{}", error);
+        assert!(!temp_dir.path().join("my-mod-1.0.zip").exists());
+    }
+}
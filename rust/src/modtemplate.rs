@@ -0,0 +1,92 @@
+//! Skeleton of a new mod, see `ja2-resources new-mod`.
+//!
+//! The skeleton has a valid manifest, a readme to fill in and an example override in the `data` folder that does not
+//! change the game until it is renamed.
+
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use modmanifest::{MANIFEST_FILE_NAME, ModManifest, is_valid_mod_id};
+
+static README_TEMPLATE: &str = "{name}
+==========
+
+Describe what the mod changes here.
+
+Installation
+------------
+
+Copy the folder {id} into the mods folder of the stracciatella home and start the game with `-mod {id}`.
+
+Development
+-----------
+
+The files in the data folder replace the files of the game with the same path, e.g. data/maps/a9.dat replaces the
+map of sector A9. data/game.json.example is a copy of the default game settings, rename it to game.json to change
+them. Run `ja2-resources package-mod {id}` to create a zip for distribution.
+";
+
+static EXAMPLE_OVERRIDE: &str = include_str!("../../assets/externalized/game.json");
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))
+}
+
+/// Creates the skeleton of a mod in `<parent_dir>/<id>` and returns its path.
+pub fn create_mod(parent_dir: &Path, id: &str) -> Result<PathBuf, String> {
+    if !is_valid_mod_id(id) {
+        return Err(format!("Mod id '{}' may only contain lowercase letters, digits, '-' and '_'", id));
+    }
+    let mod_dir = parent_dir.join(id);
+    if mod_dir.exists() {
+        return Err(format!("{} already exists", mod_dir.display()));
+    }
+    let data_dir = mod_dir.join("data");
+    fs::create_dir_all(&data_dir).map_err(|s| format!("Error creating {}: {}", data_dir.display(), s.kind()))?;
+
+    let manifest = ModManifest {
+        id: String::from(id),
+        name: String::from(id),
+        version: String::from("0.1.0"),
+        description: String::from(""),
+        files: vec!(String::from("readme.txt")),
+    };
+    let manifest_json = serde_json::to_string_pretty(&manifest).expect("Manifest should be serializable");
+    write_file(&mod_dir.join(MANIFEST_FILE_NAME), &format!("{}\n", manifest_json))?;
+    write_file(&mod_dir.join("readme.txt"), &README_TEMPLATE.replace("{name}", &manifest.name).replace("{id}", id))?;
+    write_file(&data_dir.join("game.json.example"), EXAMPLE_OVERRIDE)?;
+    Ok(mod_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use modmanifest::ModManifest;
+
+    #[test]
+    fn create_mod_should_create_a_valid_mod() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        let mod_dir = super::create_mod(temp_dir.path(), "my-mod").unwrap();
+
+        assert_eq!(mod_dir, temp_dir.path().join("my-mod"));
+        let manifest = ModManifest::load(&mod_dir).unwrap();
+        assert_eq!(manifest.id, "my-mod");
+        assert_eq!(manifest.validate(&mod_dir), Vec::<String>::new());
+        assert!(mod_dir.join("data/game.json.example").is_file());
+        assert!(super::create_mod(temp_dir.path(), "my-mod").is_err());
+    }
+
+    #[test]
+    fn create_mod_should_fail_for_invalid_ids() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        assert!(super::create_mod(temp_dir.path(), "My Mod").is_err());
+        assert!(!temp_dir.path().join("My Mod").exists());
+    }
+}
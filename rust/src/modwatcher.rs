@@ -0,0 +1,123 @@
+//! Detection of mods that are installed or removed while the launcher is open.
+//!
+//! The mods directories are compared with a snapshot whenever `mods_changed` is polled, e.g. from a timer of the
+//! launcher. This works the same on all platforms and the callbacks of `MODS_CHANGED` are called on the thread that
+//! polls, so a launcher can update its widgets in them.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use libc::c_char;
+
+use EngineOptions;
+use events::{EngineEvent, emit_event};
+use home::mods_dir;
+
+/// Mods dirs, mods and their data dirs with the time of their last change, None if they do not exist.
+pub type ModsSnapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Watcher that was started by `watch_mods`.
+static MODS_WATCHER: Mutex<Option<ModsWatcher>> = Mutex::new(None);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub fn snapshot_mods_dirs(mods_dirs: &[PathBuf]) -> ModsSnapshot {
+    let mut snapshot = BTreeMap::new();
+    for dir in mods_dirs {
+        snapshot.insert(dir.clone(), modified(dir));
+        if let Ok(entries) = fs::read_dir(dir) {
+            for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+                let data_dir = path.join("data");
+                snapshot.insert(data_dir.clone(), modified(&data_dir));
+                snapshot.insert(path.clone(), modified(&path));
+            }
+        }
+    }
+    snapshot
+}
+
+/// Returns the dirs that contain mods, see `vfs::find_mod_dir`.
+pub fn mods_dirs(extra_data_dir: &Path, stracciatella_home: &Path) -> Vec<PathBuf> {
+    vec!(mods_dir(stracciatella_home), extra_data_dir.join("mods"))
+}
+
+pub struct ModsWatcher {
+    mods_dirs: Vec<PathBuf>,
+    snapshot: ModsSnapshot,
+}
+
+impl ModsWatcher {
+    pub fn new(mods_dirs: Vec<PathBuf>) -> ModsWatcher {
+        let snapshot = snapshot_mods_dirs(&mods_dirs);
+        ModsWatcher { mods_dirs, snapshot }
+    }
+
+    /// Returns true if mods were installed, removed or changed since the watcher was created or last polled.
+    pub fn poll(&mut self) -> bool {
+        let snapshot = snapshot_mods_dirs(&self.mods_dirs);
+        let changed = snapshot != self.snapshot;
+        self.snapshot = snapshot;
+        changed
+    }
+}
+
+/// Starts to watch the mods dirs of the home and the extra data dir, a previous watcher is replaced.
+#[no_mangle]
+pub extern fn watch_mods(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> () {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let dirs = mods_dirs(Path::new(&extra_data_dir), &unsafe_from_ptr!(ptr).stracciatella_home);
+    *MODS_WATCHER.lock().unwrap_or_else(|e| e.into_inner()) = Some(ModsWatcher::new(dirs));
+}
+
+#[no_mangle]
+pub extern fn unwatch_mods() -> () {
+    MODS_WATCHER.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// Checks the watched mods dirs and emits `MODS_CHANGED` if they changed. False if no mods dirs are watched.
+#[no_mangle]
+pub extern fn mods_changed() -> bool {
+    let changed = match *MODS_WATCHER.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref mut watcher) => watcher.poll(),
+        None => false
+    };
+    // The lock is released before the callbacks are called, they may start or stop watching
+    if changed {
+        emit_event(EngineEvent::MODS_CHANGED);
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::{ModsWatcher, mods_dirs};
+
+    #[test]
+    fn poll_should_detect_installed_and_removed_mods() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let home = temp_dir.path().join("home");
+        let extra_data_dir = temp_dir.path().join("extra");
+        fs::create_dir_all(extra_data_dir.join("mods/from-the-start/data")).unwrap();
+        let mut watcher = ModsWatcher::new(mods_dirs(&extra_data_dir, &home));
+
+        assert!(!watcher.poll());
+
+        fs::create_dir_all(home.join("mods/new-mod/data")).unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_dir_all(extra_data_dir.join("mods/from-the-start")).unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+}
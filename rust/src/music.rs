@@ -0,0 +1,314 @@
+//! Music replacement packs.
+//!
+//! A music pack is a folder in `<stracciatella_home>/music-packs` containing ogg files and a `pack.json`
+//! manifest that maps the music modes of the engine (see `music.json` in the externalized data) to tracks.
+//!
+//! The selected pack is mounted in the virtual file system at `music-pack`, the engine resolves the tracks there and
+//! decodes them with `decode_ogg_vorbis`.
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::fs::File;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::ptr;
+use std::slice;
+
+use lewton::inside_ogg::OggStreamReader;
+use libc::{c_char, size_t};
+
+use {EngineOptions, set_last_error};
+
+/// Folder of the virtual file system the selected music pack is mounted at
+pub const MUSIC_PACK_MOUNT: &str = "music-pack";
+
+pub static MUSIC_MODES: [&'static str; 10] = [
+    "main_menu",
+    "laptop",
+    "tactical",
+    "tactical_enemypresent",
+    "tactical_battle",
+    "tactical_creature",
+    "tactical_creature_enemypresent",
+    "tactical_creature_battle",
+    "tactical_victory",
+    "tactical_defeat",
+];
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct MusicPackManifest {
+    name: String,
+    tracks: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MusicPack {
+    pub id: String,
+    pub name: String,
+    pub dir: PathBuf,
+    tracks: BTreeMap<String, Vec<String>>,
+}
+
+impl MusicPack {
+    pub fn load(dir: &Path) -> Result<MusicPack, String> {
+        let id = match dir.file_name() {
+            Some(n) => n.to_string_lossy().into_owned(),
+            None => return Err(format!("Invalid music pack directory {:?}", dir))
+        };
+        let manifest_path = dir.join("pack.json");
+        let manifest: MusicPackManifest = File::open(&manifest_path)
+            .map_err(|s| format!("Error reading pack.json of music pack {}: {}", id, s.kind()))
+            .and_then(|f| serde_json::from_reader(f).map_err(|s| format!("Error parsing pack.json of music pack {}: {}", id, s)))?;
+
+        for (mode, files) in &manifest.tracks {
+            if !MUSIC_MODES.contains(&mode.as_str()) {
+                return Err(format!("Music pack {} contains unknown music mode {}", id, mode));
+            }
+            for file in files {
+                validate_track(dir, file).map_err(|s| format!("Music pack {}: {}", id, s))?;
+            }
+        }
+
+        Ok(MusicPack { id, name: manifest.name, dir: PathBuf::from(dir), tracks: manifest.tracks })
+    }
+
+    /// Returns the paths of the tracks for a music mode in the virtual file system, see `MUSIC_PACK_MOUNT`.
+    ///
+    /// An empty list means that the pack does not replace the music for this mode.
+    pub fn tracks(&self, mode: &str) -> Vec<PathBuf> {
+        match self.tracks.get(mode) {
+            Some(files) => files.iter().map(|f| Path::new(MUSIC_PACK_MOUNT).join(f)).collect(),
+            None => vec!()
+        }
+    }
+}
+
+/// Samples of a decoded track, interleaved by channel.
+#[derive(Debug, PartialEq)]
+pub struct DecodedAudio {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+/// Decodes an ogg vorbis file completely.
+pub fn decode_ogg(data: &[u8]) -> Result<DecodedAudio, String> {
+    let mut reader = OggStreamReader::new(Cursor::new(data)).map_err(|s| format!("Error decoding ogg file: {}", s))?;
+    let mut samples = vec!();
+    while let Some(packet) = reader.read_dec_packet_itl().map_err(|s| format!("Error decoding ogg file: {}", s))? {
+        samples.extend(packet);
+    }
+    Ok(DecodedAudio { channels: u16::from(reader.ident_hdr.audio_channels), sample_rate: reader.ident_hdr.audio_sample_rate, samples })
+}
+
+fn validate_track(dir: &Path, file: &str) -> Result<(), String> {
+    let path = Path::new(file);
+    if path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(format!("track {} has to be a path relative to the music pack", file));
+    }
+    if !file.to_lowercase().ends_with(".ogg") {
+        return Err(format!("track {} is not an ogg file", file));
+    }
+    if !dir.join(path).is_file() {
+        return Err(format!("track {} does not exist", file));
+    }
+    Ok(())
+}
+
+pub fn music_packs_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("music-packs")
+}
+
+/// Lists the valid music packs in the stracciatella home, ordered by id.
+pub fn find_music_packs(stracciatella_home: &Path) -> Vec<MusicPack> {
+    let mut dirs: Vec<PathBuf> = match fs::read_dir(music_packs_dir(stracciatella_home)) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+        Err(_) => return vec!()
+    };
+    dirs.sort();
+    dirs.iter().filter_map(|d| MusicPack::load(d).ok()).collect()
+}
+
+/// Loads the music pack selected in the engine options, if any.
+pub fn selected_music_pack(engine_options: &EngineOptions) -> Result<Option<MusicPack>, String> {
+    if engine_options.music_pack.is_empty() {
+        return Ok(None);
+    }
+    let dir = music_packs_dir(&engine_options.stracciatella_home).join(&engine_options.music_pack);
+    MusicPack::load(&dir).map(Some)
+}
+
+fn selected_music_pack_tracks(ptr: *const EngineOptions, mode_ptr: *const c_char) -> Vec<PathBuf> {
+    let mode = unsafe { CStr::from_ptr(mode_ptr) }.to_string_lossy();
+    match selected_music_pack(unsafe_from_ptr!(ptr)) {
+        Ok(Some(pack)) => pack.tracks(&mode),
+        _ => vec!()
+    }
+}
+
+#[no_mangle]
+pub extern fn get_music_pack(ptr: *const EngineOptions) -> *mut c_char {
+    let c_str_pack = CString::new(unsafe_from_ptr!(ptr).music_pack.clone()).unwrap();
+    c_str_pack.into_raw()
+}
+
+#[no_mangle]
+pub extern fn set_music_pack(ptr: *mut EngineOptions, pack_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(pack_ptr) };
+    unsafe_from_ptr_mut!(ptr).music_pack = c_str.to_string_lossy().into_owned();
+}
+
+#[no_mangle]
+pub extern fn get_number_of_music_packs(ptr: *const EngineOptions) -> u32 {
+    find_music_packs(&unsafe_from_ptr!(ptr).stracciatella_home).len() as u32
+}
+
+#[no_mangle]
+pub extern fn get_music_pack_id(ptr: *const EngineOptions, index: u32) -> *mut c_char {
+    let packs = find_music_packs(&unsafe_from_ptr!(ptr).stracciatella_home);
+    let pack = match packs.get(index as usize) {
+        Some(p) => p,
+        None => panic!("Invalid music pack index {}", index)
+    };
+    CString::new(pack.id.clone()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn get_number_of_music_pack_tracks(ptr: *const EngineOptions, mode_ptr: *const c_char) -> u32 {
+    selected_music_pack_tracks(ptr, mode_ptr).len() as u32
+}
+
+#[no_mangle]
+pub extern fn get_music_pack_track(ptr: *const EngineOptions, mode_ptr: *const c_char, index: u32) -> *mut c_char {
+    let tracks = selected_music_pack_tracks(ptr, mode_ptr);
+    let track = match tracks.get(index as usize) {
+        Some(t) => t,
+        None => panic!("Invalid music pack track index {}", index)
+    };
+    CString::new(track.to_string_lossy().into_owned()).unwrap().into_raw()
+}
+
+/// Decodes an ogg vorbis file into 16 bit samples. Returns null on error, the samples have to be freed with
+/// `free_decoded_audio`.
+#[no_mangle]
+pub extern fn decode_ogg_vorbis(data_ptr: *const u8, len: size_t, channels_ptr: *mut u16, sample_rate_ptr: *mut u32, samples_len_ptr: *mut size_t) -> *mut i16 {
+    let data = unsafe { slice::from_raw_parts(data_ptr, len) };
+    match decode_ogg(data) {
+        Ok(decoded) => {
+            *unsafe_from_ptr_mut!(channels_ptr) = decoded.channels;
+            *unsafe_from_ptr_mut!(sample_rate_ptr) = decoded.sample_rate;
+            *unsafe_from_ptr_mut!(samples_len_ptr) = decoded.samples.len();
+            Box::into_raw(decoded.samples.into_boxed_slice()) as *mut i16
+        },
+        Err(s) => {
+            set_last_error(Some(s));
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern fn free_decoded_audio(samples_ptr: *mut i16, samples_len: size_t) -> () {
+    if samples_ptr.is_null() { return }
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(samples_ptr, samples_len)) });
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use EngineOptions;
+
+    fn write_music_pack(home: &Path, id: &str, manifest: &[u8], tracks: &[&str]) {
+        let dir = super::music_packs_dir(home).join(id);
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("pack.json")).unwrap().write_all(manifest).unwrap();
+        for track in tracks {
+            File::create(dir.join(track)).unwrap();
+        }
+    }
+
+    #[test]
+    fn find_music_packs_should_return_valid_packs_in_order() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write_music_pack(temp_dir.path(), "remaster", b"{ \"name\": \"Remaster\", \"tracks\": { \"main_menu\": [ \"menu.ogg\" ] } }", &["menu.ogg"]);
+        write_music_pack(temp_dir.path(), "broken", b"{ \"name\": \"Broken\" }", &[]);
+        write_music_pack(temp_dir.path(), "acoustic", b"{ \"name\": \"Acoustic\", \"tracks\": {} }", &[]);
+
+        let packs = super::find_music_packs(temp_dir.path());
+
+        assert_eq!(packs.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(), vec!("acoustic", "remaster"));
+        assert_eq!(packs[1].name, "Remaster");
+    }
+
+    #[test]
+    fn find_music_packs_should_return_nothing_without_music_packs_dir() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        assert_eq!(super::find_music_packs(temp_dir.path()), vec!());
+    }
+
+    #[test]
+    fn load_should_fail_with_unknown_mode() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write_music_pack(temp_dir.path(), "pack", b"{ \"name\": \"Pack\", \"tracks\": { \"credits\": [ \"a.ogg\" ] } }", &["a.ogg"]);
+
+        let result = super::MusicPack::load(&super::music_packs_dir(temp_dir.path()).join("pack"));
+
+        assert_eq!(result, Err(String::from("Music pack pack contains unknown music mode credits")));
+    }
+
+    #[test]
+    fn load_should_fail_with_invalid_tracks() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = super::music_packs_dir(temp_dir.path()).join("pack");
+
+        write_music_pack(temp_dir.path(), "pack", b"{ \"name\": \"Pack\", \"tracks\": { \"laptop\": [ \"missing.ogg\" ] } }", &[]);
+        assert_eq!(super::MusicPack::load(&dir), Err(String::from("Music pack pack: track missing.ogg does not exist")));
+
+        write_music_pack(temp_dir.path(), "pack", b"{ \"name\": \"Pack\", \"tracks\": { \"laptop\": [ \"laptop.wav\" ] } }", &["laptop.wav"]);
+        assert_eq!(super::MusicPack::load(&dir), Err(String::from("Music pack pack: track laptop.wav is not an ogg file")));
+
+        write_music_pack(temp_dir.path(), "pack", b"{ \"name\": \"Pack\", \"tracks\": { \"laptop\": [ \"../other/laptop.ogg\" ] } }", &[]);
+        assert_eq!(super::MusicPack::load(&dir), Err(String::from("Music pack pack: track ../other/laptop.ogg has to be a path relative to the music pack")));
+    }
+
+    #[test]
+    fn get_music_pack_track_should_return_tracks_of_selected_pack() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        let tactical = CString::new("tactical").unwrap();
+        let laptop = CString::new("laptop").unwrap();
+
+        write_music_pack(temp_dir.path(), "remaster", b"{ \"name\": \"Remaster\", \"tracks\": { \"tactical\": [ \"a.ogg\", \"b.ogg\" ] } }", &["a.ogg", "b.ogg"]);
+        engine_options.stracciatella_home = temp_dir.path().to_path_buf();
+
+        assert_eq!(super::get_number_of_music_pack_tracks(&engine_options, tactical.as_ptr()), 0);
+
+        super::set_music_pack(&mut engine_options, CString::new("remaster").unwrap().as_ptr());
+
+        assert_eq!(super::get_number_of_music_pack_tracks(&engine_options, tactical.as_ptr()), 2);
+        assert_eq!(super::get_number_of_music_pack_tracks(&engine_options, laptop.as_ptr()), 0);
+        unsafe {
+            assert_eq!(CString::from_raw(super::get_music_pack_track(&engine_options, tactical.as_ptr(), 1)), CString::new(format!("music-pack{}b.ogg", ::std::path::MAIN_SEPARATOR)).unwrap());
+        }
+    }
+
+    #[test]
+    fn decode_ogg_should_fail_with_other_files() {
+        assert!(super::decode_ogg(b"RIFF\0\0\0\0WAVE").unwrap_err().starts_with("Error decoding ogg file"));
+
+        let (mut channels, mut sample_rate, mut samples_len) = (0, 0, 0);
+        let data = b"OggS";
+        let samples = super::decode_ogg_vorbis(data.as_ptr(), data.len(), &mut channels, &mut sample_rate, &mut samples_len);
+        assert!(samples.is_null());
+        assert_eq!(samples_len, 0);
+    }
+}
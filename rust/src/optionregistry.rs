@@ -0,0 +1,333 @@
+//! Registry of the engine options with their key in ja2.json, command line flag, type and description.
+//!
+//! The command line flags, the comments of `-dump-defaults` and the settings of the launcher are generated from the
+//! registry. Defaults come from `EngineOptions::default()`, and the tests check that the registry matches the options
+//! in ja2.json, so a new option fails the tests until it is registered.
+
+use std::ffi::CString;
+
+use getopts::Options;
+use libc::c_char;
+use serde_json::Value;
+
+use EngineOptions;
+use DATA_DIR_OPTION_EXAMPLE;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[allow(non_camel_case_types)]
+pub enum OptionType {
+    BOOL,
+    INTEGER,
+    /// Floating point number
+    NUMBER,
+    STRING,
+    PATH,
+    /// One of the `values` of the option
+    ENUM,
+    STRING_LIST,
+    PATH_LIST,
+    /// Screen resolution, see `Resolution`
+    RESOLUTION,
+    /// Group of nested options
+    OBJECT,
+    /// Object with arbitrary keys
+    MAP,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CliArg {
+    NONE,
+    /// Takes a value, the example is shown in the help
+    VALUE(&'static str),
+    /// Takes a value and may be given multiple times
+    MULTIPLE(&'static str),
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CliFlag {
+    pub name: &'static str,
+    pub arg: CliArg,
+    /// Help of the flag if it differs from the description of the option
+    pub help: Option<&'static str>,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct OptionInfo {
+    /// Key in ja2.json with dots for nested options, None for options that are only set on the command line
+    pub json_key: Option<&'static str>,
+    pub option_type: OptionType,
+    /// Allowed values of `ENUM` options
+    pub values: &'static [&'static str],
+    pub description: &'static str,
+    pub cli_flag: Option<CliFlag>,
+    /// Paths and options that only make sense on the computer of the player, they are not shared in settings snippets
+    pub personal: bool,
+}
+
+const fn json(key: &'static str, option_type: OptionType, description: &'static str) -> OptionInfo {
+    OptionInfo { json_key: Some(key), option_type, values: &[], description, cli_flag: None, personal: false }
+}
+
+const fn cli(name: &'static str, arg: CliArg, description: &'static str) -> OptionInfo {
+    let option_type = match arg {
+        CliArg::NONE => OptionType::BOOL,
+        _ => OptionType::STRING,
+    };
+    OptionInfo { json_key: None, option_type, values: &[], description, cli_flag: Some(CliFlag { name, arg, help: None }), personal: false }
+}
+
+impl OptionInfo {
+    const fn values(self, values: &'static [&'static str]) -> OptionInfo {
+        OptionInfo { values, ..self }
+    }
+
+    const fn flag(self, name: &'static str, arg: CliArg, help: Option<&'static str>) -> OptionInfo {
+        OptionInfo { cli_flag: Some(CliFlag { name, arg, help }), ..self }
+    }
+
+    const fn personal(self) -> OptionInfo {
+        OptionInfo { personal: true, ..self }
+    }
+
+    /// Returns the help of the command line flag.
+    pub fn help(&self) -> &'static str {
+        self.cli_flag.and_then(|f| f.help).unwrap_or(self.description)
+    }
+}
+
+pub static OPTIONS: &[OptionInfo] = &[
+    json("data_dir", OptionType::PATH, "Folder of the original JA2 installation")
+        .flag("datadir", CliArg::MULTIPLE(DATA_DIR_OPTION_EXAMPLE), Some("Set path for data directory"))
+        .personal(),
+    json("canonicalize_data_dir", OptionType::BOOL, "Resolve symlinks in a data dir from the command line, disable to keep the path of a symlink"),
+    json("game_dir", OptionType::PATH, "Folder with the externalized data of stracciatella, empty for the folder of the installation")
+        .flag("gamedir", CliArg::VALUE("DIR"), Some("Folder with the externalized data of stracciatella, e.g. a checkout of the source. Default is the folder of the installation"))
+        .personal(),
+    json("mods", OptionType::STRING_LIST, "Mods to start the game with, later mods take precedence")
+        .flag("mod", CliArg::MULTIPLE("MOD_NAME"), Some("Start one of the game modifications. MOD_NAME is the name of modification, e.g. 'from-russia-with-love. See mods folder for possible options'.")),
+    json("extra_data_dirs", OptionType::PATH_LIST, "Folders with loose files that override the vanilla data, e.g. an HD asset pack")
+        .flag("extradatadir", CliArg::MULTIPLE("DIR"), Some("Folder with loose files that override the vanilla data, e.g. an HD asset pack. Can be given multiple times, later folders take precedence"))
+        .personal(),
+    json("mods_below_extra_data_dirs", OptionType::STRING_LIST, "Mods that are mounted below the extra data dirs instead of above them"),
+    json("save_game_dir", OptionType::PATH, "Folder for the saved games, e.g. a synchronized cloud folder, relative to the home. Empty for SavedGames in the home")
+        .flag("savegamedir", CliArg::VALUE("DIR"), Some("Folder for the saved games instead of SavedGames in the home. The saved games of mods are kept in subfolders"))
+        .personal(),
+    json("res", OptionType::RESOLUTION, "Screen resolution as WIDTHxHEIGHT[@REFRESHRATE] or {\"width\": ..., \"height\": ...}")
+        .flag("res", CliArg::VALUE("WIDTHxHEIGHT[@REFRESHRATE]"), Some("Screen resolution, e.g. 800x600. Default value is 640x480. A refresh rate for fullscreen can be added, e.g. 1920x1080@120")),
+    json("resversion", OptionType::ENUM, "Version of the game resources: DUTCH, ENGLISH, FRENCH, GERMAN, ITALIAN, POLISH, RUSSIAN or RUSSIAN_GOLD")
+        .values(&["DUTCH", "ENGLISH", "FRENCH", "GERMAN", "ITALIAN", "POLISH", "RUSSIAN", "RUSSIAN_GOLD"])
+        .flag("resversion", CliArg::VALUE("RUSSIAN_GOLD"), Some("Version of the game resources. Possible values: DUTCH, ENGLISH, FRENCH, GERMAN, ITALIAN, POLISH, RUSSIAN, RUSSIAN_GOLD. Default value is ENGLISH. RUSSIAN is for BUKA Agonia Vlasty release. RUSSIAN_GOLD is for Gold release")),
+    json("game_text_language", OptionType::ENUM, "Language of the texts of the interface, e.g. ENGLISH with GERMAN resources. null for the language of resversion")
+        .values(&["DUTCH", "ENGLISH", "FRENCH", "GERMAN", "ITALIAN", "POLISH", "RUSSIAN", "RUSSIAN_GOLD"])
+        .flag("textlanguage", CliArg::VALUE("ENGLISH"), Some("Language of the texts of the interface if it differs from the resource version. Possible values are the ones of resversion")),
+    json("game_language", OptionType::STRING, "Language of the translation overrides, e.g. pt_BR, pseudo for the generated pseudo-locale, empty for none")
+        .flag("language", CliArg::VALUE("LANGUAGE"), Some("Language of the translation overrides in the home and the mods, e.g. pt_BR for translations/pt_BR.po. pseudo shows accented and lengthened vanilla texts for testing")),
+    json("launcher_language", OptionType::STRING, "Language of the launcher and the error messages, e.g. de, empty for the language of the system"),
+    json("fullscreen", OptionType::BOOL, "Start the game in the fullscreen mode")
+        .flag("fullscreen", CliArg::NONE, None),
+    json("window_always_on_top", OptionType::BOOL, "Keep the game window above other windows")
+        .flag("alwaysontop", CliArg::NONE, Some("Keep the game window above all other windows")),
+    json("grab_mouse", OptionType::BOOL, "Keep the mouse inside the game window")
+        .flag("grabmouse", CliArg::NONE, Some("Keep the mouse cursor inside the game window")),
+    json("pause_on_focus_loss", OptionType::BOOL, "Pause the game while another window has the focus"),
+    json("single_instance", OptionType::BOOL, "Exit if another game with the same home is running"),
+    json("scaling", OptionType::ENUM, "Scaling of the screen: LINEAR, NEAR_PERFECT or PERFECT")
+        .values(&["LINEAR", "NEAR_PERFECT", "PERFECT"])
+        .flag("scaling", CliArg::VALUE("PERFECT"), Some("Scaling of the screen. Possible values: LINEAR, NEAR_PERFECT, PERFECT. Default value is PERFECT. PERFECT scales by whole numbers for sharp pixels, LINEAR fills the window with smoothed pixels")),
+    json("brightness", OptionType::NUMBER, "Factor for the brightness of the screen between 0.5 and 2.0, 1.0 is the vanilla palette")
+        .flag("brightness", CliArg::VALUE("FACTOR"), Some("Brightness of the screen between 0.5 and 2.0, e.g. 1.2 for a brighter palette. Default value is 1.0")),
+    json("max_fps", OptionType::INTEGER, "Highest number of frames per second between 15 and 240, 0 for no limit")
+        .flag("maxfps", CliArg::VALUE("FPS"), Some("Limit the frame rate, e.g. 60. Possible values are 0 for no limit or 15 to 240. Default value is 0")),
+    json("display_index", OptionType::INTEGER, "Index of the display that shows the game window, 0 for the primary display")
+        .flag("display", CliArg::VALUE("INDEX"), Some("Show the game window on another display, e.g. 1 for the second display. Default value is 0"))
+        .personal(),
+    json("window_x", OptionType::INTEGER, "Horizontal position of the game window on the display, null to center the window")
+        .flag("windowpos", CliArg::VALUE("X,Y"), Some("Place the game window at a position on the display, e.g. 100,50"))
+        .personal(),
+    json("window_y", OptionType::INTEGER, "Vertical position of the game window on the display, null to center the window").personal(),
+    json("debug", OptionType::BOOL, "Enable the debug output")
+        .flag("debug", CliArg::NONE, Some("Enable Debug Mode")),
+    json("enable_cheats", OptionType::BOOL, "Enable the cheat keys")
+        .flag("cheats", CliArg::NONE, Some("Enable cheats, only has an effect in debug mode")),
+    json("nosound", OptionType::BOOL, "Start the game without sound")
+        .flag("nosound", CliArg::NONE, Some("Turn the sound and music off")),
+    json("audio_device", OptionType::STRING, "Name or index of the audio output device, empty for the default device")
+        .flag("audiodevice", CliArg::VALUE("NAME_OR_INDEX"), Some("Audio output device, either by name or by index. Default is the default device of the OS"))
+        .personal(),
+    json("audio_sample_rate", OptionType::INTEGER, "Sample rate of the audio output in Hz, between 8000 and 192000")
+        .flag("samplerate", CliArg::VALUE("HZ"), Some("Audio sample rate in Hz, e.g. 48000. Default value is 44100")),
+    json("audio_driver", OptionType::STRING, "SDL audio driver, e.g. pulseaudio, empty for the default driver")
+        .flag("audiodriver", CliArg::VALUE("DRIVER"), Some("Audio driver to use instead of the default one, e.g. pulseaudio, alsa, wasapi or directsound"))
+        .personal(),
+    json("music_pack", OptionType::STRING, "Id of the music pack in the music-packs folder of the home, empty for the vanilla music"),
+    json("ui_theme", OptionType::STRING, "Id of the theme for the interface, empty for the default interface"),
+    json("gameplay", OptionType::OBJECT, "Gameplay options"),
+    json("gameplay.subtitles", OptionType::BOOL, "Show subtitles for speech"),
+    json("gameplay.speech_subtitle_duration_ms", OptionType::INTEGER, "Time in milliseconds a subtitle is shown per character when there is no speech"),
+    json("gameplay.show_tutorials", OptionType::BOOL, "Show the popups that explain the laptop and other screens the first time they are opened"),
+    json("gameplay.show_hints", OptionType::BOOL, "Show the hints of the mercs and the help screens"),
+    json("gameplay.vanilla_fixes", OptionType::OBJECT, "Fixes of the vanilla game rules, all disabled by default"),
+    json("gameplay.vanilla_fixes.interrupts", OptionType::OBJECT, "Fixes of interrupts"),
+    json("gameplay.vanilla_fixes.interrupts.no_interrupts_from_unseen_enemies", OptionType::BOOL, "Enemies that are not seen by the merc cannot interrupt"),
+    json("gameplay.vanilla_fixes.interrupts.use_remaining_action_points", OptionType::BOOL, "Interrupts use the remaining action points of the previous turn"),
+    json("gameplay.vanilla_fixes.bonuses", OptionType::OBJECT, "Fixes of bonuses"),
+    json("gameplay.vanilla_fixes.bonuses.leadership_bonus_same_sector_only", OptionType::BOOL, "The leadership bonus only applies to mercs in the same sector"),
+    json("gameplay.vanilla_fixes.bonuses.burst_marksmanship_bonus", OptionType::BOOL, "Marksmanship increases the chance to hit with bursts"),
+    json("screenshots", OptionType::OBJECT, "Screenshots taken with the print screen key, saved in the home"),
+    json("screenshots.format", OptionType::ENUM, "Format of the screenshots: BMP, PNG or JPEG")
+        .values(&["BMP", "PNG", "JPEG"]),
+    json("screenshots.jpeg_quality", OptionType::INTEGER, "Quality of JPEG screenshots between 1 and 100"),
+    json("screenshots.file_name", OptionType::STRING, "File name without extension, {date}, {time}, {day} and {sector} are replaced"),
+    json("video_capture", OptionType::OBJECT, "Video capture with ffmpeg, saved in the video-capture folder of the home"),
+    json("video_capture.target", OptionType::ENUM, "FILE to write raw frames and the ffmpeg command to encode them, PIPE to encode them with ffmpeg while playing")
+        .values(&["FILE", "PIPE"]),
+    json("video_capture.frame_rate", OptionType::INTEGER, "Frames captured per second"),
+    json("video_capture.ffmpeg", OptionType::PATH, "Path of the ffmpeg executable").personal(),
+    json("video_capture.ffmpeg_output_args", OptionType::STRING_LIST, "Arguments of ffmpeg for the output, e.g. the codec"),
+    json("video_capture.file_name", OptionType::STRING, "File name of the video, {date} and {time} are replaced"),
+    json("saves", OptionType::OBJECT, "Disk usage of the saved games shown by Manage saves in the launcher"),
+    json("saves.quota_mb", OptionType::INTEGER, "Size of the saved games of a mod in MB above which the launcher warns, 0 for no quota"),
+    json("saves.keep_backups", OptionType::INTEGER, "Number of backups of patched games that are not suggested for deletion"),
+    json("saves.backup_max_age_days", OptionType::INTEGER, "Age in days after which backups of patched games are suggested for deletion"),
+    json("log_format", OptionType::ENUM, "Format of the log: TEXT or JSON")
+        .values(&["TEXT", "JSON"])
+        .flag("logformat", CliArg::VALUE("JSON"), Some("Format of the log output. Possible values: TEXT, JSON. Default value is TEXT. JSON writes one JSON object per line")),
+    json("log_filters", OptionType::MAP, "Log levels by topic, e.g. {\"SGP\": \"DEBUG\"}"),
+    json("campaign_log", OptionType::BOOL, "Write the key events of the campaign to campaign-log.jsonl in the home for the timeline of the launcher"),
+    json("enable_rich_presence", OptionType::BOOL, "Show the state of the game in Discord, only if the game was built with rich presence"),
+    json("startup_checks", OptionType::ENUM, "Checks before the game starts: OFF, FAST for the config, the game files and mod conflicts or FULL to verify a sample of the checksums, too")
+        .values(&["OFF", "FAST", "FULL"]),
+    cli("window", CliArg::NONE, "Start the game in a window"),
+    cli("unittests", CliArg::NONE, "Perform unit tests. E.g. 'ja2.exe -unittests --gtest_output=\"xml:report.xml\" --gtest_repeat=2'"),
+    cli("smoketest", CliArg::NONE, "Start the game, load the smoke test save and exit. The exit status tells whether the game works with the data dir"),
+    cli("editor", CliArg::NONE, "Start the map editor (Editor.slf is required)"),
+    cli("seed", CliArg::VALUE("SEED"), "Seed for the random number generator, used to reproduce a game. Default is a random seed"),
+    cli("record", CliArg::NONE, "Record the input events to a replay in the replays folder of the home"),
+    cli("replay", CliArg::VALUE("FILE"), "Play the input events of a replay, the game is started with the seed of the replay"),
+    cli("portable", CliArg::NONE, "Use the folder of the executable as home, e.g. to run the game from a USB stick. A ja2.json next to the executable does the same"),
+    cli("json-errors", CliArg::NONE, "Print errors in the options as JSON object on stderr"),
+    cli("dump-defaults", CliArg::NONE, "Print the default ja2.json with a description of every option"),
+    cli("help", CliArg::NONE, "print this help menu"),
+];
+
+pub fn find_option(json_key: &str) -> Option<&'static OptionInfo> {
+    OPTIONS.iter().find(|o| o.json_key == Some(json_key))
+}
+
+/// Returns the keys in ja2.json of the personal options.
+pub fn personal_json_keys() -> impl Iterator<Item = &'static str> {
+    OPTIONS.iter().filter(|o| o.personal).filter_map(|o| o.json_key)
+}
+
+/// Returns the command line options for getopts.
+pub fn command_line_options() -> Options {
+    let mut opts = Options::new();
+    opts.long_only(true);
+    for option in OPTIONS {
+        if let Some(flag) = option.cli_flag {
+            match flag.arg {
+                CliArg::NONE => opts.optflag("", flag.name, option.help()),
+                CliArg::VALUE(hint) => opts.optopt("", flag.name, option.help(), hint),
+                CliArg::MULTIPLE(hint) => opts.optmulti("", flag.name, option.help(), hint),
+            };
+        }
+    }
+    opts
+}
+
+/// Returns the value of a dotted key in a JSON object.
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(value, |v, k| v.get(k))
+}
+
+/// Returns the registry with the default values as JSON array, e.g. for the settings of the launcher.
+pub fn registry_json() -> Value {
+    let defaults = serde_json::to_value(EngineOptions::default()).expect("Default options should be serializable");
+    Value::Array(OPTIONS.iter().map(|o| json!({
+        "json_key": o.json_key,
+        "type": o.option_type,
+        "values": o.values,
+        "description": o.description,
+        "cli_flag": o.cli_flag.map(|f| f.name),
+        "personal": o.personal,
+        "default": o.json_key.and_then(|k| lookup(&defaults, k)),
+    })).collect())
+}
+
+#[no_mangle]
+pub extern fn get_option_registry_json() -> *mut c_char {
+    CString::new(registry_json().to_string()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use EngineOptions;
+    use super::{OPTIONS, OptionType, command_line_options, find_option, registry_json};
+
+    fn collect_keys(value: &Value, prefix: &str, keys: &mut Vec<String>) {
+        if let Value::Object(ref map) = *value {
+            for (key, child) in map {
+                let full_key = format!("{}{}", prefix, key);
+                if find_option(&full_key).map(|o| o.option_type) != Some(OptionType::MAP) {
+                    collect_keys(child, &format!("{}.", full_key), keys);
+                }
+                keys.push(full_key);
+            }
+        }
+    }
+
+    #[test]
+    fn every_option_should_be_registered() {
+        let defaults = serde_json::to_value(EngineOptions::default()).unwrap();
+        let mut all_keys = vec!();
+        collect_keys(&defaults, "", &mut all_keys);
+
+        let missing: Vec<&String> = all_keys.iter().filter(|k| find_option(k).is_none()).collect();
+        let unknown: Vec<&str> = OPTIONS.iter().filter_map(|o| o.json_key).filter(|k| !all_keys.iter().any(|a| a == k)).collect();
+
+        assert!(missing.is_empty(), "Options that are not registered: {:?}", missing);
+        assert!(unknown.is_empty(), "Registered options that are not in ja2.json: {:?}", unknown);
+    }
+
+    #[test]
+    fn registered_types_should_match_the_defaults() {
+        for entry in registry_json().as_array().unwrap().iter().filter(|e| !e["json_key"].is_null()) {
+            let matches = match (&entry["type"], &entry["default"]) {
+                (t, Value::Bool(_)) => t == "BOOL",
+                (t, Value::Number(n)) => t == "NUMBER" || (t == "INTEGER" && !n.is_f64()),
+                (t, Value::String(s)) => t == "STRING" || t == "PATH" || t == "RESOLUTION" || entry["values"].as_array().unwrap().contains(&json!(s)),
+                (t, Value::Array(_)) => t == "STRING_LIST" || t == "PATH_LIST",
+                (t, Value::Object(_)) => t == "OBJECT" || t == "MAP",
+                // an enum or integer that is not set uses the value of another option or of the engine
+                (t, Value::Null) => t == "ENUM" || t == "INTEGER",
+                _ => false,
+            };
+            assert!(matches, "The type of {} does not match its default", entry);
+        }
+        assert!(OPTIONS.iter().filter(|o| o.option_type == OptionType::ENUM).all(|o| !o.values.is_empty()));
+    }
+
+    #[test]
+    fn paths_should_be_personal() {
+        let shared: Vec<&str> = OPTIONS.iter()
+            .filter(|o| (o.option_type == OptionType::PATH || o.option_type == OptionType::PATH_LIST) && !o.personal)
+            .filter_map(|o| o.json_key)
+            .collect();
+
+        assert!(shared.is_empty(), "Paths that are shared in settings snippets: {:?}", shared);
+    }
+
+    #[test]
+    fn command_line_options_should_have_the_registered_flags() {
+        let opts = command_line_options();
+
+        let matches = opts.parse(&["-datadir", "/ja2", "-mod", "a", "-mod", "b", "-alwaysontop", "-seed", "3"]).unwrap();
+
+        assert_eq!(matches.opt_strs("mod"), vec!("a", "b"));
+        assert!(matches.opt_present("alwaysontop"));
+        assert!(opts.usage("").contains("Keep the game window above all other windows"));
+        assert!(opts.parse(&["-single_instance"]).is_err());
+    }
+}
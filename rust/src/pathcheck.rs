@@ -0,0 +1,104 @@
+//! Checks of paths that cannot be written on Windows, e.g. for files of mods that are created on other systems.
+//!
+//! Windows refuses file names like `CON` or `nul.txt` in every folder and paths with more than `MAX_PATH` characters,
+//! unless the path is written in the extended `\\?\` form. The write fails with an error that does not name the reason,
+//! so the paths are checked before.
+
+use std::path::{Component, Path};
+
+/// Maximum length of a path including the terminating null character
+pub const MAX_PATH: usize = 260;
+
+/// Maximum length of a path in a mod package, so the mod can be installed in a folder with up to 100 characters
+pub const MAX_MOD_PATH_LENGTH: usize = MAX_PATH - 100;
+
+const EXTENDED_LENGTH_PREFIX: &str = r"\\?\";
+
+pub static WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Whether Windows reserves the name for a device, with any extension and in any case.
+pub fn is_reserved_windows_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    WINDOWS_RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem))
+}
+
+/// Checks that a file or folder name can be created on Windows.
+pub fn check_file_name(name: &str) -> Result<(), String> {
+    if is_reserved_windows_name(name) {
+        return Err(format!("{} is a reserved name on Windows, rename it", name));
+    }
+    if let Some(c) = name.chars().find(|c| "<>:\"/\\|?*".contains(*c) || c.is_control()) {
+        return Err(format!("{} contains {:?}, which is not allowed in names on Windows", name, c));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err(format!("\"{}\" ends with a dot or space, which Windows removes", name));
+    }
+    Ok(())
+}
+
+fn path_length(path: &str) -> usize {
+    path.encode_utf16().count()
+}
+
+/// Checks the names of a path and its length for writing on Windows. Paths in the extended form are only limited by
+/// the names.
+pub fn check_windows_path(path: &Path) -> Result<(), String> {
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            check_file_name(&name.to_string_lossy()).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        }
+    }
+    let path = path.to_string_lossy();
+    if !path.starts_with(EXTENDED_LENGTH_PREFIX) && path_length(&path) >= MAX_PATH {
+        return Err(format!("Cannot write {}: the path has {} characters, Windows allows {}, use a shorter folder", path, path_length(&path), MAX_PATH - 1));
+    }
+    Ok(())
+}
+
+/// Checks a relative path with `/` as separator that is installed on the systems of players, e.g. a file of a mod.
+pub fn check_relative_path(path: &str) -> Result<(), String> {
+    for name in path.split('/').filter(|n| !n.is_empty()) {
+        check_file_name(name).map_err(|e| format!("{} cannot be installed on Windows: {}", path, e))?;
+    }
+    if path_length(path) > MAX_MOD_PATH_LENGTH {
+        return Err(format!("{} cannot be installed on Windows: the path has {} characters, at most {} are allowed", path, path_length(path), MAX_MOD_PATH_LENGTH));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{check_file_name, check_relative_path, check_windows_path, is_reserved_windows_name};
+
+    #[test]
+    fn check_file_name_should_reject_names_that_windows_does_not_allow() {
+        assert!(is_reserved_windows_name("con"));
+        assert!(is_reserved_windows_name("Nul.txt"));
+        assert!(is_reserved_windows_name("LPT1 .tar.gz"));
+        assert!(!is_reserved_windows_name("console.txt"));
+        assert!(!is_reserved_windows_name("COM10"));
+
+        assert_eq!(check_file_name("aux.sti"), Err(String::from("aux.sti is a reserved name on Windows, rename it")));
+        assert_eq!(check_file_name("a:b"), Err(String::from("a:b contains ':', which is not allowed in names on Windows")));
+        assert!(check_file_name("readme.").is_err());
+        assert!(check_file_name("Credits.edt").is_ok());
+    }
+
+    #[test]
+    fn check_windows_path_should_limit_the_length() {
+        let long_name = "a".repeat(250);
+
+        assert!(check_windows_path(&PathBuf::from("/games/ja2/data").join("x".repeat(200))).is_ok());
+        assert!(check_windows_path(&PathBuf::from("/games/ja2/data").join(&long_name)).unwrap_err().contains("use a shorter folder"));
+        assert!(check_windows_path(&PathBuf::from("/games/ja2/data/con/a.sti")).unwrap_err().contains("con is a reserved name"));
+
+        assert!(check_relative_path("my-mod/data/tilesets/0/a.sti").is_ok());
+        assert!(check_relative_path(&format!("my-mod/data/{}", "b".repeat(150))).is_err());
+        assert_eq!(check_relative_path("my-mod/data/prn.sti"), Err(String::from("my-mod/data/prn.sti cannot be installed on Windows: prn.sti is a reserved name on Windows, rename it")));
+    }
+}
@@ -0,0 +1,246 @@
+//! Checks of the environment that the launcher runs before the game is started for the first time.
+
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+use datadir::missing_files;
+
+/// Free disk space in the home dir that is needed for saves, the tile cache and logs.
+pub const MIN_FREE_DISK_SPACE: u64 = 100 * 1024 * 1024;
+
+/// Maximum length of paths on Windows (MAX_PATH).
+pub const MAX_PATH_LENGTH: usize = 260;
+
+/// Room that is needed below the home and data dirs for the paths of the game files.
+const PATH_LENGTH_RESERVE: usize = 64;
+
+#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum PreflightStatus {
+    OK,
+    WARNING,
+    ERROR,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub status: PreflightStatus,
+    pub message: String,
+}
+
+impl PreflightCheck {
+    pub fn new(name: &'static str, status: PreflightStatus, message: String) -> PreflightCheck {
+        PreflightCheck { name, status, message }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Returns the worst status of all checks.
+    pub fn status(&self) -> PreflightStatus {
+        self.checks.iter().map(|c| c.status).fold(PreflightStatus::OK, |a, b| if b > a { b } else { a })
+    }
+}
+
+#[cfg(not(windows))]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { ::std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    use kernel32::GetDiskFreeSpaceExW;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: u64 = 0;
+    let result = unsafe { GetDiskFreeSpaceExW(wide_path.as_ptr(), &mut available, ptr::null_mut(), ptr::null_mut()) };
+    if result == 0 { None } else { Some(available) }
+}
+
+fn check_free_disk_space(home: &Path) -> PreflightCheck {
+    match free_disk_space(home) {
+        Some(space) if space < MIN_FREE_DISK_SPACE => PreflightCheck::new("disk_space", PreflightStatus::WARNING,
+            format!("Only {} MB of free disk space left in {}, at least {} MB are recommended", space / 1024 / 1024, home.display(), MIN_FREE_DISK_SPACE / 1024 / 1024)),
+        Some(space) => PreflightCheck::new("disk_space", PreflightStatus::OK, format!("{} MB of free disk space", space / 1024 / 1024)),
+        None => PreflightCheck::new("disk_space", PreflightStatus::WARNING, format!("Could not determine the free disk space in {}", home.display())),
+    }
+}
+
+fn check_home_writable(home: &Path) -> PreflightCheck {
+    let test_file = home.join(".preflight");
+    let result = fs::create_dir_all(home)
+        .and_then(|_| File::create(&test_file))
+        .and_then(|_| fs::remove_file(&test_file));
+
+    match result {
+        Ok(_) => PreflightCheck::new("home_writable", PreflightStatus::OK, format!("{} is writable", home.display())),
+        Err(e) => PreflightCheck::new("home_writable", PreflightStatus::ERROR, format!("{} is not writable: {}", home.display(), e.kind())),
+    }
+}
+
+fn check_data_dir_readable(data_dir: &Path) -> PreflightCheck {
+    if data_dir.as_os_str().is_empty() {
+        return PreflightCheck::new("data_dir_readable", PreflightStatus::ERROR, String::from("No data dir is configured"));
+    }
+    match fs::read_dir(data_dir) {
+        Ok(_) => PreflightCheck::new("data_dir_readable", PreflightStatus::OK, format!("{} is readable", data_dir.display())),
+        Err(e) => PreflightCheck::new("data_dir_readable", PreflightStatus::ERROR, format!("{} is not readable: {}", data_dir.display(), e.kind())),
+    }
+}
+
+pub fn check_data_files(data_dir: &Path) -> PreflightCheck {
+    let missing = missing_files(data_dir);
+    if missing.is_empty() {
+        PreflightCheck::new("data_files", PreflightStatus::OK, String::from("All game files were found"))
+    } else {
+        PreflightCheck::new("data_files", PreflightStatus::ERROR,
+            format!("Files are missing in {}: {}", data_dir.display(), missing.join(", ")))
+    }
+}
+
+fn check_path_length(paths: &[&Path], max_length: usize) -> PreflightCheck {
+    let too_long: Vec<String> = paths.iter()
+        .filter(|p| p.as_os_str().len() + PATH_LENGTH_RESERVE > max_length)
+        .map(|p| p.display().to_string())
+        .collect();
+
+    if too_long.is_empty() {
+        PreflightCheck::new("path_length", PreflightStatus::OK, String::from("All paths are short enough"))
+    } else {
+        PreflightCheck::new("path_length", PreflightStatus::WARNING,
+            format!("Paths might get longer than {} characters, use shorter paths for: {}", max_length, too_long.join(", ")))
+    }
+}
+
+/// Runs all checks for the home and data dirs of the engine options.
+pub fn run(engine_options: &EngineOptions) -> PreflightReport {
+    let home: &PathBuf = &engine_options.stracciatella_home;
+    let data_dir: &PathBuf = &engine_options.vanilla_data_dir;
+    let mut checks = vec!(
+        check_home_writable(home),
+        check_free_disk_space(home),
+        check_data_dir_readable(data_dir),
+        check_data_files(data_dir),
+    );
+    if cfg!(windows) {
+        checks.push(check_path_length(&[home, data_dir], MAX_PATH_LENGTH));
+    }
+    PreflightReport { checks }
+}
+
+#[no_mangle]
+pub extern fn run_preflight_checks(ptr: *const EngineOptions) -> *mut PreflightReport {
+    Box::into_raw(Box::new(run(unsafe_from_ptr!(ptr))))
+}
+
+#[no_mangle]
+pub extern fn free_preflight_report(ptr: *mut PreflightReport) -> () {
+    if ptr.is_null() { return }
+    unsafe { drop(Box::from_raw(ptr)); }
+}
+
+#[no_mangle]
+pub extern fn get_preflight_status(ptr: *const PreflightReport) -> PreflightStatus {
+    unsafe_from_ptr!(ptr).status()
+}
+
+#[no_mangle]
+pub extern fn get_number_of_preflight_checks(ptr: *const PreflightReport) -> u32 {
+    unsafe_from_ptr!(ptr).checks.len() as u32
+}
+
+fn get_check<'a>(ptr: *const PreflightReport, index: u32) -> &'a PreflightCheck {
+    match unsafe_from_ptr!(ptr).checks.get(index as usize) {
+        Some(c) => c,
+        None => panic!("Invalid preflight check index {}", index)
+    }
+}
+
+#[no_mangle]
+pub extern fn get_preflight_check_name(ptr: *const PreflightReport, index: u32) -> *mut c_char {
+    CString::new(get_check(ptr, index).name).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn get_preflight_check_status(ptr: *const PreflightReport, index: u32) -> PreflightStatus {
+    get_check(ptr, index).status
+}
+
+#[no_mangle]
+pub extern fn get_preflight_check_message(ptr: *const PreflightReport, index: u32) -> *mut c_char {
+    CString::new(get_check(ptr, index).message.clone()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::path::Path;
+
+    use EngineOptions;
+    use super::{PreflightCheck, PreflightReport, PreflightStatus};
+
+    #[test]
+    fn status_should_be_the_worst_status_of_all_checks() {
+        let check = |status| PreflightCheck::new("test", status, String::from(""));
+
+        assert_eq!(PreflightReport::default().status(), PreflightStatus::OK);
+        assert_eq!(PreflightReport { checks: vec!(check(PreflightStatus::OK), check(PreflightStatus::WARNING)) }.status(), PreflightStatus::WARNING);
+        assert_eq!(PreflightReport { checks: vec!(check(PreflightStatus::ERROR), check(PreflightStatus::WARNING)) }.status(), PreflightStatus::ERROR);
+    }
+
+    #[test]
+    fn check_path_length_should_warn_about_long_paths() {
+        let long_path = "/ja2".repeat(60);
+
+        assert_eq!(super::check_path_length(&[Path::new("/ja2")], 260).status, PreflightStatus::OK);
+        assert_eq!(super::check_path_length(&[Path::new("/ja2"), Path::new(&long_path)], 260).status, PreflightStatus::WARNING);
+    }
+
+    #[test]
+    fn run_should_check_home_and_data_dir() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = temp_dir.path().join(".ja2");
+        engine_options.vanilla_data_dir = temp_dir.path().join("missing");
+
+        let report = super::run(&engine_options);
+        let status_of = |name| report.checks.iter().find(|c| c.name == name).unwrap().status;
+
+        assert_eq!(status_of("home_writable"), PreflightStatus::OK);
+        assert!(temp_dir.path().join(".ja2").is_dir());
+        assert_eq!(status_of("data_dir_readable"), PreflightStatus::ERROR);
+        assert_eq!(report.status(), PreflightStatus::ERROR);
+
+        engine_options.vanilla_data_dir = temp_dir.path().to_path_buf();
+
+        assert_eq!(super::run(&engine_options).checks[2].status, PreflightStatus::OK);
+    }
+
+    #[test]
+    fn free_disk_space_should_be_determined_for_existing_dirs() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        assert!(super::free_disk_space(temp_dir.path()).is_some());
+    }
+}
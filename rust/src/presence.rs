@@ -0,0 +1,214 @@
+//! Discord Rich Presence with the coarse state of the game, only built with the `presence` feature.
+//!
+//! The presence is published if `enable_rich_presence` is set in ja2.json and the library was built with the id of a
+//! Discord application in `JA2_DISCORD_CLIENT_ID`. Nothing is sent if Discord is not running.
+//!
+//! Discord is reached over its local IPC socket, every message is a frame with opcode and length as little endian
+//! u32 followed by JSON.
+
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use EngineOptions;
+
+/// Id of the Discord application the presence is shown for
+pub const DISCORD_CLIENT_ID: Option<&str> = option_env!("JA2_DISCORD_CLIENT_ID");
+
+const OPCODE_HANDSHAKE: u32 = 0;
+const OPCODE_FRAME: u32 = 1;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum PresenceScreen {
+    MAIN_MENU,
+    STRATEGIC,
+    TACTICAL,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PresenceState {
+    pub screen: PresenceScreen,
+    /// Day of the campaign, 0 outside of a campaign
+    pub day: u32,
+}
+
+/// Returns the activity that Discord shows for the state.
+pub fn activity(state: &PresenceState, start: u64) -> Value {
+    let details = match state.screen {
+        PresenceScreen::MAIN_MENU => "In the main menu",
+        PresenceScreen::STRATEGIC => "Planning on the strategic map",
+        PresenceScreen::TACTICAL => "In tactical combat",
+    };
+    let mut activity = json!({ "details": details, "timestamps": { "start": start } });
+    if state.day > 0 {
+        activity["state"] = json!(format!("Day {}", state.day));
+    }
+    activity
+}
+
+pub fn encode_frame(opcode: u32, payload: &Value) -> Vec<u8> {
+    let json = payload.to_string();
+    let mut frame = Vec::with_capacity(8 + json.len());
+    frame.extend_from_slice(&opcode.to_le_bytes());
+    frame.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    frame.extend_from_slice(json.as_bytes());
+    frame
+}
+
+/// Reads a frame and returns its opcode and payload.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<(u32, Value)> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let opcode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut payload = vec!(0u8; length);
+    reader.read_exact(&mut payload)?;
+    let payload = serde_json::from_slice(&payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((opcode, payload))
+}
+
+#[cfg(unix)]
+type Connection = ::std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Connection = ::std::fs::File;
+
+/// Returns the paths of the IPC sockets that Discord may listen on, Discord uses the first free of ten.
+fn ipc_paths() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return (0..10).map(|i| PathBuf::from(format!(r"\\.\pipe\discord-ipc-{}", i))).collect();
+    }
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"].iter()
+        .filter_map(env::var_os)
+        .map(PathBuf::from)
+        .next()
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    (0..10).map(|i| dir.join(format!("discord-ipc-{}", i))).collect()
+}
+
+fn open_connection(path: &PathBuf) -> io::Result<Connection> {
+    #[cfg(unix)]
+    return Connection::connect(path);
+    #[cfg(windows)]
+    return ::std::fs::OpenOptions::new().read(true).write(true).open(path);
+}
+
+pub struct DiscordIpc {
+    connection: Connection,
+    nonce: u64,
+}
+
+impl DiscordIpc {
+    pub fn connect(client_id: &str) -> io::Result<DiscordIpc> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "Discord is not running");
+        for path in ipc_paths() {
+            match open_connection(&path) {
+                Ok(connection) => {
+                    let mut ipc = DiscordIpc { connection, nonce: 0 };
+                    ipc.send(OPCODE_HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+                    return Ok(ipc);
+                },
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Sends a frame and waits for the answer of Discord.
+    fn send(&mut self, opcode: u32, payload: &Value) -> io::Result<Value> {
+        self.connection.write_all(&encode_frame(opcode, payload))?;
+        read_frame(&mut self.connection).map(|(_, answer)| answer)
+    }
+
+    /// Shows the activity, None clears it.
+    pub fn set_activity(&mut self, activity: Option<Value>) -> io::Result<()> {
+        self.nonce += 1;
+        let payload = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": process::id(), "activity": activity },
+            "nonce": self.nonce.to_string(),
+        });
+        self.send(OPCODE_FRAME, &payload).map(|_| ())
+    }
+}
+
+struct Presence {
+    ipc: DiscordIpc,
+    state: Option<PresenceState>,
+    start: u64,
+}
+
+static PRESENCE: Mutex<Option<Presence>> = Mutex::new(None);
+
+/// Publishes the state if rich presence is enabled. Only changes are sent to Discord. Returns false if nothing was
+/// published, e.g. because Discord is not running.
+#[no_mangle]
+pub extern fn update_rich_presence(ptr: *const EngineOptions, screen: PresenceScreen, day: u32) -> bool {
+    let client_id = match DISCORD_CLIENT_ID {
+        Some(id) if unsafe_from_ptr!(ptr).enable_rich_presence => id,
+        _ => return false,
+    };
+    let state = PresenceState { screen, day };
+    let mut presence = PRESENCE.lock().unwrap_or_else(|e| e.into_inner());
+    if presence.is_none() {
+        let start = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        *presence = DiscordIpc::connect(client_id).ok().map(|ipc| Presence { ipc, state: None, start });
+    }
+    let p = match *presence {
+        Some(ref mut p) => p,
+        None => return false,
+    };
+    if p.state == Some(state) {
+        return true;
+    }
+    if p.ipc.set_activity(Some(activity(&state, p.start))).is_err() {
+        // Discord was closed, connect again with the next update
+        *presence = None;
+        return false;
+    }
+    p.state = Some(state);
+    true
+}
+
+/// Clears the presence and closes the connection to Discord.
+#[no_mangle]
+pub extern fn clear_rich_presence() -> () {
+    let mut presence = PRESENCE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref mut p) = *presence {
+        let _ = p.ipc.set_activity(None);
+    }
+    *presence = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{PresenceScreen, PresenceState, activity, encode_frame, read_frame};
+
+    #[test]
+    fn activity_should_describe_the_state() {
+        let strategic = activity(&PresenceState { screen: PresenceScreen::STRATEGIC, day: 12 }, 1520165045);
+        let menu = activity(&PresenceState { screen: PresenceScreen::MAIN_MENU, day: 0 }, 1520165045);
+
+        assert_eq!(strategic, json!({ "details": "Planning on the strategic map", "state": "Day 12", "timestamps": { "start": 1520165045 } }));
+        assert_eq!(menu.get("state"), None);
+    }
+
+    #[test]
+    fn frames_should_be_read_as_written() {
+        let payload = json!({ "v": 1, "client_id": "123" });
+        let frame = encode_frame(0, &payload);
+
+        assert_eq!(&frame[..8], &[0, 0, 0, 0, 25, 0, 0, 0]);
+        assert_eq!(read_frame(&mut Cursor::new(frame)).unwrap(), (0, payload));
+        assert!(read_frame(&mut Cursor::new(vec!(1, 0, 0, 0, 10, 0, 0, 0, b'{'))).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+//! Replay files with the input events of a game, used to reproduce bugs without sharing save games.
+//!
+//! Replays are stored as JSON lines in the `replays` folder of the stracciatella home. The first line is a header
+//! with everything needed to start the game in the same state, every other line is one input event.
+
+use std::ffi::CStr;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::c_char;
+
+use {EngineOptions, ResourceVersion, set_last_error};
+use home::path_to_c_string;
+use logger::format_timestamp;
+
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub format_version: u32,
+    pub engine_version: String,
+    pub seed: u32,
+    pub resource_version: ResourceVersion,
+    pub mods: Vec<String>,
+    pub created: String,
+}
+
+/// Input event as dequeued by the engine, with the time since the start of the recording in milliseconds.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+pub struct ReplayEvent {
+    pub time: u32,
+    pub key_state: u16,
+    pub event: u16,
+    pub param: u32,
+    pub character: u32,
+}
+
+pub fn replays_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("replays")
+}
+
+/// Returns the file name of a replay, e.g. `replay-20180304-120405-1234.jsonl` for a recording started at the given
+/// unix time with seed 1234. A counter above zero is appended to tell apart recordings of the same second and seed,
+/// e.g. `replay-20180304-120405-1234-1.jsonl`.
+pub fn replay_file_name(seconds: u64, seed: u32, counter: u32) -> String {
+    let timestamp: String = format_timestamp(seconds).chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'T')
+        .map(|c| if c == 'T' { '-' } else { c })
+        .collect();
+    if counter == 0 {
+        format!("replay-{}-{}.jsonl", timestamp, seed)
+    } else {
+        format!("replay-{}-{}-{}.jsonl", timestamp, seed, counter)
+    }
+}
+
+/// Returns the paths of all replays in the directory, oldest first.
+pub fn list_replays(dir: &Path) -> Vec<PathBuf> {
+    let mut replays: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().map(|e| e == "jsonl").unwrap_or(false))
+            .collect(),
+        Err(_) => vec!()
+    };
+    replays.sort();
+    replays
+}
+
+fn create_new_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+pub struct ReplayWriter {
+    writer: BufWriter<File>,
+}
+
+impl ReplayWriter {
+    /// Creates a new replay at the path. Fails if the file already exists.
+    pub fn create(path: &Path, header: &ReplayHeader) -> Result<ReplayWriter, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|s| format!("Error creating {}: {}", parent.display(), s.kind()))?;
+        }
+        let file = create_new_file(path).map_err(|s| format!("Error creating replay {}: {}", path.display(), s.kind()))?;
+        ReplayWriter::with_header(file, header)
+    }
+
+    /// Creates a new replay in the directory, named after the given unix time and the seed of the header.
+    /// Existing replays are never overwritten, a counter is appended to the name instead.
+    pub fn create_in_dir(dir: &Path, seconds: u64, header: &ReplayHeader) -> Result<(ReplayWriter, PathBuf), String> {
+        fs::create_dir_all(dir).map_err(|s| format!("Error creating {}: {}", dir.display(), s.kind()))?;
+        let mut counter = 0;
+        loop {
+            let path = dir.join(replay_file_name(seconds, header.seed, counter));
+            match create_new_file(&path) {
+                Ok(file) => return ReplayWriter::with_header(file, header).map(|w| (w, path)),
+                Err(ref s) if s.kind() == ErrorKind::AlreadyExists && counter < u32::MAX => counter += 1,
+                Err(s) => return Err(format!("Error creating replay {}: {}", path.display(), s.kind()))
+            }
+        }
+    }
+
+    fn with_header(file: File, header: &ReplayHeader) -> Result<ReplayWriter, String> {
+        let mut writer = ReplayWriter { writer: BufWriter::new(file) };
+        writer.write_line(header)?;
+        Ok(writer)
+    }
+
+    fn write_line<T: ::serde::Serialize>(&mut self, value: &T) -> Result<(), String> {
+        let line = serde_json::to_string(value).map_err(|s| format!("Error writing replay: {}", s))?;
+        writeln!(self.writer, "{}", line).map_err(|s| format!("Error writing replay: {}", s.kind()))
+    }
+
+    pub fn write_event(&mut self, event: &ReplayEvent) -> Result<(), String> {
+        self.write_line(event)
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|s| format!("Error writing replay: {}", s.kind()))
+    }
+}
+
+pub struct ReplayReader {
+    pub header: ReplayHeader,
+    lines: ::std::io::Lines<BufReader<File>>,
+}
+
+impl ReplayReader {
+    pub fn open(path: &Path) -> Result<ReplayReader, String> {
+        let file = File::open(path).map_err(|s| format!("Error reading replay {}: {}", path.display(), s.kind()))?;
+        let mut lines = BufReader::new(file).lines();
+        let header: ReplayHeader = match lines.next() {
+            Some(Ok(line)) => serde_json::from_str(&line).map_err(|s| format!("Error parsing replay header: {}", s))?,
+            _ => return Err(format!("Replay {} is empty", path.display()))
+        };
+        if header.format_version != REPLAY_FORMAT_VERSION {
+            return Err(format!("Replay format version {} is not supported", header.format_version));
+        }
+        Ok(ReplayReader { header, lines })
+    }
+
+    /// Returns the next event or None at the end of the replay.
+    pub fn next_event(&mut self) -> Result<Option<ReplayEvent>, String> {
+        match self.lines.next() {
+            Some(Ok(line)) => serde_json::from_str(&line).map(Some).map_err(|s| format!("Error parsing replay event: {}", s)),
+            Some(Err(s)) => Err(format!("Error reading replay: {}", s.kind())),
+            None => Ok(None)
+        }
+    }
+}
+
+static RECORDING: Mutex<Option<ReplayWriter>> = Mutex::new(None);
+static PLAYBACK: Mutex<Option<ReplayReader>> = Mutex::new(None);
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern fn get_replays_dir(ptr: *const EngineOptions) -> *mut c_char {
+    let dir = replays_dir(&unsafe_from_ptr!(ptr).stracciatella_home);
+    path_to_c_string(&dir)
+}
+
+#[no_mangle]
+pub extern fn should_record_replay(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).record_replay
+}
+
+/// Returns the replay given on the command line or null if there is none.
+#[no_mangle]
+pub extern fn get_replay_file(ptr: *const EngineOptions) -> *mut c_char {
+    match unsafe_from_ptr!(ptr).replay_file {
+        Some(ref path) => path_to_c_string(path),
+        None => ptr::null_mut()
+    }
+}
+
+/// Starts recording a new replay in the replays dir. A running recording is stopped.
+#[no_mangle]
+pub extern fn start_replay_recording(ptr: *const EngineOptions, engine_version_ptr: *const c_char, seed: u32) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let engine_version = unsafe { CStr::from_ptr(engine_version_ptr) }.to_string_lossy().into_owned();
+    let created = now();
+    let header = ReplayHeader {
+        format_version: REPLAY_FORMAT_VERSION,
+        engine_version,
+        seed,
+        resource_version: engine_options.resource_version,
+        mods: engine_options.mods.clone(),
+        created: format_timestamp(created),
+    };
+    let dir = replays_dir(&engine_options.stracciatella_home);
+
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    match ReplayWriter::create_in_dir(&dir, created, &header) {
+        Ok((writer, _)) => {
+            *recording = Some(writer);
+            true
+        },
+        Err(s) => {
+            *recording = None;
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+/// Appends an event to the running recording. Returns false if nothing is recorded or writing failed.
+#[no_mangle]
+pub extern fn record_replay_event(event_ptr: *const ReplayEvent) -> bool {
+    let event = unsafe_from_ptr!(event_ptr);
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    match *recording {
+        Some(ref mut writer) => writer.write_event(event).is_ok(),
+        None => false
+    }
+}
+
+#[no_mangle]
+pub extern fn stop_replay_recording() -> () {
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref mut writer) = *recording {
+        let _ = writer.flush();
+    }
+    *recording = None;
+}
+
+/// Opens a replay for playback, sets the last error if it cannot be opened. The seed of the replay can be read with
+/// `get_replay_seed` afterwards.
+#[no_mangle]
+pub extern fn open_replay(path_ptr: *const c_char) -> bool {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    let mut playback = PLAYBACK.lock().unwrap_or_else(|e| e.into_inner());
+    match ReplayReader::open(Path::new(&path)) {
+        Ok(reader) => {
+            *playback = Some(reader);
+            true
+        },
+        Err(s) => {
+            *playback = None;
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+/// Writes the seed of the opened replay to `seed_ptr`. Returns false if no replay is opened.
+#[no_mangle]
+pub extern fn get_replay_seed(seed_ptr: *mut u32) -> bool {
+    let seed = unsafe_from_ptr_mut!(seed_ptr);
+    let playback = PLAYBACK.lock().unwrap_or_else(|e| e.into_inner());
+    match *playback {
+        Some(ref reader) => {
+            *seed = reader.header.seed;
+            true
+        },
+        None => {
+            set_last_error(Some(String::from("No replay is opened")));
+            false
+        }
+    }
+}
+
+/// Reads the next event of the opened replay. Returns false at the end of the replay or, setting the last error, if it is broken.
+#[no_mangle]
+pub extern fn read_replay_event(event_ptr: *mut ReplayEvent) -> bool {
+    let event = unsafe_from_ptr_mut!(event_ptr);
+    let mut playback = PLAYBACK.lock().unwrap_or_else(|e| e.into_inner());
+    let next = match *playback {
+        Some(ref mut reader) => reader.next_event(),
+        None => return false
+    };
+    match next {
+        Ok(Some(e)) => {
+            *event = e;
+            true
+        },
+        Ok(None) => {
+            *playback = None;
+            false
+        },
+        Err(s) => {
+            *playback = None;
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::PathBuf;
+
+    use ResourceVersion;
+    use super::{ReplayEvent, ReplayHeader, ReplayReader, ReplayWriter};
+
+    fn header(seed: u32) -> ReplayHeader {
+        ReplayHeader {
+            format_version: super::REPLAY_FORMAT_VERSION,
+            engine_version: String::from("v0.16.0"),
+            seed,
+            resource_version: ResourceVersion::ENGLISH,
+            mods: vec!(String::from("from-russia-with-love")),
+            created: String::from("2018-03-04T12:04:05Z"),
+        }
+    }
+
+    #[test]
+    fn replay_file_name_should_contain_time_and_seed() {
+        assert_eq!(super::replay_file_name(1520165045, 42, 0), "replay-20180304-120405-42.jsonl");
+        assert_eq!(super::replay_file_name(1520165045, 42, 2), "replay-20180304-120405-42-2.jsonl");
+    }
+
+    #[test]
+    fn create_in_dir_should_not_overwrite_replays() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = super::replays_dir(temp_dir.path());
+
+        let (mut first, first_path) = ReplayWriter::create_in_dir(&dir, 1520165045, &header(42)).unwrap();
+        first.write_event(&ReplayEvent { time: 0, key_state: 0, event: 1, param: 13, character: 13 }).unwrap();
+        first.flush().unwrap();
+        let (_, second_path) = ReplayWriter::create_in_dir(&dir, 1520165045, &header(42)).unwrap();
+
+        assert_eq!(first_path, dir.join("replay-20180304-120405-42.jsonl"));
+        assert_eq!(second_path, dir.join("replay-20180304-120405-42-1.jsonl"));
+        let mut reader = ReplayReader::open(&first_path).unwrap();
+        assert!(reader.next_event().unwrap().is_some());
+    }
+
+    #[test]
+    fn open_replay_should_set_the_last_error() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = CString::new(temp_dir.path().join("missing.jsonl").to_str().unwrap()).unwrap();
+
+        assert!(!super::open_replay(path.as_ptr()));
+        let error = unsafe { CString::from_raw(::get_last_error()) };
+        assert!(error.to_str().unwrap().starts_with("Error reading replay"));
+    }
+
+    #[test]
+    fn get_replay_seed_should_fail_without_opened_replay() {
+        let mut seed = 0;
+        assert!(!super::get_replay_seed(&mut seed));
+        assert_eq!(seed, 0);
+    }
+
+    #[test]
+    fn replays_should_be_read_as_written() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = super::replays_dir(temp_dir.path()).join("test.jsonl");
+        let events = vec!(
+            ReplayEvent { time: 0, key_state: 0, event: 1, param: 13, character: 13 },
+            ReplayEvent { time: 250, key_state: 4, event: 2, param: 0x10001, character: 0 },
+        );
+
+        {
+            let mut writer = ReplayWriter::create(&path, &header(42)).unwrap();
+            for e in &events {
+                writer.write_event(e).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+
+        let mut reader = ReplayReader::open(&path).unwrap();
+        assert_eq!(reader.header, header(42));
+        assert_eq!(reader.next_event(), Ok(Some(events[0])));
+        assert_eq!(reader.next_event(), Ok(Some(events[1])));
+        assert_eq!(reader.next_event(), Ok(None));
+    }
+
+    #[test]
+    fn open_should_fail_with_unsupported_format_version() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("test.jsonl");
+        let mut h = header(1);
+        h.format_version = 99;
+        File::create(&path).unwrap().write_all(::serde_json::to_string(&h).unwrap().as_bytes()).unwrap();
+
+        assert_eq!(ReplayReader::open(&path).err(), Some(String::from("Replay format version 99 is not supported")));
+    }
+
+    #[test]
+    fn list_replays_should_only_list_replay_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        File::create(temp_dir.path().join("replay-2.jsonl")).unwrap();
+        File::create(temp_dir.path().join("replay-1.jsonl")).unwrap();
+        File::create(temp_dir.path().join("notes.txt")).unwrap();
+        fs::create_dir(temp_dir.path().join("dir.jsonl")).unwrap();
+
+        let replays = super::list_replays(temp_dir.path());
+
+        assert_eq!(replays, vec!(temp_dir.path().join("replay-1.jsonl"), temp_dir.path().join("replay-2.jsonl")));
+        assert_eq!(super::list_replays(&PathBuf::from("/non-existing")), Vec::<PathBuf>::new());
+    }
+}
@@ -0,0 +1,533 @@
+//! Parser for saved games, e.g. to list the saved games of a mod or to export them for debugging.
+//!
+//! The header of vanilla and Stracciatella on Windows has 432 bytes with a UTF-16 description, Stracciatella on Linux
+//! wrote the description in UTF-32 and has 688 bytes. All numbers are little endian.
+//!
+//! The header is followed by the tactical status, the game clock and the strategic events, which are parsed as well.
+//! The sections after them are partly encrypted and not parsed yet, see `UNSUPPORTED_SECTIONS`.
+//!
+//! `patch_saved_game` applies a few whitelisted fixes to rescue games that can no longer be loaded or played:
+//!
+//! ```json
+//! [{ "fix": "set_loaded_sector", "x": 9, "y": 1, "z": 0 }, { "fix": "remove_event", "index": 12 }]
+//! ```
+//!
+//! `set_loaded_sector` only sets the sector of the header and the sector the game loads, the positions of the mercs are
+//! stored in the encrypted soldiers and are not changed. Event indexes refer to the unpatched game.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use temp::ScratchDir;
+
+pub const HEADER_SIZE: usize = 432;
+pub const HEADER_SIZE_STRAC_LINUX: usize = 688;
+const GAME_VERSION_SIZE: usize = 16;
+const DESCRIPTION_LENGTH: usize = 128;
+const TACTICAL_STATUS_SIZE: usize = 316;
+const TACTICAL_STATUS_SIZE_STRAC_LINUX: usize = 360;
+/// Size of the tactical status plus the current sector
+const WORLD_SECTOR_SIZE: usize = 5;
+const MAX_TEAMS: usize = 6;
+const TEAM_SIZE: usize = 16;
+/// Size of the game clock including 20 bytes of padding
+const GAME_CLOCK_SIZE: usize = 62;
+const STRATEGIC_EVENT_SIZE: usize = 28;
+
+/// Sections of a saved game after the strategic events, in the order they are stored.
+pub static UNSUPPORTED_SECTIONS: &[&str] = &[
+    "laptop", "merc_profiles", "soldiers", "finances", "history", "files", "email", "strategic_map", "squads",
+    "movement_groups", "map_temp_files", "quests", "opponent_lists", "messages", "npcs", "keys", "arms_dealers",
+];
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SavedGameHeader {
+    pub saved_game_version: u32,
+    pub game_version: String,
+    pub description: String,
+    pub day: u32,
+    pub hour: u8,
+    pub minute: u8,
+    /// 0 if the game was saved before arriving in Arulco
+    pub sector_x: i16,
+    pub sector_y: i16,
+    /// -1 if the game was saved before arriving in Arulco
+    pub sector_z: i8,
+    pub number_of_mercs: u8,
+    pub balance: i32,
+    pub gun_nut: bool,
+    pub sci_fi: bool,
+    pub difficulty: u8,
+    pub turn_time_limit: bool,
+    pub save_mode: u8,
+    /// True if the header was written by Stracciatella on Linux
+    pub strac_linux_format: bool,
+}
+
+fn header_size(strac_linux_format: bool) -> usize {
+    if strac_linux_format { HEADER_SIZE_STRAC_LINUX } else { HEADER_SIZE }
+}
+
+/// Offset of the header data after the description and its 4 bytes of padding
+fn header_data_offset(strac_linux_format: bool) -> usize {
+    let char_size = if strac_linux_format { 4 } else { 2 };
+    4 + GAME_VERSION_SIZE + DESCRIPTION_LENGTH * char_size + 4
+}
+
+fn world_sector_offset(strac_linux_format: bool) -> usize {
+    header_size(strac_linux_format) + if strac_linux_format { TACTICAL_STATUS_SIZE_STRAC_LINUX } else { TACTICAL_STATUS_SIZE }
+}
+
+fn strategic_events_offset(strac_linux_format: bool) -> usize {
+    world_sector_offset(strac_linux_format) + WORLD_SECTOR_SIZE + GAME_CLOCK_SIZE
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+}
+
+fn read_i32(bytes: &[u8]) -> i32 {
+    read_u32(bytes) as i32
+}
+
+fn read_i16(bytes: &[u8]) -> i16 {
+    (u16::from(bytes[0]) | u16::from(bytes[1]) << 8) as i16
+}
+
+fn read_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn read_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2)
+        .map(|c| u16::from(c[0]) | u16::from(c[1]) << 8)
+        .take_while(|c| *c != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn read_utf32(bytes: &[u8]) -> String {
+    bytes.chunks(4)
+        .map(read_u32)
+        .take_while(|c| *c != 0)
+        .map(|c| ::std::char::from_u32(c).unwrap_or('\u{FFFD}'))
+        .collect()
+}
+
+impl SavedGameHeader {
+    /// Parses a header in the given format, `bytes` may be longer than the header.
+    pub fn parse(bytes: &[u8], strac_linux_format: bool) -> Result<SavedGameHeader, String> {
+        let size = header_size(strac_linux_format);
+        if bytes.len() < size {
+            return Err(format!("Saved game header is truncated: {} of {} bytes", bytes.len(), size));
+        }
+        let description_start = 4 + GAME_VERSION_SIZE;
+        let description = &bytes[description_start..header_data_offset(strac_linux_format) - 4];
+        let d = &bytes[header_data_offset(strac_linux_format)..size];
+
+        Ok(SavedGameHeader {
+            saved_game_version: read_u32(bytes),
+            game_version: read_string(&bytes[4..description_start]),
+            description: if strac_linux_format { read_utf32(description) } else { read_utf16(description) },
+            day: read_u32(d),
+            hour: d[4],
+            minute: d[5],
+            sector_x: read_i16(&d[6..]),
+            sector_y: read_i16(&d[8..]),
+            sector_z: d[10] as i8,
+            number_of_mercs: d[11],
+            balance: read_u32(&d[12..]) as i32,
+            gun_nut: d[23] != 0,
+            sci_fi: d[24] != 0,
+            difficulty: d[25],
+            turn_time_limit: d[26] != 0,
+            save_mode: d[27],
+            strac_linux_format,
+        })
+    }
+
+    /// Parses a header in either format like the game does: the Linux format is used if it results in a valid
+    /// header, the vanilla format otherwise.
+    pub fn parse_any(bytes: &[u8]) -> Result<SavedGameHeader, String> {
+        match SavedGameHeader::parse(bytes, true) {
+            Ok(ref h) if h.is_valid() => Ok(h.clone()),
+            _ => SavedGameHeader::parse(bytes, false),
+        }
+    }
+
+    /// Basic check of the values, it does not detect all problems.
+    pub fn is_valid(&self) -> bool {
+        if self.day == 0 || self.balance < 0 {
+            return false;
+        }
+        let before_arrival = self.sector_x == 0 && self.sector_y == 0 && self.sector_z == -1;
+        before_arrival || ((1..=16).contains(&self.sector_x) && (1..=16).contains(&self.sector_y) && (0..=3).contains(&self.sector_z))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct TacticalTeam {
+    pub first_id: u8,
+    pub last_id: u8,
+    pub side: i8,
+    pub men_in_sector: i8,
+    pub human: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct TacticalStatus {
+    pub flags: u32,
+    pub teams: Vec<TacticalTeam>,
+    pub current_team: u8,
+    /// Sector that was loaded when the game was saved
+    pub world_sector_x: i16,
+    pub world_sector_y: i16,
+    pub world_sector_z: i8,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct GameClock {
+    pub time_compress_mode: i32,
+    pub game_paused: bool,
+    /// Seconds since the start of the game
+    pub game_clock: u32,
+    pub game_seconds_per_real_second: u32,
+    pub ambient_light_level: u8,
+    pub environment_time: u32,
+    pub environment_day: u32,
+    pub lock_pause_state: bool,
+    pub time_compression_on: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct StrategicEvent {
+    /// Game clock at which the event is processed
+    pub time_stamp: u32,
+    pub param: u32,
+    pub time_offset: u32,
+    pub event_type: u8,
+    pub callback_id: u8,
+    pub flags: u8,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SavedGame {
+    pub header: SavedGameHeader,
+    pub tactical_status: TacticalStatus,
+    pub game_clock: GameClock,
+    pub strategic_events: Vec<StrategicEvent>,
+}
+
+/// Returns the next `length` bytes and advances the offset, or an error if there are not enough bytes.
+fn take<'a>(bytes: &'a [u8], offset: &mut usize, length: usize, what: &str) -> Result<&'a [u8], String> {
+    let available = bytes.len() - *offset;
+    if length > available {
+        return Err(format!("Saved game is truncated: {} needs {} bytes, {} are left", what, length, available));
+    }
+    let slice = &bytes[*offset..*offset + length];
+    *offset += length;
+    Ok(slice)
+}
+
+impl SavedGame {
+    /// Parses the header and the sections that follow it, the rest of the saved game is ignored.
+    pub fn parse(bytes: &[u8]) -> Result<SavedGame, String> {
+        let header = SavedGameHeader::parse_any(bytes)?;
+        let mut offset = header_size(header.strac_linux_format);
+
+        let status_size = world_sector_offset(header.strac_linux_format) - offset;
+        let s = take(bytes, &mut offset, status_size + WORLD_SECTOR_SIZE, "tactical status")?;
+        let teams = s[4..4 + MAX_TEAMS * TEAM_SIZE].chunks(TEAM_SIZE).map(|t| TacticalTeam {
+            first_id: t[0],
+            last_id: t[1],
+            side: t[8] as i8,
+            men_in_sector: t[9] as i8,
+            human: t[13] != 0,
+        }).collect();
+        let sector = &s[status_size..];
+        let tactical_status = TacticalStatus {
+            flags: read_u32(s),
+            teams,
+            current_team: s[4 + MAX_TEAMS * TEAM_SIZE],
+            world_sector_x: read_i16(sector),
+            world_sector_y: read_i16(&sector[2..]),
+            world_sector_z: sector[4] as i8,
+        };
+
+        let c = take(bytes, &mut offset, GAME_CLOCK_SIZE, "game clock")?;
+        let game_clock = GameClock {
+            time_compress_mode: read_i32(c),
+            game_paused: c[5] != 0,
+            game_clock: read_u32(&c[8..]),
+            game_seconds_per_real_second: read_u32(&c[12..]),
+            ambient_light_level: c[16],
+            environment_time: read_u32(&c[17..]),
+            environment_day: read_u32(&c[21..]),
+            lock_pause_state: c[30] != 0,
+            time_compression_on: c[33] != 0,
+        };
+
+        let number_of_events = read_u32(take(bytes, &mut offset, 4, "number of strategic events")?) as usize;
+        let events = take(bytes, &mut offset, number_of_events.saturating_mul(STRATEGIC_EVENT_SIZE), "strategic events")?;
+        let strategic_events = events.chunks(STRATEGIC_EVENT_SIZE).map(|e| StrategicEvent {
+            time_stamp: read_u32(&e[4..]),
+            param: read_u32(&e[8..]),
+            time_offset: read_u32(&e[12..]),
+            event_type: e[16],
+            callback_id: e[17],
+            flags: e[18],
+        }).collect();
+
+        Ok(SavedGame { header, tactical_status, game_clock, strategic_events })
+    }
+
+    /// Returns the parsed sections as JSON object, with the names of the sections that are not exported.
+    pub fn to_json(&self) -> Value {
+        let mut json = serde_json::to_value(self).expect("Saved games should be serializable");
+        json["unsupported_sections"] = json!(UNSUPPORTED_SECTIONS);
+        json
+    }
+}
+
+/// Fix that `patch_saved_game` can apply.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(tag = "fix", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SavePatch {
+    /// Sets the sector of the header and the sector the game loads, the mercs are not moved
+    SetLoadedSector { x: i16, y: i16, z: i8 },
+    /// Removes a queued strategic event by its index in `SavedGame::strategic_events`
+    RemoveEvent { index: usize },
+}
+
+impl SavePatch {
+    pub fn parse_list(json: &str) -> Result<Vec<SavePatch>, String> {
+        serde_json::from_str(json).map_err(|s| format!("Error parsing the patch: {}", s))
+    }
+}
+
+/// Applies the patches to a saved game and returns the patched bytes. Nothing is changed if a patch does not fit the
+/// game or the patched game cannot be parsed anymore.
+pub fn patch_saved_game(bytes: &[u8], patches: &[SavePatch]) -> Result<Vec<u8>, String> {
+    let game = SavedGame::parse(bytes)?;
+    let strac_linux_format = game.header.strac_linux_format;
+    let mut patched = bytes.to_vec();
+    let mut removed_events: Vec<usize> = vec!();
+
+    for patch in patches {
+        match *patch {
+            SavePatch::SetLoadedSector { x, y, z } => {
+                if !(1..=16).contains(&x) || !(1..=16).contains(&y) || !(0..=3).contains(&z) {
+                    return Err(format!("Sector {},{},{} is not a valid sector", x, y, z));
+                }
+                let d = header_data_offset(strac_linux_format);
+                patched[d + 6..d + 8].copy_from_slice(&x.to_le_bytes());
+                patched[d + 8..d + 10].copy_from_slice(&y.to_le_bytes());
+                patched[d + 10] = z as u8;
+                let w = world_sector_offset(strac_linux_format);
+                patched[w..w + 2].copy_from_slice(&x.to_le_bytes());
+                patched[w + 2..w + 4].copy_from_slice(&y.to_le_bytes());
+                patched[w + 4] = z as u8;
+            },
+            SavePatch::RemoveEvent { index } => {
+                if index >= game.strategic_events.len() {
+                    return Err(format!("There is no strategic event {}, the game has {}", index, game.strategic_events.len()));
+                }
+                if removed_events.contains(&index) {
+                    return Err(format!("Strategic event {} is removed twice", index));
+                }
+                removed_events.push(index);
+            },
+        }
+    }
+
+    // Remove from the back, so the offsets of the other events do not change
+    removed_events.sort_unstable_by(|a, b| b.cmp(a));
+    let events_offset = strategic_events_offset(strac_linux_format);
+    for index in &removed_events {
+        let start = events_offset + 4 + index * STRATEGIC_EVENT_SIZE;
+        patched.drain(start..start + STRATEGIC_EVENT_SIZE);
+    }
+    let number_of_events = (game.strategic_events.len() - removed_events.len()) as u32;
+    patched[events_offset..events_offset + 4].copy_from_slice(&number_of_events.to_le_bytes());
+
+    let patched_game = SavedGame::parse(&patched).map_err(|s| format!("The patched game is invalid: {}", s))?;
+    if !patched_game.header.is_valid() {
+        return Err(String::from("The patched game has an invalid header"));
+    }
+    Ok(patched)
+}
+
+/// Patches a saved game file after copying it to `<file>.bak`. Fails if the backup exists already, so the original
+/// game is never overwritten by repeated patching. The patched game is written in a scratch dir in the cache dir and
+/// then replaces the file, so a failed write does not leave a truncated game behind.
+pub fn patch_saved_game_file(path: &Path, patches: &[SavePatch], cache_dir: &Path) -> Result<PathBuf, String> {
+    let bytes = fs::read(path).map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    let patched = patch_saved_game(&bytes, patches).map_err(|s| format!("{}: {}", path.display(), s))?;
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(".bak");
+    let backup_path = PathBuf::from(backup_path);
+    if backup_path.exists() {
+        return Err(format!("Backup {} exists already, move it away to patch the game again", backup_path.display()));
+    }
+    let scratch = ScratchDir::new(cache_dir, "patch-save")?;
+    let patched_path = scratch.join("patched.sav");
+    fs::write(&patched_path, patched).map_err(|s| format!("Error writing {}: {}", patched_path.display(), s.kind()))?;
+    fs::copy(path, &backup_path).map_err(|s| format!("Error writing {}: {}", backup_path.display(), s.kind()))?;
+    scratch.persist("patched.sav", path)?;
+    Ok(backup_path)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::{HEADER_SIZE, HEADER_SIZE_STRAC_LINUX, SavePatch, SavedGame, SavedGameHeader, patch_saved_game, patch_saved_game_file};
+
+    fn header(strac_linux_format: bool) -> Vec<u8> {
+        let char_size = if strac_linux_format { 4 } else { 2 };
+        let mut bytes = vec!(0u8; if strac_linux_format { HEADER_SIZE_STRAC_LINUX } else { HEADER_SIZE });
+        bytes[0] = 102;
+        bytes[4..9].copy_from_slice(b"0.16a");
+        for (i, c) in "Drassen".chars().enumerate() {
+            bytes[20 + i * char_size] = c as u8;
+        }
+        let d = 20 + 128 * char_size + 4;
+        bytes[d] = 3;
+        bytes[d + 4] = 14;
+        bytes[d + 6] = 13;
+        bytes[d + 8] = 4;
+        bytes[d + 12..d + 16].copy_from_slice(&[0x10, 0x27, 0, 0]);
+        bytes[d + 25] = 2;
+        bytes
+    }
+
+    #[test]
+    fn parse_should_read_both_formats() {
+        for &strac_linux_format in &[false, true] {
+            let h = SavedGameHeader::parse(&header(strac_linux_format), strac_linux_format).unwrap();
+
+            assert_eq!(h.saved_game_version, 102);
+            assert_eq!(h.game_version, "0.16a");
+            assert_eq!(h.description, "Drassen");
+            assert_eq!((h.day, h.hour, h.sector_x, h.sector_y, h.sector_z), (3, 14, 13, 4, 0));
+            assert_eq!((h.balance, h.difficulty), (10000, 2));
+            assert!(h.is_valid());
+            assert_eq!(SavedGameHeader::parse_any(&header(strac_linux_format)), Ok(h));
+        }
+    }
+
+    #[test]
+    fn parse_should_fail_for_truncated_headers() {
+        let bytes = header(false);
+
+        assert!(SavedGameHeader::parse(&bytes[..HEADER_SIZE - 1], false).is_err());
+        assert!(SavedGameHeader::parse(&bytes, true).is_err());
+        assert!(SavedGameHeader::parse_any(&bytes[..100]).is_err());
+    }
+
+    fn event(time_stamp: u32, event_type: u8) -> Vec<u8> {
+        let mut event = vec!(0u8; 28);
+        event[4..8].copy_from_slice(&time_stamp.to_le_bytes());
+        event[16] = event_type;
+        event
+    }
+
+    fn saved_game(strac_linux_format: bool) -> Vec<u8> {
+        let mut bytes = header(strac_linux_format);
+        let mut status = vec!(0u8; if strac_linux_format { 360 } else { 316 });
+        status[4..6].copy_from_slice(&[0, 19]);
+        status[20] = 20;
+        status[29] = 3;
+        bytes.extend(status);
+        bytes.extend_from_slice(&[13, 0, 4, 0, 0]);
+        let mut clock = vec!(0u8; 62);
+        clock[8..12].copy_from_slice(&200_000u32.to_le_bytes());
+        clock[33] = 1;
+        bytes.extend(clock);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend(event(201_000, 21));
+        // Laptop info that is not parsed
+        bytes.extend(vec!(0xFF; 100));
+        bytes
+    }
+
+    #[test]
+    fn saved_game_parse_should_read_the_sections_after_the_header() {
+        for &strac_linux_format in &[false, true] {
+            let game = SavedGame::parse(&saved_game(strac_linux_format)).unwrap();
+
+            assert_eq!(game.header.strac_linux_format, strac_linux_format);
+            assert_eq!((game.tactical_status.teams[0].first_id, game.tactical_status.teams[0].last_id), (0, 19));
+            assert_eq!(game.tactical_status.teams[1].first_id, 20);
+            assert_eq!(game.tactical_status.teams[1].men_in_sector, 3);
+            assert_eq!((game.tactical_status.world_sector_x, game.tactical_status.world_sector_y), (13, 4));
+            assert_eq!((game.game_clock.game_clock, game.game_clock.time_compression_on), (200_000, true));
+            assert_eq!(game.strategic_events.len(), 1);
+            assert_eq!((game.strategic_events[0].time_stamp, game.strategic_events[0].event_type), (201_000, 21));
+
+            let json = game.to_json();
+            assert_eq!(json["header"]["description"], "Drassen");
+            assert_eq!(json["unsupported_sections"][0], "laptop");
+        }
+    }
+
+    #[test]
+    fn saved_game_parse_should_fail_for_truncated_sections() {
+        let bytes = saved_game(false);
+
+        assert_eq!(SavedGame::parse(&bytes[..HEADER_SIZE + 10]), Err(String::from("Saved game is truncated: tactical status needs 321 bytes, 10 are left")));
+        assert!(SavedGame::parse(&bytes[..bytes.len() - 110]).unwrap_err().contains("strategic events"));
+    }
+
+    #[test]
+    fn patch_saved_game_should_apply_whitelisted_fixes() {
+        let mut bytes = saved_game(true);
+        let events_offset = HEADER_SIZE_STRAC_LINUX + 360 + 5 + 62;
+        bytes[events_offset] = 3;
+        for (i, e) in [event(202_000, 5), event(203_000, 7)].iter().enumerate() {
+            let start = events_offset + 4 + (i + 1) * 28;
+            bytes.splice(start..start, e.iter().cloned());
+        }
+        let patches = SavePatch::parse_list(r#"[{ "fix": "set_loaded_sector", "x": 9, "y": 1, "z": 0 }, { "fix": "remove_event", "index": 2 }, { "fix": "remove_event", "index": 0 }]"#).unwrap();
+
+        let game = SavedGame::parse(&patch_saved_game(&bytes, &patches).unwrap()).unwrap();
+
+        assert_eq!((game.header.sector_x, game.header.sector_y, game.header.sector_z), (9, 1, 0));
+        assert_eq!((game.tactical_status.world_sector_x, game.tactical_status.world_sector_y), (9, 1));
+        assert_eq!(game.strategic_events.iter().map(|e| e.event_type).collect::<Vec<u8>>(), vec!(5));
+    }
+
+    #[test]
+    fn patch_saved_game_should_reject_patches_that_do_not_fit() {
+        let bytes = saved_game(false);
+        let patch = |json: &str| patch_saved_game(&bytes, &SavePatch::parse_list(json).unwrap());
+
+        assert_eq!(patch(r#"[{ "fix": "set_loaded_sector", "x": 17, "y": 1, "z": 0 }]"#), Err(String::from("Sector 17,1,0 is not a valid sector")));
+        assert_eq!(patch(r#"[{ "fix": "remove_event", "index": 1 }]"#), Err(String::from("There is no strategic event 1, the game has 1")));
+        assert!(patch(r#"[{ "fix": "remove_event", "index": 0 }, { "fix": "remove_event", "index": 0 }]"#).is_err());
+        assert!(SavePatch::parse_list(r#"[{ "fix": "set_balance", "balance": 1000000 }]"#).is_err());
+        assert!(patch_saved_game(&bytes[..HEADER_SIZE + 10], &[]).is_err());
+    }
+
+    #[test]
+    fn patch_saved_game_file_should_keep_a_backup() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("SaveGame01.sav");
+        let cache_dir = temp_dir.path().join("tmp");
+        let bytes = saved_game(false);
+        fs::write(&path, &bytes).unwrap();
+        let patches = SavePatch::parse_list(r#"[{ "fix": "set_loaded_sector", "x": 9, "y": 1, "z": 0 }]"#).unwrap();
+
+        let backup_path = patch_saved_game_file(&path, &patches, &cache_dir).unwrap();
+
+        assert_eq!(fs::read(&backup_path).unwrap(), bytes);
+        assert_eq!(SavedGame::parse(&fs::read(&path).unwrap()).unwrap().header.sector_x, 9);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+        assert!(patch_saved_game_file(&path, &patches, &cache_dir).unwrap_err().contains("exists already"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Disk usage of the saved games and suggestions what can be deleted, for "Manage saves" in the launcher.
+//!
+//! The engine writes `SaveGameNN.sav`, `QuickSave.sav`, the rotating autosaves `Auto00.sav` and `Auto01.sav`, and
+//! `error.sav` or `smoketest.sav` in special situations. Patching a game leaves a `.bak` next to it, see `savegame`.
+//! Only backups and the special games are suggested for deletion: backups beyond the newest `keep_backups` or older
+//! than `backup_max_age_days`, and the special games always. Nothing is deleted here, the launcher asks the user.
+
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::c_char;
+
+use EngineOptions;
+use set_last_error;
+use home::configured_save_dir;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    /// Size of the saved games of a mod in MB that is reported as exceeded, 0 for no quota
+    pub quota_mb: u64,
+    /// Number of backups of patched games that are kept
+    pub keep_backups: usize,
+    /// Age in days after which backups are suggested for deletion
+    pub backup_max_age_days: u64,
+}
+
+impl Default for SaveOptions {
+    fn default() -> SaveOptions {
+        SaveOptions {
+            quota_mb: 0,
+            keep_backups: 3,
+            backup_max_age_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[allow(non_camel_case_types)]
+pub enum SaveKind {
+    MANUAL,
+    QUICKSAVE,
+    AUTOSAVE,
+    BACKUP,
+    /// `error.sav` and `smoketest.sav`
+    SPECIAL,
+}
+
+impl SaveKind {
+    /// Returns the kind of a file in the save dir, None for files that are not saved games.
+    pub fn from_file_name(name: &str) -> Option<SaveKind> {
+        let name = name.to_lowercase();
+        if name.ends_with(".sav.bak") {
+            return Some(SaveKind::BACKUP);
+        }
+        let stem = name.strip_suffix(".sav")?;
+        Some(if stem.starts_with("quicksave") {
+            SaveKind::QUICKSAVE
+        } else if stem.starts_with("auto") {
+            SaveKind::AUTOSAVE
+        } else if stem == "error" || stem == "smoketest" {
+            SaveKind::SPECIAL
+        } else {
+            SaveKind::MANUAL
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SaveFile {
+    pub path: PathBuf,
+    pub kind: SaveKind,
+    pub size: u64,
+    /// Unix time in seconds of the last modification
+    pub modified: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct CleanupSuggestion {
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct SaveUsage {
+    pub dir: PathBuf,
+    pub total_size: u64,
+    /// 0 if there is no quota
+    pub quota: u64,
+    pub over_quota: bool,
+    pub manual_saves: usize,
+    pub quicksaves: usize,
+    pub autosaves: usize,
+    pub backups: usize,
+    /// Saved games from the newest to the oldest
+    pub files: Vec<SaveFile>,
+    pub suggestions: Vec<CleanupSuggestion>,
+    pub suggested_size: u64,
+}
+
+fn read_save_files(dir: &Path) -> Result<Vec<SaveFile>, String> {
+    if !dir.exists() {
+        return Ok(vec!());
+    }
+    let error = |s: ::std::io::Error| format!("Error reading {}: {}", dir.display(), s.kind());
+    let mut files = vec!();
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        let kind = match SaveKind::from_file_name(&entry.file_name().to_string_lossy()) {
+            Some(k) => k,
+            None => continue,
+        };
+        let metadata = entry.metadata().map_err(error)?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        files.push(SaveFile { path: entry.path(), kind, size: metadata.len(), modified });
+    }
+    files.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path)));
+    Ok(files)
+}
+
+/// Returns the usage of a save dir at the given unix time with the suggestions of the options.
+pub fn save_usage(dir: &Path, options: &SaveOptions, now: u64) -> Result<SaveUsage, String> {
+    let files = read_save_files(dir)?;
+    let count = |kind: SaveKind| files.iter().filter(|f| f.kind == kind).count();
+    let mut suggestions = vec!();
+    let mut newer_backups = 0;
+
+    for file in &files {
+        let age_days = now.saturating_sub(file.modified) / SECONDS_PER_DAY;
+        let reason = match file.kind {
+            SaveKind::SPECIAL => Some(String::from("Written for an error or a smoke test, not a game of the user")),
+            SaveKind::BACKUP => {
+                newer_backups += 1;
+                if newer_backups > options.keep_backups {
+                    Some(format!("Only the newest {} backups are kept", options.keep_backups))
+                } else if age_days > options.backup_max_age_days {
+                    Some(format!("Backup is {} days old", age_days))
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            suggestions.push(CleanupSuggestion { path: file.path.clone(), size: file.size, reason });
+        }
+    }
+
+    let total_size = files.iter().map(|f| f.size).sum();
+    let quota = options.quota_mb * 1024 * 1024;
+    Ok(SaveUsage {
+        dir: dir.to_path_buf(),
+        total_size,
+        quota,
+        over_quota: quota > 0 && total_size > quota,
+        manual_saves: count(SaveKind::MANUAL),
+        quicksaves: count(SaveKind::QUICKSAVE),
+        autosaves: count(SaveKind::AUTOSAVE),
+        backups: count(SaveKind::BACKUP),
+        suggested_size: suggestions.iter().map(|s| s.size).sum(),
+        files,
+        suggestions,
+    })
+}
+
+/// Returns the usage of the saved games of the current mods as JSON, null on errors, see `get_last_error`.
+#[no_mangle]
+pub extern fn get_save_usage_json(ptr: *const EngineOptions) -> *mut c_char {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    match save_usage(&configured_save_dir(engine_options), &engine_options.saves, now) {
+        Ok(usage) => CString::new(json!(usage).to_string()).unwrap().into_raw(),
+        Err(s) => {
+            set_last_error(Some(s));
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{SECONDS_PER_DAY, SaveKind, SaveOptions, save_usage};
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn write_save(dir: &Path, name: &str, size: usize, age_days: u64) {
+        fs::write(dir.join(name), vec!(0u8; size)).unwrap();
+        let modified = UNIX_EPOCH + Duration::from_secs(NOW - age_days * SECONDS_PER_DAY);
+        File::options().write(true).open(dir.join(name)).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn save_kind_should_follow_the_file_names_of_the_engine() {
+        assert_eq!(SaveKind::from_file_name("SaveGame03.sav"), Some(SaveKind::MANUAL));
+        assert_eq!(SaveKind::from_file_name("QuickSave.sav"), Some(SaveKind::QUICKSAVE));
+        assert_eq!(SaveKind::from_file_name("Auto01.sav"), Some(SaveKind::AUTOSAVE));
+        assert_eq!(SaveKind::from_file_name("SaveGame03.sav.bak"), Some(SaveKind::BACKUP));
+        assert_eq!(SaveKind::from_file_name("error.sav"), Some(SaveKind::SPECIAL));
+        assert_eq!(SaveKind::from_file_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn save_usage_should_count_the_games_and_suggest_old_backups() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = temp_dir.path();
+        write_save(dir, "SaveGame01.sav", 300_000, 90);
+        write_save(dir, "QuickSave.sav", 300_000, 0);
+        write_save(dir, "Auto00.sav", 300_000, 1);
+        write_save(dir, "Auto01.sav", 300_000, 2);
+        write_save(dir, "error.sav", 100, 5);
+        for (i, age) in [3, 10, 20, 40].iter().enumerate() {
+            write_save(dir, &format!("SaveGame0{}.sav.bak", i + 2), 1000, *age);
+        }
+        let options = SaveOptions { quota_mb: 1, keep_backups: 2, backup_max_age_days: 15 };
+
+        let usage = save_usage(dir, &options, NOW).unwrap();
+
+        assert_eq!((usage.manual_saves, usage.quicksaves, usage.autosaves, usage.backups), (1, 1, 2, 4));
+        assert_eq!((usage.total_size, usage.over_quota), (1_204_100, true));
+        assert_eq!(usage.files.first().map(|f| f.path.clone()), Some(dir.join("QuickSave.sav")));
+        let suggested: Vec<_> = usage.suggestions.iter().map(|s| (s.path.file_name().unwrap().to_string_lossy().into_owned(), s.reason.as_str())).collect();
+        assert_eq!(suggested, vec!(
+            (String::from("error.sav"), "Written for an error or a smoke test, not a game of the user"),
+            (String::from("SaveGame04.sav.bak"), "Only the newest 2 backups are kept"),
+            (String::from("SaveGame05.sav.bak"), "Only the newest 2 backups are kept"),
+        ));
+        assert_eq!(usage.suggested_size, 2100);
+    }
+
+    #[test]
+    fn save_usage_should_suggest_backups_by_age() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write_save(temp_dir.path(), "SaveGame01.sav.bak", 1000, 31);
+
+        let usage = save_usage(temp_dir.path(), &SaveOptions::default(), NOW).unwrap();
+        let missing = save_usage(&temp_dir.path().join("SavedGames-mod"), &SaveOptions::default(), NOW).unwrap();
+
+        assert_eq!(usage.suggestions[0].reason, "Backup is 31 days old");
+        assert!(!usage.over_quota);
+        assert_eq!((missing.total_size, missing.files.len()), (0, 0));
+    }
+}
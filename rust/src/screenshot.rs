@@ -0,0 +1,522 @@
+//! Screenshots in the format and with the file names configured in the `screenshots` section of ja2.json.
+//!
+//! The engine passes the screen buffer with RGB565 pixels to `save_screenshot`, the encoding is done here. PNG files
+//! are stored without compression and JPEG files use the baseline format with the standard Huffman tables.
+//!
+//! The file name template may contain `{date}` (2018-03-04), `{time}` (120405), `{day}` (day of the campaign) and
+//! `{sector}` (e.g. `D13`, empty outside of a sector). A number is appended if the file exists already.
+
+use std::ffi::CString;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::c_char;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use EngineOptions;
+use set_last_error;
+use campaignlog::sector_name;
+use logger::format_timestamp;
+use zip::crc32;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub enum ScreenshotFormat {
+    BMP,
+    PNG,
+    JPEG,
+}
+
+impl ScreenshotFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::BMP => "bmp",
+            ScreenshotFormat::PNG => "png",
+            ScreenshotFormat::JPEG => "jpg",
+        }
+    }
+}
+
+fn deserialize_jpeg_quality<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let quality = u8::deserialize(deserializer)?;
+    if (1..=100).contains(&quality) {
+        Ok(quality)
+    } else {
+        Err(serde::de::Error::custom("Incorrect JPEG quality, should be a value between 1 and 100."))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotOptions {
+    pub format: ScreenshotFormat,
+    #[serde(deserialize_with = "deserialize_jpeg_quality")]
+    pub jpeg_quality: u8,
+    /// Template of the file name without extension, see the module documentation
+    pub file_name: String,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> ScreenshotOptions {
+        ScreenshotOptions {
+            format: ScreenshotFormat::PNG,
+            jpeg_quality: 90,
+            file_name: String::from("screenshot-{date}-{time}"),
+        }
+    }
+}
+
+/// Game state that can be used in file names.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ScreenshotContext {
+    /// Unix time in seconds
+    pub time: u64,
+    pub day: u32,
+    pub sector: Option<String>,
+}
+
+/// Returns the file name for the template without extension. Characters that are not allowed in file names are
+/// replaced by `_`.
+pub fn screenshot_file_name(template: &str, context: &ScreenshotContext) -> String {
+    let timestamp = format_timestamp(context.time);
+    let (date, time) = timestamp.split_once('T').unwrap_or((&timestamp, ""));
+    let name = template
+        .replace("{date}", date)
+        .replace("{time}", &time.chars().filter(|c| c.is_ascii_digit()).collect::<String>())
+        .replace("{day}", &context.day.to_string())
+        .replace("{sector}", context.sector.as_deref().unwrap_or(""));
+    let name: String = name.chars().map(|c| if "/\\:*?\"<>|".contains(c) || c.is_control() { '_' } else { c }).collect();
+    if name.trim().is_empty() { String::from("screenshot") } else { name }
+}
+
+/// Converts RGB565 pixels to 8 bit RGB.
+pub fn rgb565_to_rgb(pixels: &[u16]) -> Vec<u8> {
+    let expand = |v: u16, bits: u16| ((u32::from(v) * 255 + ((1 << bits) - 1) / 2) / ((1 << bits) - 1)) as u8;
+    pixels.iter().flat_map(|p| vec!(expand(p >> 11, 5), expand((p >> 5) & 0x3F, 6), expand(p & 0x1F, 5))).collect()
+}
+
+/// Encodes RGB pixels as 24 bit BMP.
+pub fn encode_bmp(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let row_size = (width as usize * 3 + 3) & !3;
+    let image_size = row_size * height as usize;
+    let mut bytes = Vec::with_capacity(54 + image_size);
+    bytes.extend_from_slice(b"BM");
+    bytes.extend_from_slice(&(54 + image_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&54u32.to_le_bytes());
+    bytes.extend_from_slice(&40u32.to_le_bytes());
+    bytes.extend_from_slice(&(width as i32).to_le_bytes());
+    bytes.extend_from_slice(&(height as i32).to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&24u16.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&(image_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&[0; 16]);
+    // Rows are stored bottom-up in BGR order
+    for row in rgb.chunks(width as usize * 3).rev() {
+        let start = bytes.len();
+        bytes.extend(row.chunks(3).flat_map(|p| vec!(p[2], p[1], p[0])));
+        bytes.resize(start + row_size, 0);
+    }
+    bytes
+}
+
+fn png_chunk(bytes: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(chunk_type);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encodes RGB pixels as PNG with stored deflate blocks.
+pub fn encode_png(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+    for row in rgb.chunks(width as usize * 3) {
+        // Filter type None
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec!(0x78, 0x01);
+    let blocks: Vec<&[u8]> = raw.chunks(0xFFFF).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in &raw {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+
+    let mut header = vec!();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGB, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut bytes, b"IHDR", &header);
+    png_chunk(&mut bytes, b"IDAT", &zlib);
+    png_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+/// Index in an 8x8 block of the coefficients in zigzag order
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const LUMINANCE_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29, 51, 87, 80, 62,
+    18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMINANCE_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99, 47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// Standard Huffman tables of the JPEG specification as number of codes per length and values
+const LUMINANCE_DC: ([u8; 16], &[u8]) = ([0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0], &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+const CHROMINANCE_DC: ([u8; 16], &[u8]) = ([0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0], &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+const LUMINANCE_AC: ([u8; 16], &[u8]) = ([0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d], &[
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+]);
+const CHROMINANCE_AC: ([u8; 16], &[u8]) = ([0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77], &[
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+]);
+
+/// Scales a quantization table like the IJG library, quality 50 results in the table of the specification.
+pub fn scale_quantization_table(table: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = u32::from(quality.clamp(1, 100));
+    let scale = if quality < 50 { 5000 / quality } else { 200 - quality * 2 };
+    let mut scaled = [0u8; 64];
+    for (s, t) in scaled.iter_mut().zip(table.iter()) {
+        *s = ((u32::from(*t) * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    scaled
+}
+
+/// Returns the code and its length for every value of a Huffman table.
+fn huffman_codes(table: &([u8; 16], &[u8])) -> [(u16, u8); 256] {
+    let mut codes = [(0u16, 0u8); 256];
+    let mut code = 0u16;
+    let mut values = table.1.iter();
+    for (i, count) in table.0.iter().enumerate() {
+        for _ in 0..*count {
+            codes[*values.next().unwrap() as usize] = (code, i as u8 + 1);
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u16, length: u8) {
+        self.buffer = self.buffer << length | (u32::from(value) & ((1 << length) - 1));
+        self.bits += length;
+        while self.bits >= 8 {
+            let byte = (self.buffer >> (self.bits - 8)) as u8;
+            self.bytes.push(byte);
+            // 0xFF is followed by a stuffed 0 in the entropy coded data
+            if byte == 0xFF {
+                self.bytes.push(0);
+            }
+            self.bits -= 8;
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.bits > 0 {
+            let padding = 8 - self.bits;
+            self.write((1 << padding) - 1, padding);
+        }
+    }
+}
+
+/// Returns the number of bits of a coefficient and the bits that are written for it.
+fn coefficient_bits(value: i32) -> (u8, u16) {
+    let magnitude = value.unsigned_abs();
+    let length = (32 - magnitude.leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (length, bits as u16)
+}
+
+fn forward_dct(block: &[f32]) -> [f32; 64] {
+    let mut cosines = [[0f32; 8]; 8];
+    for (x, row) in cosines.iter_mut().enumerate() {
+        for (u, c) in row.iter_mut().enumerate() {
+            let scale = if u == 0 { ::std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+            *c = scale * ((2 * x + 1) as f32 * u as f32 * ::std::f32::consts::PI / 16.0).cos() / 2.0;
+        }
+    }
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| block[y * 8 + x] * cosines[x][u]).sum();
+        }
+    }
+    let mut result = [0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            result[v * 8 + u] = (0..8).map(|y| rows[y * 8 + u] * cosines[y][v]).sum();
+        }
+    }
+    result
+}
+
+/// Encodes RGB pixels as baseline JPEG without chroma subsampling.
+pub fn encode_jpeg(rgb: &[u8], width: u32, height: u32, quality: u8) -> Vec<u8> {
+    let tables = [scale_quantization_table(&LUMINANCE_QUANTIZATION, quality), scale_quantization_table(&CHROMINANCE_QUANTIZATION, quality)];
+    let dc_codes = [huffman_codes(&LUMINANCE_DC), huffman_codes(&CHROMINANCE_DC)];
+    let ac_codes = [huffman_codes(&LUMINANCE_AC), huffman_codes(&CHROMINANCE_AC)];
+
+    let mut bytes = vec!(0xFF, 0xD8);
+    let segment = |bytes: &mut Vec<u8>, marker: u8, data: &[u8]| {
+        bytes.extend_from_slice(&[0xFF, marker]);
+        bytes.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        bytes.extend_from_slice(data);
+    };
+    segment(&mut bytes, 0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+    for (i, table) in tables.iter().enumerate() {
+        let mut data = vec!(i as u8);
+        data.extend(ZIGZAG.iter().map(|z| table[*z]));
+        segment(&mut bytes, 0xDB, &data);
+    }
+    let mut frame = vec!(8);
+    frame.extend_from_slice(&(height as u16).to_be_bytes());
+    frame.extend_from_slice(&(width as u16).to_be_bytes());
+    frame.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    segment(&mut bytes, 0xC0, &frame);
+    for &(class_and_id, table) in &[(0x00, &LUMINANCE_DC), (0x10, &LUMINANCE_AC), (0x01, &CHROMINANCE_DC), (0x11, &CHROMINANCE_AC)] {
+        let mut data = vec!(class_and_id);
+        data.extend_from_slice(&table.0);
+        data.extend_from_slice(table.1);
+        segment(&mut bytes, 0xC4, &data);
+    }
+    segment(&mut bytes, 0xDA, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+
+    let mut writer = BitWriter { bytes, buffer: 0, bits: 0 };
+    let mut previous_dc = [0i32; 3];
+    let (width, height) = (width as usize, height as usize);
+    for block_y in (0..height).step_by(8) {
+        for block_x in (0..width).step_by(8) {
+            let pixels: Vec<[f32; 3]> = (0..64).map(|i| {
+                // Pixels outside of the image repeat the last row or column
+                let x = (block_x + i % 8).min(width - 1);
+                let y = (block_y + i / 8).min(height - 1);
+                let p = &rgb[(y * width + x) * 3..];
+                let (r, g, b) = (f32::from(p[0]), f32::from(p[1]), f32::from(p[2]));
+                [
+                    0.299 * r + 0.587 * g + 0.114 * b - 128.0,
+                    -0.168_736 * r - 0.331_264 * g + 0.5 * b,
+                    0.5 * r - 0.418_688 * g - 0.081_312 * b,
+                ]
+            }).collect();
+            for c in 0..3 {
+                let t = if c == 0 { 0 } else { 1 };
+                let block: Vec<f32> = pixels.iter().map(|p| p[c]).collect();
+                let coefficients = forward_dct(&block);
+                let quantized: Vec<i32> = ZIGZAG.iter().map(|z| (coefficients[*z] / f32::from(tables[t][*z])).round() as i32).collect();
+
+                let (length, bits) = coefficient_bits(quantized[0] - previous_dc[c]);
+                previous_dc[c] = quantized[0];
+                let (code, code_length) = dc_codes[t][length as usize];
+                writer.write(code, code_length);
+                writer.write(bits, length);
+
+                let mut zeros = 0;
+                for &value in &quantized[1..] {
+                    if value == 0 {
+                        zeros += 1;
+                        continue;
+                    }
+                    while zeros > 15 {
+                        let (code, code_length) = ac_codes[t][0xF0];
+                        writer.write(code, code_length);
+                        zeros -= 16;
+                    }
+                    let (length, bits) = coefficient_bits(value);
+                    let (code, code_length) = ac_codes[t][(zeros << 4 | length) as usize];
+                    writer.write(code, code_length);
+                    writer.write(bits, length);
+                    zeros = 0;
+                }
+                if zeros > 0 {
+                    let (code, code_length) = ac_codes[t][0x00];
+                    writer.write(code, code_length);
+                }
+            }
+        }
+    }
+    writer.flush();
+    let mut bytes = writer.bytes;
+    bytes.extend_from_slice(&[0xFF, 0xD9]);
+    bytes
+}
+
+pub fn encode_screenshot(rgb: &[u8], width: u32, height: u32, options: &ScreenshotOptions) -> Vec<u8> {
+    match options.format {
+        ScreenshotFormat::BMP => encode_bmp(rgb, width, height),
+        ScreenshotFormat::PNG => encode_png(rgb, width, height),
+        ScreenshotFormat::JPEG => encode_jpeg(rgb, width, height, options.jpeg_quality),
+    }
+}
+
+/// Writes the encoded screenshot to a new file in the directory and returns its path. Existing files are never
+/// overwritten, a number is appended to the name instead.
+pub fn write_screenshot(dir: &Path, name: &str, format: ScreenshotFormat, contents: &[u8]) -> Result<PathBuf, String> {
+    for i in 1.. {
+        let file_name = if i == 1 { format!("{}.{}", name, format.extension()) } else { format!("{}-{}.{}", name, i, format.extension()) };
+        let path = dir.join(file_name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut f) => {
+                f.write_all(contents).map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))?;
+                return Ok(path);
+            },
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Error creating {}: {}", path.display(), e.kind())),
+        }
+    }
+    unreachable!()
+}
+
+/// Saves the RGB565 screen buffer in the stracciatella home and returns the path of the file. Returns null if saving
+/// failed, see `get_last_error`.
+#[no_mangle]
+pub extern fn save_screenshot(ptr: *const EngineOptions, pixels_ptr: *const u16, width: u32, height: u32, day: u32, x: i16, y: i16, z: i8) -> *mut c_char {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let pixels = unsafe { slice::from_raw_parts(pixels_ptr, width as usize * height as usize) };
+    let options = &engine_options.screenshots;
+    let context = ScreenshotContext {
+        time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        day,
+        sector: sector_name(x, y, z),
+    };
+    let contents = encode_screenshot(&rgb565_to_rgb(pixels), width, height, options);
+    let result = write_screenshot(&engine_options.stracciatella_home, &screenshot_file_name(&options.file_name, &context), options.format, &contents);
+    match result {
+        Ok(path) => {
+            set_last_error(None);
+            CString::new(path.to_string_lossy().into_owned()).unwrap().into_raw()
+        },
+        Err(e) => {
+            set_last_error(Some(e));
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use zip::crc32;
+    use super::{LUMINANCE_QUANTIZATION, ScreenshotContext, ScreenshotFormat, encode_bmp, encode_jpeg, encode_png,
+        rgb565_to_rgb, scale_quantization_table, screenshot_file_name, write_screenshot};
+
+    /// 2x2 image with a red, green, blue and white pixel
+    fn rgb() -> Vec<u8> {
+        rgb565_to_rgb(&[0xF800, 0x07E0, 0x001F, 0xFFFF])
+    }
+
+    #[test]
+    fn screenshot_file_name_should_replace_the_placeholders() {
+        let context = ScreenshotContext { time: 1520165045, day: 12, sector: Some(String::from("D13")) };
+
+        assert_eq!(screenshot_file_name("screenshot-{date}-{time}", &context), "screenshot-2018-03-04-120405");
+        assert_eq!(screenshot_file_name("day{day}-{sector}", &context), "day12-D13");
+        assert_eq!(screenshot_file_name("a/b:{sector}", &ScreenshotContext::default()), "a_b_");
+        assert_eq!(screenshot_file_name("", &context), "screenshot");
+    }
+
+    #[test]
+    fn encode_bmp_and_png_should_store_the_pixels() {
+        let bmp = encode_bmp(&rgb(), 2, 2);
+        assert_eq!(bmp.len(), 54 + 2 * 8);
+        // Bottom row first in BGR order, padded to 4 bytes
+        assert_eq!(&bmp[54..62], &[255, 0, 0, 255, 255, 255, 0, 0]);
+        assert_eq!(&bmp[62..70], &[0, 0, 255, 0, 255, 0, 0, 0]);
+
+        let png = encode_png(&rgb(), 2, 2);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes([png[29], png[30], png[31], png[32]]), crc32(&png[12..29]));
+        // IDAT with the zlib header and one stored block of two rows with filter byte
+        assert_eq!(&png[37..41], b"IDAT");
+        assert_eq!(&png[41..48], &[0x78, 0x01, 1, 14, 0, !14, 0xFF]);
+        assert_eq!(&png[48..62], &[0, 255, 0, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(&png[png.len() - 12..png.len() - 4], b"\0\0\0\0IEND");
+    }
+
+    #[test]
+    fn encode_jpeg_should_write_a_baseline_image() {
+        assert_eq!(scale_quantization_table(&LUMINANCE_QUANTIZATION, 50), LUMINANCE_QUANTIZATION);
+        assert_eq!(scale_quantization_table(&LUMINANCE_QUANTIZATION, 100), [1; 64]);
+        assert_eq!(scale_quantization_table(&LUMINANCE_QUANTIZATION, 1)[0], 255);
+
+        let jpeg = encode_jpeg(&rgb(), 2, 2, 90);
+
+        assert_eq!(&jpeg[..4], &[0xFF, 0xD8, 0xFF, 0xE0]);
+        assert_eq!(&jpeg[jpeg.len() - 2..], &[0xFF, 0xD9]);
+        let sof = jpeg.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        assert_eq!(&jpeg[sof + 4..sof + 10], &[8, 0, 2, 0, 2, 3]);
+        assert_ne!(encode_jpeg(&rgb(), 2, 2, 10), jpeg);
+    }
+
+    #[test]
+    fn write_screenshot_should_not_overwrite_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+
+        let first = write_screenshot(temp_dir.path(), "shot", ScreenshotFormat::PNG, b"1").unwrap();
+        let second = write_screenshot(temp_dir.path(), "shot", ScreenshotFormat::PNG, b"2").unwrap();
+
+        assert_eq!(first, temp_dir.path().join("shot.png"));
+        assert_eq!(second, temp_dir.path().join("shot-2.png"));
+        assert_eq!(fs::read(first).unwrap(), b"1");
+    }
+}
@@ -0,0 +1,188 @@
+//! Reader for the SLF libraries of the vanilla data.
+//!
+//! A library starts with a header of 532 bytes and ends with a directory of 280 bytes per entry. All numbers are
+//! little endian and names are NUL terminated with `\` as separator.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use errorcontext::{ErrorContext, ErrorKind};
+
+const HEADER_SIZE: usize = 532;
+const ENTRY_SIZE: usize = 280;
+const NAME_SIZE: usize = 256;
+const ENTRY_STATE_OK: u8 = 0;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlfEntry {
+    /// Path of the entry relative to the library path with `/` as separator
+    pub name: String,
+    pub offset: u32,
+    pub length: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlfLibrary {
+    pub path: PathBuf,
+    /// Folder the entries are in, e.g. `binarydata/`. Empty for libraries in the root of the data.
+    pub library_path: String,
+    pub entries: Vec<SlfEntry>,
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).replace('\\', "/")
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+}
+
+impl SlfLibrary {
+    pub fn open(path: &Path) -> Result<SlfLibrary, ErrorContext> {
+        let mut file = File::open(path).map_err(|s| ErrorContext::io(&s, path).wrap(ErrorKind::RESOURCES, format!("Error reading {}", path.display())))?;
+        SlfLibrary::read_from(path, &mut file)
+    }
+
+    /// Reads the header and the directory of a library, `path` is only used for the messages and for `read`.
+    ///
+    /// Libraries are read from mods, so entries outside of the library are an error.
+    pub fn read_from<R: Read + Seek>(path: &Path, reader: &mut R) -> Result<SlfLibrary, ErrorContext> {
+        let error = |s: ::std::io::Error| ErrorContext::io(&s, path).wrap(ErrorKind::RESOURCES, format!("Error reading {}", path.display()));
+        let invalid = || ErrorContext::new(ErrorKind::RESOURCES, format!("{} is not a valid library", path.display())).with_path(path);
+        let mut header = vec!(0u8; HEADER_SIZE);
+        reader.seek(SeekFrom::Start(0)).map_err(&error)?;
+        reader.read_exact(&mut header).map_err(&error)?;
+
+        let number_of_entries = u64::from(read_u32(&header[2 * NAME_SIZE..]));
+        let directory_size = number_of_entries * ENTRY_SIZE as u64;
+        let file_size = reader.seek(SeekFrom::End(0)).map_err(&error)?;
+        if directory_size > file_size - HEADER_SIZE as u64 {
+            return Err(invalid());
+        }
+
+        let mut directory = vec!(0u8; directory_size as usize);
+        reader.seek(SeekFrom::Start(file_size - directory_size)).map_err(&error)?;
+        reader.read_exact(&mut directory).map_err(&error)?;
+
+        let entries: Vec<SlfEntry> = directory.chunks(ENTRY_SIZE)
+            .filter(|e| e[NAME_SIZE + 8] == ENTRY_STATE_OK)
+            .map(|e| SlfEntry {
+                name: read_name(&e[..NAME_SIZE]),
+                offset: read_u32(&e[NAME_SIZE..]),
+                length: read_u32(&e[NAME_SIZE + 4..]),
+            })
+            .collect();
+        if entries.iter().any(|e| u64::from(e.offset) + u64::from(e.length) > file_size) {
+            return Err(invalid());
+        }
+
+        Ok(SlfLibrary {
+            path: path.to_path_buf(),
+            library_path: read_name(&header[NAME_SIZE..2 * NAME_SIZE]).to_lowercase(),
+            entries,
+        })
+    }
+
+    /// Returns the path of an entry relative to the data, e.g. `binarydata/credits.edt`.
+    pub fn full_name(&self, entry: &SlfEntry) -> String {
+        format!("{}{}", self.library_path, entry.name)
+    }
+
+    /// Finds an entry by its path relative to the data, ignoring the case.
+    pub fn find(&self, full_name: &str) -> Option<&SlfEntry> {
+        let full_name = full_name.to_lowercase();
+        self.entries.iter().find(|e| self.full_name(e).to_lowercase() == full_name)
+    }
+
+    pub fn read(&self, entry: &SlfEntry) -> Result<Vec<u8>, ErrorContext> {
+        let error = |s: ::std::io::Error| {
+            ErrorContext::io(&s, &self.path).wrap(ErrorKind::RESOURCES, format!("Error reading {} from {}", entry.name, self.path.display()))
+        };
+        let mut file = File::open(&self.path).map_err(&error)?;
+        let mut contents = vec!(0u8; entry.length as usize);
+        file.seek(SeekFrom::Start(u64::from(entry.offset))).map_err(&error)?;
+        file.read_exact(&mut contents).map_err(&error)?;
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempdir;
+
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use super::{ENTRY_SIZE, HEADER_SIZE, NAME_SIZE, SlfEntry, SlfLibrary};
+
+    fn write_u32(bytes: &mut [u8], value: u32) {
+        for (i, b) in bytes.iter_mut().take(4).enumerate() {
+            *b = (value >> (8 * i)) as u8;
+        }
+    }
+
+    /// Writes a library with the given entries. Entries without contents are marked as deleted.
+    pub fn write_library(path: &Path, library_path: &str, entries: &[(&str, Option<&[u8]>)]) {
+        let mut header = vec!(0u8; HEADER_SIZE);
+        header[NAME_SIZE..NAME_SIZE + library_path.len()].copy_from_slice(library_path.as_bytes());
+        write_u32(&mut header[2 * NAME_SIZE..], entries.len() as u32);
+
+        let mut data = vec!();
+        let mut directory = vec!();
+        for &(name, contents) in entries {
+            let mut entry = vec!(0u8; ENTRY_SIZE);
+            entry[..name.len()].copy_from_slice(name.as_bytes());
+            write_u32(&mut entry[NAME_SIZE..], (HEADER_SIZE + data.len()) as u32);
+            write_u32(&mut entry[NAME_SIZE + 4..], contents.map(|c| c.len()).unwrap_or(0) as u32);
+            entry[NAME_SIZE + 8] = if contents.is_some() { 0 } else { 0xFF };
+            data.extend_from_slice(contents.unwrap_or(&[]));
+            directory.extend(entry);
+        }
+
+        let mut file = File::create(path).unwrap();
+        file.write_all(&header).unwrap();
+        file.write_all(&data).unwrap();
+        file.write_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn open_should_read_all_valid_entries() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("binarydata.slf");
+        write_library(&path, "BINARYDATA\\", &[("CREDITS.EDT", Some(b"credits")), ("OLD.EDT", None), ("SUB\\A.DAT", Some(b"a"))]);
+
+        let library = SlfLibrary::open(&path).unwrap();
+
+        assert_eq!(library.library_path, "binarydata/");
+        assert_eq!(library.entries, vec!(
+            SlfEntry { name: String::from("CREDITS.EDT"), offset: HEADER_SIZE as u32, length: 7 },
+            SlfEntry { name: String::from("SUB/A.DAT"), offset: HEADER_SIZE as u32 + 7, length: 1 },
+        ));
+        let entry = library.find("binarydata/sub/a.dat").unwrap();
+        assert_eq!(library.full_name(entry), "binarydata/SUB/A.DAT");
+        assert_eq!(library.read(entry), Ok(b"a".to_vec()));
+        assert_eq!(library.read(library.find("BinaryData/Credits.edt").unwrap()), Ok(b"credits".to_vec()));
+    }
+
+    #[test]
+    fn open_should_fail_for_invalid_libraries() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("invalid.slf");
+        File::create(&path).unwrap().write_all(b"not a library").unwrap();
+
+        assert!(SlfLibrary::open(&path).is_err());
+        assert!(SlfLibrary::open(&temp_dir.path().join("missing.slf")).is_err());
+
+        let mut library = vec!();
+        let library_path = temp_dir.path().join("library.slf");
+        write_library(&library_path, "", &[("A.DAT", Some(b"a"))]);
+        File::open(&library_path).unwrap().read_to_end(&mut library).unwrap();
+        library[HEADER_SIZE + 1 + NAME_SIZE + 7] = 0xFF;
+        assert!(SlfLibrary::read_from(&library_path, &mut Cursor::new(library)).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Smoke test mode that boots the game, loads a bundled save and exits.
+//!
+//! Packagers can use it to check that a build works with a data dir. The save is expected in the `smoketest` folder
+//! of the extra data dir and is copied to the saved games folder before the game is started. The copy is removed
+//! again when the smoke test ends.
+
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use {EngineOptions, set_last_error};
+
+pub fn bundled_save_path(extra_data_dir: &Path) -> PathBuf {
+    extra_data_dir.join("smoketest").join("smoketest.sav")
+}
+
+/// Copies the bundled save to the given save file, creating the saved games folder if necessary.
+pub fn install_save(extra_data_dir: &Path, save_file: &Path) -> Result<(), String> {
+    let source = bundled_save_path(extra_data_dir);
+    if !source.is_file() {
+        return Err(format!("Smoke test save {} does not exist", source.display()));
+    }
+    if let Some(parent) = save_file.parent() {
+        fs::create_dir_all(parent).map_err(|s| format!("Error creating {}: {}", parent.display(), s.kind()))?;
+    }
+    fs::copy(&source, save_file)
+        .map(|_| ())
+        .map_err(|s| format!("Error copying smoke test save to {}: {}", save_file.display(), s.kind()))
+}
+
+/// Removes the copy of the bundled save, a missing copy is not an error.
+pub fn remove_save(save_file: &Path) -> Result<(), String> {
+    match fs::remove_file(save_file) {
+        Err(ref s) if s.kind() != ::std::io::ErrorKind::NotFound => {
+            Err(format!("Error removing smoke test save {}: {}", save_file.display(), s.kind()))
+        },
+        _ => Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern fn should_run_smoketest(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).run_smoketest
+}
+
+#[no_mangle]
+pub extern fn install_smoketest_save(extra_data_dir_ptr: *const c_char, save_file_ptr: *const c_char) -> bool {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let save_file = unsafe { CStr::from_ptr(save_file_ptr) }.to_string_lossy().into_owned();
+
+    match install_save(Path::new(&extra_data_dir), Path::new(&save_file)) {
+        Ok(()) => true,
+        Err(s) => {
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern fn remove_smoketest_save(save_file_ptr: *const c_char) -> bool {
+    let save_file = unsafe { CStr::from_ptr(save_file_ptr) }.to_string_lossy().into_owned();
+
+    match remove_save(Path::new(&save_file)) {
+        Ok(()) => true,
+        Err(s) => {
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    #[test]
+    fn install_save_should_copy_the_bundled_save() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let source = super::bundled_save_path(temp_dir.path());
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        File::create(&source).unwrap().write_all(b"save").unwrap();
+        let save_file = temp_dir.path().join("home/SavedGames/smoketest.sav");
+
+        assert_eq!(super::install_save(temp_dir.path(), &save_file), Ok(()));
+        assert_eq!(fs::read(&save_file).unwrap(), b"save");
+    }
+
+    #[test]
+    fn install_save_should_fail_without_bundled_save() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let result = super::install_save(temp_dir.path(), &temp_dir.path().join("smoketest.sav"));
+
+        assert_eq!(result, Err(format!("Smoke test save {} does not exist", super::bundled_save_path(temp_dir.path()).display())));
+    }
+
+    #[test]
+    fn remove_save_should_remove_the_copy() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let save_file = temp_dir.path().join("smoketest.sav");
+        File::create(&save_file).unwrap();
+
+        assert_eq!(super::remove_save(&save_file), Ok(()));
+        assert!(!save_file.exists());
+        assert_eq!(super::remove_save(&save_file), Ok(()));
+    }
+}
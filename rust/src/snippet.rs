@@ -0,0 +1,150 @@
+//! Settings snippets that players can share, e.g. on forums.
+//!
+//! A snippet is compact JSON with the options that differ from the defaults. Paths and options that only make sense
+//! on the computer of the player are never exported and are ignored when a snippet is imported.
+
+use std::ffi::{CStr, CString};
+
+use libc::c_char;
+use serde_json::Value;
+
+use {EngineOptions, replace_config_options};
+use events::{EngineEvent, emit_event};
+use gamedata::merge_json;
+use optionregistry::personal_json_keys;
+
+/// Removes a dotted key like `video_capture.ffmpeg` from a JSON object.
+fn remove_key(value: &mut Value, key: &str) {
+    let mut parts = key.splitn(2, '.');
+    if let (Some(first), Value::Object(ref mut map)) = (parts.next(), value) {
+        match parts.next() {
+            Some(rest) => if let Some(child) = map.get_mut(first) { remove_key(child, rest) },
+            None => { map.remove(first); }
+        }
+    }
+}
+
+/// Removes the options that are flagged as personal in the option registry.
+fn remove_personal_keys(value: &mut Value) {
+    for key in personal_json_keys() {
+        remove_key(value, key);
+    }
+}
+
+/// Removes all values that are equal to the default, so only the changed options remain.
+fn remove_defaults(value: &mut Value, default: &Value) {
+    if let (&mut Value::Object(ref mut map), &Value::Object(ref default_map)) = (value, default) {
+        let keys: Vec<String> = map.keys().cloned().collect();
+        for key in keys {
+            let remove = match (map.get_mut(&key), default_map.get(&key)) {
+                (Some(v), Some(d)) => {
+                    remove_defaults(v, d);
+                    v == d || *v == Value::Object(Default::default())
+                },
+                _ => false
+            };
+            if remove {
+                map.remove(&key);
+            }
+        }
+    }
+}
+
+pub fn export_snippet(engine_options: &EngineOptions) -> Result<String, String> {
+    let to_value = |o: &EngineOptions| serde_json::to_value(o).map_err(|s| format!("Error exporting settings: {}", s));
+    let mut value = to_value(engine_options)?;
+    remove_personal_keys(&mut value);
+    remove_defaults(&mut value, &to_value(&EngineOptions::default())?);
+    serde_json::to_string(&value).map_err(|s| format!("Error exporting settings: {}", s))
+}
+
+/// Merges the options of a snippet into the engine options. Personal options in the snippet are ignored.
+pub fn import_snippet(engine_options: &mut EngineOptions, snippet: &str) -> Result<(), String> {
+    let mut snippet_value: Value = serde_json::from_str(snippet).map_err(|s| format!("Error parsing settings: {}", s))?;
+    if !snippet_value.is_object() {
+        return Err(String::from("Error parsing settings: not a JSON object"));
+    }
+    remove_personal_keys(&mut snippet_value);
+
+    let mut value = serde_json::to_value(&*engine_options).map_err(|s| format!("Error importing settings: {}", s))?;
+    merge_json(&mut value, snippet_value);
+    let imported: EngineOptions = serde_json::from_value(value).map_err(|s| format!("Error importing settings: {}", s))?;
+
+    replace_config_options(engine_options, imported);
+    Ok(())
+}
+
+#[no_mangle]
+pub extern fn export_settings_snippet(ptr: *const EngineOptions) -> *mut c_char {
+    let snippet = export_snippet(unsafe_from_ptr!(ptr)).expect("Engine options should be exportable");
+    CString::new(snippet).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn import_settings_snippet(ptr: *mut EngineOptions, snippet_ptr: *const c_char) -> bool {
+    let snippet = unsafe { CStr::from_ptr(snippet_ptr) }.to_string_lossy().into_owned();
+    let imported = import_snippet(unsafe_from_ptr_mut!(ptr), &snippet).is_ok();
+    if imported {
+        emit_event(EngineEvent::CONFIG_RELOADED);
+    }
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use {EngineOptions, Resolution};
+
+    #[test]
+    fn export_snippet_should_only_contain_changed_options() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = PathBuf::from("/home/user/ja2");
+        engine_options.audio_device = String::from("USB Headset");
+        engine_options.video_capture.ffmpeg = String::from("/home/user/bin/ffmpeg");
+        engine_options.extra_data_dirs = vec!(PathBuf::from("/home/user/Dropbox/JA2"));
+        engine_options.save_game_dir = PathBuf::from("/home/user/Dropbox/JA2/saves");
+        engine_options.game_dir = PathBuf::from("/home/user/src/ja2-stracciatella");
+        engine_options.display_index = 1;
+        engine_options.window_x = Some(-1280);
+        engine_options.resolution = Resolution::new(1024, 768);
+        engine_options.gameplay.vanilla_fixes.interrupts.no_interrupts_from_unseen_enemies = true;
+
+        assert_eq!(super::export_snippet(&engine_options), Ok(String::from(
+            r#"{"gameplay":{"vanilla_fixes":{"interrupts":{"no_interrupts_from_unseen_enemies":true}}},"res":"1024x768"}"#
+        )));
+        assert_eq!(super::export_snippet(&EngineOptions::default()), Ok(String::from("{}")));
+    }
+
+    #[test]
+    fn import_snippet_should_merge_options_and_ignore_personal_ones() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = PathBuf::from("/home/user/ja2");
+        engine_options.stracciatella_home = PathBuf::from("/home/user/.ja2");
+        engine_options.start_in_fullscreen = true;
+
+        let snippet = r#"{ "data_dir": "/tmp/evil", "audio_driver": "dummy", "res": "1024x768", "gameplay": { "subtitles": false }, "video_capture": { "ffmpeg": "/tmp/evil/ffmpeg" }, "extra_data_dirs": ["/tmp/evil"], "save_game_dir": "/tmp/evil", "game_dir": "/tmp/evil", "display_index": 2, "window_x": 0, "window_y": 0 }"#;
+
+        assert_eq!(super::import_snippet(&mut engine_options, snippet), Ok(()));
+        assert_eq!(engine_options.resolution, Resolution::new(1024, 768));
+        assert!(!engine_options.gameplay.subtitles);
+        assert!(engine_options.start_in_fullscreen);
+        assert_eq!(engine_options.vanilla_data_dir, PathBuf::from("/home/user/ja2"));
+        assert_eq!(engine_options.audio_driver, "");
+        assert_eq!(engine_options.video_capture, Default::default());
+        assert!(engine_options.extra_data_dirs.is_empty());
+        assert_eq!(engine_options.save_game_dir, PathBuf::from(""));
+        assert_eq!(engine_options.game_dir, PathBuf::from(""));
+        assert_eq!((engine_options.display_index, engine_options.window_x, engine_options.window_y), (0, None, None));
+        assert_eq!(engine_options.stracciatella_home, PathBuf::from("/home/user/.ja2"));
+    }
+
+    #[test]
+    fn import_snippet_should_fail_with_invalid_options() {
+        let mut engine_options = EngineOptions::default();
+
+        assert!(super::import_snippet(&mut engine_options, r#"{ "res": "big" }"#).is_err());
+        assert!(super::import_snippet(&mut engine_options, "[1, 2]").is_err());
+        assert_eq!(engine_options, EngineOptions::default());
+    }
+}
@@ -0,0 +1,189 @@
+//! Checks that run before the engine starts, as configured with `startup_checks` in ja2.json.
+//!
+//! `FAST` reports the warnings of the config, missing game files and files that several enabled mods provide. `FULL`
+//! also verifies a sample of the hashes in `SHA256SUMS` of the vanilla data and the mods, see `dataverify`. The
+//! results are collected in a `PreflightReport`, so the engine and the launcher read them like the preflight checks.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::path::Path;
+
+use libc::c_char;
+
+use EngineOptions;
+use dataverify::{folders_to_verify, verify_checksum_sample};
+use preflight::{PreflightCheck, PreflightReport, PreflightStatus, check_data_files};
+use vfs::find_mod_dir;
+
+/// Number of files per folder whose hashes are verified by the full checks
+const CHECKSUM_SAMPLE_SIZE: usize = 16;
+
+/// Number of conflicting files that are listed per pair of mods
+const LISTED_CONFLICTS: usize = 5;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum StartupCheckLevel {
+    OFF,
+    FAST,
+    FULL,
+}
+
+fn check_config(engine_options: &EngineOptions) -> PreflightCheck {
+    let report = &engine_options.validation_report;
+    let mut warnings = report.warnings.clone();
+    warnings.extend(report.deprecations.iter().map(|d| format!("'{}' is deprecated", d.key)));
+    if warnings.is_empty() {
+        PreflightCheck::new("config", PreflightStatus::OK, String::from("ja2.json has no problems"))
+    } else {
+        PreflightCheck::new("config", PreflightStatus::WARNING, warnings.join("; "))
+    }
+}
+
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy().to_lowercase());
+        if entry.path().is_dir() {
+            collect_files(&entry.path(), &format!("{}/", name), files);
+        } else {
+            files.push(name);
+        }
+    }
+}
+
+/// Checks that the enabled mods exist and reports the files that a mod overrides in an earlier mod.
+fn check_mods(engine_options: &EngineOptions, extra_data_dir: &Path) -> PreflightCheck {
+    let mut missing = vec!();
+    let mut providers: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for mod_name in &engine_options.mods {
+        let mod_dir = find_mod_dir(extra_data_dir, &engine_options.stracciatella_home, mod_name);
+        if !mod_dir.is_dir() {
+            missing.push(mod_name.as_str());
+            continue;
+        }
+        let mut files = vec!();
+        collect_files(&mod_dir, "", &mut files);
+        for file in files {
+            providers.entry(file).or_default().push(mod_name);
+        }
+    }
+    if !missing.is_empty() {
+        return PreflightCheck::new("mods", PreflightStatus::ERROR, format!("Mods are missing: {}", missing.join(", ")));
+    }
+
+    let mut conflicts: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+    for (file, mods) in &providers {
+        for pair in mods.windows(2) {
+            conflicts.entry((pair[0], pair[1])).or_default().push(file);
+        }
+    }
+    if conflicts.is_empty() {
+        return PreflightCheck::new("mods", PreflightStatus::OK, format!("{} mods without conflicts", engine_options.mods.len()));
+    }
+    let messages: Vec<String> = conflicts.iter().map(|((lower, higher), files)| {
+        let more = if files.len() > LISTED_CONFLICTS { format!(" and {} more", files.len() - LISTED_CONFLICTS) } else { String::new() };
+        let listed: Vec<&str> = files.iter().take(LISTED_CONFLICTS).cloned().collect();
+        format!("{} overrides {} of {}{}", higher, listed.join(", "), lower, more)
+    }).collect();
+    PreflightCheck::new("mods", PreflightStatus::WARNING, messages.join("; "))
+}
+
+fn check_checksum_sample(engine_options: &EngineOptions, extra_data_dir: &Path) -> PreflightCheck {
+    let mut problems = vec!();
+    let mut checked_files = 0;
+    for dir in folders_to_verify(engine_options, extra_data_dir) {
+        match verify_checksum_sample(&dir, CHECKSUM_SAMPLE_SIZE) {
+            Ok(report) => {
+                checked_files += report.checked_files;
+                problems.extend(report.problems);
+            },
+            Err(s) => problems.push(s),
+        }
+    }
+    if problems.is_empty() {
+        PreflightCheck::new("checksums", PreflightStatus::OK, format!("{} sampled files are intact", checked_files))
+    } else {
+        PreflightCheck::new("checksums", PreflightStatus::ERROR, problems.join("; "))
+    }
+}
+
+/// Runs the checks of the level.
+pub fn run(engine_options: &EngineOptions, extra_data_dir: &Path, level: StartupCheckLevel) -> PreflightReport {
+    let mut checks = vec!();
+    if level != StartupCheckLevel::OFF {
+        checks.push(check_config(engine_options));
+        checks.push(check_data_files(&engine_options.vanilla_data_dir));
+        checks.push(check_mods(engine_options, extra_data_dir));
+    }
+    if level == StartupCheckLevel::FULL {
+        checks.push(check_checksum_sample(engine_options, extra_data_dir));
+    }
+    PreflightReport { checks }
+}
+
+/// Runs the checks of the configured level, the report is read and freed like the preflight report.
+#[no_mangle]
+pub extern fn run_startup_checks(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> *mut PreflightReport {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    Box::into_raw(Box::new(run(engine_options, Path::new(&extra_data_dir), engine_options.startup_checks)))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::Path;
+
+    use EngineOptions;
+    use preflight::PreflightStatus;
+    use super::{StartupCheckLevel, run};
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn run_should_only_check_what_the_level_asks_for() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = temp_dir.path().join("ja2");
+        engine_options.stracciatella_home = temp_dir.path().join("home");
+
+        let names = |level| run(&engine_options, temp_dir.path(), level).checks.iter().map(|c| c.name).collect::<Vec<_>>();
+
+        assert_eq!(names(StartupCheckLevel::OFF), Vec::<&str>::new());
+        assert_eq!(names(StartupCheckLevel::FAST), vec!("config", "data_files", "mods"));
+        assert_eq!(names(StartupCheckLevel::FULL), vec!("config", "data_files", "mods", "checksums"));
+    }
+
+    #[test]
+    fn run_should_report_config_warnings_and_mod_conflicts() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = temp_dir.path().join("home");
+        engine_options.mods = vec!(String::from("a"), String::from("b"));
+        engine_options.validation_report.warnings.push(String::from("'re' is not a valid option, did you mean 'res'?"));
+        write_file(&temp_dir.path().join("mods/a/data/Maps/A9.dat"), "a");
+        write_file(&temp_dir.path().join("mods/a/data/readme.txt"), "a");
+        write_file(&temp_dir.path().join("home/mods/b/data/maps/a9.dat"), "b");
+
+        let report = run(&engine_options, temp_dir.path(), StartupCheckLevel::FAST);
+
+        assert_eq!((report.checks[0].status, report.checks[0].message.as_str()), (PreflightStatus::WARNING, "'re' is not a valid option, did you mean 'res'?"));
+        assert_eq!((report.checks[2].status, report.checks[2].message.as_str()), (PreflightStatus::WARNING, "b overrides maps/a9.dat of a"));
+
+        engine_options.mods.push(String::from("c"));
+        let report = run(&engine_options, temp_dir.path(), StartupCheckLevel::FAST);
+        assert_eq!((report.checks[2].status, report.checks[2].message.as_str()), (PreflightStatus::ERROR, "Mods are missing: c"));
+        assert_eq!(report.status(), PreflightStatus::ERROR);
+    }
+}
@@ -0,0 +1,247 @@
+//! Parser for the STI images of the vanilla data.
+//!
+//! An image starts with a header of 64 bytes, followed by the palette of indexed images, the subimages of ETRLE
+//! compressed images, the pixel data and the application data. All numbers are little endian.
+//!
+//! The images are read from mods, so every offset and size is checked before it is used.
+
+const HEADER_SIZE: usize = 64;
+const SUBIMAGE_SIZE: usize = 16;
+const ID: &[u8] = b"STCI";
+
+pub const STCI_TRANSPARENT: u32 = 0x01;
+pub const STCI_ALPHA: u32 = 0x02;
+pub const STCI_RGB: u32 = 0x04;
+pub const STCI_INDEXED: u32 = 0x08;
+pub const STCI_ZLIB_COMPRESSED: u32 = 0x10;
+pub const STCI_ETRLE_COMPRESSED: u32 = 0x20;
+
+/// Limit for the size of decompressed subimages, far larger than any image of the game
+const MAX_SUBIMAGE_PIXELS: usize = 4096 * 4096;
+/// ETRLE control byte for a run of transparent pixels, the lower bits are the length of the run.
+const ETRLE_TRANSPARENT_RUN: u8 = 0x80;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StiSubImage {
+    /// Offset of the compressed pixels in the pixel data
+    pub data_offset: u32,
+    pub data_length: u32,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub height: u16,
+    pub width: u16,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StiImage {
+    pub flags: u32,
+    pub height: u16,
+    pub width: u16,
+    /// Bits per pixel
+    pub depth: u8,
+    /// Red, green and blue of each colour, empty for RGB images
+    pub palette: Vec<[u8; 3]>,
+    /// Empty if the image is not ETRLE compressed
+    pub subimages: Vec<StiSubImage>,
+    pub data: Vec<u8>,
+    pub app_data: Vec<u8>,
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+}
+
+/// Returns the next `length` bytes and advances the offset, or an error if there are not enough bytes.
+fn take<'a>(bytes: &'a [u8], offset: &mut usize, length: u64, what: &str) -> Result<&'a [u8], String> {
+    let available = (bytes.len() - *offset) as u64;
+    if length > available {
+        return Err(format!("STI image is truncated: {} needs {} bytes, {} are left", what, length, available));
+    }
+    let slice = &bytes[*offset..*offset + length as usize];
+    *offset += length as usize;
+    Ok(slice)
+}
+
+impl StiImage {
+    pub fn parse(bytes: &[u8]) -> Result<StiImage, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[..ID.len()] != ID {
+            return Err(String::from("STI image has an invalid header"));
+        }
+        let header = &bytes[..HEADER_SIZE];
+        let stored_size = read_u32(&header[8..]);
+        let flags = read_u32(&header[16..]);
+        if flags & STCI_ZLIB_COMPRESSED != 0 {
+            return Err(String::from("Zlib compressed STI images are not supported"));
+        }
+        let indexed = match (flags & STCI_RGB != 0, flags & STCI_INDEXED != 0) {
+            (true, false) => false,
+            (false, true) => true,
+            _ => return Err(format!("STI image has an unknown data organization (flags {:#x})", flags)),
+        };
+
+        let mut offset = HEADER_SIZE;
+        let mut palette = vec!();
+        let mut subimages = vec!();
+        if indexed {
+            let number_of_colours = read_u32(&header[24..]);
+            palette = take(bytes, &mut offset, u64::from(number_of_colours) * 3, "palette")?
+                .chunks(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect();
+            if flags & STCI_ETRLE_COMPRESSED != 0 {
+                let number_of_subimages = read_u16(&header[28..]);
+                subimages = take(bytes, &mut offset, u64::from(number_of_subimages) * SUBIMAGE_SIZE as u64, "subimages")?
+                    .chunks(SUBIMAGE_SIZE)
+                    .map(|s| StiSubImage {
+                        data_offset: read_u32(s),
+                        data_length: read_u32(&s[4..]),
+                        offset_x: read_u16(&s[8..]) as i16,
+                        offset_y: read_u16(&s[10..]) as i16,
+                        height: read_u16(&s[12..]),
+                        width: read_u16(&s[14..]),
+                    })
+                    .collect();
+            }
+        }
+        let data = take(bytes, &mut offset, u64::from(stored_size), "pixel data")?.to_vec();
+        let app_data = take(bytes, &mut offset, u64::from(read_u32(&header[48..])), "application data")?.to_vec();
+
+        for (i, s) in subimages.iter().enumerate() {
+            if u64::from(s.data_offset) + u64::from(s.data_length) > data.len() as u64 {
+                return Err(format!("Subimage {} of the STI image is outside of the pixel data", i));
+            }
+        }
+
+        Ok(StiImage {
+            flags,
+            height: read_u16(&header[20..]),
+            width: read_u16(&header[22..]),
+            depth: header[44],
+            palette,
+            subimages,
+            data,
+            app_data,
+        })
+    }
+
+    /// Decompresses a subimage of an ETRLE compressed image to one palette index per pixel, transparent pixels are 0.
+    pub fn decode_subimage(&self, index: usize) -> Result<Vec<u8>, String> {
+        let subimage = self.subimages.get(index).ok_or_else(|| format!("STI image has no subimage {}", index))?;
+        let start = subimage.data_offset as usize;
+        let compressed = &self.data[start..start + subimage.data_length as usize];
+        let width = usize::from(subimage.width);
+        let height = usize::from(subimage.height);
+        let error = |s: &str| format!("Subimage {} of the STI image is invalid: {}", index, s);
+        if width * height > MAX_SUBIMAGE_PIXELS {
+            return Err(error("it is too large"));
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut bytes = compressed.iter();
+        for _ in 0..height {
+            let row_start = pixels.len();
+            loop {
+                let control = *bytes.next().ok_or_else(|| error("the data ends within a row"))?;
+                if control == 0 {
+                    break;
+                }
+                let length = usize::from(control & !ETRLE_TRANSPARENT_RUN);
+                if pixels.len() - row_start + length > width {
+                    return Err(error("a row is longer than the width"));
+                }
+                if control & ETRLE_TRANSPARENT_RUN != 0 {
+                    pixels.resize(pixels.len() + length, 0);
+                } else {
+                    let literal = bytes.as_slice();
+                    if literal.len() < length {
+                        return Err(error("the data ends within a row"));
+                    }
+                    pixels.extend_from_slice(&literal[..length]);
+                    bytes = literal[length..].iter();
+                }
+            }
+            pixels.resize(row_start + width, 0);
+        }
+        Ok(pixels)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{STCI_ETRLE_COMPRESSED, STCI_INDEXED, STCI_RGB, StiImage};
+
+    fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+        bytes.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+
+    fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+        bytes.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+    }
+
+    /// Returns an indexed ETRLE image with a palette of 2 colours and one subimage of 3x2 pixels.
+    pub fn etrle_image() -> Vec<u8> {
+        let data = [0x81, 0x02, 1, 1, 0x00, 0x03, 1, 0, 1, 0x00];
+        let mut bytes = b"STCI".to_vec();
+        push_u32(&mut bytes, 6);
+        push_u32(&mut bytes, data.len() as u32);
+        push_u32(&mut bytes, 0);
+        push_u32(&mut bytes, STCI_INDEXED | STCI_ETRLE_COMPRESSED);
+        push_u16(&mut bytes, 2);
+        push_u16(&mut bytes, 3);
+        push_u32(&mut bytes, 2);
+        push_u16(&mut bytes, 1);
+        bytes.resize(44, 8);
+        bytes.resize(64, 0);
+        bytes[44] = 8;
+        bytes.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        push_u32(&mut bytes, 0);
+        push_u32(&mut bytes, data.len() as u32);
+        push_u16(&mut bytes, 0);
+        push_u16(&mut bytes, 0);
+        push_u16(&mut bytes, 2);
+        push_u16(&mut bytes, 3);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn parse_should_read_indexed_etrle_images() {
+        let image = StiImage::parse(&etrle_image()).unwrap();
+
+        assert_eq!((image.width, image.height, image.depth), (3, 2, 8));
+        assert_eq!(image.palette, vec!([0, 0, 0], [255, 255, 255]));
+        assert_eq!(image.subimages.len(), 1);
+        assert_eq!(image.decode_subimage(0), Ok(vec!(0, 1, 1, 1, 0, 1)));
+        assert!(image.decode_subimage(1).is_err());
+    }
+
+    #[test]
+    fn parse_should_fail_for_invalid_images() {
+        let bytes = etrle_image();
+        for length in 0..bytes.len() {
+            assert!(StiImage::parse(&bytes[..length]).is_err(), "truncated to {} bytes", length);
+        }
+
+        let mut rgb_and_indexed = bytes.clone();
+        rgb_and_indexed[16] |= STCI_RGB as u8;
+        assert!(StiImage::parse(&rgb_and_indexed).is_err());
+
+        let mut subimage_outside = bytes.clone();
+        subimage_outside[70] = 1;
+        assert!(StiImage::parse(&subimage_outside).is_err());
+    }
+
+    #[test]
+    fn decode_subimage_should_fail_for_invalid_rows() {
+        let mut bytes = etrle_image();
+        let length = bytes.len();
+        bytes[length - 5] = 0x04;
+        let image = StiImage::parse(&bytes).unwrap();
+
+        assert!(image.decode_subimage(0).is_err());
+    }
+}
@@ -1,8 +1,10 @@
 #![crate_type = "lib"]
 
 extern crate getopts;
+extern crate lewton;
 extern crate libc;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
@@ -12,6 +14,8 @@ extern crate winapi;
 extern crate user32;
 #[cfg(windows)]
 extern crate shell32;
+#[cfg(windows)]
+extern crate kernel32;
 
 use std::slice;
 use std::str;
@@ -19,13 +23,16 @@ use std::str::FromStr;
 use std::ptr;
 use std::fmt;
 use std::fmt::Display;
+use std::env;
 use std::fs;
 use std::ffi::{CStr, CString};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::default::Default;
+use std::collections::BTreeMap;
 use std::io::prelude::*;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::error::Error;
+use std::sync::Mutex;
 use serde::Deserializer;
 use serde::Deserialize;
 use serde::Serializer;
@@ -34,8 +41,88 @@ use serde::Serialize;
 use getopts::Options;
 use libc::{size_t, c_char};
 
+use gameplay::GameplayOptions;
+use saves::SaveOptions;
+use screenshot::ScreenshotOptions;
+use startupchecks::StartupCheckLevel;
+use videocapture::VideoCaptureOptions;
+use logger::{LogFormat, LogLevel};
+use errorcontext::{ErrorContext, ErrorKind};
+use messages::Message;
+use validation::ValidationReport;
+
+macro_rules! unsafe_from_ptr {
+    ($ptr:expr) => { unsafe { assert!(!$ptr.is_null()); &*$ptr } }
+}
+
+macro_rules! unsafe_from_ptr_mut {
+    ($ptr:expr) => { unsafe { assert!(!$ptr.is_null()); &mut *$ptr } }
+}
+
+pub mod animation;
+pub mod campaignlog;
+pub mod config;
+pub mod datadiff;
+pub mod dataimport;
+pub mod dataverify;
+pub mod datadir;
+pub mod defaults;
+pub mod deprecation;
+pub mod difficulty;
+pub mod errorcontext;
+pub mod events;
+pub mod fileplan;
+pub mod formats;
+pub mod fuzzing;
+pub mod gamedata;
+pub mod gameplay;
+pub mod home;
+pub mod inflate;
+pub mod installshield;
+pub mod interfacecheck;
+pub mod jobs;
+pub mod keybindings;
+pub mod legacyimport;
+pub mod lock;
+pub mod logger;
+pub mod messages;
+pub mod migration;
+pub mod modcatalog;
+pub mod modmanifest;
+pub mod modpackage;
+pub mod modtemplate;
+pub mod modwatcher;
+pub mod music;
+pub mod optionregistry;
+pub mod pathcheck;
+pub mod preflight;
+#[cfg(feature = "presence")]
+pub mod presence;
+pub mod replay;
+pub mod savegame;
+pub mod saves;
+pub mod screenshot;
+pub mod sha256;
+pub mod slf;
+pub mod smoketest;
+pub mod snippet;
+pub mod startupchecks;
+pub mod sti;
+pub mod temp;
+pub mod text;
+pub mod theme;
+pub mod translation;
+pub mod uilayout;
+pub mod unittests;
+pub mod validation;
+pub mod vfs;
+pub mod videocapture;
+pub mod zip;
+
+pub use home::find_stracciatella_home;
+
 #[cfg(not(windows))]
-static DATA_DIR_OPTION_EXAMPLE: &'static str = "/opt/ja2";
+const DATA_DIR_OPTION_EXAMPLE: &str = "/opt/ja2";
 #[cfg(not(windows))]
 static DEFAULT_JSON_CONTENT: &'static str = r##"{
     "help": "Put the directory to your original ja2 installation into the line below",
@@ -43,7 +130,7 @@ static DEFAULT_JSON_CONTENT: &'static str = r##"{
 }"##;
 
 #[cfg(windows)]
-static DATA_DIR_OPTION_EXAMPLE: &'static str = "C:\\JA2";
+const DATA_DIR_OPTION_EXAMPLE: &str = "C:\\JA2";
 #[cfg(windows)]
 static DEFAULT_JSON_CONTENT: &'static str = r##"{
    "help": "Put the directory to your original ja2 installation into the line below. Make sure to use double backslashes.",
@@ -129,28 +216,209 @@ impl Display for ScalingQuality {
     }
 }
 
-fn parse_resolution(resolution_str: &str) -> Result<(u16, u16), String> {
-    let mut resolutions = resolution_str.split("x").filter_map(|r_str| r_str.parse::<u16>().ok());
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Resolution {
+    pub width: u16,
+    pub height: u16,
+    /// Refresh rate of the fullscreen mode in Hz, the one of the desktop is used if `None`
+    pub refresh_rate: Option<u16>,
+    /// Whether ja2.json gets `{"width": 1280, "height": 720}` instead of `"1280x720"`. Set if ja2.json had the
+    /// object form, so configs of external tools keep it.
+    pub object_form: bool,
+}
+
+impl Resolution {
+    pub fn new(width: u16, height: u16) -> Resolution {
+        Resolution { width, height, refresh_rate: None, object_form: false }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.refresh_rate {
+            Some(r) => write!(f, "{}x{}@{}", self.width, self.height, r),
+            None => write!(f, "{}x{}", self.width, self.height),
+        }
+    }
+}
+
+fn parse_resolution(resolution_str: &str) -> Result<Resolution, String> {
+    let error = || String::from("Incorrect resolution format, should be WIDTHxHEIGHT or WIDTHxHEIGHT@REFRESHRATE.");
+    let (size_str, refresh_rate) = match resolution_str.split_once('@') {
+        Some((size_str, r_str)) => match r_str.parse::<u16>() {
+            Ok(r) if r > 0 => (size_str, Some(r)),
+            _ => return Err(error())
+        },
+        None => (resolution_str, None)
+    };
+    let mut resolutions = size_str.split("x").filter_map(|r_str| r_str.parse::<u16>().ok());
 
     match (resolutions.next(), resolutions.next()) {
-        (Some(x), Some(y)) => Ok((x, y)),
-        _ => Err(String::from("Incorrect resolution format, should be WIDTHxHEIGHT."))
+        (Some(x), Some(y)) => Ok(Resolution { width: x, height: y, refresh_rate, object_form: false }),
+        _ => Err(error())
     }
 }
 
-fn deserialize_resolution<'de, D>(deserializer: D) -> Result<(u16, u16), D::Error>
+/// Smallest resolution of the interface, see `UILayout::setScreenSize` of the engine.
+pub const MIN_RESOLUTION_WIDTH: u16 = 640;
+pub const MIN_RESOLUTION_HEIGHT: u16 = 480;
+
+pub fn validate_resolution(resolution: &Resolution) -> Result<(), String> {
+    if resolution.width < MIN_RESOLUTION_WIDTH || resolution.height < MIN_RESOLUTION_HEIGHT {
+        return Err(format!("Invalid resolution {}x{}, JA2 Stracciatella needs a resolution of at least {}x{}.", resolution.width, resolution.height, MIN_RESOLUTION_WIDTH, MIN_RESOLUTION_HEIGHT));
+    }
+    Ok(())
+}
+
+fn deserialize_resolution<'de, D>(deserializer: D) -> Result<Resolution, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let res = String::deserialize(deserializer)?;
-    parse_resolution(&res).map_err(|s| serde::de::Error::custom(s))
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct ResolutionObject {
+        width: u16,
+        height: u16,
+        refresh_rate: Option<u16>,
+    }
+
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => parse_resolution(&s).map_err(serde::de::Error::custom),
+        value @ serde_json::Value::Object(_) => {
+            let r = ResolutionObject::deserialize(value).map_err(|s| serde::de::Error::custom(format!("Incorrect resolution object: {}", s)))?;
+            if r.refresh_rate == Some(0) {
+                return Err(serde::de::Error::custom("Incorrect resolution object: refresh_rate has to be larger than 0"));
+            }
+            Ok(Resolution { width: r.width, height: r.height, refresh_rate: r.refresh_rate, object_form: true })
+        },
+        _ => Err(serde::de::Error::custom("Resolution should be a string like \"1280x720\" or an object like {\"width\": 1280, \"height\": 720}.")),
+    }
 }
 
-fn serialize_resolution<S>(&(x, y): &(u16, u16), serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_resolution<S>(resolution: &Resolution, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    String::serialize(&format!("{}x{}", x, y), serializer)
+    if resolution.object_form {
+        let mut object = json!({ "width": resolution.width, "height": resolution.height });
+        if let Some(r) = resolution.refresh_rate {
+            object["refresh_rate"] = json!(r);
+        }
+        return object.serialize(serializer);
+    }
+    String::serialize(&resolution.to_string(), serializer)
+}
+
+fn deserialize_audio_device<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(ref n) if n.is_u64() => Ok(n.to_string()),
+        _ => Err(serde::de::Error::custom("Audio device should be a device name or a device index.")),
+    }
+}
+
+fn validate_audio_sample_rate(rate: u32) -> Result<u32, String> {
+    if (8000..=192000).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(String::from("Incorrect sample rate, should be a value between 8000 and 192000 Hz."))
+    }
+}
+
+fn parse_audio_sample_rate(sample_rate_str: &str) -> Result<u32, String> {
+    sample_rate_str.parse::<u32>()
+        .map_err(|_| String::from("Incorrect sample rate, should be a value between 8000 and 192000 Hz."))
+        .and_then(validate_audio_sample_rate)
+}
+
+fn deserialize_audio_sample_rate<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let rate = u32::deserialize(deserializer)?;
+    validate_audio_sample_rate(rate).map_err(serde::de::Error::custom)
+}
+
+/// Lowest and highest factor for the brightness of the screen
+pub const BRIGHTNESS_RANGE: (f32, f32) = (0.5, 2.0);
+
+fn validate_brightness(brightness: f32) -> Result<f32, String> {
+    if (BRIGHTNESS_RANGE.0..=BRIGHTNESS_RANGE.1).contains(&brightness) {
+        Ok(brightness)
+    } else {
+        Err(String::from("Incorrect brightness, should be a value between 0.5 and 2.0."))
+    }
+}
+
+fn parse_brightness(brightness_str: &str) -> Result<f32, String> {
+    brightness_str.parse::<f32>()
+        .map_err(|_| String::from("Incorrect brightness, should be a value between 0.5 and 2.0."))
+        .and_then(validate_brightness)
+}
+
+fn deserialize_brightness<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let brightness = f32::deserialize(deserializer)?;
+    validate_brightness(brightness).map_err(serde::de::Error::custom)
+}
+
+/// Lowest and highest frame rate limit, 0 is allowed for no limit
+pub const MAX_FPS_RANGE: (u16, u16) = (15, 240);
+
+fn validate_max_fps(max_fps: u16) -> Result<u16, String> {
+    if max_fps == 0 || (MAX_FPS_RANGE.0..=MAX_FPS_RANGE.1).contains(&max_fps) {
+        Ok(max_fps)
+    } else {
+        Err(String::from("Incorrect frame rate limit, should be 0 for no limit or a value between 15 and 240."))
+    }
+}
+
+fn parse_max_fps(max_fps_str: &str) -> Result<u16, String> {
+    max_fps_str.parse::<u16>()
+        .map_err(|_| String::from("Incorrect frame rate limit, should be 0 for no limit or a value between 15 and 240."))
+        .and_then(validate_max_fps)
+}
+
+fn deserialize_max_fps<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max_fps = u16::deserialize(deserializer)?;
+    validate_max_fps(max_fps).map_err(serde::de::Error::custom)
+}
+
+/// Parses a window position like `100,50`, negative values are allowed for displays left of or above the primary one.
+fn parse_window_position(position_str: &str) -> Option<(i32, i32)> {
+    let mut parts = position_str.splitn(2, ',');
+    let x = parts.next()?.trim().parse::<i32>().ok()?;
+    let y = parts.next()?.trim().parse::<i32>().ok()?;
+    Some((x, y))
+}
+
+/// Resolves the configured audio device against the list of output devices reported by the engine.
+///
+/// The device can be given either by name or by index. Returns `None` if the OS default device should be used.
+pub fn resolve_audio_device(audio_device: &str, device_names: &[String]) -> Option<usize> {
+    if audio_device.is_empty() {
+        return None;
+    }
+
+    if let Some(i) = device_names.iter().position(|n| n == audio_device) {
+        return Some(i);
+    }
+    if let Some(i) = device_names.iter().position(|n| n.to_lowercase() == audio_device.to_lowercase()) {
+        return Some(i);
+    }
+
+    match audio_device.parse::<usize>() {
+        Ok(i) if i < device_names.len() => Some(i),
+        _ => None
+    }
 }
 
 fn default_window() -> bool { false }
@@ -159,140 +427,209 @@ fn default_window() -> bool { false }
 pub struct EngineOptions {
     #[serde(skip)]
     stracciatella_home: PathBuf,
+    /// Folder with the saved games, the stracciatella home unless the XDG base directories are used
+    #[serde(skip)]
+    saves_home: PathBuf,
     #[serde(rename = "data_dir")]
     vanilla_data_dir: PathBuf,
+    /// Whether symlinks in a data dir from the command line are resolved, a symlink farm needs the path as it was given
+    canonicalize_data_dir: bool,
+    /// Folder with the externalized data of stracciatella, empty for the default of the build, see `home::game_dir`
+    game_dir: PathBuf,
     mods: Vec<String>,
+    /// Folders with loose files that override the vanilla data, mounted below the mods
+    extra_data_dirs: Vec<PathBuf>,
+    /// Mods that are mounted below instead of above the extra data dirs, e.g. to let an HD asset pack override them
+    mods_below_extra_data_dirs: Vec<String>,
+    /// Folder for the saved games instead of `SavedGames` in the saves home, see `home::configured_save_dir`
+    save_game_dir: PathBuf,
     #[serde(rename ="res", serialize_with = "serialize_resolution", deserialize_with = "deserialize_resolution")]
-    resolution: (u16, u16),
+    resolution: Resolution,
     #[serde(rename = "resversion")]
     resource_version: ResourceVersion,
+    /// Language of the texts of the interface, None for the language of the resource version
+    game_text_language: Option<ResourceVersion>,
+    game_language: String,
+    /// Language of the launcher and of the error messages, empty for the language of the system
+    launcher_language: String,
     #[serde(skip)]
     show_help: bool,
     #[serde(skip)]
     run_unittests: bool,
     #[serde(skip)]
+    unittest_report: Option<PathBuf>,
+    #[serde(skip)]
+    run_smoketest: bool,
+    #[serde(skip)]
     run_editor: bool,
     #[serde(rename = "fullscreen")]
     start_in_fullscreen: bool,
     #[serde(skip, default = "default_window")]
     start_in_window: bool,
+    window_always_on_top: bool,
+    grab_mouse: bool,
+    pause_on_focus_loss: bool,
+    single_instance: bool,
 	#[serde(rename = "scaling")]
 	scaling_quality: ScalingQuality,
+    /// Factor for the brightness of the screen, 1.0 is the vanilla palette
+    #[serde(deserialize_with = "deserialize_brightness")]
+    brightness: f32,
+    /// Highest number of frames per second, 0 for no limit
+    #[serde(deserialize_with = "deserialize_max_fps")]
+    max_fps: u16,
+    /// Index of the display that shows the game window, 0 for the primary display
+    display_index: u16,
+    /// Position of the window relative to the display, null to center it
+    window_x: Option<i32>,
+    window_y: Option<i32>,
     #[serde(rename = "debug")]
     start_in_debug_mode: bool,
+    enable_cheats: bool,
+    #[serde(skip)]
+    random_seed: Option<u64>,
+    #[serde(skip)]
+    record_replay: bool,
+    #[serde(skip)]
+    replay_file: Option<PathBuf>,
     #[serde(rename = "nosound")]
     start_without_sound: bool,
+    #[serde(deserialize_with = "deserialize_audio_device")]
+    audio_device: String,
+    #[serde(deserialize_with = "deserialize_audio_sample_rate")]
+    audio_sample_rate: u32,
+    audio_driver: String,
+    music_pack: String,
+    /// Id of the theme for the interface, empty for the default interface
+    ui_theme: String,
+    gameplay: GameplayOptions,
+    screenshots: ScreenshotOptions,
+    video_capture: VideoCaptureOptions,
+    saves: SaveOptions,
+    log_format: LogFormat,
+    #[serde(deserialize_with = "logger::deserialize_log_filters")]
+    log_filters: BTreeMap<String, LogLevel>,
+    /// Whether key events of the campaign are written to the campaign log, see `campaignlog`
+    campaign_log: bool,
+    /// Whether the state of the game is shown in Discord, needs the `presence` feature
+    enable_rich_presence: bool,
+    /// Checks that run before the engine starts, see `startupchecks`
+    startup_checks: StartupCheckLevel,
+    #[serde(skip)]
+    validation_report: ValidationReport,
 }
 
 impl Default for EngineOptions {
     fn default() -> EngineOptions {
         EngineOptions {
             stracciatella_home: PathBuf::from(""),
+            saves_home: PathBuf::from(""),
             vanilla_data_dir: PathBuf::from(""),
+            canonicalize_data_dir: true,
+            game_dir: PathBuf::from(""),
             mods: vec!(),
-            resolution: (640, 480),
+            extra_data_dirs: vec!(),
+            mods_below_extra_data_dirs: vec!(),
+            save_game_dir: PathBuf::from(""),
+            resolution: Resolution::new(640, 480),
             resource_version: ResourceVersion::ENGLISH,
+            game_text_language: None,
+            game_language: String::from(""),
+            launcher_language: String::from(""),
             show_help: false,
             run_unittests: false,
+            unittest_report: None,
+            run_smoketest: false,
             run_editor: false,
             start_in_fullscreen: false,
             start_in_window: true,
+            window_always_on_top: false,
+            grab_mouse: false,
+            pause_on_focus_loss: true,
+            single_instance: false,
 			scaling_quality: ScalingQuality::PERFECT,
+            brightness: 1.0,
+            max_fps: 0,
+            display_index: 0,
+            window_x: None,
+            window_y: None,
             start_in_debug_mode: false,
+            enable_cheats: false,
+            random_seed: None,
+            record_replay: false,
+            replay_file: None,
             start_without_sound: false,
+            audio_device: String::from(""),
+            audio_sample_rate: 44100,
+            audio_driver: String::from(""),
+            music_pack: String::from(""),
+            ui_theme: String::from(""),
+            gameplay: GameplayOptions::default(),
+            screenshots: ScreenshotOptions::default(),
+            video_capture: VideoCaptureOptions::default(),
+            saves: SaveOptions::default(),
+            log_format: LogFormat::TEXT,
+            log_filters: BTreeMap::new(),
+            campaign_log: false,
+            enable_rich_presence: false,
+            startup_checks: StartupCheckLevel::FAST,
+            validation_report: ValidationReport::default(),
         }
     }
 }
 
+/// Returns the command line options, see `optionregistry`.
 pub fn get_command_line_options() -> Options {
-    let mut opts = Options::new();
-
-    opts.long_only(true);
-
-    opts.optmulti(
-        "",
-        "datadir",
-        "Set path for data directory",
-        DATA_DIR_OPTION_EXAMPLE
-    );
-    opts.optmulti(
-        "",
-        "mod",
-        "Start one of the game modifications. MOD_NAME is the name of modification, e.g. 'from-russia-with-love. See mods folder for possible options'.",
-        "MOD_NAME"
-    );
-    opts.optopt(
-        "",
-        "res",
-        "Screen resolution, e.g. 800x600. Default value is 640x480",
-        "WIDTHxHEIGHT"
-    );
-    opts.optopt(
-        "",
-        "resversion",
-        "Version of the game resources. Possible values: DUTCH, ENGLISH, FRENCH, GERMAN, ITALIAN, POLISH, RUSSIAN, RUSSIAN_GOLD. Default value is ENGLISH. RUSSIAN is for BUKA Agonia Vlasty release. RUSSIAN_GOLD is for Gold release",
-        "RUSSIAN_GOLD"
-    );
-    opts.optflag(
-        "",
-        "unittests",
-        "Perform unit tests. E.g. 'ja2.exe -unittests --gtest_output=\"xml:report.xml\" --gtest_repeat=2'");
-    opts.optflag(
-        "",
-        "editor",
-        "Start the map editor (Editor.slf is required)"
-    );
-    opts.optflag(
-        "",
-        "fullscreen",
-        "Start the game in the fullscreen mode"
-    );
-    opts.optflag(
-        "",
-        "nosound",
-        "Turn the sound and music off"
-    );
-    opts.optflag(
-        "",
-        "window",
-        "Start the game in a window"
-    );
-    opts.optflag(
-        "",
-        "debug",
-        "Enable Debug Mode"
-    );
-    opts.optflag(
-        "",
-        "help",
-        "print this help menu"
-    );
-
-    return opts;
-}
-
-fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<String> {
+    optionregistry::command_line_options()
+}
+
+fn canonical_data_dir(data_dir: &str) -> Option<PathBuf> {
+    let canonical = fs::canonicalize(PathBuf::from(data_dir)).ok()?;
+    // paths that are no valid Unicode can only come from Windows and keep the UNC prefix
+    let mut temp = match canonical.to_str() {
+        Some(s) => String::from(s),
+        None => return Some(canonical),
+    };
+    // remove UNC path prefix (Windows)
+    if temp.starts_with("\\\\") {
+        temp.drain(..2);
+        let pos = temp.find("\\").unwrap() + 1;
+        temp.drain(..pos);
+    }
+    Some(PathBuf::from(temp))
+}
+
+/// Returns the data dir relative to the working directory, without resolving symlinks.
+fn absolute_data_dir(data_dir: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(data_dir);
+    let absolute = if path.is_absolute() { path } else { env::current_dir().ok()?.join(path) };
+    if absolute.is_dir() { Some(absolute) } else { None }
+}
+
+fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<Message> {
     let opts = get_command_line_options();
+    // Flags of googletest are left for the unit tests of the engine
+    let (gtest_args, args): (Vec<String>, Vec<String>) = args.into_iter()
+        .partition(|a| a.trim_start_matches('-').starts_with("gtest_"));
 
     match opts.parse(&args[1..]) {
         Ok(m) => {
             if m.free.len() > 0 {
-                return Some(format!("Unknown arguments: '{}'.", m.free.join(" ")));
+                return Some(Message::new("unknown_arguments", vec!(m.free.join(" "))));
             }
 
             if let Some(s) = m.opt_str("datadir") {
-                match fs::canonicalize(PathBuf::from(s)) {
-                    Ok(s) => {
-                        let mut temp = String::from(s.to_str().expect("Should not happen"));
-                        // remove UNC path prefix (Windows)
-                        if temp.starts_with("\\\\") {
-                            temp.drain(..2);
-                            let pos = temp.find("\\").unwrap() + 1;
-                            temp.drain(..pos);
-                        }
-                        engine_options.vanilla_data_dir = PathBuf::from(temp)
-                    },
-                    Err(_) => return Some(String::from("Please specify an existing datadir."))
+                let data_dir = if engine_options.canonicalize_data_dir { canonical_data_dir(&s) } else { absolute_data_dir(&s) };
+                match data_dir {
+                    Some(d) => engine_options.vanilla_data_dir = datadir::normalize_data_dir(&d),
+                    None => return Some(Message::new("nonexistent_data_dir", vec!()))
+                };
+            }
+
+            if let Some(s) = m.opt_str("gamedir") {
+                match absolute_data_dir(&s) {
+                    Some(d) => engine_options.game_dir = d,
+                    None => return Some(Message::new("nonexistent_game_dir", vec!(s)))
                 };
             }
 
@@ -300,12 +637,20 @@ fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<S
                 engine_options.mods = m.opt_strs("mod");
             }
 
+            if !m.opt_strs("extradatadir").is_empty() {
+                engine_options.extra_data_dirs = m.opt_strs("extradatadir").into_iter().map(PathBuf::from).collect();
+            }
+
+            if let Some(s) = m.opt_str("savegamedir") {
+                engine_options.save_game_dir = PathBuf::from(s);
+            }
+
             if let Some(s) = m.opt_str("res") {
                 match parse_resolution(&s) {
                     Ok(res) => {
                         engine_options.resolution = res;
                     },
-                    Err(s) => return Some(s)
+                    Err(_) => return Some(Message::new("incorrect_resolution", vec!()))
                 }
             }
 
@@ -314,7 +659,109 @@ fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<S
                     Ok(resource_version) => {
                         engine_options.resource_version = resource_version
                     },
-                    Err(str) => return Some(str)
+                    Err(_) => return Some(Message::new("unknown_resource_version", vec!(s)))
+                }
+            }
+
+            if let Some(s) = m.opt_str("scaling") {
+                match ScalingQuality::from_str(&s) {
+                    Ok(scaling_quality) => {
+                        engine_options.scaling_quality = scaling_quality
+                    },
+                    Err(_) => return Some(Message::new("unknown_scaling", vec!(s)))
+                }
+            }
+
+            if let Some(s) = m.opt_str("textlanguage") {
+                match ResourceVersion::from_str(&s) {
+                    Ok(text_language) => {
+                        engine_options.game_text_language = Some(text_language)
+                    },
+                    Err(_) => return Some(Message::new("unknown_text_language", vec!(s)))
+                }
+            }
+
+            if let Some(s) = m.opt_str("language") {
+                engine_options.game_language = s;
+            }
+
+            if let Some(s) = m.opt_str("audiodevice") {
+                engine_options.audio_device = s;
+            }
+
+            if let Some(s) = m.opt_str("samplerate") {
+                match parse_audio_sample_rate(&s) {
+                    Ok(rate) => {
+                        engine_options.audio_sample_rate = rate;
+                    },
+                    Err(_) => return Some(Message::new("incorrect_sample_rate", vec!()))
+                }
+            }
+
+            if let Some(s) = m.opt_str("brightness") {
+                match parse_brightness(&s) {
+                    Ok(brightness) => {
+                        engine_options.brightness = brightness;
+                    },
+                    Err(_) => return Some(Message::new("incorrect_brightness", vec!()))
+                }
+            }
+
+            if let Some(s) = m.opt_str("maxfps") {
+                match parse_max_fps(&s) {
+                    Ok(max_fps) => {
+                        engine_options.max_fps = max_fps;
+                    },
+                    Err(_) => return Some(Message::new("incorrect_max_fps", vec!()))
+                }
+            }
+
+            if let Some(s) = m.opt_str("display") {
+                match s.parse::<u16>() {
+                    Ok(display_index) => {
+                        engine_options.display_index = display_index;
+                    },
+                    Err(_) => return Some(Message::new("incorrect_display_index", vec!(s)))
+                }
+            }
+
+            if let Some(s) = m.opt_str("windowpos") {
+                match parse_window_position(&s) {
+                    Some((x, y)) => {
+                        engine_options.window_x = Some(x);
+                        engine_options.window_y = Some(y);
+                    },
+                    None => return Some(Message::new("incorrect_window_position", vec!(s)))
+                }
+            }
+
+            if let Some(s) = m.opt_str("audiodriver") {
+                engine_options.audio_driver = s;
+            }
+
+            if let Some(s) = m.opt_str("seed") {
+                match s.parse::<u64>() {
+                    Ok(seed) => {
+                        engine_options.random_seed = Some(seed);
+                    },
+                    Err(_) => return Some(Message::new("incorrect_seed", vec!()))
+                }
+            }
+
+            if m.opt_present("record") {
+                engine_options.record_replay = true;
+            }
+
+            if let Some(s) = m.opt_str("replay") {
+                engine_options.replay_file = Some(PathBuf::from(s));
+            }
+
+            if let Some(s) = m.opt_str("logformat") {
+                match LogFormat::from_str(&s) {
+                    Ok(log_format) => {
+                        engine_options.log_format = log_format
+                    },
+                    Err(_) => return Some(Message::new("unknown_log_format", vec!(s)))
                 }
             }
 
@@ -325,6 +772,11 @@ fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<S
 
             if m.opt_present("unittests") {
                 engine_options.run_unittests = true;
+                engine_options.unittest_report = unittests::gtest_report_path(&gtest_args, &args[0]);
+            }
+
+            if m.opt_present("smoketest") {
+                engine_options.run_smoketest = true;
             }
 
             if m.opt_present("editor") {
@@ -343,120 +795,323 @@ fn parse_args(engine_options: &mut EngineOptions, args: Vec<String>) -> Option<S
                 engine_options.start_in_window = true;
             }
 
+            if m.opt_present("alwaysontop") {
+                engine_options.window_always_on_top = true;
+            }
+
+            if m.opt_present("grabmouse") {
+                engine_options.grab_mouse = true;
+            }
+
             if m.opt_present("debug") {
                 engine_options.start_in_debug_mode = true;
             }
 
+            if m.opt_present("cheats") {
+                engine_options.enable_cheats = true;
+            }
+
             return None;
         }
-        Err(f) => Some(f.to_string())
+        Err(f) => Some(getopts_message(f))
+    }
+}
+
+fn getopts_message(fail: getopts::Fail) -> Message {
+    match fail {
+        getopts::Fail::ArgumentMissing(o) => Message::new("argument_missing", vec!(o)),
+        getopts::Fail::UnrecognizedOption(o) => Message::new("unrecognized_option", vec!(o)),
+        getopts::Fail::OptionMissing(o) => Message::new("option_missing", vec!(o)),
+        getopts::Fail::OptionDuplicated(o) => Message::new("option_duplicated", vec!(o)),
+        getopts::Fail::UnexpectedArgument(o) => Message::new("unexpected_argument", vec!(o)),
     }
 }
 
 fn build_json_config_location(stracciatella_home: &PathBuf) -> PathBuf {
-    let mut path = PathBuf::from(stracciatella_home);
-    path.push("ja2.json");
-    return path;
+    home::config_file_path(stracciatella_home)
 }
 
-pub fn ensure_json_config_existence(stracciatella_home: PathBuf) -> Result<PathBuf, String> {
-    macro_rules! make_string_err { ($msg:expr) => { $msg.map_err(|why| format!("! {:?}", why.kind())) }; }
+/// Returns the contents of a new ja2.json. Inside a sandbox a data dir the game can access is suggested.
+fn default_json_content() -> String {
+    match home::suggested_data_dir(home::detect_sandbox(&home::read_env_var), &home::read_env_var) {
+        Some(data_dir) => serde_json::to_string_pretty(&json!({
+            "help": "Put the directory to your original ja2 installation into the line below. The game can only access other directories if you allow it in the sandbox settings",
+            "data_dir": data_dir
+        })).unwrap(),
+        None => String::from(DEFAULT_JSON_CONTENT)
+    }
+}
 
+/// Returns the contents of a new ja2.json with the settings of a `ja2.ini` of an older version in the home.
+fn legacy_json_content(stracciatella_home: &Path) -> Option<String> {
+    let ini_path = config::legacy::find_legacy_ini(stracciatella_home)?;
+    match config::legacy::import_legacy_ini_file(&ini_path) {
+        Ok(import) => {
+            for warning in &import.warnings {
+                logger::log(LogLevel::WARNING, "config", &format!("Importing {}: {}", ini_path.display(), warning));
+            }
+            serde_json::to_string_pretty(&import.engine_options).ok()
+        },
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "config", &format!("Error importing the legacy config: {}", s));
+            None
+        }
+    }
+}
+
+pub fn ensure_json_config_existence(stracciatella_home: PathBuf) -> Result<PathBuf, ErrorContext> {
     let path = build_json_config_location(&stracciatella_home);
 
     if !stracciatella_home.exists() {
-        try!(make_string_err!(fs::create_dir_all(&stracciatella_home)));
+        fs::create_dir_all(&stracciatella_home).map_err(|s| {
+            ErrorContext::io(&s, &stracciatella_home).wrap(ErrorKind::CONFIG, format!("Error creating the home dir {}", stracciatella_home.display()))
+        })?;
     }
 
     if !path.is_file() {
-        let mut f = try!(make_string_err!(File::create(path)));
-        try!(make_string_err!(f.write_all(DEFAULT_JSON_CONTENT.as_bytes())));
+        File::create(&path)
+            .and_then(|mut f| f.write_all(legacy_json_content(&stracciatella_home).unwrap_or_else(default_json_content).as_bytes()))
+            .map_err(|s| ErrorContext::io(&s, &path).wrap(ErrorKind::CONFIG, "Error creating ja2.json config file"))?;
     }
 
     return Ok(stracciatella_home);
 }
 
+/// Replaces `//` and `/* */` comments outside of strings with spaces, so errors of serde still point to the right line and column.
+pub fn strip_json_comments(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    stripped.push(escaped);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            },
+            ('/', Some('/')) => {
+                stripped.push(' ');
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    stripped.push(' ');
+                    chars.next();
+                }
+            },
+            ('/', Some('*')) => {
+                stripped.push_str("  ");
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    stripped.push(if next == '\n' { '\n' } else { ' ' });
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            },
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
 
-pub fn parse_json_config(stracciatella_home: PathBuf) -> Result<EngineOptions, String> {
+/// Reads ja2.json, `//` and `/* */` comments are allowed.
+pub fn parse_json_config(stracciatella_home: PathBuf) -> Result<EngineOptions, Message> {
     let path = build_json_config_location(&stracciatella_home);
-    return File::open(path).map_err(|s| format!("Error reading ja2.json config file: {}", s.description()))
-        .and_then(|f| serde_json::from_reader(f).map_err(|s| format!("Error parsing ja2.json config file: {}", s)))
-        .map(|mut engine_options: EngineOptions| {
-            engine_options.stracciatella_home = stracciatella_home;
-            engine_options
-        });
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| lock::lock_file(&f, false).and_then(|_| f.read_to_string(&mut contents)))
+        .map_err(|s| Message::new("config_read_error", vec!(s.kind().to_string())))?;
+    let contents = strip_json_comments(&contents);
+
+    let mut config: serde_json::Value = serde_json::from_str(&contents).map_err(|s| Message::new("config_parse_error", vec!(s.to_string())))?;
+    let deprecations = deprecation::migrate_deprecated_options(&mut config, deprecation::DEPRECATED_OPTIONS);
+    // Parsing the text keeps the line numbers in errors, they are lost if deprecated options were moved
+    let parsed = if deprecations.is_empty() { serde_json::from_str(&contents) } else { serde_json::from_value(config.clone()) };
+    let mut engine_options: EngineOptions = parsed.map_err(|s| Message::new("config_parse_error", vec!(s.to_string())))?;
+
+    engine_options.saves_home = stracciatella_home.clone();
+    engine_options.stracciatella_home = stracciatella_home;
+    engine_options.vanilla_data_dir = datadir::normalize_data_dir(&engine_options.vanilla_data_dir);
+    engine_options.validation_report.warnings.extend(validation::lint_json_config(&config));
+    engine_options.validation_report.deprecations = deprecations;
+    Ok(engine_options)
+}
+
+/// Replaces the options that are part of the config. Options that are only set on the command line stay as they are.
+pub fn replace_config_options(engine_options: &mut EngineOptions, config: EngineOptions) {
+    *engine_options = EngineOptions {
+        stracciatella_home: engine_options.stracciatella_home.clone(),
+        saves_home: engine_options.saves_home.clone(),
+        show_help: engine_options.show_help,
+        run_unittests: engine_options.run_unittests,
+        unittest_report: engine_options.unittest_report.clone(),
+        run_smoketest: engine_options.run_smoketest,
+        run_editor: engine_options.run_editor,
+        start_in_window: engine_options.start_in_window,
+        random_seed: engine_options.random_seed,
+        record_replay: engine_options.record_replay,
+        replay_file: engine_options.replay_file.clone(),
+        validation_report: engine_options.validation_report.clone(),
+        ..config
+    };
+}
+
+/// Reads ja2.json again, e.g. after the launcher changed it. Options from the command line are not applied again.
+pub fn reload_json_config(engine_options: &mut EngineOptions) -> Result<(), String> {
+    let config = parse_json_config(engine_options.stracciatella_home.clone())
+        .map_err(|m| m.localize(&messages::message_language(&engine_options.launcher_language, &home::read_env_var)))?;
+    let validation_report = config.validation_report.clone();
+    replace_config_options(engine_options, config);
+    engine_options.validation_report = validation_report;
+    Ok(())
 }
 
+/// Writes ja2.json from the engine options. Comments of the previous file are not kept.
 pub fn write_json_config(engine_options: &EngineOptions) -> Result<(), String> {
     let json = serde_json::to_string_pretty(engine_options).map_err(|s| format!("Error creating contents of ja2.json config file: {}", s.description()))?;
     let path = build_json_config_location(&engine_options.stracciatella_home);
-    let mut f = File::create(path).map_err(|s| format!("Error creating ja2.json config file: {}", s.description()))?;
+    let mut f = OpenOptions::new().write(true).create(true).truncate(false).open(path).map_err(|s| format!("Error creating ja2.json config file: {}", s.description()))?;
 
-    f.write_all(json.as_bytes()).map_err(|s| format!("Error creating ja2.json config file: {}", s.description()))
+    // Truncate only after the lock is held, a reader could see an empty file otherwise
+    lock::lock_file(&f, true)
+        .and_then(|_| f.set_len(0))
+        .and_then(|_| f.write_all(json.as_bytes()))
+        .map_err(|s| format!("Error creating ja2.json config file: {}", s.description()))
 }
 
-#[cfg(not(windows))]
-pub fn find_stracciatella_home() -> Result<PathBuf, String> {
-    use std::env;
+/// Exit codes of the process if building the engine options fails, based on sysexits.h.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum OptionsExitCode {
+    HELP_SHOWN = 0,
+    INVALID_ARGUMENTS = 64,
+    INVALID_CONFIG = 65,
+    INVALID_DATA_DIR = 66,
+    HOME_DIR_ERROR = 73,
+}
 
-    match env::home_dir() {
-        Some(mut path) => {
-            path.push(".ja2");
-            return Ok(path);
-        },
-        None => Err(String::from("Could not find home directory")),
+#[derive(Debug, PartialEq, Clone)]
+pub struct OptionsError {
+    pub exit_code: OptionsExitCode,
+    pub message: String,
+    /// Layers and files that failed, if known
+    pub context: Option<ErrorContext>,
+}
+
+impl OptionsError {
+    fn new(exit_code: OptionsExitCode, message: String) -> OptionsError {
+        OptionsError { exit_code, message, context: None }
+    }
+
+    fn with_context(exit_code: OptionsExitCode, context: ErrorContext) -> OptionsError {
+        OptionsError { exit_code, message: context.to_string(), context: Some(context) }
+    }
+
+    /// Returns the diagnostic of the context, the message if there is none.
+    pub fn diagnostic(&self) -> String {
+        self.context.as_ref().map(ErrorContext::diagnostic).unwrap_or_else(|| self.message.clone())
     }
 }
 
-#[cfg(windows)]
-pub fn find_stracciatella_home() -> Result<PathBuf, String> {
-    use shell32::SHGetFolderPathW;
-    use winapi::shlobj::{CSIDL_PERSONAL, CSIDL_FLAG_CREATE};
-    use winapi::minwindef::MAX_PATH;
-    use std::ffi::OsString;
-    use std::os::windows::ffi::OsStringExt;
-
-    let mut home: [u16; MAX_PATH] = [0; MAX_PATH];
-
-    return match unsafe { SHGetFolderPathW(ptr::null_mut(), CSIDL_PERSONAL | CSIDL_FLAG_CREATE, ptr::null_mut(), 0, home.as_mut_ptr()) } {
-        0 => {
-            let home_trimmed: Vec<u16> = home.iter().take_while(|x| **x != 0).map(|x| *x).collect();
-
-            return match OsString::from_wide(&home_trimmed).to_str() {
-                Some(s) => {
-                    let mut buf = PathBuf::from(s);
-                    buf.push("JA2");
-                    return Ok(buf);
-                },
-                None => Err(format!("Could not decode documents folder string."))
-            }
-        },
-        i => Err(format!("Could not get documents folder: {}", i))
-    };
+/// Exit code of the last failure in `create_engine_options`, `HELP_SHOWN` until something fails.
+static LAST_OPTIONS_EXIT_CODE: Mutex<OptionsExitCode> = Mutex::new(OptionsExitCode::HELP_SHOWN);
+
+/// Message of the last failed call of a function that reports its errors with `get_last_error`.
+static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Context of the last error, if the failed function had one.
+static LAST_ERROR_CONTEXT: Mutex<Option<ErrorContext>> = Mutex::new(None);
+
+pub fn set_last_error(error: Option<String>) {
+    *LAST_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = error;
+    *LAST_ERROR_CONTEXT.lock().unwrap_or_else(|e| e.into_inner()) = None;
 }
 
-pub fn build_engine_options_from_env_and_args(args: Vec<String>) -> Result<EngineOptions, String> {
-    let home_dir = find_stracciatella_home().and_then(|h| ensure_json_config_existence(h))?;
-    let mut engine_options = parse_json_config(home_dir)?;
+pub fn set_last_error_context(error: ErrorContext) {
+    set_last_error(Some(error.to_string()));
+    *LAST_ERROR_CONTEXT.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+}
+
+pub fn build_engine_options_from_env_and_args(args: Vec<String>) -> Result<EngineOptions, OptionsError> {
+    let portable_home_dirs = home::portable_home_dirs(home::wants_portable_mode(&args), home::executable_dir().as_deref());
+    let home_dirs = portable_home_dirs.and_then(|d| d.map_or_else(home::find_home_dirs, Ok))
+        .map_err(|s| OptionsError::with_context(OptionsExitCode::HOME_DIR_ERROR, ErrorContext::new(ErrorKind::CONFIG, s)))?;
+    build_engine_options_with_home(home_dirs, args)
+}
 
-    match parse_args(&mut engine_options, args) {
-        None => Ok(()),
-        Some(str) => Err(str)
-    }?;
+/// Builds the engine options from ja2.json in the given home and the arguments, without looking for the home in the
+/// environment. Tools and tests that keep their own home use this.
+pub fn build_engine_options_with_home(home_dirs: home::HomeDirs, args: Vec<String>) -> Result<EngineOptions, OptionsError> {
+    let home_dir = ensure_json_config_existence(home_dirs.home)
+        .map_err(|c| OptionsError::with_context(OptionsExitCode::HOME_DIR_ERROR, c))?;
+    // ja2.json can only be reported in the language of the system, as the launcher language is part of it
+    let mut engine_options = parse_json_config(home_dir.clone()).map_err(|m| {
+        let message = m.localize(&messages::system_language(&home::read_env_var));
+        OptionsError::with_context(OptionsExitCode::INVALID_CONFIG, ErrorContext::new(ErrorKind::CONFIG, message).with_path(&home::config_file_path(&home_dir)))
+    })?;
+    engine_options.saves_home = home_dirs.saves_home;
+    let language = messages::message_language(&engine_options.launcher_language, &home::read_env_var);
+
+    if let Some(m) = parse_args(&mut engine_options, args) {
+        let exit_code = if m.key == "nonexistent_data_dir" || m.key == "nonexistent_game_dir" { OptionsExitCode::INVALID_DATA_DIR } else { OptionsExitCode::INVALID_ARGUMENTS };
+        return Err(OptionsError::new(exit_code, m.localize(&language)));
+    }
+
+    let game_dir = &engine_options.game_dir;
+    if !game_dir.as_os_str().is_empty() && !game_dir.is_dir() {
+        let message = Message::new("nonexistent_game_dir", vec!(game_dir.display().to_string())).localize(&language);
+        return Err(OptionsError::new(OptionsExitCode::INVALID_DATA_DIR, message));
+    }
+
+    if !engine_options.save_game_dir.as_os_str().is_empty() {
+        let save_dir = home::configured_save_dir(&engine_options);
+        fs::create_dir_all(&save_dir).map_err(|s| {
+            let message = Message::new("save_game_dir_error", vec!(save_dir.display().to_string())).localize(&language);
+            OptionsError::with_context(OptionsExitCode::HOME_DIR_ERROR, ErrorContext::io(&s, &save_dir).wrap(ErrorKind::CONFIG, message))
+        })?;
+    }
 
     if engine_options.vanilla_data_dir == PathBuf::from("") {
-        return Err(String::from("Vanilla data directory has to be set either in config file or per command line switch"))
+        return Err(OptionsError::new(
+            OptionsExitCode::INVALID_DATA_DIR,
+            Message::new("data_dir_not_set", vec!()).localize(&language)
+        ))
     }
+    let speech_warnings = datadir::check_speech_libraries(&engine_options.vanilla_data_dir, engine_options.resource_version);
+    engine_options.validation_report.warnings.extend(speech_warnings);
 
     Ok(engine_options)
 }
 
-macro_rules! unsafe_from_ptr {
-    ($ptr:expr) => { unsafe { assert!(!$ptr.is_null()); &*$ptr } }
+/// Checks whether errors should be printed as JSON. The arguments are checked directly, because the flag has to work
+/// even if parsing the arguments fails.
+pub fn wants_json_errors(args: &[String]) -> bool {
+    args.iter().skip(1).any(|a| a == "-json-errors" || a == "--json-errors")
 }
 
-macro_rules! unsafe_from_ptr_mut {
-    ($ptr:expr) => { unsafe { assert!(!$ptr.is_null()); &mut *$ptr } }
+/// Returns the JSON object for an error in the options: `{"error":{"exit_code":65,"kind":"INVALID_CONFIG","message":"..."}}`.
+/// Errors with a context also have a `diagnostic`.
+pub fn error_json(error: &OptionsError) -> String {
+    let mut value = json!({ "error": { "exit_code": error.exit_code as i32, "kind": error.exit_code, "message": error.message } });
+    if error.context.is_some() {
+        value["error"]["diagnostic"] = json!(error.diagnostic());
+    }
+    value.to_string()
 }
 
 #[no_mangle]
@@ -467,9 +1122,23 @@ pub fn create_engine_options(array: *const *const c_char, length: size_t) -> *mu
         .map(|cs| cs.to_bytes())                 // iterator of &[u8]
         .map(|bs| String::from(str::from_utf8(bs).unwrap()))   // iterator of &str
         .collect();
+    let json_errors = wants_json_errors(&args);
+
+    if defaults::wants_defaults_dump(&args) {
+        println!("{}", defaults::dump_defaults());
+        // Exits like after showing the help
+        *LAST_OPTIONS_EXIT_CODE.lock().unwrap_or_else(|e| e.into_inner()) = OptionsExitCode::HELP_SHOWN;
+        return ptr::null_mut();
+    }
 
     return match build_engine_options_from_env_and_args(args) {
         Ok(engine_options) => {
+            for warning in &engine_options.validation_report.warnings {
+                println!("Warning: {}", warning);
+            }
+            for deprecation in &engine_options.validation_report.deprecations {
+                println!("Warning: {}", deprecation.message());
+            }
             if engine_options.show_help {
                 let opts = get_command_line_options();
                 let brief = format!("Usage: ja2 [options]");
@@ -477,41 +1146,166 @@ pub fn create_engine_options(array: *const *const c_char, length: size_t) -> *mu
             }
             Box::into_raw(Box::new(engine_options))
         },
-        Err(msg) => {
-            println!("{}", msg);
+        Err(error) => {
+            if json_errors {
+                eprintln!("{}", error_json(&error));
+            } else {
+                println!("{}", error.diagnostic());
+            }
+            match error.context {
+                Some(context) => set_last_error_context(context),
+                None => set_last_error(Some(error.message)),
+            }
+            *LAST_OPTIONS_EXIT_CODE.lock().unwrap_or_else(|e| e.into_inner()) = error.exit_code;
             return ptr::null_mut();
         }
     };
 }
 
+/// Returns the exit code for the last failure of `create_engine_options`.
+#[no_mangle]
+pub extern fn get_options_exit_code() -> OptionsExitCode {
+    *LAST_OPTIONS_EXIT_CODE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 #[no_mangle]
 pub fn write_engine_options(ptr: *mut EngineOptions) -> bool {
     let engine_options = unsafe_from_ptr!(ptr);
     write_json_config(engine_options).is_ok()
 }
 
+/// Returns the options of ja2.json and, in `runtime`, the options that are only set by the command line or the engine.
+pub fn engine_options_json(engine_options: &EngineOptions) -> serde_json::Value {
+    let mut value = serde_json::to_value(engine_options).expect("Engine options should be serializable");
+    value["runtime"] = json!({
+        "stracciatella_home": engine_options.stracciatella_home,
+        "saves_home": engine_options.saves_home,
+        "show_help": engine_options.show_help,
+        "run_unittests": engine_options.run_unittests,
+        "unittest_report": engine_options.unittest_report,
+        "run_smoketest": engine_options.run_smoketest,
+        "run_editor": engine_options.run_editor,
+        "start_in_window": engine_options.start_in_window,
+        "random_seed": engine_options.random_seed,
+        "record_replay": engine_options.record_replay,
+        "replay_file": engine_options.replay_file,
+        "validation_warnings": engine_options.validation_report.warnings,
+        "deprecation_warnings": engine_options.validation_report.deprecations,
+    });
+    value
+}
+
+#[no_mangle]
+pub extern fn get_engine_options_json(ptr: *const EngineOptions) -> *mut c_char {
+    let json = engine_options_json(unsafe_from_ptr!(ptr)).to_string();
+    CString::new(json).unwrap().into_raw()
+}
+
+/// Merges the options of a JSON object into the engine options, e.g. from a settings form. Options that are missing in
+/// the object stay as they are, the `runtime` options of `engine_options_json` are ignored. Nothing is changed if any
+/// option is invalid, the errors list every invalid option.
+pub fn apply_options_json(engine_options: &mut EngineOptions, json: &str) -> Result<(), Vec<String>> {
+    let mut document: serde_json::Value = serde_json::from_str(json).map_err(|s| vec!(format!("Error parsing the options: {}", s)))?;
+    match document {
+        serde_json::Value::Object(ref mut m) => m.remove("runtime"),
+        _ => return Err(vec!(String::from("Error parsing the options: not a JSON object")))
+    };
+    let fields = document.as_object().cloned().unwrap_or_default();
+    let current = serde_json::to_value(&*engine_options).map_err(|s| vec!(format!("Error applying the options: {}", s)))?;
+
+    let mut errors = validation::lint_json_config(&document);
+    for (key, value) in fields {
+        let mut merged = current.clone();
+        gamedata::merge_json(&mut merged, json!({ key.as_str(): value }));
+        if let Err(s) = serde_json::from_value::<EngineOptions>(merged) {
+            errors.push(format!("'{}': {}", key, s));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut merged = current;
+    gamedata::merge_json(&mut merged, document);
+    let applied: EngineOptions = serde_json::from_value(merged).map_err(|s| vec!(format!("Error applying the options: {}", s)))?;
+    replace_config_options(engine_options, applied);
+    Ok(())
+}
+
+/// Returns false if the options were not applied, `get_last_error` returns one line per error then.
+#[no_mangle]
+pub extern fn apply_engine_options_json(ptr: *mut EngineOptions, json_ptr: *const c_char) -> bool {
+    let json = unsafe { CStr::from_ptr(json_ptr) }.to_string_lossy().into_owned();
+    match apply_options_json(unsafe_from_ptr_mut!(ptr), &json) {
+        Ok(()) => {
+            set_last_error(None);
+            events::emit_event(events::EngineEvent::CONFIG_RELOADED);
+            true
+        },
+        Err(errors) => {
+            set_last_error(Some(errors.join("\n")));
+            false
+        }
+    }
+}
+
 #[no_mangle]
 pub fn free_engine_options(ptr: *mut EngineOptions) {
     if ptr.is_null() { return }
-    unsafe { Box::from_raw(ptr); }
+    unsafe { drop(Box::from_raw(ptr)); }
+}
+
+#[no_mangle]
+pub extern fn reload_engine_options(ptr: *mut EngineOptions) -> bool {
+    match reload_json_config(unsafe_from_ptr_mut!(ptr)) {
+        Ok(()) => {
+            events::emit_event(events::EngineEvent::CONFIG_RELOADED);
+            true
+        },
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "config", &format!("Error reloading the engine options: {}", s));
+            false
+        }
+    }
 }
 
 #[no_mangle]
 pub extern fn get_stracciatella_home(ptr: *const EngineOptions) -> *mut c_char {
-    let c_str_home = CString::new(unsafe_from_ptr!(ptr).stracciatella_home.to_str().unwrap()).unwrap();
-    c_str_home.into_raw()
+    home::path_to_c_string(&unsafe_from_ptr!(ptr).stracciatella_home)
+}
+
+/// Returns the home as UTF-16, for the wide functions of the Windows API.
+#[no_mangle]
+pub extern fn get_stracciatella_home_wide(ptr: *const EngineOptions) -> *mut u16 {
+    home::path_to_wide_string(&unsafe_from_ptr!(ptr).stracciatella_home)
 }
 
 #[no_mangle]
 pub extern fn get_vanilla_data_dir(ptr: *const EngineOptions) -> *mut c_char {
-    let c_str_home = CString::new(unsafe_from_ptr!(ptr).vanilla_data_dir.to_str().unwrap()).unwrap();
-    c_str_home.into_raw()
+    home::path_to_c_string(&unsafe_from_ptr!(ptr).vanilla_data_dir)
+}
+
+#[no_mangle]
+pub extern fn get_vanilla_data_dir_wide(ptr: *const EngineOptions) -> *mut u16 {
+    home::path_to_wide_string(&unsafe_from_ptr!(ptr).vanilla_data_dir)
 }
 
 #[no_mangle]
 pub extern fn set_vanilla_data_dir(ptr: *mut EngineOptions, data_dir_ptr: *const c_char) -> () {
     let c_str = unsafe { CStr::from_ptr(data_dir_ptr) };
-    unsafe_from_ptr_mut!(ptr).vanilla_data_dir = PathBuf::from(c_str.to_string_lossy().into_owned());
+    unsafe_from_ptr_mut!(ptr).vanilla_data_dir = datadir::normalize_data_dir(&PathBuf::from(c_str.to_string_lossy().into_owned()));
+}
+
+/// Returns `save_game_dir` as configured, empty for the default. The folder that is used is returned by `get_save_dir`.
+#[no_mangle]
+pub extern fn get_save_game_dir(ptr: *const EngineOptions) -> *mut c_char {
+    home::path_to_c_string(&unsafe_from_ptr!(ptr).save_game_dir)
+}
+
+#[no_mangle]
+pub extern fn set_save_game_dir(ptr: *mut EngineOptions, dir_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(dir_ptr) };
+    unsafe_from_ptr_mut!(ptr).save_game_dir = PathBuf::from(c_str.to_string_lossy().into_owned());
 }
 
 #[no_mangle]
@@ -529,19 +1323,162 @@ pub extern fn get_mod(ptr: *const EngineOptions, index: u32) -> *mut c_char {
     c_str_mod.into_raw()
 }
 
+/// Replaces the list of mods that are started.
+#[no_mangle]
+pub extern fn set_mods(ptr: *mut EngineOptions, mods: *const *const c_char, length: size_t) -> () {
+    let values = unsafe { slice::from_raw_parts(mods, length as usize) };
+    unsafe_from_ptr_mut!(ptr).mods = values.iter()
+        .map(|&p| unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned())
+        .collect();
+    events::emit_event(events::EngineEvent::MODS_CHANGED);
+}
+
+#[no_mangle]
+pub extern fn get_number_of_extra_data_dirs(ptr: *const EngineOptions) -> u32 {
+    unsafe_from_ptr!(ptr).extra_data_dirs.len() as u32
+}
+
+/// Returns null if there is no extra data dir at the index.
+#[no_mangle]
+pub extern fn get_extra_data_dir(ptr: *const EngineOptions, index: u32) -> *mut c_char {
+    match unsafe_from_ptr!(ptr).extra_data_dirs.get(index as usize) {
+        Some(d) => CString::new(d.to_string_lossy().into_owned()).unwrap().into_raw(),
+        None => ptr::null_mut()
+    }
+}
+
+/// Replaces the list of extra data dirs, later dirs take precedence.
+#[no_mangle]
+pub extern fn set_extra_data_dirs(ptr: *mut EngineOptions, dirs: *const *const c_char, length: size_t) -> () {
+    let values = unsafe { slice::from_raw_parts(dirs, length as usize) };
+    unsafe_from_ptr_mut!(ptr).extra_data_dirs = values.iter()
+        .map(|&p| PathBuf::from(unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned()))
+        .collect();
+}
+
+/// Adds an extra data dir with the highest precedence.
+#[no_mangle]
+pub extern fn add_extra_data_dir(ptr: *mut EngineOptions, dir_ptr: *const c_char) -> () {
+    let dir = unsafe { CStr::from_ptr(dir_ptr) }.to_string_lossy().into_owned();
+    unsafe_from_ptr_mut!(ptr).extra_data_dirs.push(PathBuf::from(dir));
+}
+
+/// Returns false if there is no extra data dir at the index.
+#[no_mangle]
+pub extern fn remove_extra_data_dir(ptr: *mut EngineOptions, index: u32) -> bool {
+    let extra_data_dirs = &mut unsafe_from_ptr_mut!(ptr).extra_data_dirs;
+    if (index as usize) < extra_data_dirs.len() {
+        extra_data_dirs.remove(index as usize);
+        true
+    } else {
+        false
+    }
+}
+
 #[no_mangle]
 pub extern fn get_resolution_x(ptr: *const EngineOptions) -> u16 {
-    unsafe_from_ptr!(ptr).resolution.0
+    unsafe_from_ptr!(ptr).resolution.width
 }
 
 #[no_mangle]
 pub extern fn get_resolution_y(ptr: *const EngineOptions) -> u16 {
-    unsafe_from_ptr!(ptr).resolution.1
+    unsafe_from_ptr!(ptr).resolution.height
 }
 
+/// Keeps the refresh rate.
 #[no_mangle]
 pub extern fn set_resolution(ptr: *mut EngineOptions, x: u16, y: u16) -> () {
-    unsafe_from_ptr_mut!(ptr).resolution = (x, y)
+    let resolution = &mut unsafe_from_ptr_mut!(ptr).resolution;
+    resolution.width = x;
+    resolution.height = y;
+}
+
+/// Parses a resolution with the rules of the engine, e.g. for the input of the launcher. The refresh rate is optional
+/// and is not returned. Returns false and sets the last error if the resolution is invalid.
+#[no_mangle]
+pub extern fn parse_and_validate_resolution(resolution_ptr: *const c_char, width_ptr: *mut u16, height_ptr: *mut u16) -> bool {
+    let resolution_str = unsafe { CStr::from_ptr(resolution_ptr) }.to_string_lossy();
+    match parse_resolution(resolution_str.trim()).and_then(|r| validate_resolution(&r).map(|_| r)) {
+        Ok(r) => {
+            *unsafe_from_ptr_mut!(width_ptr) = r.width;
+            *unsafe_from_ptr_mut!(height_ptr) = r.height;
+            set_last_error(None);
+            true
+        },
+        Err(s) => {
+            set_last_error(Some(s));
+            false
+        }
+    }
+}
+
+/// Version of the functions and types in RustInterface.h. Increase it with every incompatible change, e.g. a changed
+/// signature or `repr(C)` type, together with `RUST_FFI_API_VERSION` in the header.
+pub const FFI_API_VERSION: u32 = 2;
+
+#[no_mangle]
+pub extern fn ffi_api_version() -> u32 {
+    FFI_API_VERSION
+}
+
+pub fn check_ffi_api_version(expected: u32) -> Result<(), String> {
+    if expected != FFI_API_VERSION {
+        return Err(format!("The stracciatella library has interface version {}, but the executable expects version {}. They are from different builds, please reinstall the game.", FFI_API_VERSION, expected));
+    }
+    Ok(())
+}
+
+/// Called by the executables at startup with `RUST_FFI_API_VERSION` before any other function. False if the library
+/// is from another build, see `get_last_error`.
+#[no_mangle]
+pub extern fn negotiate_ffi_api_version(expected: u32) -> bool {
+    let result = check_ffi_api_version(expected);
+    let success = result.is_ok();
+    set_last_error(result.err());
+    success
+}
+
+/// Returns the message of the last error or null if the last call succeeded.
+#[no_mangle]
+pub extern fn get_last_error() -> *mut c_char {
+    match *LAST_ERROR.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref s) => CString::new(s.clone()).unwrap().into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the message of the last error with one line per layer that failed, see `errorcontext`. Errors without
+/// context only have the message, null if the last call succeeded.
+#[no_mangle]
+pub extern fn get_last_error_diagnostic() -> *mut c_char {
+    let context = LAST_ERROR_CONTEXT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match context {
+        Some(c) => CString::new(c.diagnostic()).unwrap().into_raw(),
+        None => get_last_error(),
+    }
+}
+
+/// Returns 0 if the refresh rate of the desktop should be used.
+#[no_mangle]
+pub extern fn get_refresh_rate(ptr: *const EngineOptions) -> u16 {
+    unsafe_from_ptr!(ptr).resolution.refresh_rate.unwrap_or(0)
+}
+
+/// Sets the refresh rate of the fullscreen mode, 0 to use the one of the desktop.
+#[no_mangle]
+pub extern fn set_refresh_rate(ptr: *mut EngineOptions, refresh_rate: u16) -> () {
+    unsafe_from_ptr_mut!(ptr).resolution.refresh_rate = if refresh_rate == 0 { None } else { Some(refresh_rate) };
+}
+
+/// Whether the resolution is written to ja2.json as object instead of a string.
+#[no_mangle]
+pub extern fn is_resolution_object_form(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).resolution.object_form
+}
+
+#[no_mangle]
+pub extern fn set_resolution_object_form(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).resolution.object_form = val
 }
 
 #[no_mangle]
@@ -559,6 +1496,25 @@ pub extern fn set_resource_version(ptr: *mut EngineOptions, res_ptr: *const c_ch
     }
 }
 
+/// Returns the language of the texts of the interface, which is the one of the resource version unless it is set.
+#[no_mangle]
+pub extern fn get_game_text_language(ptr: *const EngineOptions) -> ResourceVersion {
+    let engine_options = unsafe_from_ptr!(ptr);
+    engine_options.game_text_language.unwrap_or(engine_options.resource_version)
+}
+
+/// Sets the language of the texts of the interface, an empty string for the language of the resource version.
+#[no_mangle]
+pub extern fn set_game_text_language(ptr: *mut EngineOptions, language_ptr: *const c_char) -> () {
+    let language = unsafe { CStr::from_ptr(language_ptr) }.to_string_lossy().into_owned();
+    let engine_options = unsafe_from_ptr_mut!(ptr);
+    if language.is_empty() {
+        engine_options.game_text_language = None;
+    } else if let Ok(v) = ResourceVersion::from_str(&language) {
+        engine_options.game_text_language = Some(v);
+    }
+}
+
 #[no_mangle]
 pub fn should_run_unittests(ptr: *const EngineOptions) -> bool {
     unsafe_from_ptr!(ptr).run_unittests
@@ -605,11 +1561,97 @@ pub fn should_start_in_window(ptr: *const EngineOptions) -> bool {
     unsafe_from_ptr!(ptr).start_in_window
 }
 
+#[no_mangle]
+pub fn should_keep_window_on_top(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).window_always_on_top
+}
+
+#[no_mangle]
+pub fn set_window_always_on_top(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).window_always_on_top = val
+}
+
+#[no_mangle]
+pub fn should_grab_mouse(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).grab_mouse
+}
+
+#[no_mangle]
+pub fn set_grab_mouse(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).grab_mouse = val
+}
+
+/// Whether the game stops while another window has the focus.
+#[no_mangle]
+pub fn should_pause_on_focus_loss(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).pause_on_focus_loss
+}
+
+#[no_mangle]
+pub fn set_pause_on_focus_loss(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).pause_on_focus_loss = val
+}
+
+/// Whether the game exits if another game with the same stracciatella home is running.
+#[no_mangle]
+pub fn should_enforce_single_instance(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).single_instance
+}
+
+#[no_mangle]
+pub fn set_single_instance(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).single_instance = val
+}
+
+/// Whether the key events of the campaign are logged for the timeline of the launcher.
+#[no_mangle]
+pub fn should_write_campaign_log(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).campaign_log
+}
+
+#[no_mangle]
+pub fn set_campaign_log(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).campaign_log = val
+}
+
+#[no_mangle]
+pub fn should_canonicalize_data_dir(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).canonicalize_data_dir
+}
+
+#[no_mangle]
+pub fn set_canonicalize_data_dir(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).canonicalize_data_dir = val
+}
+
 #[no_mangle]
 pub fn should_start_in_debug_mode(ptr: *const EngineOptions) -> bool {
     unsafe_from_ptr!(ptr).start_in_debug_mode
 }
 
+/// Cheats are only enabled in debug mode.
+#[no_mangle]
+pub fn should_enable_cheats(ptr: *const EngineOptions) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    engine_options.enable_cheats && engine_options.start_in_debug_mode
+}
+
+#[no_mangle]
+pub fn set_enable_cheats(ptr: *mut EngineOptions, val: bool) -> () {
+    unsafe_from_ptr_mut!(ptr).enable_cheats = val
+}
+
+#[no_mangle]
+pub extern fn has_random_seed(ptr: *const EngineOptions) -> bool {
+    unsafe_from_ptr!(ptr).random_seed.is_some()
+}
+
+/// Returns the seed given on the command line or 0 if there is none, see `has_random_seed`.
+#[no_mangle]
+pub extern fn get_random_seed(ptr: *const EngineOptions) -> u64 {
+    unsafe_from_ptr!(ptr).random_seed.unwrap_or(0)
+}
+
 #[no_mangle]
 pub fn should_start_without_sound(ptr: *const EngineOptions) -> bool {
     unsafe_from_ptr!(ptr).start_without_sound
@@ -620,6 +1662,162 @@ pub fn set_start_without_sound(ptr: *mut EngineOptions, val: bool) -> () {
     unsafe_from_ptr_mut!(ptr).start_without_sound = val
 }
 
+#[no_mangle]
+pub extern fn get_game_language(ptr: *const EngineOptions) -> *mut c_char {
+    CString::new(unsafe_from_ptr!(ptr).game_language.clone()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn set_game_language(ptr: *mut EngineOptions, language_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(language_ptr) };
+    unsafe_from_ptr_mut!(ptr).game_language = c_str.to_string_lossy().into_owned();
+}
+
+#[no_mangle]
+pub extern fn get_launcher_language(ptr: *const EngineOptions) -> *mut c_char {
+    CString::new(unsafe_from_ptr!(ptr).launcher_language.clone()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn set_launcher_language(ptr: *mut EngineOptions, language_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(language_ptr) };
+    unsafe_from_ptr_mut!(ptr).launcher_language = c_str.to_string_lossy().into_owned();
+}
+
+#[no_mangle]
+pub extern fn get_audio_device(ptr: *const EngineOptions) -> *mut c_char {
+    let c_str_device = CString::new(unsafe_from_ptr!(ptr).audio_device.clone()).unwrap();
+    c_str_device.into_raw()
+}
+
+#[no_mangle]
+pub extern fn set_audio_device(ptr: *mut EngineOptions, device_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(device_ptr) };
+    unsafe_from_ptr_mut!(ptr).audio_device = c_str.to_string_lossy().into_owned();
+}
+
+#[no_mangle]
+pub extern fn get_audio_sample_rate(ptr: *const EngineOptions) -> u32 {
+    unsafe_from_ptr!(ptr).audio_sample_rate
+}
+
+#[no_mangle]
+pub extern fn set_audio_sample_rate(ptr: *mut EngineOptions, rate: u32) -> bool {
+    match validate_audio_sample_rate(rate) {
+        Ok(rate) => {
+            unsafe_from_ptr_mut!(ptr).audio_sample_rate = rate;
+            true
+        },
+        Err(_) => false
+    }
+}
+
+#[no_mangle]
+pub extern fn get_brightness(ptr: *const EngineOptions) -> f32 {
+    unsafe_from_ptr!(ptr).brightness
+}
+
+/// Sets the brightness, returns false if it is not in `BRIGHTNESS_RANGE`.
+#[no_mangle]
+pub extern fn set_brightness(ptr: *mut EngineOptions, brightness: f32) -> bool {
+    match validate_brightness(brightness) {
+        Ok(brightness) => {
+            unsafe_from_ptr_mut!(ptr).brightness = brightness;
+            true
+        },
+        Err(_) => false
+    }
+}
+
+#[no_mangle]
+pub extern fn get_max_fps(ptr: *const EngineOptions) -> u16 {
+    unsafe_from_ptr!(ptr).max_fps
+}
+
+/// Sets the frame rate limit, returns false if it is neither 0 nor in `MAX_FPS_RANGE`.
+#[no_mangle]
+pub extern fn set_max_fps(ptr: *mut EngineOptions, max_fps: u16) -> bool {
+    match validate_max_fps(max_fps) {
+        Ok(max_fps) => {
+            unsafe_from_ptr_mut!(ptr).max_fps = max_fps;
+            true
+        },
+        Err(_) => false
+    }
+}
+
+/// Returns the index of the display for the game window, the engine falls back to the primary display if it does not exist.
+#[no_mangle]
+pub extern fn get_display_index(ptr: *const EngineOptions) -> u16 {
+    unsafe_from_ptr!(ptr).display_index
+}
+
+#[no_mangle]
+pub extern fn set_display_index(ptr: *mut EngineOptions, display_index: u16) -> () {
+    unsafe_from_ptr_mut!(ptr).display_index = display_index;
+}
+
+/// Returns true if both coordinates of the window position are set, otherwise the window is centered on the display.
+#[no_mangle]
+pub extern fn has_window_position(ptr: *const EngineOptions) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    engine_options.window_x.is_some() && engine_options.window_y.is_some()
+}
+
+/// Returns the horizontal window position relative to the display or 0 if there is none, see `has_window_position`.
+#[no_mangle]
+pub extern fn get_window_x(ptr: *const EngineOptions) -> i32 {
+    unsafe_from_ptr!(ptr).window_x.unwrap_or(0)
+}
+
+/// Returns the vertical window position relative to the display or 0 if there is none, see `has_window_position`.
+#[no_mangle]
+pub extern fn get_window_y(ptr: *const EngineOptions) -> i32 {
+    unsafe_from_ptr!(ptr).window_y.unwrap_or(0)
+}
+
+/// Sets the window position relative to the display, e.g. to remember the last placement.
+#[no_mangle]
+pub extern fn set_window_position(ptr: *mut EngineOptions, x: i32, y: i32) -> () {
+    let engine_options = unsafe_from_ptr_mut!(ptr);
+    engine_options.window_x = Some(x);
+    engine_options.window_y = Some(y);
+}
+
+/// Removes the window position so the window is centered on the display again.
+#[no_mangle]
+pub extern fn clear_window_position(ptr: *mut EngineOptions) -> () {
+    let engine_options = unsafe_from_ptr_mut!(ptr);
+    engine_options.window_x = None;
+    engine_options.window_y = None;
+}
+
+#[no_mangle]
+pub extern fn get_audio_driver(ptr: *const EngineOptions) -> *mut c_char {
+    let c_str_driver = CString::new(unsafe_from_ptr!(ptr).audio_driver.clone()).unwrap();
+    c_str_driver.into_raw()
+}
+
+#[no_mangle]
+pub extern fn set_audio_driver(ptr: *mut EngineOptions, driver_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(driver_ptr) };
+    unsafe_from_ptr_mut!(ptr).audio_driver = c_str.to_string_lossy().into_owned();
+}
+
+/// Returns the index of the configured audio device in the list of device names or -1 for the OS default.
+#[no_mangle]
+pub extern fn find_audio_device_index(ptr: *const EngineOptions, device_names: *const *const c_char, length: size_t) -> i32 {
+    let values = unsafe { slice::from_raw_parts(device_names, length as usize) };
+    let names: Vec<String> = values.iter()
+        .map(|&p| unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned())
+        .collect();
+
+    match resolve_audio_device(&unsafe_from_ptr!(ptr).audio_device, &names) {
+        Some(i) => i as i32,
+        None => -1
+    }
+}
+
 #[no_mangle]
 pub extern fn get_resource_version_string(version: ResourceVersion) -> *mut c_char {
     let c_str_home = CString::new(version.to_string()).unwrap();
@@ -644,7 +1842,7 @@ pub extern fn find_ja2_executable(launcher_path_ptr: *const c_char) -> *const c_
 pub fn free_rust_string(s: *mut c_char) {
     unsafe {
         if s.is_null() { return }
-        CString::from_raw(s)
+        drop(CString::from_raw(s));
     };
 }
 
@@ -654,7 +1852,7 @@ mod tests {
     extern crate regex;
     extern crate tempdir;
 
-    use std::path::{PathBuf};
+    use std::path::PathBuf;
     use std::str;
     use std::ffi::{CStr, CString};
     use std::fs;
@@ -672,14 +1870,14 @@ mod tests {
     fn parse_args_should_abort_on_unknown_arguments() {
         let mut engine_options: super::EngineOptions = Default::default();
         let input = vec!(String::from("ja2"), String::from("testunknown"));
-        assert_eq!(super::parse_args(&mut engine_options, input).unwrap(), "Unknown arguments: 'testunknown'.");
+        assert_eq!(super::parse_args(&mut engine_options, input).unwrap().to_string(), "Unknown arguments: 'testunknown'.");
     }
 
     #[test]
     fn parse_args_should_abort_on_unknown_switch() {
         let mut engine_options: super::EngineOptions = Default::default();
         let input = vec!(String::from("ja2"), String::from("--testunknown"));
-        assert_eq!(super::parse_args(&mut engine_options, input).unwrap(), "Unrecognized option: 'testunknown'");
+        assert_eq!(super::parse_args(&mut engine_options, input).unwrap().to_string(), "Unrecognized option: 'testunknown'");
     }
 
     #[test]
@@ -698,6 +1896,18 @@ mod tests {
         assert!(super::should_start_in_fullscreen(&engine_options));
     }
 
+    #[test]
+    fn parse_args_should_be_able_to_change_the_window_behavior() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        assert!(!super::should_keep_window_on_top(&engine_options));
+        assert!(!super::should_grab_mouse(&engine_options));
+
+        let input = vec!(String::from("ja2"), String::from("-alwaysontop"), String::from("--grabmouse"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::should_keep_window_on_top(&engine_options));
+        assert!(super::should_grab_mouse(&engine_options));
+    }
+
     #[test]
     fn parse_args_should_be_able_to_show_help() {
         let mut engine_options: super::EngineOptions = Default::default();
@@ -706,6 +1916,15 @@ mod tests {
         assert!(super::should_show_help(&engine_options));
     }
 
+    #[test]
+    fn parse_args_should_leave_gtest_flags_to_the_unit_tests() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-unittests"), String::from("--gtest_output=xml:report.xml"), String::from("--gtest_repeat=2"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::should_run_unittests(&engine_options));
+        assert_eq!(engine_options.unittest_report, Some(PathBuf::from("report.xml")));
+    }
+
     #[test]
     fn parse_args_should_continue_with_multiple_known_switches() {
         let mut engine_options: super::EngineOptions = Default::default();
@@ -719,11 +1938,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_args_should_only_enable_cheats_in_debug_mode() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-cheats"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(!super::should_enable_cheats(&engine_options));
+
+        let input = vec!(String::from("ja2"), String::from("-cheats"), String::from("-debug"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::should_enable_cheats(&engine_options));
+    }
+
     #[test]
     fn parse_args_should_fail_with_unknown_resversion() {
         let mut engine_options: super::EngineOptions = Default::default();
         let input = vec!(String::from("ja2"), String::from("--resversion"), String::from("TESTUNKNOWN"));
-        assert_eq!(super::parse_args(&mut engine_options, input).unwrap(), "Resource version TESTUNKNOWN is unknown");
+        assert_eq!(super::parse_args(&mut engine_options, input).unwrap().to_string(), "Resource version TESTUNKNOWN is unknown");
     }
 
     #[test]
@@ -734,6 +1965,25 @@ mod tests {
         assert!(super::get_resource_version(&engine_options) == super::ResourceVersion::RUSSIAN);
     }
 
+    #[test]
+    fn game_text_language_should_default_to_the_resource_version() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-resversion"), String::from("GERMAN"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_game_text_language(&engine_options), super::ResourceVersion::GERMAN);
+
+        let input = vec!(String::from("ja2"), String::from("--textlanguage"), String::from("ENGLISH"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_game_text_language(&engine_options), super::ResourceVersion::ENGLISH);
+        assert_eq!(super::get_resource_version(&engine_options), super::ResourceVersion::GERMAN);
+
+        let empty = CString::new("").unwrap();
+        super::set_game_text_language(&mut engine_options, empty.as_ptr());
+        assert_eq!(super::get_game_text_language(&engine_options), super::ResourceVersion::GERMAN);
+        let input = vec!(String::from("ja2"), String::from("--textlanguage"), String::from("KLINGON"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Text language KLINGON is unknown")));
+    }
+
     #[test]
     fn parse_args_should_return_the_correct_resversion_for_italian() {
         let mut engine_options: super::EngineOptions = Default::default();
@@ -742,6 +1992,31 @@ mod tests {
         assert!(super::get_resource_version(&engine_options) == super::ResourceVersion::ITALIAN);
     }
 
+    #[test]
+    fn parse_args_should_return_the_extra_data_dirs() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-extradatadir"), String::from("/hd"), String::from("--extradatadir"), String::from("overrides"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(engine_options.extra_data_dirs, vec!(PathBuf::from("/hd"), PathBuf::from("overrides")));
+    }
+
+    #[test]
+    fn extra_data_dirs_should_be_editable_over_ffi() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let dirs = vec!(CString::new("/hd").unwrap(), CString::new("/overrides").unwrap());
+        let dir_ptrs: Vec<*const super::c_char> = dirs.iter().map(|d| d.as_ptr()).collect();
+
+        super::set_extra_data_dirs(&mut engine_options, dir_ptrs.as_ptr(), dir_ptrs.len());
+        super::add_extra_data_dir(&mut engine_options, CString::new("/last").unwrap().as_ptr());
+        assert!(super::remove_extra_data_dir(&mut engine_options, 0));
+        assert!(!super::remove_extra_data_dir(&mut engine_options, 2));
+
+        assert_eq!(super::get_number_of_extra_data_dirs(&engine_options), 2);
+        assert_chars_eq!(super::get_extra_data_dir(&engine_options, 0), "/overrides");
+        assert_chars_eq!(super::get_extra_data_dir(&engine_options, 1), "/last");
+        assert!(super::get_extra_data_dir(&engine_options, 2).is_null());
+    }
+
     #[test]
     fn parse_args_should_return_the_correct_resolution() {
         let mut engine_options: super::EngineOptions = Default::default();
@@ -749,6 +2024,31 @@ mod tests {
         assert_eq!(super::parse_args(&mut engine_options, input), None);
         assert_eq!(super::get_resolution_x(&engine_options), 1120);
         assert_eq!(super::get_resolution_y(&engine_options), 960);
+        assert_eq!(super::get_refresh_rate(&engine_options), 0);
+    }
+
+    #[test]
+    fn parse_args_should_return_the_correct_refresh_rate() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--res"), String::from("1920x1080@120"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_resolution_x(&engine_options), 1920);
+        assert_eq!(super::get_resolution_y(&engine_options), 1080);
+        assert_eq!(super::get_refresh_rate(&engine_options), 120);
+
+        super::set_resolution(&mut engine_options, 800, 600);
+        assert_eq!(super::get_refresh_rate(&engine_options), 120);
+        super::set_refresh_rate(&mut engine_options, 0);
+        assert_eq!(engine_options.resolution, super::Resolution::new(800, 600));
+    }
+
+    #[test]
+    fn parse_args_should_fail_for_invalid_refresh_rates() {
+        for res in &["1920x1080@", "1920x1080@0", "1920x1080@fast", "1920x1080@60@60"] {
+            let mut engine_options: super::EngineOptions = Default::default();
+            let input = vec!(String::from("ja2"), String::from("--res"), String::from(*res));
+            assert!(super::parse_args(&mut engine_options, input).is_some(), "{}", res);
+        }
     }
 
     #[test]
@@ -789,6 +2089,44 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn parse_args_should_keep_symlinked_data_dirs_if_canonicalization_is_disabled() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let target = fs::canonicalize(temp_dir.path()).unwrap().join("ja2-1.13");
+        let link = fs::canonicalize(temp_dir.path()).unwrap().join("ja2");
+        fs::create_dir_all(&target).unwrap();
+        symlink(&target, &link).unwrap();
+        let input = || vec!(String::from("ja2"), String::from("--datadir"), String::from(link.to_str().unwrap()));
+
+        let mut engine_options: super::EngineOptions = Default::default();
+        assert_eq!(super::parse_args(&mut engine_options, input()), None);
+        assert_eq!(engine_options.vanilla_data_dir, target);
+
+        engine_options.canonicalize_data_dir = false;
+        assert_eq!(super::parse_args(&mut engine_options, input()), None);
+        assert_eq!(engine_options.vanilla_data_dir, link);
+
+        fs::remove_dir(&target).unwrap();
+        assert_eq!(super::parse_args(&mut engine_options, input()).map(|m| m.key), Some("nonexistent_data_dir"));
+    }
+
+    #[test]
+    fn parse_args_should_use_the_installation_root_for_the_data_folder() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let data_folder = fs::canonicalize(temp_dir.path()).unwrap().join("DATA");
+
+        fs::create_dir_all(&data_folder).unwrap();
+
+        let input = vec!(String::from("ja2"), String::from("--datadir"), String::from(data_folder.to_str().unwrap()));
+
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(engine_options.vanilla_data_dir, fs::canonicalize(temp_dir.path()).unwrap());
+    }
+
     #[test]
     #[cfg(windows)]
     fn parse_args_should_return_the_correct_canonical_data_dir_on_windows() {
@@ -806,12 +2144,107 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_args_should_return_the_correct_audio_options() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--audiodevice"), String::from("USB Headset"), String::from("-samplerate"), String::from("48000"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_chars_eq!(super::get_audio_device(&engine_options), "USB Headset");
+        assert_eq!(super::get_audio_sample_rate(&engine_options), 48000);
+    }
+
+    #[test]
+    fn parse_args_should_return_the_correct_audio_driver() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-audiodriver"), String::from("alsa"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_chars_eq!(super::get_audio_driver(&engine_options), "alsa");
+    }
+
+    #[test]
+    fn json_errors_should_be_detected_before_parsing() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--json-errors"), String::from("-res"), String::from("big"));
+
+        assert!(super::wants_json_errors(&input));
+        assert!(!super::wants_json_errors(&vec!(String::from("-json-errors"))));
+        assert!(super::parse_args(&mut engine_options, input).is_some());
+        assert_eq!(super::parse_args(&mut engine_options, vec!(String::from("ja2"), String::from("-json-errors"))), None);
+    }
+
+    #[test]
+    fn error_json_should_have_a_stable_schema() {
+        let error = super::OptionsError::new(super::OptionsExitCode::INVALID_ARGUMENTS, String::from("Unknown arguments: 'a \"b\"'."));
+        let error: ::serde_json::Value = ::serde_json::from_str(&super::error_json(&error)).unwrap();
+
+        assert_eq!(error, json!({ "error": { "exit_code": 64, "kind": "INVALID_ARGUMENTS", "message": "Unknown arguments: 'a \"b\"'." } }));
+    }
+
+    #[test]
+    fn parse_args_should_return_the_correct_game_language() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-language"), String::from("pt_BR"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_chars_eq!(super::get_game_language(&engine_options), "pt_BR");
+    }
+
+    #[test]
+    fn parse_args_should_return_the_correct_log_format() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-logformat"), String::from("JSON"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::logger::get_log_format(&engine_options), super::LogFormat::JSON);
+    }
+
+    #[test]
+    fn parse_args_should_fail_with_unknown_log_format() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-logformat"), String::from("XML"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Log format XML is unknown")));
+    }
+
+    #[test]
+    fn parse_args_should_set_the_scaling() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--scaling"), String::from("NEAR_PERFECT"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_scaling_quality(&engine_options), super::ScalingQuality::NEAR_PERFECT);
+
+        let input = vec!(String::from("ja2"), String::from("--scaling"), String::from("BLURRY"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Scaling BLURRY is unknown, use LINEAR, NEAR_PERFECT or PERFECT")));
+    }
+
+    #[test]
+    fn parse_args_should_fail_with_invalid_sample_rate() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--samplerate"), String::from("100"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Incorrect sample rate, should be a value between 8000 and 192000 Hz.")));
+    }
+
+    #[test]
+    fn parse_args_should_set_the_random_seed() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        assert!(!super::has_random_seed(&engine_options));
+
+        let input = vec!(String::from("ja2"), String::from("-seed"), String::from("18446744073709551615"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::has_random_seed(&engine_options));
+        assert_eq!(super::get_random_seed(&engine_options), 18446744073709551615);
+    }
+
+    #[test]
+    fn parse_args_should_fail_with_invalid_seed() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--seed"), String::from("-1"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Incorrect seed, should be a positive number.")));
+    }
+
     #[test]
     fn parse_args_should_fail_with_non_existing_directory() {
         let mut engine_options: super::EngineOptions = Default::default();
         let input = vec!(String::from("ja2"), String::from("--datadir"), String::from("somethingelse"));
 
-        assert_eq!(super::parse_args(&mut engine_options, input), Some(String::from("Please specify an existing datadir.")));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Please specify an existing datadir.")));
     }
 
     fn write_temp_folder_with_ja2_ini(contents: &[u8]) -> tempdir::TempDir {
@@ -839,6 +2272,35 @@ mod tests {
         assert!(ja2json_path.is_file());
     }
 
+    #[test]
+    fn ensure_json_config_existence_should_import_a_legacy_ja2_ini() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        fs::write(dir.path().join("JA2.ini"), "data_dir = /opt/ja2\nres = 1280x720\n").unwrap();
+
+        super::ensure_json_config_existence(dir.path().to_path_buf()).unwrap();
+        let engine_options = super::parse_json_config(dir.path().to_path_buf()).unwrap();
+
+        assert_eq!(engine_options.vanilla_data_dir, PathBuf::from("/opt/ja2"));
+        assert_eq!(engine_options.resolution, super::Resolution::new(1280, 720));
+        assert!(dir.path().join("JA2.ini").is_file());
+    }
+
+    #[test]
+    fn ensure_json_config_existence_should_report_the_failed_file() {
+        let dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let home_path = dir.path().join("ja2_home");
+        fs::write(&home_path, "not a dir").unwrap();
+
+        let error = super::ensure_json_config_existence(home_path.clone()).unwrap_err();
+        let options_error = super::OptionsError::with_context(super::OptionsExitCode::HOME_DIR_ERROR, error.clone());
+
+        assert_eq!(error.chain().map(|c| c.kind).collect::<Vec<_>>(), vec!(super::ErrorKind::CONFIG, super::ErrorKind::IO));
+        assert_eq!(error.message, "Error creating ja2.json config file");
+        assert_eq!(error.source.unwrap().path, Some(home_path.join("ja2.json")));
+        let json: ::serde_json::Value = ::serde_json::from_str(&super::error_json(&options_error)).unwrap();
+        assert_eq!(json["error"]["diagnostic"], json!(options_error.diagnostic()));
+    }
+
     #[test]
     fn ensure_json_config_existence_should_not_overwrite_existing_ja2json() {
         let dir = write_temp_folder_with_ja2_ini(b"Test");
@@ -859,7 +2321,7 @@ mod tests {
         let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
         let stracciatella_home = PathBuf::from(temp_dir.path());
 
-        assert_eq!(super::parse_json_config(stracciatella_home), Err(String::from("Error reading ja2.json config file: entity not found")));
+        assert_eq!(super::parse_json_config(stracciatella_home).map_err(|m| m.to_string()), Err(String::from("Error reading ja2.json config file: entity not found")));
     }
 
     #[test]
@@ -867,7 +2329,26 @@ mod tests {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ not json }");
         let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
 
-        assert_eq!(super::parse_json_config(stracciatella_home), Err(String::from("Error parsing ja2.json config file: key must be a string at line 1 column 3")));
+        assert_eq!(super::parse_json_config(stracciatella_home).map_err(|m| m.to_string()), Err(String::from("Error parsing ja2.json config file: key must be a string at line 1 column 3")));
+    }
+
+    #[test]
+    fn parse_json_config_should_ignore_comments() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{\n  // the old monitor\n  \"res\": \"1024x768\", /* \"fullscreen\": true, */\n  \"game_language\": \"//*\"\n}");
+        let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
+        let engine_options = super::parse_json_config(stracciatella_home).unwrap();
+
+        assert_eq!(engine_options.resolution, super::Resolution::new(1024, 768));
+        assert!(!engine_options.start_in_fullscreen);
+        assert_eq!(engine_options.game_language, "//*");
+    }
+
+    #[test]
+    fn strip_json_comments_should_keep_the_positions_of_errors() {
+        let stripped = super::strip_json_comments("{ /* a\nb */ // c\n  \"a\\\" // \": 1 }");
+        assert_eq!(stripped, "{     \n         \n  \"a\\\" // \": 1 }");
+        let error = ::serde_json::from_str::<::serde_json::Value>(&super::strip_json_comments("{\n// comment\n  x }")).unwrap_err();
+        assert_eq!((error.line(), error.column()), (3, 3));
     }
 
     #[test]
@@ -879,6 +2360,24 @@ mod tests {
         assert_eq!(engine_options.stracciatella_home, stracciatella_home);
     }
 
+    #[test]
+    fn reload_json_config_should_keep_the_command_line_options() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": \"1024x768\" }");
+        let mut engine_options = super::EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
+        engine_options.start_in_window = true;
+        engine_options.random_seed = Some(42);
+        engine_options.mods = vec!(String::from("a"));
+
+        assert_eq!(super::reload_json_config(&mut engine_options), Ok(()));
+
+        assert_eq!(engine_options.resolution, super::Resolution::new(1024, 768));
+        assert_eq!(engine_options.mods, Vec::<String>::new());
+        assert!(engine_options.start_in_window);
+        assert_eq!(engine_options.random_seed, Some(42));
+        assert_eq!(engine_options.stracciatella_home, temp_dir.path().join(".ja2"));
+    }
+
     #[test]
     fn parse_json_config_should_not_be_able_to_set_stracciatella_home() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"stracciatella_home\": \"/aaa\" }");
@@ -904,6 +2403,39 @@ mod tests {
         assert!(super::should_start_in_fullscreen(&engine_options));
     }
 
+    #[test]
+    fn parse_json_config_should_be_able_to_change_the_window_behavior() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"window_always_on_top\": true, \"grab_mouse\": true }");
+        let mut engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert!(super::should_keep_window_on_top(&engine_options));
+        assert!(super::should_grab_mouse(&engine_options));
+        super::set_grab_mouse(&mut engine_options, false);
+        assert!(!super::should_grab_mouse(&engine_options));
+    }
+
+    #[test]
+    fn parse_json_config_should_be_able_to_disable_pause_on_focus_loss() {
+        let mut engine_options = super::EngineOptions::default();
+        assert!(super::should_pause_on_focus_loss(&engine_options));
+        super::set_pause_on_focus_loss(&mut engine_options, false);
+        assert!(!super::should_pause_on_focus_loss(&engine_options));
+
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"pause_on_focus_loss\": false }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert!(!super::should_pause_on_focus_loss(&engine_options));
+    }
+
+    #[test]
+    fn parse_json_config_should_be_able_to_enable_single_instance() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"single_instance\": true }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert!(super::should_enforce_single_instance(&engine_options));
+        assert!(!super::should_enforce_single_instance(&super::EngineOptions::default()));
+    }
+
     #[test]
     fn parse_json_config_should_be_able_to_change_debug_value() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"debug\": true }");
@@ -912,6 +2444,14 @@ mod tests {
         assert!(super::should_start_in_debug_mode(&engine_options));
     }
 
+    #[test]
+    fn parse_json_config_should_be_able_to_enable_cheats() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"debug\": true, \"enable_cheats\": true }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert!(super::should_enable_cheats(&engine_options));
+    }
+
     #[test]
     fn parse_json_config_should_be_able_to_start_without_sound() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"nosound\": true }");
@@ -928,6 +2468,25 @@ mod tests {
         assert!(!super::should_show_help(&engine_options));
     }
 
+    #[test]
+    fn parse_args_should_be_able_to_run_smoketest() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-smoketest"));
+
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::smoketest::should_run_smoketest(&engine_options));
+    }
+
+    #[test]
+    fn parse_args_should_set_the_replay_options() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("-record"), String::from("-replay"), String::from("bug.jsonl"));
+
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert!(super::replay::should_record_replay(&engine_options));
+        assert_eq!(engine_options.replay_file, Some(PathBuf::from("bug.jsonl")));
+    }
+
     #[test]
     fn parse_json_config_should_not_be_able_to_run_unittests() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"unittests\": true, \"run_unittests\": true }");
@@ -952,12 +2511,22 @@ mod tests {
         assert!(!super::should_start_in_window(&engine_options));
     }
 
+    #[test]
+    fn parse_json_config_should_report_unknown_keys() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"help\": \"Some help\", \"fullscren\": true }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert!(!super::should_start_in_fullscreen(&engine_options));
+        assert_eq!(super::validation::get_number_of_validation_warnings(&engine_options), 1);
+        assert_chars_eq!(super::validation::get_validation_warning(&engine_options, 0), "'fullscren' is not a valid option, did you mean 'fullscreen'?");
+    }
+
     #[test]
     fn parse_json_config_should_fail_with_invalid_mod() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"mods\": [ \"a\", true ] }");
         let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
 
-        assert_eq!(super::parse_json_config(stracciatella_home), Err(String::from("Error parsing ja2.json config file: invalid type: boolean `true`, expected a string at line 1 column 21")));
+        assert_eq!(super::parse_json_config(stracciatella_home).map_err(|m| m.to_string()), Err(String::from("Error parsing ja2.json config file: invalid type: boolean `true`, expected a string at line 1 column 21")));
     }
 
     #[test]
@@ -974,7 +2543,7 @@ mod tests {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"resversion\": \"TESTUNKNOWN\" }");
         let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
 
-        assert_eq!(super::parse_json_config(stracciatella_home), Err(String::from("Error parsing ja2.json config file: unknown variant `TESTUNKNOWN`, expected one of `DUTCH`, `ENGLISH`, `FRENCH`, `GERMAN`, `ITALIAN`, `POLISH`, `RUSSIAN`, `RUSSIAN_GOLD` at line 1 column 29")));
+        assert_eq!(super::parse_json_config(stracciatella_home).map_err(|m| m.to_string()), Err(String::from("Error parsing ja2.json config file: unknown variant `TESTUNKNOWN`, expected one of `DUTCH`, `ENGLISH`, `FRENCH`, `GERMAN`, `ITALIAN`, `POLISH`, `RUSSIAN`, `RUSSIAN_GOLD` at line 1 column 29")));
     }
 
     #[test]
@@ -1003,13 +2572,229 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(windows))]
+    fn parse_json_config_should_return_the_extra_data_dirs() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"extra_data_dirs\": [\"/hd\", \"/overrides\"] }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_eq!(engine_options.extra_data_dirs, vec!(PathBuf::from("/hd"), PathBuf::from("/overrides")));
+    }
+
+    #[test]
+    fn parse_json_config_should_return_the_correct_refresh_rate() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": \"2560x1440@144\" }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_eq!(engine_options.resolution, super::Resolution { width: 2560, height: 1440, refresh_rate: Some(144), object_form: false });
+        assert_eq!(super::get_refresh_rate(&engine_options), 144);
+        assert_eq!(engine_options.resolution.to_string(), "2560x1440@144");
+    }
+
+    #[test]
+    fn get_engine_options_json_should_return_config_and_runtime_options() {
+        let mut engine_options = super::EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from("/home/.ja2");
+        engine_options.run_editor = true;
+        engine_options.mods = vec!(String::from("mod"));
+
+        let json: serde_json::Value = serde_json::from_str(unsafe { CString::from_raw(super::get_engine_options_json(&engine_options)) }.to_str().unwrap()).unwrap();
+
+        assert_eq!(json["mods"], json!(["mod"]));
+        assert_eq!(json["res"], json!("640x480"));
+        assert_eq!(json["gameplay"]["subtitles"], json!(true));
+        assert_eq!(json["runtime"]["stracciatella_home"], json!("/home/.ja2"));
+        assert_eq!(json["runtime"]["run_editor"], json!(true));
+        assert_eq!(json["runtime"]["random_seed"], json!(null));
+    }
+
+    #[test]
+    fn apply_options_json_should_merge_valid_options() {
+        let mut engine_options = super::EngineOptions::default();
+        engine_options.stracciatella_home = PathBuf::from("/home/.ja2");
+        let snapshot = super::engine_options_json(&engine_options);
+
+        super::apply_options_json(&mut engine_options, "{ \"res\": \"1024x768\", \"gameplay\": { \"subtitles\": false } }").unwrap();
+        assert_eq!(engine_options.resolution, super::Resolution::new(1024, 768));
+        assert!(!engine_options.gameplay.subtitles);
+        assert!(engine_options.gameplay.show_hints);
+
+        super::apply_options_json(&mut engine_options, &snapshot.to_string()).unwrap();
+        assert_eq!(engine_options.resolution, super::Resolution::new(640, 480));
+        assert_eq!(engine_options.stracciatella_home, PathBuf::from("/home/.ja2"));
+    }
+
+    #[test]
+    fn apply_options_json_should_report_every_invalid_option() {
+        let mut engine_options = super::EngineOptions::default();
+
+        let errors = super::apply_options_json(&mut engine_options, "{ \"res\": \"big\", \"fullscreen\": true, \"nosound\": 1, \"fulscreen\": true }").unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], "'fulscreen' is not a valid option, did you mean 'fullscreen'?");
+        assert!(errors[1].starts_with("'nosound': invalid type"), "{}", errors[1]);
+        assert!(errors[2].starts_with("'res': Incorrect resolution format"), "{}", errors[2]);
+        assert!(!engine_options.start_in_fullscreen);
+        assert!(super::apply_options_json(&mut engine_options, "[]").is_err());
+    }
+
+    #[test]
+    fn parse_and_validate_resolution_should_report_errors() {
+        let (mut width, mut height) = (0u16, 0u16);
+        let parse = |s: &str, w: &mut u16, h: &mut u16| super::parse_and_validate_resolution(CString::new(s).unwrap().as_ptr(), w, h);
+
+        assert!(parse(" 1920x1080@144 ", &mut width, &mut height));
+        assert_eq!((width, height), (1920, 1080));
+        assert!(super::get_last_error().is_null());
+
+        assert!(!parse("800x400", &mut width, &mut height));
+        assert_eq!(unsafe { CString::from_raw(super::get_last_error()) }.to_str().unwrap(), "Invalid resolution 800x400, JA2 Stracciatella needs a resolution of at least 640x480.");
+        assert!(!parse("800 x 600", &mut width, &mut height));
+        assert_eq!(unsafe { CString::from_raw(super::get_last_error()) }.to_str().unwrap(), "Incorrect resolution format, should be WIDTHxHEIGHT or WIDTHxHEIGHT@REFRESHRATE.");
+        assert_eq!((width, height), (1920, 1080));
+    }
+
+    #[test]
+    fn parse_json_config_should_accept_resolution_objects() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": { \"width\": 1280, \"height\": 720, \"refresh_rate\": 60 } }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_eq!(engine_options.resolution, super::Resolution { width: 1280, height: 720, refresh_rate: Some(60), object_form: true });
+        assert!(super::is_resolution_object_form(&engine_options));
+
+        for res in &["{ \"width\": 1280 }", "{ \"width\": 1280, \"height\": 720, \"depth\": 32 }", "{ \"width\": 1280, \"height\": 720, \"refresh_rate\": 0 }", "1280"] {
+            let temp_dir = write_temp_folder_with_ja2_ini(format!("{{ \"res\": {} }}", res).as_bytes());
+            assert!(super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).is_err(), "{}", res);
+        }
+    }
+
+    #[test]
+    fn parse_json_config_should_accept_audio_device_name_or_index() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"audio_device\": \"Speakers\", \"audio_sample_rate\": 22050 }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_chars_eq!(super::get_audio_device(&engine_options), "Speakers");
+        assert_eq!(super::get_audio_sample_rate(&engine_options), 22050);
+
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"audio_device\": 2 }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_chars_eq!(super::get_audio_device(&engine_options), "2");
+    }
+
+    #[test]
+    fn parse_json_config_should_be_able_to_change_audio_driver() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"audio_driver\": \"pulseaudio\" }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_chars_eq!(super::get_audio_driver(&engine_options), "pulseaudio");
+    }
+
+    #[test]
+    fn parse_json_config_should_be_able_to_change_log_format() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"log_format\": \"JSON\" }");
+        let engine_options = super::parse_json_config(PathBuf::from(temp_dir.path().join(".ja2"))).unwrap();
+
+        assert_eq!(super::logger::get_log_format(&engine_options), super::LogFormat::JSON);
+    }
+
+    #[test]
+    fn parse_json_config_should_fail_with_invalid_audio_device() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"audio_device\": true }");
+        let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
+
+        assert_eq!(super::parse_json_config(stracciatella_home).map_err(|m| m.to_string()), Err(String::from("Error parsing ja2.json config file: Audio device should be a device name or a device index. at line 1 column 24")));
+    }
+
+    #[test]
+    fn brightness_should_be_validated() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--brightness"), String::from("1.25"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_brightness(&engine_options), 1.25);
+
+        let input = vec!(String::from("ja2"), String::from("--brightness"), String::from("3"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Incorrect brightness, should be a value between 0.5 and 2.0.")));
+        assert!(!super::set_brightness(&mut engine_options, f32::NAN));
+        assert!(super::set_brightness(&mut engine_options, 0.5));
+        assert_eq!(super::get_brightness(&engine_options), 0.5);
+        assert!(::serde_json::from_str::<super::EngineOptions>("{ \"brightness\": 0.1 }").is_err());
+    }
+
+    #[test]
+    fn max_fps_should_allow_no_limit_or_a_limit_in_the_range() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let input = vec!(String::from("ja2"), String::from("--maxfps"), String::from("60"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_max_fps(&engine_options), 60);
+
+        let input = vec!(String::from("ja2"), String::from("--maxfps"), String::from("10"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Incorrect frame rate limit, should be 0 for no limit or a value between 15 and 240.")));
+        assert!(!super::set_max_fps(&mut engine_options, 241));
+        assert!(super::set_max_fps(&mut engine_options, 0));
+        assert_eq!(super::get_max_fps(&engine_options), 0);
+    }
+
+    #[test]
+    fn parse_args_should_set_the_display_and_the_window_position() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        assert!(!super::has_window_position(&engine_options));
+
+        let input = vec!(String::from("ja2"), String::from("--display"), String::from("1"), String::from("--windowpos"), String::from("-1280,40"));
+        assert_eq!(super::parse_args(&mut engine_options, input), None);
+        assert_eq!(super::get_display_index(&engine_options), 1);
+        assert!(super::has_window_position(&engine_options));
+        assert_eq!(super::get_window_x(&engine_options), -1280);
+        assert_eq!(super::get_window_y(&engine_options), 40);
+
+        let input = vec!(String::from("ja2"), String::from("--windowpos"), String::from("40"));
+        assert_eq!(super::parse_args(&mut engine_options, input).map(|m| m.to_string()), Some(String::from("Incorrect window position 40, should be X,Y.")));
+
+        super::clear_window_position(&mut engine_options);
+        assert!(!super::has_window_position(&engine_options));
+    }
+
+    #[test]
+    fn set_audio_sample_rate_should_reject_invalid_rates() {
+        let mut engine_options: super::EngineOptions = Default::default();
+
+        assert!(!super::set_audio_sample_rate(&mut engine_options, 1000));
+        assert_eq!(super::get_audio_sample_rate(&engine_options), 44100);
+        assert!(super::set_audio_sample_rate(&mut engine_options, 96000));
+        assert_eq!(super::get_audio_sample_rate(&engine_options), 96000);
+    }
+
+    #[test]
+    fn resolve_audio_device_should_match_names_before_indices() {
+        let names = vec!(String::from("Speakers"), String::from("USB Headset"), String::from("1"));
+
+        assert_eq!(super::resolve_audio_device("", &names), None);
+        assert_eq!(super::resolve_audio_device("USB Headset", &names), Some(1));
+        assert_eq!(super::resolve_audio_device("usb headset", &names), Some(1));
+        assert_eq!(super::resolve_audio_device("1", &names), Some(2));
+        assert_eq!(super::resolve_audio_device("0", &names), Some(0));
+        assert_eq!(super::resolve_audio_device("5", &names), None);
+        assert_eq!(super::resolve_audio_device("HDMI", &names), None);
+    }
+
+    #[test]
+    fn find_audio_device_index_should_return_minus_one_for_default_device() {
+        let mut engine_options: super::EngineOptions = Default::default();
+        let names = vec!(CString::new("Speakers").unwrap(), CString::new("USB Headset").unwrap());
+        let name_ptrs: Vec<*const super::c_char> = names.iter().map(|n| n.as_ptr()).collect();
+
+        assert_eq!(super::find_audio_device_index(&engine_options, name_ptrs.as_ptr(), name_ptrs.len()), -1);
+        engine_options.audio_device = String::from("USB Headset");
+        assert_eq!(super::find_audio_device_index(&engine_options, name_ptrs.as_ptr(), name_ptrs.len()), 1);
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "macos", target_os = "android")))]
     fn find_stracciatella_home_should_find_the_correct_stracciatella_home_path_on_unixlike() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
         let mut engine_options: super::EngineOptions = Default::default();
-        engine_options.stracciatella_home = super::find_stracciatella_home().unwrap();
+        engine_options.stracciatella_home = super::home::find_xdg_home_dirs(&|_| None, temp_dir.path()).home;
 
         unsafe {
-            assert_eq!(str::from_utf8(CStr::from_ptr(super::get_stracciatella_home(&engine_options)).to_bytes()).unwrap(), format!("{}/.ja2", env::var("HOME").unwrap()));
+            assert_eq!(str::from_utf8(CStr::from_ptr(super::get_stracciatella_home(&engine_options)).to_bytes()).unwrap(), format!("{}/.config/ja2", temp_dir.path().display()));
         }
     }
 
@@ -1027,40 +2812,61 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(windows))]
-    fn build_engine_options_from_env_and_args_should_overwrite_json_with_command_line_args() {
+    fn build_engine_options_with_home_should_overwrite_json_with_command_line_args() {
         let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"data_dir\": \"/some/place/where/the/data/is\", \"res\": \"1024x768\", \"fullscreen\": true }");
         let args = vec!(String::from("ja2"), String::from("--res"), String::from("1100x480"));
-        let old_home = env::var("HOME");
+        let home_dirs = super::home::HomeDirs::single(temp_dir.path().join(".ja2"));
 
-        env::set_var("HOME", temp_dir.path());
-        let engine_options_res = super::build_engine_options_from_env_and_args(args);
-        match old_home {
-            Ok(home) => env::set_var("HOME", home),
-            _ => {}
-        }
-        let engine_options = engine_options_res.unwrap();
+        let engine_options = super::build_engine_options_with_home(home_dirs, args).unwrap();
 
         assert_eq!(super::get_resolution_x(&engine_options), 1100);
         assert_eq!(super::get_resolution_y(&engine_options), 480);
         assert_eq!(super::should_start_in_fullscreen(&engine_options), true);
+        assert_eq!(engine_options.saves_home, temp_dir.path().join(".ja2"));
+    }
+
+    #[test]
+    fn build_engine_options_with_home_should_check_the_game_dir() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"data_dir\": \"/ja2\", \"game_dir\": \"/nonexistent/ja2\" }");
+        let home_dirs = super::home::HomeDirs::single(temp_dir.path().join(".ja2"));
+        let args = |game_dir: &::std::path::Path| vec!(String::from("ja2"), String::from("--gamedir"), game_dir.display().to_string());
+
+        let error = super::build_engine_options_with_home(home_dirs.clone(), vec!(String::from("ja2"))).unwrap_err();
+        let engine_options = super::build_engine_options_with_home(home_dirs.clone(), args(temp_dir.path())).unwrap();
+        let missing = super::build_engine_options_with_home(home_dirs, args(&temp_dir.path().join("missing"))).unwrap_err();
+
+        assert_eq!(error, super::OptionsError::new(super::OptionsExitCode::INVALID_DATA_DIR, String::from("The game dir /nonexistent/ja2 does not exist")));
+        assert_eq!(engine_options.game_dir, temp_dir.path());
+        assert_eq!(missing.exit_code, super::OptionsExitCode::INVALID_DATA_DIR);
+    }
+
+    #[test]
+    fn build_engine_options_with_home_should_create_the_save_game_dir() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"data_dir\": \"/ja2\", \"save_game_dir\": \"../cloud/saves\" }");
+        let home_dirs = super::home::HomeDirs::single(temp_dir.path().join(".ja2"));
+        fs::write(temp_dir.path().join("file"), b"").unwrap();
+
+        super::build_engine_options_with_home(home_dirs.clone(), vec!(String::from("ja2"))).unwrap();
+        let error = super::build_engine_options_with_home(home_dirs, vec!(String::from("ja2"), String::from("--savegamedir"), String::from("../file/saves"))).unwrap_err();
+
+        assert!(temp_dir.path().join("cloud/saves").is_dir());
+        assert_eq!(error.exit_code, super::OptionsExitCode::HOME_DIR_ERROR);
+        assert!(error.message.starts_with("Could not use "), "{}", error.message);
     }
 
     #[test]
-    #[cfg(not(windows))]
-    fn build_engine_options_from_env_and_args_should_return_an_error_if_datadir_is_not_set() {
-        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": \"1024x768\", \"fullscreen\": true }");
+    fn build_engine_options_with_home_should_return_an_error_if_datadir_is_not_set() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": \"1024x768\", \"fullscreen\": true, \"launcher_language\": \"en\" }");
         let args = vec!(String::from("ja2"), String::from("--res"), String::from("1100x480"));
-        let old_home = env::var("HOME");
+        let home_dirs = super::home::HomeDirs::single(temp_dir.path().join(".ja2"));
         let expected_error_message = "Vanilla data directory has to be set either in config file or per command line switch";
 
-        env::set_var("HOME", temp_dir.path());
-        let engine_options_res = super::build_engine_options_from_env_and_args(args);
-        match old_home {
-            Ok(home) => env::set_var("HOME", home),
-            _ => {}
-        }
-        assert_eq!(engine_options_res, Err(String::from(expected_error_message)));
+        let engine_options_res = super::build_engine_options_with_home(home_dirs.clone(), args.clone());
+        fs::write(temp_dir.path().join(".ja2/ja2.json"), b"{ \"launcher_language\": \"de\" }").unwrap();
+        let localized_engine_options_res = super::build_engine_options_with_home(home_dirs, args);
+
+        assert_eq!(engine_options_res, Err(super::OptionsError::new(super::OptionsExitCode::INVALID_DATA_DIR, String::from(expected_error_message))));
+        assert_eq!(localized_engine_options_res.unwrap_err().message, "Das Verzeichnis des Originalspiels muss in der Konfigurationsdatei oder per Kommandozeilenparameter angegeben werden");
     }
 
     #[test]
@@ -1070,7 +2876,7 @@ mod tests {
         let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
 
         engine_options.stracciatella_home = stracciatella_home.clone();
-        engine_options.resolution = (100, 100);
+        engine_options.resolution = super::Resolution::new(100, 100);
 
         super::write_engine_options(&mut engine_options);
 
@@ -1079,6 +2885,22 @@ mod tests {
         assert_eq!(got_engine_options.resolution, engine_options.resolution);
     }
 
+    #[test]
+    fn write_engine_options_should_keep_the_object_form_of_the_resolution() {
+        let temp_dir = write_temp_folder_with_ja2_ini(b"{ \"res\": { \"width\": 1280, \"height\": 720 } }");
+        let stracciatella_home = PathBuf::from(temp_dir.path().join(".ja2"));
+        let mut engine_options = super::parse_json_config(stracciatella_home.clone()).unwrap();
+
+        super::write_engine_options(&mut engine_options);
+        let config: serde_json::Value = serde_json::from_str(&fs::read_to_string(stracciatella_home.join("ja2.json")).unwrap()).unwrap();
+        assert_eq!(config["res"], json!({ "width": 1280, "height": 720 }));
+
+        super::set_resolution_object_form(&mut engine_options, false);
+        super::write_engine_options(&mut engine_options);
+        let config: serde_json::Value = serde_json::from_str(&fs::read_to_string(stracciatella_home.join("ja2.json")).unwrap()).unwrap();
+        assert_eq!(config["res"], json!("1280x720"));
+    }
+
     #[test]
     fn write_engine_options_should_write_a_pretty_json_file() {
         let mut engine_options = super::EngineOptions::default();
@@ -1087,7 +2909,7 @@ mod tests {
         let stracciatella_json = PathBuf::from(temp_dir.path().join(".ja2/ja2.json"));
 
         engine_options.stracciatella_home = stracciatella_home.clone();
-        engine_options.resolution = (100, 100);
+        engine_options.resolution = super::Resolution::new(100, 100);
 
         super::write_engine_options(&mut engine_options);
 
@@ -1097,13 +2919,79 @@ mod tests {
         assert_eq!(config_file_contents,
 r##"{
   "data_dir": "",
+  "canonicalize_data_dir": true,
+  "game_dir": "",
   "mods": [],
+  "extra_data_dirs": [],
+  "mods_below_extra_data_dirs": [],
+  "save_game_dir": "",
   "res": "100x100",
   "resversion": "ENGLISH",
+  "game_text_language": null,
+  "game_language": "",
+  "launcher_language": "",
   "fullscreen": false,
+  "window_always_on_top": false,
+  "grab_mouse": false,
+  "pause_on_focus_loss": true,
+  "single_instance": false,
   "scaling": "PERFECT",
+  "brightness": 1.0,
+  "max_fps": 0,
+  "display_index": 0,
+  "window_x": null,
+  "window_y": null,
   "debug": false,
-  "nosound": false
+  "enable_cheats": false,
+  "nosound": false,
+  "audio_device": "",
+  "audio_sample_rate": 44100,
+  "audio_driver": "",
+  "music_pack": "",
+  "ui_theme": "",
+  "gameplay": {
+    "subtitles": true,
+    "speech_subtitle_duration_ms": 60,
+    "show_tutorials": true,
+    "show_hints": true,
+    "vanilla_fixes": {
+      "interrupts": {
+        "no_interrupts_from_unseen_enemies": false,
+        "use_remaining_action_points": false
+      },
+      "bonuses": {
+        "leadership_bonus_same_sector_only": false,
+        "burst_marksmanship_bonus": false
+      }
+    }
+  },
+  "screenshots": {
+    "format": "PNG",
+    "jpeg_quality": 90,
+    "file_name": "screenshot-{date}-{time}"
+  },
+  "video_capture": {
+    "target": "FILE",
+    "frame_rate": 25,
+    "ffmpeg": "ffmpeg",
+    "ffmpeg_output_args": [
+      "-c:v",
+      "libx264",
+      "-pix_fmt",
+      "yuv420p"
+    ],
+    "file_name": "capture-{date}-{time}.mp4"
+  },
+  "saves": {
+    "quota_mb": 0,
+    "keep_backups": 3,
+    "backup_max_age_days": 30
+  },
+  "log_format": "TEXT",
+  "log_filters": {},
+  "campaign_log": false,
+  "enable_rich_presence": false,
+  "startup_checks": "FAST"
 }"##);
     }
 
@@ -1128,4 +3016,14 @@ r##"{
         assert_chars_eq!(super::find_ja2_executable(CString::new("ja2-launcher.exe").unwrap().as_ptr()), "ja2.exe");
         assert_chars_eq!(super::find_ja2_executable(CString::new("JA2-LAUNCHER.EXE").unwrap().as_ptr()), "JA2.exe");
     }
+
+    #[test]
+    fn check_ffi_api_version_should_match_the_header() {
+        let header = fs::read_to_string(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../src/externalized/RustInterface.h")).unwrap();
+
+        assert!(header.contains(&format!("#define RUST_FFI_API_VERSION {}\n", super::FFI_API_VERSION)));
+        assert_eq!(super::check_ffi_api_version(super::FFI_API_VERSION), Ok(()));
+        assert!(!super::negotiate_ffi_api_version(super::FFI_API_VERSION + 1));
+        assert!(super::check_ffi_api_version(0).unwrap_err().starts_with("The stracciatella library has interface version 2, but the executable expects version 0."));
+    }
 }
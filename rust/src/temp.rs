@@ -0,0 +1,107 @@
+//! Scratch directories for operations that write several files or replace files, e.g. imports and repairs.
+//!
+//! Every operation gets its own directory in the cache dir, so results are only moved to their destination when
+//! they are complete. The directory is removed when the `ScratchDir` is dropped, on success as well as on errors.
+//! Leftovers of a crash are removed with the rest of the cache dir, see `home::clear_cache_dir`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use logger::{self, LogLevel};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    /// Creates an empty directory `<operation>-<pid>-<n>` in the cache dir.
+    pub fn new(cache_dir: &Path, operation: &str) -> Result<ScratchDir, String> {
+        fs::create_dir_all(cache_dir).map_err(|s| format!("Error creating {}: {}", cache_dir.display(), s.kind()))?;
+        loop {
+            let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+            let path = cache_dir.join(format!("{}-{}-{}", operation, process::id(), id));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(ScratchDir { path }),
+                Err(ref s) if s.kind() == ::std::io::ErrorKind::AlreadyExists => continue,
+                Err(s) => return Err(format!("Error creating {}: {}", path.display(), s.kind())),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// Moves a file of the scratch dir to its destination, replacing an existing file. Falls back to copying if the
+    /// destination is on another file system.
+    pub fn persist(&self, name: &str, target: &Path) -> Result<(), String> {
+        let source = self.path.join(name);
+        if fs::rename(&source, target).is_ok() {
+            return Ok(());
+        }
+        fs::copy(&source, target).map(|_| ()).map_err(|s| format!("Error writing {}: {}", target.display(), s.kind()))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(s) = fs::remove_dir_all(&self.path) {
+            logger::log(LogLevel::WARNING, "temp", &format!("Error removing {}: {}", self.path.display(), s.kind()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+
+    use super::ScratchDir;
+
+    #[test]
+    fn scratch_dirs_should_be_unique_and_removed_when_dropped() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let cache_dir = temp_dir.path().join("tmp");
+
+        let first = ScratchDir::new(&cache_dir, "import").unwrap();
+        let second = ScratchDir::new(&cache_dir, "import").unwrap();
+        fs::write(first.join("a.txt"), "a").unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().starts_with(&cache_dir));
+
+        let first_path = first.path().to_path_buf();
+        drop(first);
+        drop(second);
+        assert!(!first_path.exists());
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn scratch_dirs_should_be_removed_after_errors() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let operation = |fail: bool| -> Result<(), String> {
+            let scratch = ScratchDir::new(temp_dir.path(), "repair")?;
+            fs::write(scratch.join("save.sav"), "patched").unwrap();
+            if fail {
+                return Err(String::from("failed"));
+            }
+            scratch.persist("save.sav", &temp_dir.path().join("save.sav"))
+        };
+
+        assert!(operation(true).is_err());
+        assert_eq!(operation(false), Ok(()));
+
+        assert_eq!(fs::read_to_string(temp_dir.path().join("save.sav")).unwrap(), "patched");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+}
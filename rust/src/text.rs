@@ -0,0 +1,340 @@
+//! Texts of the vanilla data.
+//!
+//! Most texts of the game are in EDT files, which contain records of fixed size UTF-16 strings that are "encrypted"
+//! with ROT-1. The strings are exported keyed by the path of the resource and their index, so translators can work on
+//! them without knowing the layout of the records. The index of a string is the number of the field counted over all
+//! records, e.g. the 3rd field of the 2nd record of `binarydata/itemdesc.edt` has index 7.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use libc::c_char;
+
+use {EngineOptions, ResourceVersion};
+use datadir::find_data_folder;
+use logger::{self, LogLevel};
+use slf::SlfLibrary;
+
+pub mod tables;
+
+/// Number of characters of a dialogue quote.
+const DIALOGUE_SIZE: u32 = 240;
+
+/// Number of characters of a civilian quote.
+const CIV_QUOTE_SIZE: u32 = 160;
+
+/// Number of characters of a line, used for EDT files without a known layout.
+const LINE_SIZE: u32 = 80;
+
+/// Sizes of the fields of a record in EDT files, in characters.
+static EDT_LAYOUTS: [(&'static str, &'static [u32]); 20] = [
+    ("binarydata/aimbios.edt", &[400, 160]),
+    ("binarydata/aimhist.edt", &[400]),
+    ("binarydata/aimpol.edt", &[400]),
+    ("binarydata/alumname.edt", &[80]),
+    ("binarydata/alumni.edt", &[80, 560]),
+    ("binarydata/braydesc.edt", &[80, 320]),
+    ("binarydata/credits.edt", &[80]),
+    ("binarydata/email.edt", &[320]),
+    ("binarydata/files.edt", &[400]),
+    ("binarydata/flowercard.edt", &[400]),
+    ("binarydata/flowerdesc.edt", &[80, 80, 320]),
+    ("binarydata/help.edt", &[640]),
+    ("binarydata/impass.edt", &[320]),
+    ("binarydata/imptext.edt", &[400]),
+    ("binarydata/insurancemulti.edt", &[400]),
+    ("binarydata/insurancesingle.edt", &[80]),
+    ("binarydata/itemdesc.edt", &[80, 80, 240, 160, 160]),
+    ("binarydata/mercbios.edt", &[400, 160]),
+    ("binarydata/quests.edt", &[80]),
+    ("binarydata/ris.edt", &[400]),
+];
+
+/// Encoding quirks of the data files of the different resource versions, see `tables`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum StringEncoding {
+    ENGLISH,
+    RUSSIAN,
+    POLISH,
+    NORMAL,
+}
+
+impl StringEncoding {
+    pub fn from_resource_version(version: ResourceVersion) -> StringEncoding {
+        match version {
+            ResourceVersion::RUSSIAN | ResourceVersion::RUSSIAN_GOLD => StringEncoding::RUSSIAN,
+            ResourceVersion::POLISH => StringEncoding::POLISH,
+            ResourceVersion::ENGLISH => StringEncoding::ENGLISH,
+            _ => StringEncoding::NORMAL,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextEntry {
+    pub resource: String,
+    pub index: u32,
+    pub text: String,
+}
+
+/// Returns the sizes of the fields of a record in an EDT file.
+pub fn record_layout(resource: &str) -> &'static [u32] {
+    let resource = resource.to_lowercase();
+    match EDT_LAYOUTS.iter().find(|&&(r, _)| r == resource) {
+        Some(&(_, layout)) => layout,
+        None if resource.starts_with("mercedt/") => &[DIALOGUE_SIZE],
+        None if resource.starts_with("npcdata/") => &[CIV_QUOTE_SIZE],
+        None => &[LINE_SIZE],
+    }
+}
+
+/// Returns the index of the string that starts at an offset in characters, see the module documentation. Returns
+/// `None` if no field starts at the offset.
+pub fn string_index(resource: &str, seek_chars: u32) -> Option<u32> {
+    let layout = record_layout(resource);
+    let record_size: u32 = layout.iter().sum();
+    let offset_in_record = seek_chars % record_size;
+    let mut field_offset = 0;
+
+    for (field, size) in layout.iter().enumerate() {
+        if field_offset == offset_in_record {
+            return Some(seek_chars / record_size * layout.len() as u32 + field as u32);
+        }
+        field_offset += size;
+    }
+    None
+}
+
+/// Decodes a string of an EDT file. The string ends at the first NUL character.
+pub fn decode_edt_string(bytes: &[u8], encoding: StringEncoding) -> String {
+    bytes.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| u16::from(c[0]) | u16::from(c[1]) << 8)
+        .take_while(|c| *c != 0)
+        .map(|c| if c > 33 { c - 1 } else { c })
+        .map(|c| ::std::char::from_u32(u32::from(tables::decode_code_point(encoding, c))).unwrap_or('\u{FFFD}'))
+        .collect()
+}
+
+/// Splits the contents of an EDT file into its strings.
+pub fn decode_edt(resource: &str, bytes: &[u8], encoding: StringEncoding) -> Vec<String> {
+    let layout = record_layout(resource);
+    let mut strings = vec!();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        for size in layout {
+            let end = ::std::cmp::min(offset + 2 * *size as usize, bytes.len());
+            if offset < end {
+                strings.push(decode_edt_string(&bytes[offset..end], encoding));
+            }
+            offset = end;
+        }
+    }
+    strings
+}
+
+fn is_edt(name: &str) -> bool {
+    name.to_lowercase().ends_with(".edt")
+}
+
+fn read_loose_edt_files(dir: &Path, data_folder: &Path, files: &mut BTreeMap<String, Vec<u8>>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", dir.display(), s.kind()))?.path();
+        if path.is_dir() {
+            read_loose_edt_files(&path, data_folder, files)?;
+        } else if is_edt(&path.to_string_lossy()) {
+            let resource = path.strip_prefix(data_folder).unwrap().to_string_lossy().replace('\\', "/").to_lowercase();
+            let mut contents = vec!();
+            File::open(&path)
+                .and_then(|mut f| f.read_to_end(&mut contents))
+                .map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+            files.insert(resource, contents);
+        }
+    }
+    Ok(())
+}
+
+/// Reads all EDT files of the vanilla data, from the libraries and loose files. Loose files take precedence like in
+/// the engine.
+pub fn read_edt_files(vanilla_data_dir: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let data_folder = find_data_folder(vanilla_data_dir).ok_or_else(|| format!("{} contains no Data folder", vanilla_data_dir.display()))?;
+    let mut files = BTreeMap::new();
+
+    let entries = fs::read_dir(&data_folder).map_err(|s| format!("Error reading {}: {}", data_folder.display(), s.kind()))?;
+    for entry in entries {
+        let path = entry.map_err(|s| format!("Error reading {}: {}", data_folder.display(), s.kind()))?.path();
+        if !path.to_string_lossy().to_lowercase().ends_with(".slf") {
+            continue;
+        }
+        let library = SlfLibrary::open(&path)?;
+        for entry in library.entries.iter().filter(|e| is_edt(&e.name)) {
+            files.insert(library.full_name(entry).to_lowercase(), library.read(entry)?);
+        }
+    }
+    read_loose_edt_files(&data_folder, &data_folder, &mut files)?;
+    Ok(files)
+}
+
+/// Returns all non-empty strings of the EDT files in the vanilla data.
+pub fn export_strings(vanilla_data_dir: &Path, version: ResourceVersion) -> Result<Vec<TextEntry>, String> {
+    let encoding = StringEncoding::from_resource_version(version);
+    let mut entries = vec!();
+
+    for (resource, contents) in read_edt_files(vanilla_data_dir)? {
+        for (index, text) in decode_edt(&resource, &contents, encoding).into_iter().enumerate() {
+            if !text.is_empty() {
+                entries.push(TextEntry { resource: resource.clone(), index: index as u32, text });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the strings as JSON object with the resources as keys and objects of index and text as values.
+pub fn to_json(entries: &[TextEntry]) -> Result<String, String> {
+    let mut resources: BTreeMap<&str, BTreeMap<u32, &str>> = BTreeMap::new();
+    for e in entries {
+        resources.entry(&e.resource).or_default().insert(e.index, &e.text);
+    }
+    serde_json::to_string_pretty(&resources).map_err(|s| format!("Error exporting strings: {}", s))
+}
+
+fn escape_po(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+/// Returns the strings as gettext PO template. The context of every string is `<resource>:<index>`.
+pub fn to_po(entries: &[TextEntry], version: ResourceVersion) -> String {
+    let mut po = format!(
+        "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n\"X-Resource-Version: {}\\n\"\n",
+        version
+    );
+    for e in entries {
+        po.push_str(&format!(
+            "\n#: {0}:{1}\nmsgctxt \"{0}:{1}\"\nmsgid \"{2}\"\nmsgstr \"\"\n",
+            escape_po(&e.resource), e.index, escape_po(&e.text)
+        ));
+    }
+    po
+}
+
+/// Exports the strings of the vanilla data to a file. The file is a PO template if the extension is `po` or `pot`,
+/// otherwise JSON.
+#[no_mangle]
+pub extern fn export_vanilla_strings(ptr: *const EngineOptions, output_path_ptr: *const c_char) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let output_path = unsafe { CStr::from_ptr(output_path_ptr) }.to_string_lossy().into_owned();
+    let is_po = output_path.to_lowercase().ends_with(".po") || output_path.to_lowercase().ends_with(".pot");
+
+    let contents = export_strings(&engine_options.vanilla_data_dir, engine_options.resource_version).and_then(|entries| {
+        if is_po { Ok(to_po(&entries, engine_options.resource_version)) } else { to_json(&entries) }
+    });
+    let result = contents.and_then(|c| {
+        File::create(&output_path)
+            .and_then(|mut f| f.write_all(c.as_bytes()))
+            .map_err(|s| format!("Error writing {}: {}", output_path, s.kind()))
+    });
+
+    match result {
+        Ok(()) => true,
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "text", &format!("Error exporting the strings: {}", s));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use ResourceVersion;
+    use slf::tests::write_library;
+    use super::{StringEncoding, TextEntry};
+
+    /// Encodes strings like the EDT files with fixed size fields.
+    pub fn encode_edt(strings: &[(&str, usize)]) -> Vec<u8> {
+        let mut bytes = vec!();
+        for &(s, size) in strings {
+            let mut chars: Vec<u32> = s.chars().map(|c| c as u32).map(|c| if c > 32 { c + 1 } else { c }).collect();
+            chars.resize(size, 0);
+            for c in chars {
+                bytes.push(c as u8);
+                bytes.push((c >> 8) as u8);
+            }
+        }
+        bytes
+    }
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents).unwrap();
+    }
+
+    #[test]
+    fn decode_edt_string_should_fix_the_encoding() {
+        assert_eq!(super::decode_edt_string(&encode_edt(&[("Hello, world!", 20)]), StringEncoding::NORMAL), "Hello, world!");
+        assert_eq!(super::decode_edt_string(&[0x81, 0x00], StringEncoding::ENGLISH), "Ç");
+        assert_eq!(super::decode_edt_string(&[0xA4, 0x00], StringEncoding::POLISH), "Ł");
+        assert_eq!(super::decode_edt_string(&[0xC1, 0x00], StringEncoding::RUSSIAN), "А");
+        assert_eq!(super::decode_edt_string(&[0x4E, 0x04], StringEncoding::NORMAL), "А");
+    }
+
+    #[test]
+    fn decode_edt_should_use_the_record_layout() {
+        let bytes = encode_edt(&[("Name", 80), ("Short", 80), ("Info", 240), ("", 160), ("Cons", 160), ("Second", 80)]);
+
+        assert_eq!(super::decode_edt("BinaryData/ItemDesc.edt", &bytes, StringEncoding::NORMAL), vec!("Name", "Short", "Info", "", "Cons", "Second"));
+        assert_eq!(super::record_layout("mercedt/051.edt"), &[240]);
+        assert_eq!(super::record_layout("binarydata/unknown.edt"), &[80]);
+    }
+
+    #[test]
+    fn string_index_should_count_the_fields_of_all_records() {
+        assert_eq!(super::string_index("binarydata/itemdesc.edt", 0), Some(0));
+        assert_eq!(super::string_index("binarydata/itemdesc.edt", 720 + 160), Some(7));
+        assert_eq!(super::string_index("binarydata/itemdesc.edt", 720 + 161), None);
+        assert_eq!(super::string_index("mercedt/051.edt", 3 * 240), Some(3));
+    }
+
+    #[test]
+    fn export_strings_should_read_libraries_and_loose_files() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        fs::create_dir_all(temp_dir.path().join("Data")).unwrap();
+        let credits = encode_edt(&[("Credits", 80), ("", 80), ("Team", 80)]);
+        let quote = encode_edt(&[("Hi \"boss\"", 240)]);
+        write_library(&temp_dir.path().join("Data/BinaryData.slf"), "BINARYDATA\\", &[("CREDITS.EDT", Some(&credits)), ("SOUND.WAV", Some(b"wav"))]);
+        write_library(&temp_dir.path().join("Data/MercEdt.slf"), "MERCEDT\\", &[("051.EDT", Some(&quote))]);
+        write_file(&temp_dir.path().join("Data/mercedt/052.edt"), &encode_edt(&[("Loose", 240)]));
+
+        let entries = super::export_strings(temp_dir.path(), ResourceVersion::ENGLISH).unwrap();
+
+        let entry = |resource: &str, index, text: &str| TextEntry { resource: String::from(resource), index, text: String::from(text) };
+        assert_eq!(entries, vec!(
+            entry("binarydata/credits.edt", 0, "Credits"),
+            entry("binarydata/credits.edt", 2, "Team"),
+            entry("mercedt/051.edt", 0, "Hi \"boss\""),
+            entry("mercedt/052.edt", 0, "Loose"),
+        ));
+        assert_eq!(
+            ::serde_json::from_str::<::serde_json::Value>(&super::to_json(&entries).unwrap()).unwrap(),
+            json!({ "binarydata/credits.edt": { "0": "Credits", "2": "Team" }, "mercedt/051.edt": { "0": "Hi \"boss\"" }, "mercedt/052.edt": { "0": "Loose" } })
+        );
+        assert!(super::to_po(&entries, ResourceVersion::ENGLISH).contains(
+            "\n#: mercedt/051.edt:0\nmsgctxt \"mercedt/051.edt:0\"\nmsgid \"Hi \\\"boss\\\"\"\nmsgstr \"\"\n"
+        ));
+    }
+}
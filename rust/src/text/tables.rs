@@ -0,0 +1,138 @@
+//! Encoding tables of the texts in the data files.
+//!
+//! The translators of the original game wrote the texts in a single-byte code page (CP437, CP1250 or CP1251), but
+//! they were converted to UTF-16 as if they were CP1252. The tables map the code points in the data files to the
+//! intended characters and back, so the data can be read as well as written again.
+
+use super::StringEncoding;
+
+/// Code points that map to a single other code point.
+type Pairs = &'static [(u16, u16)];
+
+/// Ranges of code points `(first, last, first target)` that map to a contiguous range.
+type Ranges = &'static [(u16, u16, u16)];
+
+/// Some lines by Malice are encoded in CP437.
+static ENGLISH_PAIRS: [(u16, u16); 3] = [
+    (128, 0x00C7), // Ç
+    (130, 0x00E9), // é
+    (135, 0x00E7), // ç
+];
+
+/// Polish texts are encoded in CP1250, the format code for centering texts differs as well.
+static POLISH_PAIRS: [(u16, u16); 17] = [
+    (143, 0x0179), // Ź
+    (163, 0x0141), // Ł
+    (165, 0x0104), // Ą
+    (175, 0x017B), // Ż
+    (179, 0x0142), // ł
+    (182, 179),    // format code for centering
+    (185, 0x0105), // ą
+    (191, 0x017C), // ż
+    (198, 0x0106), // Ć
+    (202, 0x0118), // Ę
+    (209, 0x0143), // Ń
+    (230, 0x0107), // ć
+    (234, 0x0119), // ę
+    (241, 0x0144), // ń
+    (338, 0x015A), // Ś
+    (339, 0x015B), // ś
+    (376, 0x017A), // ź
+];
+
+/// Russian texts are encoded in CP1251, where the letters are the upper half of the code page.
+static RUSSIAN_RANGES: [(u16, u16, u16); 1] = [
+    (0x00C0, 0x00FF, 0x0410),
+];
+
+/// Cyrillic texts in the other versions (by Ivan Dolvich) are encoded in their own way.
+static CYRILLIC_RANGES: [(u16, u16, u16); 4] = [
+    (0x044D, 0x0452, 0x0410), // A to IE
+    (0x0453, 0x0453, 0x0401), // IO
+    (0x0454, 0x0467, 0x0416), // ZHE to SHCHA
+    (0x0468, 0x046C, 0x042B), // YERU to YA
+];
+
+fn tables(encoding: StringEncoding) -> (Pairs, Ranges) {
+    match encoding {
+        StringEncoding::ENGLISH => (&ENGLISH_PAIRS, &CYRILLIC_RANGES),
+        StringEncoding::RUSSIAN => (&[], &RUSSIAN_RANGES),
+        StringEncoding::POLISH => (&POLISH_PAIRS, &CYRILLIC_RANGES),
+        StringEncoding::NORMAL => (&[], &CYRILLIC_RANGES),
+    }
+}
+
+/// Returns the intended character of a code point in the data files.
+pub fn decode_code_point(encoding: StringEncoding, code_point: u16) -> u16 {
+    let (pairs, ranges) = tables(encoding);
+    if let Some(&(_, to)) = pairs.iter().find(|&&(from, _)| from == code_point) {
+        return to;
+    }
+    match ranges.iter().find(|&&(first, last, _)| first <= code_point && code_point <= last) {
+        Some(&(first, _, target)) => code_point - first + target,
+        None => code_point
+    }
+}
+
+/// Returns the code point in the data files for a character, the inverse of `decode_code_point`.
+pub fn encode_code_point(encoding: StringEncoding, code_point: u16) -> u16 {
+    let (pairs, ranges) = tables(encoding);
+    if let Some(&(from, _)) = pairs.iter().find(|&&(_, to)| to == code_point) {
+        return from;
+    }
+    match ranges.iter().find(|&&(first, last, target)| target <= code_point && code_point <= target + (last - first)) {
+        Some(&(first, _, target)) => code_point - target + first,
+        None => code_point
+    }
+}
+
+/// Fixes a UTF-16 code point of a text in the data files.
+#[no_mangle]
+pub extern fn fix_data_code_point(encoding: StringEncoding, code_point: u16) -> u16 {
+    decode_code_point(encoding, code_point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::StringEncoding;
+
+    static ALL_ENCODINGS: [StringEncoding; 4] = [StringEncoding::ENGLISH, StringEncoding::RUSSIAN, StringEncoding::POLISH, StringEncoding::NORMAL];
+
+    #[test]
+    fn decode_code_point_should_fix_the_code_pages() {
+        assert_eq!(super::decode_code_point(StringEncoding::ENGLISH, 130), 0x00E9);
+        assert_eq!(super::decode_code_point(StringEncoding::POLISH, 163), 0x0141);
+        assert_eq!(super::decode_code_point(StringEncoding::POLISH, 182), 179);
+        assert_eq!(super::decode_code_point(StringEncoding::RUSSIAN, 0xC0), 0x0410);
+        assert_eq!(super::decode_code_point(StringEncoding::RUSSIAN, 0xFF), 0x044F);
+        assert_eq!(super::decode_code_point(StringEncoding::RUSSIAN, 0x044D), 0x044D);
+        assert_eq!(super::decode_code_point(StringEncoding::NORMAL, 0x0453), 0x0401);
+        assert_eq!(super::decode_code_point(StringEncoding::NORMAL, 130), 130);
+    }
+
+    #[test]
+    fn encode_code_point_should_be_the_inverse_for_all_code_points() {
+        for &encoding in ALL_ENCODINGS.iter() {
+            for code_point in 0..=0xFFFFu16 {
+                let decoded = super::decode_code_point(encoding, code_point);
+                let encoded = super::encode_code_point(encoding, decoded);
+                assert_eq!(super::decode_code_point(encoding, encoded), decoded, "{:?} {:#x}", encoding, code_point);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_code_point_should_restore_all_fixed_code_points() {
+        for &encoding in ALL_ENCODINGS.iter() {
+            let (pairs, ranges) = super::tables(encoding);
+            for &(from, to) in pairs {
+                assert_eq!(super::encode_code_point(encoding, to), from);
+            }
+            for &(first, last, _) in ranges {
+                for code_point in first..=last {
+                    assert_eq!(super::encode_code_point(encoding, super::decode_code_point(encoding, code_point)), code_point);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Themes for the interface of widescreen resolutions.
+//!
+//! A theme pack is a folder `themes/<id>` with a `theme.json` manifest in any mount of the virtual file system, e.g.
+//! in a mod or an extra data dir. Files of the theme replace the interface files with the same path relative to the
+//! theme folder, so a theme never has to overwrite the files of the game or of other themes.
+
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+use vfs::{Vfs, VfsFile};
+
+pub const THEMES_DIR: &str = "themes";
+pub const THEME_MANIFEST: &str = "theme.json";
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct ThemeManifest {
+    name: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    description: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Theme {
+    pub id: String,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+}
+
+/// Returns the path of a file of a theme relative to the data.
+pub fn theme_file_path(theme_id: &str, relative_path: &Path) -> PathBuf {
+    Path::new(THEMES_DIR).join(theme_id).join(relative_path)
+}
+
+/// Collects the ids of the folders in `themes` of all mounts, including the ones in libraries.
+fn find_theme_ids(vfs: &Vfs) -> BTreeSet<String> {
+    let mut ids = BTreeSet::new();
+    for mount in &vfs.mounts {
+        match mount.library {
+            Some(ref library) => {
+                let prefix = format!("{}/", THEMES_DIR);
+                for entry in &library.entries {
+                    let full_name = library.full_name(entry).to_lowercase();
+                    if let Some(id) = full_name.strip_prefix(&prefix).and_then(|n| n.strip_suffix(&format!("/{}", THEME_MANIFEST))) {
+                        if !id.contains('/') {
+                            ids.insert(String::from(id));
+                        }
+                    }
+                }
+            },
+            None => if let Ok(entries) = fs::read_dir(mount.path.join(THEMES_DIR)) {
+                ids.extend(entries.filter_map(|e| e.ok())
+                    .filter(|e| e.path().join(THEME_MANIFEST).is_file())
+                    .map(|e| e.file_name().to_string_lossy().into_owned()));
+            }
+        }
+    }
+    ids
+}
+
+/// Reads the manifest of a theme from the mount with the highest priority that has it.
+pub fn load_theme(vfs: &Vfs, id: &str) -> Result<Theme, String> {
+    let contents = vfs.read(&theme_file_path(id, Path::new(THEME_MANIFEST)))?;
+    let manifest: ThemeManifest = serde_json::from_slice(&contents)
+        .map_err(|s| format!("Error parsing {} of theme {}: {}", THEME_MANIFEST, id, s))?;
+    Ok(Theme { id: String::from(id), name: manifest.name, author: manifest.author, description: manifest.description })
+}
+
+/// Lists the valid themes in the virtual file system, ordered by id.
+pub fn find_themes(vfs: &Vfs) -> Vec<Theme> {
+    find_theme_ids(vfs).iter().filter_map(|id| load_theme(vfs, id).ok()).collect()
+}
+
+/// Returns the file of the selected theme, or the file without theme if the theme does not replace it.
+pub fn find_themed_file<'a>(vfs: &'a Vfs, theme_id: &str, relative_path: &Path) -> Option<VfsFile<'a>> {
+    if theme_id.is_empty() {
+        return vfs.find(relative_path);
+    }
+    vfs.find(&theme_file_path(theme_id, relative_path)).or_else(|| vfs.find(relative_path))
+}
+
+fn theme_at(ptr: *const Vfs, index: u32) -> Theme {
+    let themes = find_themes(unsafe_from_ptr!(ptr));
+    match themes.into_iter().nth(index as usize) {
+        Some(t) => t,
+        None => panic!("Invalid theme index {}", index)
+    }
+}
+
+#[no_mangle]
+pub extern fn get_ui_theme(ptr: *const EngineOptions) -> *mut c_char {
+    CString::new(unsafe_from_ptr!(ptr).ui_theme.clone()).unwrap().into_raw()
+}
+
+/// Selects a theme by id, an empty id selects the default interface.
+#[no_mangle]
+pub extern fn set_ui_theme(ptr: *mut EngineOptions, theme_ptr: *const c_char) -> () {
+    let c_str = unsafe { CStr::from_ptr(theme_ptr) };
+    unsafe_from_ptr_mut!(ptr).ui_theme = c_str.to_string_lossy().into_owned();
+}
+
+#[no_mangle]
+pub extern fn get_number_of_ui_themes(ptr: *const Vfs) -> u32 {
+    find_themes(unsafe_from_ptr!(ptr)).len() as u32
+}
+
+#[no_mangle]
+pub extern fn get_ui_theme_id(ptr: *const Vfs, index: u32) -> *mut c_char {
+    CString::new(theme_at(ptr, index).id).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern fn get_ui_theme_name(ptr: *const Vfs, index: u32) -> *mut c_char {
+    CString::new(theme_at(ptr, index).name).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use slf::SlfLibrary;
+    use slf::tests::write_library;
+    use vfs::{MountSource, Vfs, VfsFile};
+
+    fn write_theme(dir: &Path, id: &str, manifest: &str) {
+        let theme_dir = dir.join("themes").join(id);
+        fs::create_dir_all(&theme_dir).unwrap();
+        fs::write(theme_dir.join("theme.json"), manifest).unwrap();
+    }
+
+    #[test]
+    fn find_themes_should_return_valid_themes_of_all_mounts() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let base = temp_dir.path().join("base");
+        let hd = temp_dir.path().join("hd");
+        write_theme(&base, "desert", "{ \"name\": \"Desert\" }");
+        write_theme(&base, "broken", "{ \"author\": \"Nobody\" }");
+        write_theme(&hd, "desert", "{ \"name\": \"Desert HD\", \"author\": \"Someone\" }");
+        let library_path = temp_dir.path().join("themes.slf");
+        write_library(&library_path, "themes/", &[("night/theme.json", Some(b"{ \"name\": \"Night\" }")), ("night/interface/bars.sti", Some(b""))]);
+        let mut vfs = Vfs::default();
+        vfs.mount(MountSource::VANILLA, base, false);
+        vfs.mount_library(MountSource::MOD, SlfLibrary::open(&library_path).unwrap());
+        vfs.mount(MountSource::EXTRA, hd, false);
+
+        let themes = super::find_themes(&vfs);
+
+        assert_eq!(themes.iter().map(|t| t.id.as_str()).collect::<Vec<&str>>(), vec!("desert", "night"));
+        assert_eq!((themes[0].name.as_str(), themes[0].author.as_str()), ("Desert HD", "Someone"));
+        assert_eq!(themes[1].name, "Night");
+    }
+
+    #[test]
+    fn find_themed_file_should_fall_back_to_the_file_without_theme() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        write_theme(temp_dir.path(), "desert", "{ \"name\": \"Desert\" }");
+        fs::create_dir_all(temp_dir.path().join("themes/desert/interface")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("interface")).unwrap();
+        for file in &["themes/desert/interface/bars.sti", "interface/bars.sti", "interface/panel.sti"] {
+            fs::write(temp_dir.path().join(file), b"").unwrap();
+        }
+        let mut vfs = Vfs::default();
+        vfs.mount(MountSource::VANILLA, PathBuf::from(temp_dir.path()), false);
+
+        let themed = |theme: &str, file: &str| match super::find_themed_file(&vfs, theme, Path::new(file)) {
+            Some(VfsFile::Loose(p)) => p,
+            f => panic!("Unexpected file {:?}", f),
+        };
+
+        assert_eq!(themed("desert", "interface/bars.sti"), temp_dir.path().join("themes/desert/interface/bars.sti"));
+        assert_eq!(themed("desert", "interface/panel.sti"), temp_dir.path().join("interface/panel.sti"));
+        assert_eq!(themed("", "interface/bars.sti"), temp_dir.path().join("interface/bars.sti"));
+    }
+}
@@ -0,0 +1,365 @@
+//! Translation overrides for the texts of the vanilla data.
+//!
+//! Translations use the formats of the export in `text`: PO files with `<resource>:<index>` as context, or JSON with
+//! the resources as keys. They are read from `translations/<game_language>.po` or `.json` in the data of the mods and
+//! in the home. Translations in the home take precedence over mods, later mods over earlier ones.
+//!
+//! The game language `pseudo` translates every string of the vanilla data to an accented and lengthened version of
+//! itself, e.g. `[Çrédíts !!!]`. It shows untranslatable strings, strings that are cut off and the characters the
+//! fonts are missing before real translations exist.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Mutex;
+
+use libc::c_char;
+use serde_json::Value;
+
+use EngineOptions;
+use logger::{self, LogLevel};
+use text::{export_strings, string_index, TextEntry};
+use vfs::find_mod_dir;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Translations {
+    strings: HashMap<(String, u32), String>,
+}
+
+/// Translations that are used by the engine, see `load_translations`.
+static TRANSLATIONS: Mutex<Option<Translations>> = Mutex::new(None);
+
+/// Game language of the generated pseudo-locale, see `pseudo_localize`.
+pub const PSEUDO_LANGUAGE: &str = "pseudo";
+
+/// Accented replacements of the pseudo-locale. They are in the translation tables of all fonts.
+static PSEUDO_CHARACTERS: [(char, char); 12] = [
+    ('A', 'Â'), ('C', 'Ç'), ('E', 'É'), ('I', 'Ï'), ('O', 'Ô'), ('U', 'Û'),
+    ('a', 'á'), ('c', 'ç'), ('e', 'é'), ('i', 'í'), ('o', 'ó'), ('u', 'ú'),
+];
+
+/// Returns the pseudo-localized version of a string: accented, about 40% longer and in brackets, so it is obvious
+/// when the end is cut off. Format specifiers like `%d` or `%ls` are kept as they are.
+pub fn pseudo_localize(text: &str) -> String {
+    let mut result = String::from("[");
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            result.push(c);
+            // Flags, width and length modifiers up to the conversion character
+            for next in chars.by_ref() {
+                result.push(next);
+                if next.is_ascii_alphabetic() && next != 'l' && next != 'h' || next == '%' {
+                    break;
+                }
+            }
+            continue;
+        }
+        result.push(PSEUDO_CHARACTERS.iter().find(|&&(from, _)| from == c).map(|&(_, to)| to).unwrap_or(c));
+    }
+    let padding = (text.chars().count() * 2).div_ceil(5);
+    result.push(' ');
+    result.push_str(&"!".repeat(padding.max(1)));
+    result.push(']');
+    result
+}
+
+fn unescape_po(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+    result
+}
+
+/// Returns the contents of a quoted PO string.
+fn parse_po_string(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return Err(format!("Expected a quoted string instead of {}", s));
+    }
+    Ok(unescape_po(&s[1..s.len() - 1]))
+}
+
+fn to_text_entry(context: &str, text: String) -> Option<TextEntry> {
+    let separator = context.rfind(':')?;
+    let index = context[separator + 1..].parse().ok()?;
+    if text.is_empty() {
+        return None;
+    }
+    Some(TextEntry { resource: context[..separator].to_lowercase(), index, text })
+}
+
+/// Parses the translated strings of a PO file. Untranslated strings and strings without a valid context are skipped.
+pub fn parse_po(contents: &str) -> Result<Vec<TextEntry>, String> {
+    let mut entries = vec!();
+    // Keyword and value of the message that is currently parsed
+    let mut message: Vec<(String, String)> = vec!();
+
+    let mut finish_message = |message: &mut Vec<(String, String)>| {
+        let field = |name: &str| message.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+        if let (Some(context), Some(text)) = (field("msgctxt"), field("msgstr")) {
+            entries.extend(to_text_entry(&context, text));
+        }
+        message.clear();
+    };
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let error = |s: String| format!("Error in line {}: {}", number + 1, s);
+
+        if line.is_empty() {
+            finish_message(&mut message);
+        } else if line.starts_with('#') {
+            continue;
+        } else if line.starts_with('"') {
+            let value = parse_po_string(line).map_err(&error)?;
+            match message.last_mut() {
+                Some(&mut (_, ref mut v)) => v.push_str(&value),
+                None => return Err(error(String::from("String without keyword")))
+            }
+        } else {
+            let separator = line.find(' ').ok_or_else(|| error(format!("Unknown line {}", line)))?;
+            let keyword = &line[..separator];
+            if keyword == "msgctxt" && message.iter().any(|(k, _)| k == "msgid") {
+                finish_message(&mut message);
+            }
+            message.push((String::from(keyword), parse_po_string(&line[separator..]).map_err(&error)?));
+        }
+    }
+    finish_message(&mut message);
+    Ok(entries)
+}
+
+/// Parses the strings of a JSON file with the resources as keys and objects of index and text as values.
+pub fn parse_json(contents: &str) -> Result<Vec<TextEntry>, String> {
+    let value: Value = serde_json::from_str(contents).map_err(|s| format!("Error parsing translation: {}", s))?;
+    let resources = value.as_object().ok_or_else(|| String::from("Error parsing translation: not a JSON object"))?;
+    let mut entries = vec!();
+
+    for (resource, strings) in resources {
+        let strings = strings.as_object().ok_or_else(|| format!("Error parsing translation: {} is not a JSON object", resource))?;
+        for (index, text) in strings {
+            let text = text.as_str().ok_or_else(|| format!("Error parsing translation: {}:{} is not a string", resource, index))?;
+            entries.extend(to_text_entry(&format!("{}:{}", resource, index), String::from(text)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the folders that can contain translations, from lowest to highest priority.
+pub fn translation_dirs(engine_options: &EngineOptions, extra_data_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = engine_options.mods.iter()
+        .map(|m| find_mod_dir(extra_data_dir, &engine_options.stracciatella_home, m).join("translations"))
+        .collect();
+    dirs.push(engine_options.stracciatella_home.join("translations"));
+    dirs
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|s| format!("Error reading {}: {}", path.display(), s.kind()))?;
+    Ok(contents)
+}
+
+impl Translations {
+    pub fn insert(&mut self, entries: Vec<TextEntry>) {
+        for e in entries {
+            self.strings.insert((e.resource, e.index), e.text);
+        }
+    }
+
+    pub fn get(&self, resource: &str, index: u32) -> Option<&str> {
+        self.strings.get(&(resource.to_lowercase(), index)).map(|s| s.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Loads the translations for the game language of the engine options. Without a game language there are no
+    /// translations, the pseudo-locale is generated from the vanilla data.
+    pub fn load(engine_options: &EngineOptions, extra_data_dir: &Path) -> Result<Translations, String> {
+        let mut translations = Translations::default();
+        if engine_options.game_language.is_empty() {
+            return Ok(translations);
+        }
+        if engine_options.game_language == PSEUDO_LANGUAGE {
+            let entries = export_strings(&engine_options.vanilla_data_dir, engine_options.resource_version)?;
+            translations.insert(entries.into_iter().map(|e| TextEntry { text: pseudo_localize(&e.text), ..e }).collect());
+            return Ok(translations);
+        }
+
+        for dir in translation_dirs(engine_options, extra_data_dir) {
+            let po_path = dir.join(format!("{}.po", engine_options.game_language));
+            let json_path = dir.join(format!("{}.json", engine_options.game_language));
+            if po_path.is_file() {
+                translations.insert(parse_po(&read_file(&po_path)?).map_err(|s| format!("{}: {}", po_path.display(), s))?);
+            }
+            if json_path.is_file() {
+                translations.insert(parse_json(&read_file(&json_path)?).map_err(|s| format!("{}: {}", json_path.display(), s))?);
+            }
+        }
+        Ok(translations)
+    }
+}
+
+/// Loads the translations that are used by `get_translated_string`.
+#[no_mangle]
+pub extern fn load_translations(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> bool {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let result = Translations::load(unsafe_from_ptr!(ptr), Path::new(&extra_data_dir));
+    let mut translations = TRANSLATIONS.lock().unwrap_or_else(|e| e.into_inner());
+
+    match result {
+        Ok(t) => {
+            *translations = Some(t);
+            true
+        },
+        Err(s) => {
+            logger::log(LogLevel::ERROR, "translation", &format!("Error loading the translations: {}", s));
+            *translations = None;
+            false
+        }
+    }
+}
+
+/// Returns the translation of the string at an offset in characters of an EDT file or null if it is not translated.
+#[no_mangle]
+pub extern fn get_translated_string(resource_ptr: *const c_char, seek_chars: u32) -> *mut c_char {
+    let resource = unsafe { CStr::from_ptr(resource_ptr) }.to_string_lossy().into_owned();
+    let translations = TRANSLATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let translated = translations.as_ref()
+        .and_then(|t| string_index(&resource, seek_chars).and_then(|i| t.get(&resource, i)));
+
+    match translated {
+        Some(s) => CString::new(s).unwrap().into_raw(),
+        None => ptr::null_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use EngineOptions;
+    use text::TextEntry;
+    use text::tests::encode_edt;
+    use super::{PSEUDO_LANGUAGE, Translations, pseudo_localize};
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn entry(resource: &str, index: u32, text: &str) -> TextEntry {
+        TextEntry { resource: String::from(resource), index, text: String::from(text) }
+    }
+
+    #[test]
+    fn parse_po_should_read_translated_strings() {
+        let po = r#"msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: binarydata/credits.edt:0
+msgctxt "binarydata/credits.edt:0"
+msgid "Credits"
+msgstr "Créditos"
+
+msgctxt "mercedt/051.edt:3"
+msgid "Hi \"boss\""
+msgstr ""
+"Olá \"chefe\"\n"
+"tudo bem?"
+msgctxt "binarydata/credits.edt:2"
+msgid "Team"
+msgstr ""
+"#;
+
+        assert_eq!(super::parse_po(po), Ok(vec!(
+            entry("binarydata/credits.edt", 0, "Créditos"),
+            entry("mercedt/051.edt", 3, "Olá \"chefe\"\ntudo bem?"),
+        )));
+        assert!(super::parse_po("msgid Credits").is_err());
+        assert!(super::parse_po("\"Credits\"").is_err());
+    }
+
+    #[test]
+    fn parse_json_should_read_the_export_format() {
+        let json = r#"{ "BinaryData/Credits.edt": { "0": "Créditos", "2": "" }, "mercedt/051.edt": { "3": "Olá" } }"#;
+
+        assert_eq!(super::parse_json(json), Ok(vec!(
+            entry("binarydata/credits.edt", 0, "Créditos"),
+            entry("mercedt/051.edt", 3, "Olá"),
+        )));
+        assert!(super::parse_json(r#"{ "mercedt/051.edt": ["Olá"] }"#).is_err());
+    }
+
+    #[test]
+    fn load_should_prefer_translations_in_the_home() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let extra_data_dir = temp_dir.path().join("extra");
+        let mut engine_options = EngineOptions::default();
+        engine_options.stracciatella_home = temp_dir.path().join("home");
+        engine_options.mods = vec!(String::from("portuguese"));
+        engine_options.game_language = String::from("pt_BR");
+        write_file(&extra_data_dir.join("mods/portuguese/data/translations/pt_BR.json"), r#"{ "binarydata/credits.edt": { "0": "Créditos", "2": "Equipe" } }"#);
+        write_file(&temp_dir.path().join("home/translations/pt_BR.po"), "msgctxt \"binarydata/credits.edt:2\"\nmsgid \"Team\"\nmsgstr \"Time\"\n");
+        write_file(&temp_dir.path().join("home/translations/de.po"), "msgctxt \"binarydata/credits.edt:0\"\nmsgid \"Credits\"\nmsgstr \"Mitwirkende\"\n");
+
+        let translations = Translations::load(&engine_options, &extra_data_dir).unwrap();
+
+        assert_eq!(translations.get("binarydata/credits.edt", 0), Some("Créditos"));
+        assert_eq!(translations.get("BINARYDATA/credits.edt", 2), Some("Time"));
+        assert_eq!(translations.get("binarydata/credits.edt", 1), None);
+
+        engine_options.game_language = String::from("");
+        assert!(Translations::load(&engine_options, &extra_data_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn load_should_generate_the_pseudo_locale_from_the_vanilla_data() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = temp_dir.path().to_path_buf();
+        engine_options.stracciatella_home = temp_dir.path().join("home");
+        engine_options.game_language = String::from(PSEUDO_LANGUAGE);
+        fs::create_dir_all(temp_dir.path().join("Data/BinaryData")).unwrap();
+        fs::write(temp_dir.path().join("Data/BinaryData/credits.edt"), encode_edt(&[("Credits", 80), ("", 80), ("Team", 80)])).unwrap();
+
+        let translations = Translations::load(&engine_options, &temp_dir.path().join("extra")).unwrap();
+
+        assert_eq!(translations.get("binarydata/credits.edt", 0), Some("[Çrédíts !!!]"));
+        assert_eq!(translations.get("binarydata/credits.edt", 1), None);
+        assert_eq!(translations.get("binarydata/credits.edt", 2), Some("[Téám !!]"));
+    }
+
+    #[test]
+    fn pseudo_localize_should_accent_and_lengthen_strings() {
+        assert_eq!(pseudo_localize("Credits"), "[Çrédíts !!!]");
+        assert_eq!(pseudo_localize("%d%% of %ls done\n"), "[%d%% óf %ls dóné\n !!!!!!!]");
+        assert_eq!(pseudo_localize(""), "[ !]");
+    }
+}
@@ -0,0 +1,217 @@
+//! Layout of the interface panels for widescreen resolutions from `ui_layout.json`.
+//!
+//! Screens are grouped into aspect ratio buckets, e.g. `16:9`. Each panel is anchored to a side or corner of the
+//! screen and can be moved by an offset in pixels per bucket. Mods can change single panels or buckets, see
+//! `gamedata::load_layered_json`.
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::path::Path;
+
+use libc::c_char;
+
+use EngineOptions;
+use gamedata::{game_data_dirs, load_layered_json};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum Anchor {
+    TOP_LEFT,
+    TOP,
+    TOP_RIGHT,
+    LEFT,
+    CENTER,
+    RIGHT,
+    BOTTOM_LEFT,
+    BOTTOM,
+    BOTTOM_RIGHT,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[repr(C)]
+pub struct PanelPosition {
+    pub anchor: Anchor,
+    pub offset_x: i32,
+    pub offset_y: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AspectRatioBucket {
+    /// Largest width divided by height that belongs to the bucket
+    pub max_aspect_ratio: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PanelLayout {
+    pub anchor: Anchor,
+    /// Offsets in pixels by aspect ratio bucket, panels are not moved in buckets without offset
+    #[serde(default)]
+    pub offsets: BTreeMap<String, [i32; 2]>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UiLayout {
+    pub aspect_ratios: BTreeMap<String, AspectRatioBucket>,
+    pub panels: BTreeMap<String, PanelLayout>,
+}
+
+impl UiLayout {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.aspect_ratios.is_empty() {
+            return Err(String::from("ui_layout.json has no aspect ratios"));
+        }
+        for (name, bucket) in &self.aspect_ratios {
+            if !bucket.max_aspect_ratio.is_finite() || bucket.max_aspect_ratio <= 0.0 {
+                return Err(format!("Aspect ratio {} in ui_layout.json has an invalid max_aspect_ratio {}", name, bucket.max_aspect_ratio));
+            }
+        }
+        for (panel_name, panel) in &self.panels {
+            if let Some(bucket) = panel.offsets.keys().find(|b| !self.aspect_ratios.contains_key(*b)) {
+                return Err(format!("Panel {} in ui_layout.json has an offset for unknown aspect ratio {}", panel_name, bucket));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the bucket with the smallest maximum that fits the screen, the largest bucket for even wider screens.
+    pub fn aspect_ratio_bucket(&self, width: u16, height: u16) -> Option<&str> {
+        let ratio = f64::from(width) / f64::from(height.max(1));
+        let mut buckets: Vec<(&String, &AspectRatioBucket)> = self.aspect_ratios.iter().collect();
+        buckets.sort_by(|a, b| a.1.max_aspect_ratio.partial_cmp(&b.1.max_aspect_ratio).unwrap());
+        buckets.iter()
+            .find(|(_, b)| ratio <= b.max_aspect_ratio)
+            .or_else(|| buckets.last())
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn panel_position(&self, panel_name: &str, width: u16, height: u16) -> Option<PanelPosition> {
+        let panel = self.panels.get(panel_name)?;
+        let offset = self.aspect_ratio_bucket(width, height)
+            .and_then(|b| panel.offsets.get(b))
+            .cloned()
+            .unwrap_or([0, 0]);
+        Some(PanelPosition { anchor: panel.anchor, offset_x: offset[0], offset_y: offset[1] })
+    }
+}
+
+pub fn load_ui_layout(extra_data_dir: &Path, stracciatella_home: &Path, mods: &[String]) -> Result<UiLayout, String> {
+    let value = load_layered_json(&game_data_dirs(extra_data_dir, stracciatella_home, mods), "ui_layout.json")?;
+    let layout: UiLayout = serde_json::from_value(value).map_err(|s| format!("Error parsing ui_layout.json: {}", s))?;
+    layout.validate()?;
+    Ok(layout)
+}
+
+/// Loads the position of a panel for the resolution of the engine options, taking the enabled mods into account.
+///
+/// Returns false if ui_layout.json could not be loaded or has no such panel.
+#[no_mangle]
+pub extern fn load_ui_panel_position(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char, panel_ptr: *const c_char, position_ptr: *mut PanelPosition) -> bool {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let panel = unsafe { CStr::from_ptr(panel_ptr) }.to_string_lossy().into_owned();
+    let position = unsafe_from_ptr_mut!(position_ptr);
+
+    let engine_options = unsafe_from_ptr!(ptr);
+    let resolution = &engine_options.resolution;
+
+    match load_ui_layout(Path::new(&extra_data_dir), &engine_options.stracciatella_home, &engine_options.mods) {
+        Ok(layout) => match layout.panel_position(&panel, resolution.width, resolution.height) {
+            Some(p) => {
+                *position = p;
+                true
+            },
+            None => false
+        },
+        Err(_) => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::ffi::CString;
+    use std::fs;
+    use std::path::Path;
+
+    use EngineOptions;
+    use super::{Anchor, PanelPosition};
+
+    fn write_file(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn copy_vanilla_ui_layout_json(extra_data_dir: &Path) {
+        let vanilla = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/externalized/ui_layout.json");
+        fs::create_dir_all(extra_data_dir.join("externalized")).unwrap();
+        fs::copy(vanilla, extra_data_dir.join("externalized/ui_layout.json")).unwrap();
+    }
+
+    #[test]
+    fn load_ui_layout_should_load_vanilla_values() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        copy_vanilla_ui_layout_json(temp_dir.path());
+
+        let layout = super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[]).unwrap();
+
+        assert_eq!(layout.aspect_ratio_bucket(640, 480), Some("4:3"));
+        assert_eq!(layout.aspect_ratio_bucket(1920, 1080), Some("16:9"));
+        assert_eq!(layout.aspect_ratio_bucket(1920, 1200), Some("16:10"));
+        assert_eq!(layout.aspect_ratio_bucket(3440, 1440), Some("21:9"));
+        assert_eq!(layout.panel_position("tactical_panel", 1920, 1080), Some(PanelPosition { anchor: Anchor::BOTTOM, offset_x: 0, offset_y: 0 }));
+        assert_eq!(layout.panel_position("unknown", 1920, 1080), None);
+    }
+
+    #[test]
+    fn load_ui_layout_should_apply_mod_offsets() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        copy_vanilla_ui_layout_json(temp_dir.path());
+        write_file(&temp_dir.path().join("mods/hud/data/ui_layout.json"), b"{ \"panels\": { \"radar\": { \"offsets\": { \"16:9\": [-10, 5] } } } }");
+
+        let layout = super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[String::from("hud")]).unwrap();
+
+        assert_eq!(layout.panel_position("radar", 1280, 720), Some(PanelPosition { anchor: Anchor::BOTTOM_RIGHT, offset_x: -10, offset_y: 5 }));
+        assert_eq!(layout.panel_position("radar", 800, 600), Some(PanelPosition { anchor: Anchor::BOTTOM_RIGHT, offset_x: 0, offset_y: 0 }));
+    }
+
+    #[test]
+    fn load_ui_layout_should_fail_with_invalid_layouts() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let path = temp_dir.path().join("externalized/ui_layout.json");
+
+        write_file(&path, b"{ \"aspect_ratios\": {}, \"panels\": {} }");
+        assert_eq!(super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[]), Err(String::from("ui_layout.json has no aspect ratios")));
+
+        write_file(&path, b"{ \"aspect_ratios\": { \"4:3\": { \"max_aspect_ratio\": 0 } }, \"panels\": {} }");
+        assert_eq!(super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[]), Err(String::from("Aspect ratio 4:3 in ui_layout.json has an invalid max_aspect_ratio 0")));
+
+        write_file(&path, b"{ \"aspect_ratios\": { \"4:3\": { \"max_aspect_ratio\": 1.4 } }, \"panels\": { \"radar\": { \"anchor\": \"TOP\", \"offsets\": { \"5:4\": [1, 1] } } } }");
+        assert_eq!(super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[]), Err(String::from("Panel radar in ui_layout.json has an offset for unknown aspect ratio 5:4")));
+
+        write_file(&path, b"{ \"aspect_ratios\": { \"4:3\": { \"max_aspect_ratio\": 1.4 } }, \"panels\": { \"radar\": { \"anchor\": \"MIDDLE\" } } }");
+        assert!(super::load_ui_layout(temp_dir.path(), temp_dir.path(), &[]).unwrap_err().starts_with("Error parsing ui_layout.json: unknown variant `MIDDLE`"));
+    }
+
+    #[test]
+    fn load_ui_panel_position_should_use_the_resolution_of_the_engine_options() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = EngineOptions::default();
+        let extra_data_dir = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let panel = CString::new("message_log").unwrap();
+        let mut position = PanelPosition { anchor: Anchor::CENTER, offset_x: 0, offset_y: 0 };
+        copy_vanilla_ui_layout_json(temp_dir.path());
+        write_file(&temp_dir.path().join("externalized/ui_layout.json"), &[]);
+
+        assert!(!super::load_ui_panel_position(&engine_options, extra_data_dir.as_ptr(), panel.as_ptr(), &mut position));
+
+        copy_vanilla_ui_layout_json(temp_dir.path());
+        engine_options.resolution = ::Resolution::new(2560, 1080);
+
+        assert!(super::load_ui_panel_position(&engine_options, extra_data_dir.as_ptr(), panel.as_ptr(), &mut position));
+        assert_eq!(position.anchor, Anchor::BOTTOM_LEFT);
+    }
+}
@@ -0,0 +1,261 @@
+//! Tests of the library that run together with the unit tests of the engine (`-unittests`).
+//!
+//! The tests check the configuration and the game data of the installation the engine runs with. The results are
+//! printed like googletest does and merged into the XML report that googletest writes for
+//! `--gtest_output=xml[:<path>]`, so there is only a single report.
+
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libc::c_char;
+
+use EngineOptions;
+use difficulty::load_difficulties;
+use logger::{self, LogLevel};
+use preflight;
+use preflight::PreflightStatus;
+
+/// Preflight checks that need the vanilla data. They are skipped without data, e.g. on CI servers.
+static DATA_CHECKS: [&str; 2] = ["data_dir_readable", "data_files"];
+
+#[derive(Debug, PartialEq, Clone)]
+#[allow(non_camel_case_types)]
+pub enum TestOutcome {
+    PASSED,
+    FAILED(String),
+    SKIPPED(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestResult {
+    pub suite: &'static str,
+    pub name: String,
+    pub outcome: TestOutcome,
+}
+
+impl TestResult {
+    fn new(suite: &'static str, name: &str, result: Result<(), String>) -> TestResult {
+        let outcome = match result {
+            Ok(()) => TestOutcome::PASSED,
+            Err(s) => TestOutcome::FAILED(s)
+        };
+        TestResult { suite, name: String::from(name), outcome }
+    }
+
+    fn failed(&self) -> bool {
+        matches!(self.outcome, TestOutcome::FAILED(_))
+    }
+}
+
+/// Returns the path of the XML report that googletest writes for its command line flags, if any.
+pub fn gtest_report_path(gtest_args: &[String], executable: &str) -> Option<PathBuf> {
+    let output = gtest_args.iter().rev()
+        .filter_map(|a| a.trim_start_matches('-').strip_prefix("gtest_output="))
+        .next()?;
+    if output == "xml" {
+        return Some(PathBuf::from("test_detail.xml"));
+    }
+    let path = output.strip_prefix("xml:")?;
+    if path.ends_with('/') || path.ends_with('\\') {
+        let name = Path::new(executable).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        return Some(Path::new(path).join(format!("{}.xml", name)));
+    }
+    Some(PathBuf::from(path))
+}
+
+fn test_config_round_trip(engine_options: &EngineOptions) -> Result<(), String> {
+    let json = serde_json::to_value(engine_options).map_err(|s| format!("Error serializing the options: {}", s))?;
+    let parsed: EngineOptions = serde_json::from_value(json.clone()).map_err(|s| format!("Error parsing the serialized options: {}", s))?;
+    let json_again = serde_json::to_value(&parsed).map_err(|s| format!("Error serializing the parsed options: {}", s))?;
+    if json_again != json {
+        return Err(format!("Options changed when written and read again:\n{}\n{}", json, json_again));
+    }
+    Ok(())
+}
+
+fn test_config_validation(engine_options: &EngineOptions) -> Result<(), String> {
+    match engine_options.validation_report.warnings.as_slice() {
+        [] => Ok(()),
+        warnings => Err(warnings.join("\n"))
+    }
+}
+
+/// Runs the tests of the library for the engine options.
+pub fn run_library_tests(engine_options: &EngineOptions, extra_data_dir: &Path) -> Vec<TestResult> {
+    let mut results = vec!(
+        TestResult::new("RustConfig", "RoundTrip", test_config_round_trip(engine_options)),
+        TestResult::new("RustConfig", "Validation", test_config_validation(engine_options)),
+    );
+    let has_data = engine_options.vanilla_data_dir.is_dir();
+    for check in preflight::run(engine_options).checks {
+        if !has_data && DATA_CHECKS.contains(&check.name) {
+            let reason = format!("{} is not a directory", engine_options.vanilla_data_dir.display());
+            results.push(TestResult { suite: "RustPreflight", name: String::from(check.name), outcome: TestOutcome::SKIPPED(reason) });
+            continue;
+        }
+        let result = if check.status == PreflightStatus::ERROR { Err(check.message) } else { Ok(()) };
+        results.push(TestResult::new("RustPreflight", check.name, result));
+    }
+    let difficulties = load_difficulties(extra_data_dir, &engine_options.stracciatella_home, &engine_options.mods);
+    results.push(TestResult::new("RustGameData", "Difficulties", difficulties.map(|_| ())));
+    results
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Returns the results as `testsuite` elements of a googletest report.
+pub fn to_xml_suites(results: &[TestResult]) -> String {
+    let mut xml = String::new();
+    let mut suites: Vec<&str> = results.iter().map(|r| r.suite).collect();
+    suites.dedup();
+
+    for suite in suites {
+        let suite_results: Vec<&TestResult> = results.iter().filter(|r| r.suite == suite).collect();
+        let failures = suite_results.iter().filter(|r| r.failed()).count();
+        let skipped = suite_results.iter().filter(|r| matches!(r.outcome, TestOutcome::SKIPPED(_))).count();
+        xml.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" disabled=\"0\" skipped=\"{}\" errors=\"0\" time=\"0\">\n", suite, suite_results.len(), failures, skipped));
+        for r in suite_results {
+            let (result, child) = match r.outcome {
+                TestOutcome::PASSED => ("completed", None),
+                TestOutcome::FAILED(ref f) => ("completed", Some(format!("<failure message=\"{}\" type=\"\"></failure>", escape_xml(f)))),
+                TestOutcome::SKIPPED(ref s) => ("skipped", Some(format!("<skipped message=\"{}\" />", escape_xml(s)))),
+            };
+            xml.push_str(&format!("    <testcase name=\"{}\" status=\"run\" result=\"{}\" time=\"0\" classname=\"{}\"", escape_xml(&r.name), result, suite));
+            match child {
+                Some(c) => xml.push_str(&format!(">\n      {}\n    </testcase>\n", c)),
+                None => xml.push_str(" />\n")
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml
+}
+
+/// Adds to a number in an attribute of an XML tag.
+fn add_to_attribute(tag: &str, name: &str, value: usize) -> String {
+    let prefix = format!(" {}=\"", name);
+    let start = match tag.find(&prefix) {
+        Some(i) => i + prefix.len(),
+        None => return String::from(tag)
+    };
+    let end = start + tag[start..].find('"').unwrap_or(0);
+    let old_value: usize = tag[start..end].parse().unwrap_or(0);
+    format!("{}{}{}", &tag[..start], old_value + value, &tag[end..])
+}
+
+/// Merges the results into a googletest XML report. Without a valid report, a new one is created.
+pub fn merge_into_report(report: &str, results: &[TestResult]) -> String {
+    let failures = results.iter().filter(|r| r.failed()).count();
+    let root_start = report.find("<testsuites");
+    let root_end = root_start.and_then(|s| report[s..].find('>').map(|e| s + e + 1));
+    let closing = report.rfind("</testsuites>");
+
+    match (root_start, root_end, closing) {
+        (Some(start), Some(end), Some(closing)) if end <= closing => {
+            let root = add_to_attribute(&add_to_attribute(&report[start..end], "tests", results.len()), "failures", failures);
+            format!("{}{}{}{}{}", &report[..start], root, &report[end..closing], to_xml_suites(results), &report[closing..])
+        },
+        _ => format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" disabled=\"0\" errors=\"0\" time=\"0\" name=\"AllTests\">\n{}</testsuites>\n",
+            results.len(), failures, to_xml_suites(results)
+        )
+    }
+}
+
+fn print_results(results: &[TestResult]) {
+    for r in results {
+        println!("[ RUN      ] {}.{}", r.suite, r.name);
+        match r.outcome {
+            TestOutcome::PASSED => println!("[       OK ] {}.{}", r.suite, r.name),
+            TestOutcome::FAILED(ref f) => println!("{}\n[  FAILED  ] {}.{}", f, r.suite, r.name),
+            TestOutcome::SKIPPED(ref s) => println!("{}\n[  SKIPPED ] {}.{}", s, r.suite, r.name),
+        }
+    }
+    let failures = results.iter().filter(|r| r.failed()).count();
+    println!("[==========] {} library tests ran, {} failed.", results.len(), failures);
+}
+
+/// Runs the library tests and merges the results into the report of the engine tests. Returns false if a test or
+/// writing the report failed.
+#[no_mangle]
+pub extern fn run_library_unittests(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let results = run_library_tests(engine_options, Path::new(&extra_data_dir));
+    print_results(&results);
+
+    if let Some(ref path) = engine_options.unittest_report {
+        let report = fs::read_to_string(path).unwrap_or_default();
+        if let Err(e) = fs::write(path, merge_into_report(&report, &results)) {
+            logger::log(LogLevel::ERROR, "unittests", &format!("Error writing the test report {}: {}", path.display(), e.kind()));
+            return false;
+        }
+    }
+    !results.iter().any(|r| r.failed())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use EngineOptions;
+    use super::{TestOutcome, TestResult};
+
+    fn result(suite: &'static str, name: &str, outcome: TestOutcome) -> TestResult {
+        TestResult { suite, name: String::from(name), outcome }
+    }
+
+    #[test]
+    fn gtest_report_path_should_follow_the_output_flag() {
+        let report_path = |arg: &str| super::gtest_report_path(&[String::from("--gtest_repeat=2"), String::from(arg)], "/usr/bin/ja2");
+
+        assert_eq!(report_path("--gtest_output=xml:report.xml"), Some(PathBuf::from("report.xml")));
+        assert_eq!(report_path("--gtest_output=xml"), Some(PathBuf::from("test_detail.xml")));
+        assert_eq!(report_path("--gtest_output=xml:reports/"), Some(PathBuf::from("reports/ja2.xml")));
+        assert_eq!(report_path("--gtest_output=json:report.json"), None);
+        assert_eq!(report_path("--gtest_filter=A.*"), None);
+    }
+
+    #[test]
+    fn merge_into_report_should_add_the_suites_and_totals() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" disabled="0" errors="0" time="0.1" name="AllTests">
+  <testsuite name="Engine" tests="2" failures="1" disabled="0" errors="0" time="0.1">
+  </testsuite>
+</testsuites>
+"#;
+        let results = vec!(
+            result("RustConfig", "RoundTrip", TestOutcome::PASSED),
+            result("RustConfig", "Validation", TestOutcome::FAILED(String::from("'a' is not <valid>"))),
+            result("RustPreflight", "data_files", TestOutcome::SKIPPED(String::from("No data"))),
+        );
+
+        let merged = super::merge_into_report(report, &results);
+
+        assert!(merged.contains(r#"<testsuites tests="5" failures="2" disabled="0""#), "{}", merged);
+        assert!(merged.contains(r#"<testsuite name="Engine" tests="2" failures="1""#));
+        assert!(merged.contains(r#"<testsuite name="RustConfig" tests="2" failures="1""#));
+        assert!(merged.contains(r#"<failure message="'a' is not &lt;valid&gt;" type="">"#));
+        assert!(merged.contains(r#"<testcase name="data_files" status="run" result="skipped" time="0" classname="RustPreflight">"#));
+        assert!(merged.ends_with("  </testsuite>\n</testsuites>\n"));
+        assert!(super::merge_into_report("", &results).contains(r#"<testsuites tests="3" failures="1""#));
+    }
+
+    #[test]
+    fn run_library_tests_should_check_the_config_and_skip_missing_data() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = PathBuf::from("/nonexistent");
+        engine_options.validation_report.warnings.push(String::from("'fulscreen' is not a valid option"));
+
+        let results = super::run_library_tests(&engine_options, &PathBuf::from("/nonexistent"));
+
+        assert_eq!(results[0], result("RustConfig", "RoundTrip", TestOutcome::PASSED));
+        assert_eq!(results[1], result("RustConfig", "Validation", TestOutcome::FAILED(String::from("'fulscreen' is not a valid option"))));
+        let data_files = results.iter().find(|r| r.name == "data_files").unwrap();
+        assert_eq!(data_files.outcome, TestOutcome::SKIPPED(String::from("/nonexistent is not a directory")));
+        assert!(results.last().unwrap().failed());
+    }
+}
@@ -0,0 +1,153 @@
+//! Validation report with problems that do not prevent the game from starting.
+
+use std::ffi::CString;
+
+use libc::c_char;
+use serde_json::Value;
+
+use EngineOptions;
+use deprecation::DeprecationWarning;
+use optionregistry::{OptionType, find_option};
+
+/// Keys that are allowed in ja2.json although they are not options.
+static IGNORED_CONFIG_KEYS: [&'static str; 1] = ["help"];
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ValidationReport {
+    pub warnings: Vec<String>,
+    /// Deprecated options that are set in ja2.json
+    pub deprecations: Vec<DeprecationWarning>,
+}
+
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec!(i + 1);
+        for (j, cb) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b_chars.len()]
+}
+
+fn find_similar_key<'a>(key: &str, known_keys: &[&'a String]) -> Option<&'a String> {
+    let max_distance = ::std::cmp::max(1, key.len() / 3);
+
+    known_keys.iter()
+        .map(|k| (levenshtein_distance(key, k), *k))
+        .filter(|&(d, _)| d <= max_distance)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, k)| k)
+}
+
+fn lint_object(value: &Value, known: &Value, prefix: &str, warnings: &mut Vec<String>) {
+    let (map, known_map) = match (value, known) {
+        (Value::Object(m), Value::Object(k)) => (m, k),
+        _ => return
+    };
+    let known_keys: Vec<&String> = known_map.keys().collect();
+
+    for (key, child) in map {
+        let path = format!("{}{}", prefix, key);
+        match known_map.get(key) {
+            // the keys of a map like log_filters are not options
+            Some(_) if find_option(&path).map(|o| o.option_type) == Some(OptionType::MAP) => {},
+            Some(known_child) => lint_object(child, known_child, &format!("{}.", path), warnings),
+            None if prefix.is_empty() && IGNORED_CONFIG_KEYS.contains(&key.as_str()) => {},
+            None => warnings.push(match find_similar_key(key, &known_keys) {
+                Some(similar) => format!("'{}' is not a valid option, did you mean '{}{}'?", path, prefix, similar),
+                None => format!("'{}' is not a valid option", path)
+            })
+        }
+    }
+}
+
+/// Returns warnings for all keys in the config that are not known options.
+pub fn lint_json_config(config: &Value) -> Vec<String> {
+    let known = serde_json::to_value(EngineOptions::default()).expect("Default options should be serializable");
+    let mut warnings = vec!();
+
+    lint_object(config, &known, "", &mut warnings);
+    warnings
+}
+
+#[no_mangle]
+pub extern fn get_number_of_validation_warnings(ptr: *const EngineOptions) -> u32 {
+    unsafe_from_ptr!(ptr).validation_report.warnings.len() as u32
+}
+
+#[no_mangle]
+pub extern fn get_validation_warning(ptr: *const EngineOptions, index: u32) -> *mut c_char {
+    let warning = match unsafe_from_ptr!(ptr).validation_report.warnings.get(index as usize) {
+        Some(w) => w,
+        None => panic!("Invalid validation warning index {}", index)
+    };
+    CString::new(warning.clone()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use EngineOptions;
+
+    #[test]
+    fn levenshtein_distance_should_count_edits() {
+        assert_eq!(super::levenshtein_distance("fullscreen", "fullscreen"), 0);
+        assert_eq!(super::levenshtein_distance("fullscren", "fullscreen"), 1);
+        assert_eq!(super::levenshtein_distance("datadir", "data_dir"), 1);
+        assert_eq!(super::levenshtein_distance("", "res"), 3);
+        assert_eq!(super::levenshtein_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn lint_json_config_should_accept_known_keys() {
+        let config = json!({ "help": "text", "data_dir": "/dd", "fullscreen": true, "gameplay": { "subtitles": false } });
+
+        assert_eq!(super::lint_json_config(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn lint_json_config_should_accept_any_key_in_maps() {
+        let config = json!({ "log_filters": { "vfs": "trace", "config": "warn" } });
+
+        assert_eq!(super::lint_json_config(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn lint_json_config_should_suggest_similar_keys() {
+        let config = json!({ "fullscren": true, "datadir": "/dd", "gameplay": { "subtitle": false } });
+
+        assert_eq!(super::lint_json_config(&config), vec!(
+            String::from("'datadir' is not a valid option, did you mean 'data_dir'?"),
+            String::from("'fullscren' is not a valid option, did you mean 'fullscreen'?"),
+            String::from("'gameplay.subtitle' is not a valid option, did you mean 'gameplay.subtitles'?"),
+        ));
+    }
+
+    #[test]
+    fn lint_json_config_should_report_unknown_keys_without_suggestion() {
+        let config = json!({ "window_title": "JA2", "gameplay": { "vanilla_fixes": { "help": true } } });
+
+        assert_eq!(super::lint_json_config(&config), vec!(
+            String::from("'gameplay.vanilla_fixes.help' is not a valid option"),
+            String::from("'window_title' is not a valid option"),
+        ));
+    }
+
+    #[test]
+    fn get_validation_warning_should_return_warnings_of_the_report() {
+        let mut engine_options = EngineOptions::default();
+        engine_options.validation_report.warnings.push(String::from("warning"));
+
+        assert_eq!(super::get_number_of_validation_warnings(&engine_options), 1);
+        unsafe {
+            assert_eq!(CString::from_raw(super::get_validation_warning(&engine_options, 0)), CString::new("warning").unwrap());
+        }
+    }
+}
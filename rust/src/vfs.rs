@@ -0,0 +1,475 @@
+//! Virtual file system that layers the game data directories.
+//!
+//! The vanilla data and the extra data dir can be installed in a read-only system location (e.g. `/usr/share/ja2`),
+//! while mods and user overrides can also live in the stracciatella home. Files are resolved from the mount with the
+//! highest priority, writes only ever go to writable mounts in the home.
+//!
+//! Mods can ship their files in SLF libraries in their data folder. The libraries are mounted below the loose files of
+//! the mod, in the order of their names.
+//!
+//! The selected music pack is mounted at `music-pack`, so its tracks do not shadow any game data.
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use libc::c_char;
+use serde_json;
+
+use EngineOptions;
+use errorcontext::{ErrorContext, ErrorKind};
+use datadir::find_data_folder;
+use home::mods_dir;
+use logger::{self, LogLevel};
+use music::{MUSIC_PACK_MOUNT, selected_music_pack};
+use pathcheck::check_windows_path;
+use slf::{SlfEntry, SlfLibrary};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum MountSource {
+    VANILLA,
+    EXTERNALIZED,
+    MOD,
+    USER,
+    /// One of the extra data dirs of the engine options
+    EXTRA,
+    /// The selected music pack
+    MUSIC_PACK,
+}
+
+impl Display for MountSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            MountSource::VANILLA => "VANILLA",
+            MountSource::EXTERNALIZED => "EXTERNALIZED",
+            MountSource::MOD => "MOD",
+            MountSource::USER => "USER",
+            MountSource::EXTRA => "EXTRA",
+            MountSource::MUSIC_PACK => "MUSIC_PACK",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mount {
+    pub source: MountSource,
+    /// Folder or, for libraries, the SLF file
+    pub path: PathBuf,
+    pub writable: bool,
+    pub library: Option<SlfLibrary>,
+    /// Folder of the virtual file system the mount appears in, empty for the root
+    pub prefix: PathBuf,
+}
+
+/// A file in the virtual file system.
+#[derive(Debug, PartialEq, Clone)]
+pub enum VfsFile<'a> {
+    Loose(PathBuf),
+    LibraryEntry(&'a SlfLibrary, &'a SlfEntry),
+}
+
+impl<'a> VfsFile<'a> {
+    pub fn read(&self) -> Result<Vec<u8>, ErrorContext> {
+        match *self {
+            VfsFile::Loose(ref path) => fs::read(path).map_err(|s| ErrorContext::io(&s, path).wrap(ErrorKind::RESOURCES, format!("Error reading {}", path.display()))),
+            VfsFile::LibraryEntry(library, entry) => library.read(entry),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Vfs {
+    /// Mounts from lowest to highest priority
+    pub mounts: Vec<Mount>,
+}
+
+/// Returns the data dir of a mod. Mods in the home take precedence over mods in the extra data dir.
+pub fn find_mod_dir(extra_data_dir: &Path, stracciatella_home: &Path, mod_name: &str) -> PathBuf {
+    let user_mod_dir = mods_dir(stracciatella_home).join(mod_name).join("data");
+    if user_mod_dir.is_dir() {
+        user_mod_dir
+    } else {
+        extra_data_dir.join("mods").join(mod_name).join("data")
+    }
+}
+
+/// Returns the SLF libraries in the data dir of a mod, sorted by name.
+pub fn find_mod_libraries(mod_dir: &Path) -> Vec<PathBuf> {
+    let mut libraries: Vec<PathBuf> = match fs::read_dir(mod_dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().map(|e| e.to_string_lossy().to_lowercase() == "slf").unwrap_or(false))
+            .collect(),
+        Err(_) => vec!()
+    };
+    libraries.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+    libraries
+}
+
+/// Returns the folder in the home for files that override the game data.
+pub fn user_data_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("data")
+}
+
+impl Vfs {
+    pub fn mount(&mut self, source: MountSource, path: PathBuf, writable: bool) {
+        self.mounts.push(Mount { source, path, writable, library: None, prefix: PathBuf::new() });
+    }
+
+    /// Mounts a read-only folder at the prefix instead of the root of the virtual file system.
+    pub fn mount_at(&mut self, source: MountSource, path: PathBuf, prefix: &Path) {
+        self.mounts.push(Mount { source, path, writable: false, library: None, prefix: prefix.to_path_buf() });
+    }
+
+    pub fn mount_library(&mut self, source: MountSource, library: SlfLibrary) {
+        self.mounts.push(Mount { source, path: library.path.clone(), writable: false, library: Some(library), prefix: PathBuf::new() });
+    }
+
+    /// Mounts the libraries of a mod and then its loose files. Libraries that cannot be read are skipped.
+    fn mount_mod(&mut self, mod_dir: PathBuf) {
+        for path in find_mod_libraries(&mod_dir) {
+            match SlfLibrary::open(&path) {
+                Ok(library) => self.mount_library(MountSource::MOD, library),
+                Err(s) => logger::log(LogLevel::ERROR, "vfs", &format!("Error mounting library of mod: {}", s))
+            }
+        }
+        self.mount(MountSource::MOD, mod_dir, false);
+    }
+
+    /// Creates the mounts for the engine options: vanilla data, externalized data, extra data dirs, mods, the music pack
+    /// and user overrides. Mods in `mods_below_extra_data_dirs` are mounted below the extra data dirs instead.
+    ///
+    /// The `Data` folder of the vanilla data dir is mounted, which contains the loose files of the Gold releases. Later
+    /// extra data dirs and mods take precedence over earlier ones.
+    pub fn from_engine_options(engine_options: &EngineOptions, extra_data_dir: &Path) -> Vfs {
+        let mut vfs = Vfs::default();
+        let vanilla_data_folder = find_data_folder(&engine_options.vanilla_data_dir)
+            .unwrap_or_else(|| engine_options.vanilla_data_dir.join("Data"));
+        vfs.mount(MountSource::VANILLA, vanilla_data_folder, false);
+        vfs.mount(MountSource::EXTERNALIZED, extra_data_dir.join("externalized"), false);
+        let (mods_below, mods_above): (Vec<&String>, Vec<&String>) = engine_options.mods.iter()
+            .partition(|m| engine_options.mods_below_extra_data_dirs.contains(m));
+        for m in mods_below {
+            vfs.mount_mod(find_mod_dir(extra_data_dir, &engine_options.stracciatella_home, m));
+        }
+        for d in &engine_options.extra_data_dirs {
+            vfs.mount(MountSource::EXTRA, d.clone(), false);
+        }
+        for m in mods_above {
+            vfs.mount_mod(find_mod_dir(extra_data_dir, &engine_options.stracciatella_home, m));
+        }
+        match selected_music_pack(engine_options) {
+            Ok(Some(pack)) => vfs.mount_at(MountSource::MUSIC_PACK, pack.dir, Path::new(MUSIC_PACK_MOUNT)),
+            Ok(None) => {},
+            Err(s) => logger::log(LogLevel::ERROR, "vfs", &format!("Error mounting the music pack, the vanilla music is used: {}", s))
+        }
+        vfs.mount(MountSource::USER, user_data_dir(&engine_options.stracciatella_home), true);
+        vfs
+    }
+
+    /// Returns the file in the mount with the highest priority that contains it.
+    pub fn find(&self, relative_path: &Path) -> Option<VfsFile<'_>> {
+        self.mounts.iter().rev()
+            .filter_map(|m| {
+                let path = relative_path.strip_prefix(&m.prefix).ok()?;
+                match m.library {
+                    Some(ref library) => library.find(&path.to_string_lossy().replace('\\', "/")).map(|e| VfsFile::LibraryEntry(library, e)),
+                    None => Some(m.path.join(path)).filter(|p| p.exists()).map(VfsFile::Loose)
+                }
+            })
+            .next()
+    }
+
+    pub fn read(&self, relative_path: &Path) -> Result<Vec<u8>, ErrorContext> {
+        match self.find(relative_path) {
+            Some(f) => f.read(),
+            None => Err(ErrorContext::new(ErrorKind::RESOURCES, format!("{} does not exist in the virtual file system", relative_path.display())).with_path(relative_path))
+        }
+    }
+
+    /// Returns the path of a file in the mount with the highest priority that contains it. Returns `None` if that
+    /// mount is a library, see `find`.
+    pub fn resolve(&self, relative_path: &Path) -> Option<PathBuf> {
+        match self.find(relative_path) {
+            Some(VfsFile::Loose(path)) => Some(path),
+            _ => None
+        }
+    }
+
+    /// Returns the path a file should be written to. Read-only mounts are never written to. On Windows the path is
+    /// checked for reserved names and its length, see `pathcheck`.
+    pub fn writable_path(&self, relative_path: &Path) -> Result<PathBuf, String> {
+        if relative_path.is_absolute() {
+            return Err(format!("{} is not a relative path", relative_path.display()));
+        }
+        let path = match self.mounts.iter().rev().find(|m| m.writable) {
+            Some(m) => m.path.join(relative_path),
+            None => return Err(String::from("There is no writable location in the virtual file system"))
+        };
+        if cfg!(windows) {
+            check_windows_path(&path)?;
+        }
+        Ok(path)
+    }
+
+    /// Returns the mounts from the highest to the lowest priority, the priority of the first mount is the number of
+    /// mounts.
+    pub fn mounts_json(&self) -> serde_json::Value {
+        let mounts: Vec<serde_json::Value> = self.mounts.iter().enumerate().rev()
+            .map(|(i, m)| json!({
+                "source": m.source,
+                "path": m.path,
+                "priority": i + 1,
+                "writable": m.writable,
+                "library": m.library.is_some(),
+                "prefix": m.prefix,
+                "exists": m.path.exists(),
+            }))
+            .collect();
+        serde_json::Value::Array(mounts)
+    }
+
+    /// Checks whether a path is inside a read-only mount and no writable mount.
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        let containing: Vec<&Mount> = self.mounts.iter().filter(|m| path.starts_with(&m.path)).collect();
+        !containing.is_empty() && containing.iter().all(|m| !m.writable)
+    }
+}
+
+#[no_mangle]
+pub extern fn create_vfs(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char) -> *mut Vfs {
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    Box::into_raw(Box::new(Vfs::from_engine_options(unsafe_from_ptr!(ptr), Path::new(&extra_data_dir))))
+}
+
+#[no_mangle]
+pub extern fn free_vfs(ptr: *mut Vfs) -> () {
+    if ptr.is_null() { return }
+    unsafe { drop(Box::from_raw(ptr)); }
+}
+
+/// Returns the path of the file or null if it does not exist in any mount or is in a library.
+#[no_mangle]
+pub extern fn vfs_resolve(ptr: *const Vfs, path_ptr: *const c_char) -> *mut c_char {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    match unsafe_from_ptr!(ptr).resolve(Path::new(&path)) {
+        Some(p) => CString::new(p.to_string_lossy().into_owned()).unwrap().into_raw(),
+        None => ptr::null_mut()
+    }
+}
+
+/// Returns the path a file should be written to or null if there is no writable mount.
+#[no_mangle]
+pub extern fn vfs_writable_path(ptr: *const Vfs, path_ptr: *const c_char) -> *mut c_char {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    match unsafe_from_ptr!(ptr).writable_path(Path::new(&path)) {
+        Ok(p) => CString::new(p.to_string_lossy().into_owned()).unwrap().into_raw(),
+        Err(_) => ptr::null_mut()
+    }
+}
+
+#[no_mangle]
+pub extern fn vfs_is_read_only(ptr: *const Vfs, path_ptr: *const c_char) -> bool {
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned();
+    unsafe_from_ptr!(ptr).is_read_only(Path::new(&path))
+}
+
+/// Returns the mounts as JSON array for debugging, see `Vfs::mounts_json`.
+#[no_mangle]
+pub extern fn vfs_get_mounts_json(ptr: *const Vfs) -> *mut c_char {
+    let json = unsafe_from_ptr!(ptr).mounts_json().to_string();
+    CString::new(json).unwrap().into_raw()
+}
+
+/// Returns the data dir of the mod at the index, see `find_mod_dir`.
+#[no_mangle]
+pub extern fn get_mod_data_dir(ptr: *const EngineOptions, extra_data_dir_ptr: *const c_char, index: u32) -> *mut c_char {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let extra_data_dir = unsafe { CStr::from_ptr(extra_data_dir_ptr) }.to_string_lossy().into_owned();
+    let mod_name = match engine_options.mods.get(index as usize) {
+        Some(m) => m,
+        None => panic!("Invalid mod index for game options {}", index)
+    };
+    let mod_dir = find_mod_dir(Path::new(&extra_data_dir), &engine_options.stracciatella_home, mod_name);
+    CString::new(mod_dir.to_string_lossy().into_owned()).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    use EngineOptions;
+    use slf::tests::write_library;
+    use super::{MountSource, Vfs, VfsFile};
+
+    fn create_file(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap();
+    }
+
+    fn engine_options_in(dir: &Path) -> EngineOptions {
+        let mut engine_options = EngineOptions::default();
+        engine_options.vanilla_data_dir = dir.join("usr/share/ja2");
+        engine_options.stracciatella_home = dir.join("home/.ja2");
+        engine_options.mods = vec!(String::from("system-mod"), String::from("user-mod"));
+        engine_options.extra_data_dirs = vec!(dir.join("hd"));
+        engine_options
+    }
+
+    #[test]
+    fn find_mod_dir_should_prefer_mods_in_the_home() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let extra_data_dir = temp_dir.path().join("usr/share/ja2");
+        let home = temp_dir.path().join("home/.ja2");
+        fs::create_dir_all(home.join("mods/user-mod/data")).unwrap();
+
+        assert_eq!(super::find_mod_dir(&extra_data_dir, &home, "user-mod"), home.join("mods/user-mod/data"));
+        assert_eq!(super::find_mod_dir(&extra_data_dir, &home, "system-mod"), extra_data_dir.join("mods/system-mod/data"));
+    }
+
+    #[test]
+    fn from_engine_options_should_mount_system_and_user_dirs() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = engine_options_in(temp_dir.path());
+        fs::create_dir_all(temp_dir.path().join("home/.ja2/mods/user-mod/data")).unwrap();
+
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+        let mounts: Vec<(MountSource, PathBuf, bool)> = vfs.mounts.iter().map(|m| (m.source, m.path.clone(), m.writable)).collect();
+
+        assert_eq!(mounts, vec!(
+            (MountSource::VANILLA, temp_dir.path().join("usr/share/ja2/Data"), false),
+            (MountSource::EXTERNALIZED, temp_dir.path().join("usr/share/ja2/externalized"), false),
+            (MountSource::EXTRA, temp_dir.path().join("hd"), false),
+            (MountSource::MOD, temp_dir.path().join("usr/share/ja2/mods/system-mod/data"), false),
+            (MountSource::MOD, temp_dir.path().join("home/.ja2/mods/user-mod/data"), false),
+            (MountSource::USER, temp_dir.path().join("home/.ja2/data"), true),
+        ));
+    }
+
+    #[test]
+    fn from_engine_options_should_mount_pinned_mods_below_the_extra_data_dirs() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = engine_options_in(temp_dir.path());
+        engine_options.mods_below_extra_data_dirs = vec!(String::from("user-mod"), String::from("not-started"));
+
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+        let sources: Vec<(MountSource, PathBuf)> = vfs.mounts.iter().map(|m| (m.source, m.path.clone())).collect();
+
+        assert_eq!(&sources[2..5], &[
+            (MountSource::MOD, temp_dir.path().join("usr/share/ja2/mods/user-mod/data")),
+            (MountSource::EXTRA, temp_dir.path().join("hd")),
+            (MountSource::MOD, temp_dir.path().join("usr/share/ja2/mods/system-mod/data")),
+        ]);
+    }
+
+    #[test]
+    fn mounts_json_should_list_the_mounts_by_priority() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = engine_options_in(temp_dir.path());
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+        fs::create_dir_all(temp_dir.path().join("hd")).unwrap();
+
+        let json = vfs.mounts_json();
+        let mounts = json.as_array().unwrap();
+
+        assert_eq!(mounts.len(), 6);
+        assert_eq!(mounts[0]["source"], "USER");
+        assert_eq!(mounts[0]["priority"], 6);
+        assert_eq!(mounts[0]["writable"], true);
+        assert_eq!(mounts[3], json!({
+            "source": "EXTRA",
+            "path": temp_dir.path().join("hd"),
+            "priority": 3,
+            "writable": false,
+            "library": false,
+            "prefix": "",
+            "exists": true,
+        }));
+        assert_eq!(mounts[5]["source"], "VANILLA");
+    }
+
+    #[test]
+    fn resolve_should_use_the_mount_with_the_highest_priority() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = engine_options_in(temp_dir.path());
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/a.sti"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/b.sti"));
+        create_file(&temp_dir.path().join("home/.ja2/data/b.sti"));
+        create_file(&temp_dir.path().join("hd/b.sti"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/d.sti"));
+        create_file(&temp_dir.path().join("hd/d.sti"));
+        create_file(&temp_dir.path().join("usr/share/ja2/mods/system-mod/data/d.sti"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/e.sti"));
+        create_file(&temp_dir.path().join("hd/e.sti"));
+
+        assert_eq!(vfs.resolve(Path::new("a.sti")), Some(temp_dir.path().join("usr/share/ja2/Data/a.sti")));
+        assert_eq!(vfs.resolve(Path::new("b.sti")), Some(temp_dir.path().join("home/.ja2/data/b.sti")));
+        assert_eq!(vfs.resolve(Path::new("d.sti")), Some(temp_dir.path().join("usr/share/ja2/mods/system-mod/data/d.sti")));
+        assert_eq!(vfs.resolve(Path::new("e.sti")), Some(temp_dir.path().join("hd/e.sti")));
+        assert_eq!(vfs.resolve(Path::new("c.sti")), None);
+    }
+
+    #[test]
+    fn from_engine_options_should_mount_the_music_pack_at_its_prefix() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let mut engine_options = engine_options_in(temp_dir.path());
+        let pack_dir = temp_dir.path().join("home/.ja2/music-packs/remaster");
+        fs::create_dir_all(&pack_dir).unwrap();
+        fs::write(pack_dir.join("pack.json"), r#"{ "name": "Remaster", "tracks": { "laptop": [ "laptop.ogg" ] } }"#).unwrap();
+        create_file(&pack_dir.join("laptop.ogg"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/laptop.ogg"));
+        engine_options.music_pack = String::from("remaster");
+
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+
+        assert_eq!(vfs.mounts[vfs.mounts.len() - 2].source, MountSource::MUSIC_PACK);
+        assert_eq!(vfs.resolve(Path::new("music-pack/laptop.ogg")), Some(pack_dir.join("laptop.ogg")));
+        assert_eq!(vfs.resolve(Path::new("laptop.ogg")), Some(temp_dir.path().join("usr/share/ja2/Data/laptop.ogg")));
+        engine_options.music_pack = String::from("missing");
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+        assert!(vfs.mounts.iter().all(|m| m.source != MountSource::MUSIC_PACK));
+    }
+
+    #[test]
+    fn find_should_read_files_from_the_libraries_of_mods() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = engine_options_in(temp_dir.path());
+        let mod_dir = temp_dir.path().join("usr/share/ja2/mods/system-mod/data");
+        fs::create_dir_all(&mod_dir).unwrap();
+        write_library(&mod_dir.join("a.slf"), "INTERFACE\\", &[("A.STI", Some(b"a1")), ("B.STI", Some(b"b1"))]);
+        write_library(&mod_dir.join("B.SLF"), "INTERFACE\\", &[("A.STI", Some(b"a2"))]);
+        create_file(&mod_dir.join("interface/b.sti"));
+        create_file(&temp_dir.path().join("usr/share/ja2/Data/interface/c.sti"));
+
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+
+        assert_eq!(vfs.read(Path::new("interface/a.sti")), Ok(b"a2".to_vec()));
+        assert_eq!(vfs.read(Path::new("interface/b.sti")), Ok(vec!()));
+        assert_eq!(vfs.find(Path::new("interface/c.sti")), Some(VfsFile::Loose(temp_dir.path().join("usr/share/ja2/Data/interface/c.sti"))));
+        assert_eq!(vfs.resolve(Path::new("interface/a.sti")), None);
+        assert!(vfs.read(Path::new("interface/d.sti")).is_err());
+        assert_eq!(vfs.mounts.iter().filter(|m| m.library.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn writable_path_should_never_be_in_a_system_location() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let engine_options = engine_options_in(temp_dir.path());
+        let vfs = Vfs::from_engine_options(&engine_options, &temp_dir.path().join("usr/share/ja2"));
+
+        assert_eq!(vfs.writable_path(Path::new("tilecache/a.sti")), Ok(temp_dir.path().join("home/.ja2/data/tilecache/a.sti")));
+        assert!(vfs.is_read_only(&temp_dir.path().join("usr/share/ja2/Data/a.sti")));
+        assert!(!vfs.is_read_only(&temp_dir.path().join("home/.ja2/data/a.sti")));
+        assert!(Vfs::default().writable_path(Path::new("a.sti")).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Video capture of the screen with ffmpeg, configured in the `video_capture` section of ja2.json.
+//!
+//! The engine passes every captured frame with RGB565 pixels to `add_video_capture_frame`. The frames are written to
+//! the `video-capture` folder of the stracciatella home, either as raw file next to the ffmpeg command that encodes it
+//! (`FILE`) or directly to the standard input of ffmpeg (`PIPE`).
+
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::slice;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use EngineOptions;
+use set_last_error;
+use screenshot::{ScreenshotContext, screenshot_file_name};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub enum VideoCaptureTarget {
+    /// Raw frames are written to a file that is encoded later
+    FILE,
+    /// Frames are encoded by ffmpeg while the game runs
+    PIPE,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoCaptureOptions {
+    pub target: VideoCaptureTarget,
+    pub frame_rate: u32,
+    /// Path of the ffmpeg executable, just `ffmpeg` to search the PATH
+    pub ffmpeg: String,
+    /// Arguments for the output of ffmpeg, e.g. the codec
+    pub ffmpeg_output_args: Vec<String>,
+    /// Template of the file name of the video with extension, see `screenshot::screenshot_file_name`
+    pub file_name: String,
+}
+
+impl Default for VideoCaptureOptions {
+    fn default() -> VideoCaptureOptions {
+        VideoCaptureOptions {
+            target: VideoCaptureTarget::FILE,
+            frame_rate: 25,
+            ffmpeg: String::from("ffmpeg"),
+            ffmpeg_output_args: ["-c:v", "libx264", "-pix_fmt", "yuv420p"].iter().map(|s| String::from(*s)).collect(),
+            file_name: String::from("capture-{date}-{time}.mp4"),
+        }
+    }
+}
+
+pub fn video_capture_dir(stracciatella_home: &Path) -> PathBuf {
+    stracciatella_home.join("video-capture")
+}
+
+/// Returns the ffmpeg command line that encodes raw RGB565 frames from `input`, `-` for the standard input.
+pub fn ffmpeg_command(options: &VideoCaptureOptions, width: u32, height: u32, input: &str, output: &Path) -> Vec<String> {
+    let mut command: Vec<String> = vec!(options.ffmpeg.clone(), String::from("-y"));
+    for arg in &["-f", "rawvideo", "-pixel_format", "rgb565le"] {
+        command.push(String::from(*arg));
+    }
+    command.push(String::from("-video_size"));
+    command.push(format!("{}x{}", width, height));
+    command.push(String::from("-framerate"));
+    command.push(options.frame_rate.to_string());
+    command.push(String::from("-i"));
+    command.push(String::from(input));
+    command.extend(options.ffmpeg_output_args.iter().cloned());
+    command.push(output.to_string_lossy().into_owned());
+    command
+}
+
+/// Quotes the arguments for a shell if needed, so the command can be copied to a terminal.
+pub fn format_command(command: &[String]) -> String {
+    command.iter().map(|a| {
+        if !a.is_empty() && a.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=+".contains(c)) {
+            a.clone()
+        } else {
+            format!("\"{}\"", a.replace('"', "\\\""))
+        }
+    }).collect::<Vec<String>>().join(" ")
+}
+
+enum Sink {
+    File(BufWriter<File>),
+    Pipe(Child),
+}
+
+pub struct VideoCapture {
+    sink: Sink,
+    frame_size: usize,
+    /// Path of the video, or of the raw frames for `FILE`
+    pub path: PathBuf,
+}
+
+impl VideoCapture {
+    /// Starts a capture in the directory. For `FILE` the ffmpeg command is written to `<raw file>.txt`.
+    pub fn start(dir: &Path, options: &VideoCaptureOptions, width: u32, height: u32, name: &str) -> Result<VideoCapture, String> {
+        fs::create_dir_all(dir).map_err(|s| format!("Error creating {}: {}", dir.display(), s.kind()))?;
+        let output = dir.join(name);
+        let frame_size = width as usize * height as usize * 2;
+        match options.target {
+            VideoCaptureTarget::FILE => {
+                let raw_path = output.with_extension("rgb565");
+                let command = ffmpeg_command(options, width, height, &raw_path.to_string_lossy(), &output);
+                let command_path = output.with_extension("txt");
+                fs::write(&command_path, format!("{}\n", format_command(&command)))
+                    .map_err(|s| format!("Error writing {}: {}", command_path.display(), s.kind()))?;
+                let file = File::create(&raw_path).map_err(|s| format!("Error creating {}: {}", raw_path.display(), s.kind()))?;
+                Ok(VideoCapture { sink: Sink::File(BufWriter::new(file)), frame_size, path: raw_path })
+            },
+            VideoCaptureTarget::PIPE => {
+                let command = ffmpeg_command(options, width, height, "-", &output);
+                let child = Command::new(&command[0]).args(&command[1..])
+                    .stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|s| format!("Error starting {}: {}", options.ffmpeg, s.kind()))?;
+                Ok(VideoCapture { sink: Sink::Pipe(child), frame_size, path: output })
+            },
+        }
+    }
+
+    pub fn add_frame(&mut self, frame: &[u8]) -> Result<(), String> {
+        if frame.len() != self.frame_size {
+            return Err(format!("Frame has {} bytes, expected {}", frame.len(), self.frame_size));
+        }
+        let result = match self.sink {
+            Sink::File(ref mut writer) => writer.write_all(frame),
+            Sink::Pipe(ref mut child) => child.stdin.as_mut().expect("ffmpeg should have a standard input").write_all(frame),
+        };
+        result.map_err(|s| format!("Error writing a frame of {}: {}", self.path.display(), s.kind()))
+    }
+
+    /// Finishes the capture, for `PIPE` after ffmpeg encoded all frames.
+    pub fn stop(self) -> Result<PathBuf, String> {
+        let VideoCapture { sink, path, .. } = self;
+        match sink {
+            Sink::File(mut writer) => writer.flush().map_err(|s| format!("Error writing {}: {}", path.display(), s.kind()))?,
+            Sink::Pipe(mut child) => {
+                drop(child.stdin.take());
+                let status = child.wait().map_err(|s| format!("Error waiting for ffmpeg: {}", s.kind()))?;
+                if !status.success() {
+                    return Err(format!("ffmpeg failed to encode {}: {}", path.display(), status));
+                }
+            },
+        }
+        Ok(path)
+    }
+}
+
+static CAPTURE: Mutex<Option<VideoCapture>> = Mutex::new(None);
+
+/// Starts capturing frames of the given size. A running capture is stopped. Returns false if the capture could not be
+/// started, see `get_last_error`.
+#[no_mangle]
+pub extern fn start_video_capture(ptr: *const EngineOptions, width: u32, height: u32) -> bool {
+    let engine_options = unsafe_from_ptr!(ptr);
+    let options = &engine_options.video_capture;
+    let context = ScreenshotContext {
+        time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        ..ScreenshotContext::default()
+    };
+    let name = screenshot_file_name(&options.file_name, &context);
+    let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(running) = capture.take() {
+        let _ = running.stop();
+    }
+    let result = VideoCapture::start(&video_capture_dir(&engine_options.stracciatella_home), options, width, height, &name);
+    let success = result.is_ok();
+    *capture = result.map_err(|e| set_last_error(Some(e))).ok();
+    success
+}
+
+/// Adds a frame with the size given to `start_video_capture`. Returns false if nothing is captured or writing failed,
+/// which stops the capture.
+#[no_mangle]
+pub extern fn add_video_capture_frame(pixels_ptr: *const u16) -> bool {
+    let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    let result = match *capture {
+        Some(ref mut c) => {
+            let frame = unsafe { slice::from_raw_parts(pixels_ptr as *const u8, c.frame_size) };
+            c.add_frame(frame)
+        },
+        None => return false,
+    };
+    if let Err(e) = result {
+        set_last_error(Some(e));
+        *capture = None;
+        return false;
+    }
+    true
+}
+
+/// Stops the capture. Returns false if nothing was captured or the video could not be finished.
+#[no_mangle]
+pub extern fn stop_video_capture() -> bool {
+    let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    match capture.take().map(VideoCapture::stop) {
+        Some(Ok(_)) => true,
+        Some(Err(e)) => {
+            set_last_error(Some(e));
+            false
+        },
+        None => false,
+    }
+}
+
+/// Frames per second that the engine should capture.
+#[no_mangle]
+pub extern fn get_video_capture_frame_rate(ptr: *const EngineOptions) -> u32 {
+    unsafe_from_ptr!(ptr).video_capture.frame_rate.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use std::fs;
+    use std::path::Path;
+
+    use super::{VideoCapture, VideoCaptureOptions, VideoCaptureTarget, ffmpeg_command, format_command};
+
+    #[test]
+    fn ffmpeg_command_should_read_raw_rgb565_frames() {
+        let command = ffmpeg_command(&VideoCaptureOptions::default(), 640, 480, "-", Path::new("/tmp/my capture.mp4"));
+
+        assert_eq!(format_command(&command), "ffmpeg -y -f rawvideo -pixel_format rgb565le -video_size 640x480 -framerate 25 -i - -c:v libx264 -pix_fmt yuv420p \"/tmp/my capture.mp4\"");
+    }
+
+    #[test]
+    fn file_capture_should_write_the_frames_and_the_command() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let dir = temp_dir.path().join("video-capture");
+
+        let mut capture = VideoCapture::start(&dir, &VideoCaptureOptions::default(), 2, 1, "capture.mp4").unwrap();
+        capture.add_frame(&[1, 2, 3, 4]).unwrap();
+        capture.add_frame(&[5, 6, 7, 8]).unwrap();
+        assert!(capture.add_frame(&[1, 2]).is_err());
+        let path = capture.stop().unwrap();
+
+        assert_eq!(path, dir.join("capture.rgb565"));
+        assert_eq!(fs::read(&path).unwrap(), vec!(1, 2, 3, 4, 5, 6, 7, 8));
+        let command = fs::read_to_string(dir.join("capture.txt")).unwrap();
+        assert!(command.contains(&format!("-video_size 2x1 -framerate 25 -i {}", path.display())));
+    }
+
+    #[test]
+    fn pipe_capture_should_fail_without_ffmpeg() {
+        let temp_dir = tempdir::TempDir::new("ja2-tests").unwrap();
+        let options = VideoCaptureOptions { target: VideoCaptureTarget::PIPE, ffmpeg: String::from("/non-existing/ffmpeg"), ..VideoCaptureOptions::default() };
+
+        let result = VideoCapture::start(temp_dir.path(), &options, 2, 1, "capture.mp4");
+
+        assert!(result.err().unwrap().starts_with("Error starting /non-existing/ffmpeg: "));
+    }
+}
@@ -0,0 +1,164 @@
+//! Writer for zip archives, e.g. for packaged mods.
+//!
+//! The files are stored without compression and with a fixed modification time, so packaging the same files again
+//! results in an identical archive.
+
+use std::io;
+use std::io::prelude::*;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+/// Version 1.0 of the specification is sufficient for stored files
+const VERSION: u16 = 10;
+/// The names are encoded in UTF-8
+const FLAGS: u16 = 0x0800;
+const METHOD_STORED: u16 = 0;
+/// 1980-01-01 00:00 in MS-DOS format, the earliest time that can be stored
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+
+/// Returns the CRC-32 of the contents as used by zip and PNG.
+pub fn crc32(contents: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in contents {
+        crc ^= u32::from(*b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter<W: Write> {
+    writer: W,
+    entries: Vec<CentralEntry>,
+    offset: u64,
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "zip archives are limited to 4 GiB")
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(writer: W) -> ZipWriter<W> {
+        ZipWriter { writer, entries: vec!(), offset: 0 }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.write_bytes(&[value as u8, (value >> 8) as u8])
+    }
+
+    fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.write_bytes(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+    }
+
+    /// Adds a file with `/` as separator in the name.
+    pub fn add_file(&mut self, name: &str, contents: &[u8]) -> io::Result<()> {
+        if contents.len() as u64 > u64::from(u32::MAX) || self.offset > u64::from(u32::MAX) {
+            return Err(too_large());
+        }
+        let entry = CentralEntry { name: String::from(name), crc: crc32(contents), size: contents.len() as u32, offset: self.offset as u32 };
+
+        self.write_u32(LOCAL_HEADER_SIGNATURE)?;
+        self.write_u16(VERSION)?;
+        self.write_u16(FLAGS)?;
+        self.write_u16(METHOD_STORED)?;
+        self.write_u16(DOS_TIME)?;
+        self.write_u16(DOS_DATE)?;
+        self.write_u32(entry.crc)?;
+        self.write_u32(entry.size)?;
+        self.write_u32(entry.size)?;
+        self.write_u16(name.len() as u16)?;
+        self.write_u16(0)?;
+        self.write_bytes(name.as_bytes())?;
+        self.write_bytes(contents)?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let entries = ::std::mem::take(&mut self.entries);
+        let directory_offset = self.offset;
+        if directory_offset > u64::from(u32::MAX) || entries.len() > usize::from(u16::MAX) {
+            return Err(too_large());
+        }
+
+        for entry in &entries {
+            self.write_u32(CENTRAL_HEADER_SIGNATURE)?;
+            self.write_u16(VERSION)?;
+            self.write_u16(VERSION)?;
+            self.write_u16(FLAGS)?;
+            self.write_u16(METHOD_STORED)?;
+            self.write_u16(DOS_TIME)?;
+            self.write_u16(DOS_DATE)?;
+            self.write_u32(entry.crc)?;
+            self.write_u32(entry.size)?;
+            self.write_u32(entry.size)?;
+            self.write_u16(entry.name.len() as u16)?;
+            // Extra field, comment, disk number, internal and external attributes
+            self.write_bytes(&[0; 12])?;
+            self.write_u32(entry.offset)?;
+            self.write_bytes(entry.name.as_bytes())?;
+        }
+        let directory_size = self.offset - directory_offset;
+
+        self.write_u32(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+        // Number of this disk and the disk with the central directory
+        self.write_bytes(&[0; 4])?;
+        self.write_u16(entries.len() as u16)?;
+        self.write_u16(entries.len() as u16)?;
+        self.write_u32(directory_size as u32)?;
+        self.write_u32(directory_offset as u32)?;
+        self.write_u16(0)?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZipWriter;
+
+    fn read_u32(bytes: &[u8]) -> u32 {
+        u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24
+    }
+
+    #[test]
+    fn crc32_should_match_the_check_value() {
+        assert_eq!(super::crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(super::crc32(b""), 0);
+    }
+
+    #[test]
+    fn finish_should_write_the_central_directory() {
+        let mut zip = ZipWriter::new(vec!());
+        zip.add_file("mod/mod.json", b"{}").unwrap();
+        zip.add_file("mod/data/a.txt", b"a").unwrap();
+        let bytes = zip.finish().unwrap();
+
+        assert_eq!(read_u32(&bytes), super::LOCAL_HEADER_SIGNATURE);
+        assert_eq!(&bytes[30..42], b"mod/mod.json");
+        assert_eq!(&bytes[42..44], b"{}");
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(read_u32(end), super::END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        assert_eq!(&end[8..12], &[2, 0, 2, 0]);
+        let directory_offset = read_u32(&end[16..]) as usize;
+        assert_eq!(read_u32(&bytes[directory_offset..]), super::CENTRAL_HEADER_SIGNATURE);
+        assert_eq!(directory_offset + read_u32(&end[12..]) as usize, bytes.len() - 22);
+    }
+}
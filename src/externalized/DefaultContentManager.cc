@@ -18,6 +18,7 @@
 #include "sgp/FileMan.h"
 #include "sgp/LibraryDataBase.h"
 #include "sgp/MemMan.h"
+#include "sgp/SGPStrings.h"
 #include "sgp/StrUtils.h"
 #include "sgp/UTF8String.h"
 
@@ -31,6 +32,7 @@
 #include "WeaponModels.h"
 #include "policy/DefaultGamePolicy.h"
 #include "policy/DefaultIMPPolicy.h"
+#include "RustInterface.h"
 
 #include "slog/slog.h"
 
@@ -59,78 +61,8 @@ static void LoadEncryptedData(STRING_ENC_TYPE encType, SGPFile* const File, wcha
 	Str[read_chars - 1] = '\0';
 	for (const UINT16* i = Str; *i != '\0'; ++i)
 	{
-		/* "Decrypt" the ROT-1 "encrypted" data */
-		wchar_t c = (*i > 33 ? *i - 1 : *i);
-
-		if(encType == SE_RUSSIAN)
-		{
-			/* The Russian data files are incorrectly encoded. The original texts seem to
-			 * be encoded in CP1251, but then they were converted from CP1252 (!) to
-			 * UTF-16 to store them in the data files. Undo this damage here. */
-			if (0xC0 <= c && c <= 0xFF) c += 0x0350;
-		}
-		else
-		{
-			if(encType == SE_ENGLISH)
-			{
-				/* The English data files are incorrectly encoded. The original texts seem
-				 * to be encoded in CP437, but then they were converted from CP1252 (!) to
-				 * UTF-16 to store them in the data files. Undo this damage here. This
-				 * problem only occurs for a few lines by Malice. */
-				switch (c)
-				{
-					case 128: c = 0x00C7; break; // Ç
-					case 130: c = 0x00E9; break; // é
-					case 135: c = 0x00E7; break; // ç
-				}
-			}
-			else if(encType == SE_POLISH)
-			{
-				/* The Polish data files are incorrectly encoded. The original texts seem to
-				 * be encoded in CP1250, but then they were converted from CP1252 (!) to
-				 * UTF-16 to store them in the data files. Undo this damage here.
-				 * Also the format code for centering texts differs. */
-				switch (c)
-				{
-					case 143: c = 0x0179; break;
-					case 163: c = 0x0141; break;
-					case 165: c = 0x0104; break;
-					case 175: c = 0x017B; break;
-					case 179: c = 0x0142; break;
-					case 182: c = 179;    break; // not a char, but a format code (centering)
-					case 185: c = 0x0105; break;
-					case 191: c = 0x017C; break;
-					case 198: c = 0x0106; break;
-					case 202: c = 0x0118; break;
-					case 209: c = 0x0143; break;
-					case 230: c = 0x0107; break;
-					case 234: c = 0x0119; break;
-					case 241: c = 0x0144; break;
-					case 338: c = 0x015A; break;
-					case 339: c = 0x015B; break;
-					case 376: c = 0x017A; break;
-				}
-			}
-
-			/* Cyrillic texts (by Ivan Dolvich) in the non-Russian versions are encoded
-			 * in some wild manner. Undo this damage here. */
-			if (0x044D <= c && c <= 0x0452) // cyrillic A to IE
-			{
-				c += -0x044D + 0x0410;
-			}
-			else if (c == 0x0453) // cyrillic IO
-			{
-				c = 0x0401;
-			}
-			else if (0x0454 <= c && c <= 0x0467) // cyrillic ZHE to SHCHA
-			{
-				c += -0x0454 + 0x0416;
-			}
-			else if (0x0468 <= c && c <= 0x046C) // cyrillic YERU to YA
-			{
-				c += -0x0468 + 0x042B;
-			}
-		}
+		/* "Decrypt" the ROT-1 "encrypted" data and fix the encoding of the version */
+		wchar_t c = fix_data_code_point(encType, *i > 33 ? *i - 1 : *i);
 
 		*DestString++ = c;
 	}
@@ -140,6 +72,7 @@ static void LoadEncryptedData(STRING_ENC_TYPE encType, SGPFile* const File, wcha
 
 DefaultContentManager::DefaultContentManager(GameVersion gameVersion,
 						const std::string &configFolder,
+						const std::string &savesFolder,
 						const std::string &gameResRootPath,
 						const std::string &externalizedDataPath
 	)
@@ -155,6 +88,7 @@ DefaultContentManager::DefaultContentManager(GameVersion gameVersion,
 	 */
 
 	m_configFolder = configFolder;
+	m_savesFolder = savesFolder;
 	m_gameResRootPath = gameResRootPath;
 	m_externalizedDataPath = externalizedDataPath;
 
@@ -498,12 +432,27 @@ std::string DefaultContentManager::getVideoCaptureFolder() const
 /** Get folder for saved games. */
 std::string DefaultContentManager::getSavedGamesFolder() const
 {
-	return FileMan::joinPaths(m_configFolder, "SavedGames");
+	return m_savesFolder;
+}
+
+/** Load the translation override of a string.
+ * @return false when the string is not translated. */
+static bool LoadTranslatedString(const char* fileName, wchar_t* DestString, uint32_t seek_chars, uint32_t read_chars)
+{
+	char* translated = get_translated_string(fileName, seek_chars);
+	if (translated == NULL) return false;
+
+	UTF8String str(translated);
+	free_rust_string(translated);
+	wcslcpy(DestString, &str.getWCHAR()[0], read_chars);
+	return true;
 }
 
 /** Load encrypted string from game resource file. */
 void DefaultContentManager::loadEncryptedString(const char *fileName, wchar_t* DestString, uint32_t seek_chars, uint32_t read_chars) const
 {
+	if (LoadTranslatedString(fileName, DestString, seek_chars, read_chars)) return;
+
 	AutoSGPFile File(openGameResForReading(fileName));
 	loadEncryptedString(File, DestString, seek_chars, read_chars);
 }
@@ -516,9 +465,10 @@ void DefaultContentManager::loadEncryptedString(SGPFile* const File, wchar_t* De
 /** Load dialogue quote from file. */
 UTF8String* DefaultContentManager::loadDialogQuoteFromFile(const char* fileName, int quote_number)
 {
-	AutoSGPFile File(openGameResForReading(fileName));
-
 	wchar_t quote[DIALOGUESIZE];
+	if (LoadTranslatedString(fileName, quote, quote_number * DIALOGUESIZE, DIALOGUESIZE)) return new UTF8String(quote);
+
+	AutoSGPFile File(openGameResForReading(fileName));
 	LoadEncryptedData(getStringEncType(), File, quote, quote_number * DIALOGUESIZE, DIALOGUESIZE);
 	return new UTF8String(quote);
 }
@@ -983,7 +933,25 @@ const DealerInventory* DefaultContentManager::getDealerInventory(int dealerId) c
 	return m_dealersInventory[dealerId];
 }
 
+void DefaultContentManager::setMusicPackTracks(MusicMode mode, const std::vector<std::string> &tracks)
+{
+	std::vector<const UTF8String*>& musicModeList = m_musicPackMap[mode];
+	for (const std::string &track : tracks)
+	{
+		musicModeList.push_back(new UTF8String(track.c_str()));
+		SLOGD(DEBUG_TAG_DEFAULTCM, "Loaded music pack track %s", track.c_str());
+	}
+}
+
 const UTF8String* DefaultContentManager::getMusicForMode(MusicMode mode) const {
+	std::map<MusicMode, std::vector<const UTF8String*> >::const_iterator pack = m_musicPackMap.find(mode);
+	if (pack != m_musicPackMap.end() && !pack->second.empty())
+	{
+		const uint32_t index = Random((uint32_t)pack->second.size());
+		SLOGD(DEBUG_TAG_DEFAULTCM, "Choosing music pack track %d of %d for: '%s'", index, pack->second.size(), pack->second[index]->getUTF8());
+		return pack->second[index];
+	}
+
 	const uint32_t index = Random((uint32_t)m_musicMap.find(mode)->second->size());
 	const UTF8String* chosen = m_musicMap.find(mode)->second->at(index);
 
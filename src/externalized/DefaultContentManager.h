@@ -22,6 +22,7 @@ public:
 
 	DefaultContentManager(GameVersion gameVersion,
 				const std::string &configFolder,
+				const std::string &savesFolder,
 				const std::string &gameResRootPath,
 				const std::string &externalizedDataPath);
 
@@ -96,6 +97,13 @@ public:
 
 	const std::string& getExternalizedDataDir() { return m_externalizedDataPath; }
 
+	/** Set the tracks of the music pack for a music mode, they are played instead of the music of music.json. */
+	void setMusicPackTracks(MusicMode mode, const std::vector<std::string> &tracks);
+
+	/** Set the extra data dirs in mount order, only used to report them. */
+	void setExtraDataDirs(const std::vector<std::string> &dirs) { m_extraDataDirs = dirs; }
+	const std::vector<std::string>& getExtraDataDirs() { return m_extraDataDirs; }
+
 	/** Get folder for saved games. */
 	virtual std::string getSavedGamesFolder() const;
 
@@ -145,8 +153,11 @@ protected:
 	std::string m_dataDir;
 	std::string m_tileDir;
 	std::string m_configFolder;
+	/** Folder for the saved games of the current mod, see `get_save_dir`. */
+	std::string m_savesFolder;
 	std::string m_gameResRootPath;
 	std::string m_externalizedDataPath;
+	std::vector<std::string> m_extraDataDirs;
 
 	const GameVersion m_gameVersion;
 
@@ -168,6 +179,7 @@ protected:
 	std::map<std::string, const WeaponModel*> m_weaponMap;
 	std::map<std::string, const ItemModel*> m_itemMap;
 	std::map<MusicMode, const std::vector<const UTF8String*>*> m_musicMap;
+	std::map<MusicMode, std::vector<const UTF8String*> > m_musicPackMap;
 
 	std::vector<std::vector<const WeaponModel*> > mNormalGunChoice;
 	std::vector<std::vector<const WeaponModel*> > mExtendedGunChoice;
@@ -17,7 +17,7 @@ DefaultContentManager * createDefaultCMForTesting()
 	DefaultContentManager *cm;
 
 	cm = new DefaultContentManager(GV_ENGLISH,
-					configFolderPath,
+					configFolderPath, configFolderPath,
 					gameResRootPath, externalizedDataPath);
 
 	// we don't load game resources
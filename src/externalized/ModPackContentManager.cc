@@ -2,6 +2,9 @@
 
 #include "JsonUtility.h"
 #include "sgp/FileMan.h"
+#include "sgp/LibraryDataBase.h"
+#include "sgp/MemMan.h"
+#include "sgp/StrUtils.h"
 #include "sgp/UTF8String.h"
 
 #include "slog/slog.h"
@@ -12,16 +15,36 @@ ModPackContentManager::ModPackContentManager(GameVersion gameVersion,
 						const std::string &modName,
 						const std::string &modResFolder,
 						const std::string &configFolder,
+						const std::string &savesFolder,
 						const std::string &gameResRootPath,
 						const std::string &externalizedDataPath)
-	:DefaultContentManager(gameVersion, configFolder, gameResRootPath, externalizedDataPath)
+	:DefaultContentManager(gameVersion, configFolder, savesFolder, gameResRootPath, externalizedDataPath)
 {
 	m_modName = modName;
 	m_modResFolder = modResFolder;
+	m_modLibraryDB = new LibraryDB();
 }
 
 ModPackContentManager::~ModPackContentManager()
 {
+	m_modLibraryDB->ShutDownFileDatabase();
+	delete m_modLibraryDB;
+}
+
+void ModPackContentManager::initGameResouces(const std::string &stracciatellaHomeDir, const std::vector<std::string> &libraries)
+{
+	DefaultContentManager::initGameResouces(stracciatellaHomeDir, libraries);
+
+	std::vector<std::string> modLibraries = GetResourceLibraries(m_modResFolder);
+	const char *failedLib = m_modLibraryDB->InitializeFileDatabase(m_modResFolder, modLibraries);
+	if(failedLib)
+	{
+		throw LibraryFileNotFoundException(FormattedString("Library '%s' of the mod '%s' cannot be read.\n", failedLib, m_modName.c_str()));
+	}
+	for (size_t i = 0; i < modLibraries.size(); i++)
+	{
+		SLOGI(DEBUG_TAG_MODPACK, "mod library: %s", modLibraries[i].c_str());
+	}
 }
 
 /* Checks if a game resource exists. */
@@ -31,6 +54,10 @@ bool ModPackContentManager::doesGameResExists(char const* fileName) const
 	{
 		return true;
 	}
+	else if(m_modLibraryDB->CheckIfFileExistInLibrary(fileName))
+	{
+		return true;
+	}
 	else
 	{
 		return DefaultContentManager::doesGameResExists(fileName);
@@ -49,6 +76,18 @@ SGPFile* ModPackContentManager::openGameResForReading(const char* filename) cons
 	int d = FileMan::openFileCaseInsensitive(m_modResFolder, filename, mode);
 	if (d < 0)
 	{
+		LibraryFile libFile;
+		memset(&libFile, 0, sizeof(libFile));
+		std::string _filename(filename);
+		FileMan::slashifyPath(_filename);
+		if (m_modLibraryDB->FindFileInTheLibrarry(_filename, &libFile))
+		{
+			SLOGI(DEBUG_TAG_MODPACK, "opening mod's resource from library: %s", filename);
+			SGPFile *file = MALLOCZ(SGPFile);
+			file->flags = SGPFILE_NONE;
+			file->u.lib = libFile;
+			return file;
+		}
 		return DefaultContentManager::openGameResForReading(filename);
 	}
 	else
@@ -63,13 +102,6 @@ SGPFile* ModPackContentManager::openGameResForReading(const std::string& filenam
 	return openGameResForReading(filename.c_str());
 }
 
-/** Get folder for saved games. */
-std::string ModPackContentManager::getSavedGamesFolder() const
-{
-	std::string folderName = std::string("SavedGames-") + m_modName;
-	return FileMan::joinPaths(m_configFolder, folderName);
-}
-
 /** Load dialogue quote from file. */
 UTF8String* ModPackContentManager::loadDialogQuoteFromFile(const char* filename, int quote_number)
 {
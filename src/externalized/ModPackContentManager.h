@@ -13,25 +13,28 @@ public:
 				const std::string &modName,
 				const std::string &modResFolder,
 				const std::string &configFolder,
+				const std::string &savesFolder,
 				const std::string &gameResRootPath,
 				const std::string &externalizedDataPath);
 
 	virtual ~ModPackContentManager();
 
+	/** Also loads the SLF libraries in the mod's directory. */
+	virtual void initGameResouces(const std::string &stracciatellaHomeDir, const std::vector<std::string> &libraries);
+
 	/* Checks if a game resource exists. */
 	virtual bool doesGameResExists(char const* fileName) const;
 
 	virtual SGPFile* openGameResForReading(const char* filename) const;
 	virtual SGPFile* openGameResForReading(const std::string& filename) const;
 
-	/** Get folder for saved games. */
-	std::string getSavedGamesFolder() const;
-
 	/** Load dialogue quote from file. */
 	virtual UTF8String* loadDialogQuoteFromFile(const char* filename, int quote_number);
 
 protected:
 	std::string m_modName;
 	std::string m_modResFolder;
+	/** Libraries of the mod, searched after the loose files of the mod. */
+	LibraryDB *m_modLibraryDB;
 	std::map<std::string, std::vector<std::string> > m_dialogQuotesMap;
 };
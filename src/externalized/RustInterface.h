@@ -1,35 +1,312 @@
+#pragma once
+
 #include "Types.h"
 #include "GameRes.h"
 #include "Video.h"
 
+// Version of the declarations below, must match FFI_API_VERSION of the library
+#define RUST_FFI_API_VERSION 2
+
 extern "C" {
 	typedef struct engine_options_S engine_options_t;
+	typedef enum { LOG_ERROR, LOG_WARNING, LOG_INFO, LOG_DEBUG, LOG_TRACE } log_level_t;
+	typedef enum { LOG_FORMAT_TEXT, LOG_FORMAT_JSON } log_format_t;
+	typedef struct preflight_report_S preflight_report_t;
+	typedef struct vfs_S vfs_t;
+	typedef struct cancellation_token_S cancellation_token_t;
+	typedef struct job_S job_t;
+	typedef enum { JOB_QUEUED, JOB_RUNNING, JOB_SUCCEEDED, JOB_FAILED, JOB_CANCELLED } job_status_t;
+	typedef enum { SANDBOX_NONE, SANDBOX_FLATPAK, SANDBOX_SNAP } sandbox_t;
+	typedef enum { DATA_LAYOUT_CLASSIC, DATA_LAYOUT_GOLD, DATA_LAYOUT_UNKNOWN } data_layout_t;
+	typedef void (*import_progress_t)(uint64_t, uint64_t);
+	typedef enum { ENGINE_EVENT_CONFIG_RELOADED, ENGINE_EVENT_MODS_CHANGED, ENGINE_EVENT_CACHE_CLEARED } engine_event_t;
+	typedef void (*event_callback_t)(engine_event_t, void *);
+	typedef enum {
+		OPTIONS_HELP_SHOWN = 0,
+		OPTIONS_INVALID_ARGUMENTS = 64,
+		OPTIONS_INVALID_CONFIG = 65,
+		OPTIONS_INVALID_DATA_DIR = 66,
+		OPTIONS_HOME_DIR_ERROR = 73
+	} options_exit_code_t;
+	typedef enum { CAMPAIGN_STARTED, CAMPAIGN_SECTOR_TAKEN, CAMPAIGN_MERC_DIED, CAMPAIGN_DAY_ENDED } campaign_event_t;
+	typedef enum { PRESENCE_MAIN_MENU, PRESENCE_STRATEGIC, PRESENCE_TACTICAL } presence_screen_t;
+	typedef enum { PREFLIGHT_OK, PREFLIGHT_WARNING, PREFLIGHT_ERROR } preflight_status_t;
+	typedef struct {
+		bool no_interrupts_from_unseen_enemies;
+		bool use_remaining_action_points;
+	} interrupt_fixes_t;
+	typedef struct {
+		bool leadership_bonus_same_sector_only;
+		bool burst_marksmanship_bonus;
+	} bonus_fixes_t;
+	typedef struct {
+		UINT32 queens_pool_of_troops;
+		UINT32 initial_garrison_percentage;
+		UINT8 min_enemy_group_size;
+		UINT32 income_percentage;
+		UINT32 item_drop_percentage;
+	} difficulty_settings_t;
+	typedef enum {
+		ANCHOR_TOP_LEFT, ANCHOR_TOP, ANCHOR_TOP_RIGHT,
+		ANCHOR_LEFT, ANCHOR_CENTER, ANCHOR_RIGHT,
+		ANCHOR_BOTTOM_LEFT, ANCHOR_BOTTOM, ANCHOR_BOTTOM_RIGHT
+	} anchor_t;
+	typedef struct {
+		anchor_t anchor;
+		INT32 offset_x;
+		INT32 offset_y;
+	} panel_position_t;
+	#define GAMEPLAY_OPTIONS_VERSION 2
+	typedef struct {
+		UINT32 version;
+		bool subtitles;
+		UINT32 speech_subtitle_duration_ms;
+		interrupt_fixes_t interrupt_fixes;
+		bonus_fixes_t bonus_fixes;
+		bool show_tutorials;
+		bool show_hints;
+	} gameplay_options_t;
+	typedef struct {
+		UINT32 time;
+		UINT16 key_state;
+		UINT16 event;
+		UINT32 param;
+		UINT32 character;
+	} replay_event_t;
+	extern UINT32 ffi_api_version();
+	extern bool negotiate_ffi_api_version(UINT32 expected);
 	extern engine_options_t* create_engine_options(char **argv, int argc);
+	extern options_exit_code_t get_options_exit_code();
+	extern char * get_engine_options_json(const engine_options_t *);
+	extern bool apply_engine_options_json(engine_options_t *, const char *);
 	extern bool write_engine_options(const engine_options_t *);
+	extern bool reload_engine_options(engine_options_t *);
 	extern void free_engine_options(engine_options_t *);
 	extern char * get_stracciatella_home(const engine_options_t *);
+	extern uint16_t * get_stracciatella_home_wide(const engine_options_t *);
+	extern char * get_config_file_path(const engine_options_t *);
+	extern char * get_log_dir(const engine_options_t *);
+	extern char * get_save_dir(const engine_options_t *);
+	extern uint16_t * get_save_dir_wide(const engine_options_t *);
+	extern char * get_saves_home(const engine_options_t *);
+	extern uint16_t * get_saves_home_wide(const engine_options_t *);
+	extern char * get_save_usage_json(const engine_options_t *);
+#ifdef __ANDROID__
+	extern void set_android_storage_dir(const char *);
+#endif
+	extern char * get_cache_dir(const engine_options_t *);
+	extern char * get_mods_dir(const engine_options_t *);
+	extern bool create_user_dirs(const engine_options_t *);
+	extern bool clear_cache(const engine_options_t *);
 	extern char * get_vanilla_data_dir(const engine_options_t *);
+	extern uint16_t * get_vanilla_data_dir_wide(const engine_options_t *);
 	extern void set_vanilla_data_dir(const engine_options_t *, const char *);
+	extern char * get_game_dir(const engine_options_t *, const char *);
+	extern void set_game_dir(engine_options_t *, const char *);
+	extern char * get_save_game_dir(const engine_options_t *);
+	extern void set_save_game_dir(engine_options_t *, const char *);
 	extern UINT32 get_number_of_mods(const engine_options_t *);
 	extern char * get_mod(const engine_options_t *, uint32_t index);
+	extern void set_mods(engine_options_t *, const char **, size_t);
+	extern UINT32 get_number_of_extra_data_dirs(const engine_options_t *);
+	extern char * get_extra_data_dir(const engine_options_t *, UINT32);
+	extern void set_extra_data_dirs(engine_options_t *, const char **, size_t);
+	extern void add_extra_data_dir(engine_options_t *, const char *);
+	extern bool remove_extra_data_dir(engine_options_t *, UINT32);
+	extern data_layout_t get_data_layout(const engine_options_t *);
+	extern char * get_mod_data_dir(const engine_options_t *, const char *, uint32_t index);
 	extern UINT16 get_resolution_x(const engine_options_t *);
 	extern UINT16 get_resolution_y(const engine_options_t *);
 	extern void set_resolution(const engine_options_t *, UINT16, UINT16);
+	extern UINT16 get_refresh_rate(const engine_options_t *);
+	extern void set_refresh_rate(engine_options_t *, UINT16);
+	extern bool parse_and_validate_resolution(const char *, UINT16 *, UINT16 *);
+	extern char * get_last_error();
+	extern char * get_last_error_diagnostic();
+	extern bool is_resolution_object_form(const engine_options_t *);
+	extern void set_resolution_object_form(engine_options_t *, bool);
 	extern GameVersion get_resource_version(const engine_options_t *);
 	extern void set_resource_version(const engine_options_t *, const char *);
+	extern GameVersion get_game_text_language(const engine_options_t *);
+	extern void set_game_text_language(engine_options_t *, const char *);
 	extern char * get_resource_version_string(GameVersion);
 	extern void free_rust_string(char *);
+	extern void free_rust_wide_string(uint16_t *);
+	extern uint32_t register_event_callback(engine_event_t, event_callback_t, void *);
+	extern bool unregister_event_callback(uint32_t);
+
+	extern void watch_mods(const engine_options_t *, const char *);
+	extern void unwatch_mods();
+	extern bool mods_changed();
+	extern bool refresh_mod_catalog(const engine_options_t *, const char *);
+	extern char * get_mod_update_hints_json(const engine_options_t *, const char *, const char *);
 	extern bool should_show_help(const engine_options_t *);
 	extern bool should_run_unittests(const engine_options_t *);
+	extern bool run_library_unittests(const engine_options_t *, const char *);
+	extern bool should_run_smoketest(const engine_options_t *);
+	extern bool install_smoketest_save(const char *, const char *);
+	extern bool remove_smoketest_save(const char *);
 	extern bool should_run_editor(const engine_options_t *);
 	extern bool should_start_in_fullscreen(const engine_options_t *);
 	extern VideoScaleQuality get_scaling_quality(const engine_options_t *);
 	extern void set_scaling_quality(const engine_options_t *, const char *);
 	extern void set_start_in_fullscreen(const engine_options_t *, bool);
 	extern bool should_start_in_window(const engine_options_t *);
+	extern bool should_keep_window_on_top(const engine_options_t *);
+	extern void set_window_always_on_top(const engine_options_t *, bool);
+	extern bool should_grab_mouse(const engine_options_t *);
+	extern void set_grab_mouse(const engine_options_t *, bool);
+	extern bool should_pause_on_focus_loss(const engine_options_t *);
+	extern void set_pause_on_focus_loss(const engine_options_t *, bool);
+	extern bool should_enforce_single_instance(const engine_options_t *);
+	extern void set_single_instance(const engine_options_t *, bool);
+	extern bool should_write_campaign_log(const engine_options_t *);
+	extern void set_campaign_log(const engine_options_t *, bool);
+	extern bool should_canonicalize_data_dir(const engine_options_t *);
+	extern void set_canonicalize_data_dir(engine_options_t *, bool);
 	extern bool should_start_in_debug_mode(const engine_options_t *);
+	extern bool should_enable_cheats(const engine_options_t *);
+	extern void set_enable_cheats(const engine_options_t *, bool);
+	extern bool has_random_seed(const engine_options_t *);
+	extern uint64_t get_random_seed(const engine_options_t *);
 	extern bool should_start_without_sound(const engine_options_t *);
 	extern void set_start_without_sound(const engine_options_t *, bool);
+	extern char * get_audio_device(const engine_options_t *);
+	extern void set_audio_device(const engine_options_t *, const char *);
+	extern UINT32 get_audio_sample_rate(const engine_options_t *);
+	extern bool set_audio_sample_rate(const engine_options_t *, UINT32);
+	extern float get_brightness(const engine_options_t *);
+	extern bool set_brightness(engine_options_t *, float);
+	extern UINT16 get_max_fps(const engine_options_t *);
+	extern bool set_max_fps(engine_options_t *, UINT16);
+	extern UINT16 get_display_index(const engine_options_t *);
+	extern void set_display_index(engine_options_t *, UINT16);
+	extern bool has_window_position(const engine_options_t *);
+	extern INT32 get_window_x(const engine_options_t *);
+	extern INT32 get_window_y(const engine_options_t *);
+	extern void set_window_position(engine_options_t *, INT32, INT32);
+	extern void clear_window_position(engine_options_t *);
+	extern char * get_audio_driver(const engine_options_t *);
+	extern void set_audio_driver(const engine_options_t *, const char *);
+	extern INT32 find_audio_device_index(const engine_options_t *, const char **, size_t);
+	extern char * get_music_pack(const engine_options_t *);
+	extern void set_music_pack(const engine_options_t *, const char *);
+	extern UINT32 get_number_of_music_packs(const engine_options_t *);
+	extern char * get_music_pack_id(const engine_options_t *, UINT32 index);
+	extern UINT32 get_number_of_music_pack_tracks(const engine_options_t *, const char *);
+	extern char * get_music_pack_track(const engine_options_t *, const char *, UINT32 index);
+	extern INT16 * decode_ogg_vorbis(const UINT8 *, size_t, UINT16 *channels, UINT32 *sampleRate, size_t *samplesLength);
+	extern void free_decoded_audio(INT16 *, size_t);
+
+	extern char * get_ui_theme(const engine_options_t *);
+	extern void set_ui_theme(engine_options_t *, const char *);
+	extern UINT32 get_number_of_ui_themes(const vfs_t *);
+	extern char * get_ui_theme_id(const vfs_t *, UINT32 index);
+	extern char * get_ui_theme_name(const vfs_t *, UINT32 index);
+	extern bool get_gameplay_options(const engine_options_t *, gameplay_options_t *, size_t);
+	extern bool should_show_subtitles(const engine_options_t *);
+	extern void set_show_subtitles(const engine_options_t *, bool);
+	extern UINT32 get_speech_subtitle_duration_ms(const engine_options_t *);
+	extern bool set_speech_subtitle_duration_ms(const engine_options_t *, UINT32);
+	extern bool should_show_tutorials(const engine_options_t *);
+	extern void set_show_tutorials(const engine_options_t *, bool);
+	extern bool should_show_hints(const engine_options_t *);
+	extern void set_show_hints(const engine_options_t *, bool);
+	extern interrupt_fixes_t get_interrupt_fixes(const engine_options_t *);
+	extern bonus_fixes_t get_bonus_fixes(const engine_options_t *);
+	extern bool load_difficulty_settings(const engine_options_t *, const char *, UINT8, difficulty_settings_t *);
+	extern bool load_ui_panel_position(const engine_options_t *, const char *, const char *, panel_position_t *);
+
+	extern log_format_t get_log_format(const engine_options_t *);
+	extern bool init_logger(const engine_options_t *, const char *);
+	extern void log_message(log_level_t, const char *, const char *);
+	extern UINT32 get_number_of_validation_warnings(const engine_options_t *);
+	extern char * get_validation_warning(const engine_options_t *, UINT32 index);
+	extern char * get_deprecation_warnings_json(const engine_options_t *);
+	extern char * get_option_registry_json();
+	extern UINT32 validate_interface_images(engine_options_t *, const vfs_t *);
+
+	extern vfs_t* create_vfs(const engine_options_t *, const char *);
+	extern void free_vfs(vfs_t *);
+	extern char * vfs_resolve(const vfs_t *, const char *);
+	extern char * vfs_writable_path(const vfs_t *, const char *);
+	extern bool vfs_is_read_only(const vfs_t *, const char *);
+	extern char * vfs_get_mounts_json(const vfs_t *);
+	extern char * get_body_type_structures_json(const vfs_t *);
+
+	extern UINT16 fix_data_code_point(STRING_ENC_TYPE, UINT16);
+	extern bool export_vanilla_strings(const engine_options_t *, const char *);
+
+	extern char * get_game_language(const engine_options_t *);
+	extern void set_game_language(engine_options_t *, const char *);
+	extern char * get_launcher_language(const engine_options_t *);
+	extern void set_launcher_language(engine_options_t *, const char *);
+	extern bool load_translations(const engine_options_t *, const char *);
+	extern char * get_translated_string(const char *, UINT32);
+
+	extern char * export_settings_snippet(const engine_options_t *);
+	extern bool import_settings_snippet(engine_options_t *, const char *);
+
+	extern sandbox_t get_sandbox();
+	extern char * translate_chosen_path(const char *);
+
+	extern bool migrate_stracciatella_home(engine_options_t *, const char *);
+	extern bool import_vanilla_data(const engine_options_t *, const char *, const char *, import_progress_t);
+	extern char * get_vanilla_data_import_plan_json(const engine_options_t *, const char *, const char *);
+	extern cancellation_token_t* create_cancellation_token();
+	extern void cancel_operation(const cancellation_token_t *);
+	extern void free_cancellation_token(cancellation_token_t *);
+	extern char * verify_game_data_json(const engine_options_t *, const char *, const cancellation_token_t *);
+	extern job_t* start_verification_job(const engine_options_t *, const char *);
+	extern job_t* start_data_import_job(const engine_options_t *, const char *, const char *);
+	extern job_t* start_mod_package_job(const engine_options_t *, const char *, const char *);
+	extern job_t* start_clear_cache_job(const engine_options_t *);
+	extern job_status_t get_job_status(const job_t *);
+	extern void get_job_progress(const job_t *, uint64_t *done, uint64_t *total);
+	extern char * get_job_result(const job_t *);
+	extern void cancel_job(const job_t *);
+	extern void free_job(job_t *);
+
+	extern preflight_report_t* run_preflight_checks(const engine_options_t *);
+	extern void free_preflight_report(preflight_report_t *);
+	extern preflight_status_t get_preflight_status(const preflight_report_t *);
+	extern UINT32 get_number_of_preflight_checks(const preflight_report_t *);
+	extern char * get_preflight_check_name(const preflight_report_t *, UINT32 index);
+	extern preflight_status_t get_preflight_check_status(const preflight_report_t *, UINT32 index);
+	extern char * get_preflight_check_message(const preflight_report_t *, UINT32 index);
+	extern preflight_report_t* run_startup_checks(const engine_options_t *, const char *extra_data_dir);
+
+	extern bool export_keybindings(const engine_options_t *, const char *);
+	extern bool import_keybindings(const engine_options_t *, const char *);
+	extern bool reset_keybindings(const engine_options_t *);
+
+	extern bool is_game_running(const engine_options_t *);
+	extern char * get_running_game_message(const engine_options_t *);
+	extern bool lock_game(const engine_options_t *);
+	extern void unlock_game();
+
+	extern char * get_replays_dir(const engine_options_t *);
+	extern bool should_record_replay(const engine_options_t *);
+	extern char * get_replay_file(const engine_options_t *);
+	extern bool start_replay_recording(const engine_options_t *, const char *, UINT32);
+	extern bool record_replay_event(const replay_event_t *);
+	extern void stop_replay_recording();
+	extern bool open_replay(const char *);
+	extern bool get_replay_seed(UINT32 *);
+	extern bool read_replay_event(replay_event_t *);
+
+	extern bool log_campaign_event(const engine_options_t *, campaign_event_t, UINT32 day, UINT8 hour, UINT8 minute, INT16 x, INT16 y, INT8 z, const char *details);
+	extern char * get_campaign_log_json(const engine_options_t *);
+
+	// Only available if the library is built with the presence feature
+	extern bool update_rich_presence(const engine_options_t *, presence_screen_t, UINT32 day);
+	extern void clear_rich_presence();
+
+	extern bool start_video_capture(const engine_options_t *, UINT32 width, UINT32 height);
+	extern bool add_video_capture_frame(const UINT16 *pixels);
+	extern bool stop_video_capture();
+	extern UINT32 get_video_capture_frame_rate(const engine_options_t *);
+	extern char * save_screenshot(const engine_options_t *, const UINT16 *pixels, UINT32 width, UINT32 height, UINT32 day, INT16 x, INT16 y, INT8 z);
 
 	extern char * find_ja2_executable(const char *);
 }
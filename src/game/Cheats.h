@@ -4,14 +4,8 @@
 #include "Types.h"
 #include "GameState.h"
 
-extern UINT8 gubCheatLevel;
-
-/** Get cheat code. */
-extern const char * getCheatCode();
-
-#define INFORMATION_CHEAT_LEVEL( )	( (gubCheatLevel >= (isGermanVersion() ? 5 : 3)) || GameState::getInstance()->debugging())
-#define CHEATER_CHEAT_LEVEL( )		( gubCheatLevel >= (isGermanVersion() ? 6 : 5) )
-#define DEBUG_CHEAT_LEVEL( )		( gubCheatLevel >= (isGermanVersion() ? 7 : 6) || GameState::getInstance()->debugging())
-#define RESET_CHEAT_LEVEL( )		( gubCheatLevel = 0 )
+#define INFORMATION_CHEAT_LEVEL( )	( GameState::getInstance()->debugging() )
+#define CHEATER_CHEAT_LEVEL( )		( GameState::getInstance()->cheatsEnabled() )
+#define DEBUG_CHEAT_LEVEL( )		( GameState::getInstance()->debugging() )
 
 #endif
@@ -80,6 +80,24 @@ void setGameVersion(GameVersion ver)
 }
 
 
+/** Choose the language of the interface texts, the data files keep the language of the game version. */
+void setGameTextLanguage(GameVersion lang)
+{
+	switch(lang)
+	{
+		case GV_DUTCH:        g_langRes = &g_LanguageResDutch;       break;
+		case GV_ENGLISH:      g_langRes = &g_LanguageResEnglish;     break;
+		case GV_FRENCH:       g_langRes = &g_LanguageResFrench;      break;
+		case GV_GERMAN:       g_langRes = &g_LanguageResGerman;      break;
+		case GV_ITALIAN:      g_langRes = &g_LanguageResItalian;     break;
+		case GV_POLISH:       g_langRes = &g_LanguageResPolish;      break;
+		case GV_RUSSIAN:      g_langRes = &g_LanguageResRussian;     break;
+		case GV_RUSSIAN_GOLD: g_langRes = &g_LanguageResRussianGold; break;
+		default:              SLOGW(DEBUG_TAG_RESOURCES, "Unknown text language. Keeping the one of the game version");
+	}
+}
+
+
 /** Check if this is English version of the game. */
 bool isEnglishVersion()
 {
@@ -65,6 +65,9 @@ char const* GetMLGFilename(MultiLanguageGraphic);
 /** Choose game version. */
 void setGameVersion(GameVersion ver);
 
+/** Choose the language of the interface texts, call after setGameVersion. */
+void setGameTextLanguage(GameVersion lang);
+
 /** Get list of resource libraries. */
 std::vector<std::string> GetResourceLibraries(const std::string &dataDir);
 
@@ -26,6 +26,16 @@ bool GameState::debugging()
 	return debug;
 }
 
+bool GameState::cheatsEnabled()
+{
+	return cheats;
+}
+
+bool GameState::runningSmoketest()
+{
+	return smoketest;
+}
+
 /** Set editor mode. */
 void GameState::setEditorMode(bool autoLoad)
 {
@@ -36,10 +46,20 @@ void GameState::setDebugging(bool enabled) {
 	debug = enabled;
 }
 
+void GameState::setCheatsEnabled(bool enabled) {
+	cheats = enabled;
+}
+
+void GameState::setRunningSmoketest(bool enabled) {
+	smoketest = enabled;
+}
+
 
 /** Private constructor to avoid instantiation. */
 GameState::GameState()
 	:m_mode(GAME_MODE_GAME)
 {
 	debug = false;
+	cheats = false;
+	smoketest = false;
 }
@@ -23,15 +23,21 @@ public:
 	/** Set editor mode. */
 	void setEditorMode(bool autoLoad);
 	void setDebugging(bool enabled);
+	void setCheatsEnabled(bool enabled);
+	void setRunningSmoketest(bool enabled);
 
 	/** Check if we are in the editor mode. */
 	bool isEditorMode();
 	bool debugging();
+	bool cheatsEnabled();
+	bool runningSmoketest();
 
 private:
 
 	GameMode m_mode;
 	bool debug;
+	bool cheats;
+	bool smoketest;
 
 	/** Private constructor to avoid instantiation. */
 	GameState();
@@ -29,6 +29,13 @@
 #include "Video.h"
 #include "WordWrap.h"
 #include "UILayout.h"
+#include "GameState.h"
+#include "SaveLoadGame.h"
+#include "slog/slog.h"
+#include "RustInterface.h"
+
+#include <exception>
+#include <stdlib.h>
 
 
 //#define TESTFOREIGNFONTS
@@ -79,6 +86,7 @@ static void RenderGameVersion(void);
 static void RenderSDLVersionWarningIfNeccessary(void);
 static void RenderCopyright(void);
 static void RestoreButtonBackGrounds(void);
+static void HandleSmoketest(void);
 
 
 ScreenID MainMenuScreenHandle(void)
@@ -121,6 +129,12 @@ ScreenID MainMenuScreenHandle(void)
 		SetMusicMode(MUSIC_MAIN_MENU);
 	}
 
+	if (GameState::getInstance()->runningSmoketest())
+	{
+		HandleSmoketest();
+		return MAINMENU_SCREEN;
+	}
+
 
 	if (fInitialRender)
 	{
@@ -382,3 +396,38 @@ static void RestoreButtonBackGrounds(void)
 	}
 #endif
 }
+
+
+/* Removes the copy of the bundled save from the saved games folder. */
+static void RemoveSmoketestSave(void)
+{
+	char saveFile[512];
+	CreateSavedGameFileNameFromNumber(SAVE__SMOKETEST_NUM, saveFile);
+	if (!remove_smoketest_save(saveFile))
+	{
+		char* rustError = get_last_error();
+		SLOGW(DEBUG_TAG_INIT, "%s", rustError);
+		free_rust_string(rustError);
+	}
+}
+
+
+/* Loads the smoke test save and exits the game. The exit status is the result
+ * of the smoke test. */
+static void HandleSmoketest(void)
+{
+	try
+	{
+		LoadSavedGame(SAVE__SMOKETEST_NUM);
+	}
+	catch (const std::exception& e)
+	{
+		SLOGE(DEBUG_TAG_INIT, "Failed to load the smoke test save: %s", e.what());
+		RemoveSmoketestSave();
+		exit(EXIT_FAILURE);
+	}
+	RemoveSmoketestSave();
+	SLOGI(DEBUG_TAG_INIT, "Smoke test passed");
+	GameState::getInstance()->setRunningSmoketest(false);
+	requestGameExit();
+}
@@ -1563,6 +1563,10 @@ void CreateSavedGameFileNameFromNumber(const UINT8 ubSaveGameID, char* const pzN
 			sprintf(pzNewFileName, "%s/error.%s", dir.c_str(), ext);
 			break;
 
+		case SAVE__SMOKETEST_NUM:
+			sprintf(pzNewFileName, "%s/smoketest.%s", dir.c_str(), ext);
+			break;
+
 		default:
 			sprintf(pzNewFileName, "%s/%s%02d.%s", dir.c_str(), g_savegame_name, ubSaveGameID, ext);
 			break;
@@ -16,6 +16,7 @@
 
 #define SAVE__ERROR_NUM				99
 #define SAVE__END_TURN_NUM				98
+#define SAVE__SMOKETEST_NUM				97
 
 #define SAVED_GAME_HEADER_ON_DISK_SIZE			(432) // Size of SAVED_GAME_HEADER on disk in Vanilla and Stracciatella Windows
 #define SAVED_GAME_HEADER_ON_DISK_SIZE_STRAC_LIN	(688) // Size of SAVED_GAME_HEADER on disk in Stracciatella Linux
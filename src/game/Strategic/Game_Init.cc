@@ -335,8 +335,6 @@ void InitNewGame()
 	uiMeanWhileFlags = 0;
 	SetSelectedMan(0);
 
-	RESET_CHEAT_LEVEL();
-
 	if (gubScreenCount == 0)
 	{
 		LoadMercProfiles();
@@ -464,6 +462,4 @@ void ReStartingGame()
 
 	// Reset timer callbacks
 	gpCustomizableTimerCallback = NULL;
-
-	RESET_CHEAT_LEVEL();
 }
@@ -87,23 +87,33 @@ The weight generated will range between -100 and +100.  The calculated weight is
 of the group.  If the priority of the group is high, they
 */
 
-//Modifies the number of troops the queen has at the beginning of the game on top
-//of all of the garrison and patrol groups.  Additionally, there are a total of
+//The number of troops the queen has at the beginning of the game, the starting and desired
+//garrison sizes and the minimum size of enemy groups come from difficulty.json.  The queen's
+//pool is on top of all of the garrison and patrol groups.  Additionally, there are a total of
 //16 sectors that are LEVEL 1, 2, or 3 garrison groups.  The lower the level, the more
 //troops stay in that sector, and the rest will also be used as a secondary pool when
 //the primary pool runs dry.  So basically, this number is only part of the equation.
-#define EASY_QUEENS_POOL_OF_TROOPS		150
-#define NORMAL_QUEENS_POOL_OF_TROOPS		200
-#define HARD_QUEENS_POOL_OF_TROOPS		400
+static difficulty_settings_t gDifficultySettings[DIF_LEVEL_HARD];
+
+bool LoadDifficultySettings(engine_options_t const* const params, char const* const extra_data_dir)
+{
+	for (UINT8 level = DIF_LEVEL_EASY; level <= DIF_LEVEL_HARD; ++level)
+	{
+		if (!load_difficulty_settings(params, extra_data_dir, level, &gDifficultySettings[level - 1])) return false;
+	}
+	return true;
+}
 
-//Modifies the starting values as well as the desired values for all of the garrisons.
-#define EASY_INITIAL_GARRISON_PERCENTAGES	70
-#define NORMAL_INITIAL_GARRISON_PERCENTAGES	100
-#define HARD_INITIAL_GARRISON_PERCENTAGES	125
 
-#define EASY_MIN_ENEMY_GROUP_SIZE		3
-#define NORMAL_MIN_ENEMY_GROUP_SIZE		4
-#define HARD_MIN_ENEMY_GROUP_SIZE		6
+difficulty_settings_t const& GetDifficultySettings()
+{
+	UINT8 const difficulty = gGameOptions.ubDifficultyLevel;
+	if (difficulty < DIF_LEVEL_EASY || DIF_LEVEL_HARD < difficulty)
+	{
+		throw std::logic_error("invalid difficulty level");
+	}
+	return gDifficultySettings[difficulty - 1];
+}
 
 //Sets the starting alert chances.  Everytime an enemy arrives in a new sector, or the player,
 //this is the chance the enemy will detect the player in adjacent sectors.  This chance is associated
@@ -568,35 +578,31 @@ void InitStrategicAI()
 
 	// 475 is 7:55am in minutes since midnight, the time the game starts on day 1
 	UINT32      evaluate_time = 475;
+	difficulty_settings_t const& settings = GetDifficultySettings();
+	giReinforcementPool  = settings.queens_pool_of_troops;
+	giForcePercentage    = settings.initial_garrison_percentage;
+	gubMinEnemyGroupSize = settings.min_enemy_group_size;
+
 	UINT8 const difficulty    = gGameOptions.ubDifficultyLevel;
 	switch (difficulty)
 	{
 		case DIF_LEVEL_EASY:
-			giReinforcementPool		= EASY_QUEENS_POOL_OF_TROOPS;
-			giForcePercentage			= EASY_INITIAL_GARRISON_PERCENTAGES;
 			giArmyAlertness				= EASY_ENEMY_STARTING_ALERT_LEVEL;
 			giArmyAlertnessDecay	= EASY_ENEMY_STARTING_ALERT_DECAY;
-			gubMinEnemyGroupSize	= EASY_MIN_ENEMY_GROUP_SIZE;
 			gubHoursGracePeriod   = EASY_GRACE_PERIOD_IN_HOURS;
 			evaluate_time += EASY_TIME_EVALUATE_IN_MINUTES + Random(EASY_TIME_EVALUATE_VARIANCE);
 			break;
 
 		case DIF_LEVEL_MEDIUM:
-			giReinforcementPool		= NORMAL_QUEENS_POOL_OF_TROOPS;
-			giForcePercentage			= NORMAL_INITIAL_GARRISON_PERCENTAGES;
 			giArmyAlertness				= NORMAL_ENEMY_STARTING_ALERT_LEVEL;
 			giArmyAlertnessDecay	= NORMAL_ENEMY_STARTING_ALERT_DECAY;
-			gubMinEnemyGroupSize	= NORMAL_MIN_ENEMY_GROUP_SIZE;
 			gubHoursGracePeriod   = NORMAL_GRACE_PERIOD_IN_HOURS;
 			evaluate_time += NORMAL_TIME_EVALUATE_IN_MINUTES + Random(NORMAL_TIME_EVALUATE_VARIANCE);
 			break;
 
 		case DIF_LEVEL_HARD:
-			giReinforcementPool		= HARD_QUEENS_POOL_OF_TROOPS;
-			giForcePercentage			= HARD_INITIAL_GARRISON_PERCENTAGES;
 			giArmyAlertness				= HARD_ENEMY_STARTING_ALERT_LEVEL;
 			giArmyAlertnessDecay	= HARD_ENEMY_STARTING_ALERT_DECAY;
-			gubMinEnemyGroupSize	= HARD_MIN_ENEMY_GROUP_SIZE;
 			gubHoursGracePeriod   = HARD_GRACE_PERIOD_IN_HOURS;
 			evaluate_time += HARD_TIME_EVALUATE_IN_MINUTES + Random(HARD_TIME_EVALUATE_VARIANCE);
 			break;
@@ -2,6 +2,13 @@
 #define __STRATEGIC_AI_H
 
 #include "Strategic_Movement.h"
+#include "RustInterface.h"
+
+/* Loads the settings of all difficulty levels from difficulty.json, taking the
+ * enabled mods into account. */
+bool LoadDifficultySettings(engine_options_t const* params, char const* extra_data_dir);
+/* Returns the settings of the difficulty level of the current game. */
+difficulty_settings_t const& GetDifficultySettings();
 
 void InitStrategicAI(void);
 void KillStrategicAI(void);
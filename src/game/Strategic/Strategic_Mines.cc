@@ -524,6 +524,8 @@ static INT32 MineAMine(INT8 bMineIndex)
 	{
 		// player controlled
 		iAmtExtracted = ExtractOreFromMine( bMineIndex , GetCurrentWorkRateOfMineForPlayer( bMineIndex ) );
+		// the income is scaled by the difficulty settings, the ore supply is not
+		iAmtExtracted = iAmtExtracted * GetDifficultySettings().income_percentage / 100;
 
 		// SHOW ME THE MONEY!!!!
 		if( iAmtExtracted > 0 )
@@ -745,9 +745,7 @@ void RenderTalkingMenu()
 			str = zDealerStrings[GetTypeOfArmsDealer(GetArmsDealerIDFromMercID(pid))];
 		}
 		else if (cnt != 0 &&
-#ifndef _DEBUG
 			CHEATER_CHEAT_LEVEL() &&
-#endif
 			gubSrcSoldierProfile != NO_PROFILE &&
 			pid                  != NO_PROFILE)
 		{
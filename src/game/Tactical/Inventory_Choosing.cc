@@ -7,6 +7,7 @@
 #include "Campaign.h"
 #include "GameSettings.h"
 #include "StrategicMap.h"
+#include "Strategic_AI.h"
 #include "Auto_Resolve.h"
 #include "Map_Screen_Interface_Map.h"
 #include "Debug.h"
@@ -1703,10 +1704,11 @@ static void RandomlyChooseWhichItemsAreDroppable(SOLDIERCREATE_STRUCT* pp, INT8
 	}
 	else
 	{
-		// enemy army
-		ubAmmoDropRate = ENEMYAMMODROPRATE;
-		ubGrenadeDropRate = ENEMYGRENADEDROPRATE;
-		ubOtherDropRate = ENEMYEQUIPDROPRATE;
+		// enemy army, the drop rates are scaled by the difficulty settings
+		UINT32 const uiDropPercentage = GetDifficultySettings().item_drop_percentage;
+		ubAmmoDropRate = (UINT8)MIN(100U, ENEMYAMMODROPRATE * uiDropPercentage / 100);
+		ubGrenadeDropRate = (UINT8)MIN(100U, ENEMYGRENADEDROPRATE * uiDropPercentage / 100);
+		ubOtherDropRate = (UINT8)MIN(100U, ENEMYEQUIPDROPRATE * uiDropPercentage / 100);
 	}
 
 
@@ -2271,29 +2271,6 @@ void GetKeyboardInput(UIEventKind* const puiNewEvent)
 			UINT16 const mod = InputEvent.usKeyState;
 			UINT32 const key = InputEvent.usParam;
 
-			if (mod == CTRL_DOWN)
-			{
-				if (gubCheatLevel < strlen(getCheatCode()))
-				{
-					if (key == getCheatCode()[gubCheatLevel])
-					{
-						if (++gubCheatLevel == strlen(getCheatCode()))
-						{
-							ScreenMsg(FONT_MCOLOR_LTYELLOW, MSG_INTERFACE, pMessageStrings[MSG_CHEAT_LEVEL_TWO]);
-							AddHistoryToPlayersLog(HISTORY_CHEAT_ENABLED, 0, GetWorldTotalMin(), -1, -1);
-						}
-						continue;
-					}
-				}
-				else if (gubCheatLevel == strlen(getCheatCode()) && key == 'b')
-				{
-					++gubCheatLevel;
-					continue;
-				}
-			}
-
-			if (gubCheatLevel < strlen(getCheatCode())) RESET_CHEAT_LEVEL();
-
 			switch (mod)
 			{
 				case 0:          HandleModNone( key, puiNewEvent); break;
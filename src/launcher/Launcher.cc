@@ -1,3 +1,4 @@
+#include <stdlib.h>
 #include <string>
 #include "FL/Fl_Native_File_Chooser.H"
 #include <FL/Fl_PNG_Image.H>
@@ -50,9 +51,22 @@ void Launcher::show() {
 	customResolutionButton->callback( (Fl_Callback*)enableCustomResolutionSelection, (void*)(this) );
 	playButton->callback( (Fl_Callback*)startGame, (void*)(this) );
 	editorButton->callback( (Fl_Callback*)startEditor, (void*)(this) );
+	moveHomeButton->callback( (Fl_Callback*)moveHomeDirectory, (void*)(this) );
 
 	populateChoices();
 	initializeInputsFromDefaults();
+	updateRunningGame(this);
+
+	std::string extraDataDir = EXTRA_DATA_DIR;
+	if (extraDataDir.empty()) {
+		// use location of the exe file
+		extraDataDir = exePath.substr(0, exePath.find_last_of("/\\") + 1);
+	}
+	char* rustGameDir = get_game_dir(this->engine_options, extraDataDir.c_str());
+	extraDataDir = std::string(rustGameDir);
+	free_rust_string(rustGameDir);
+	watch_mods(this->engine_options, extraDataDir.c_str());
+	updateMods(this);
 
 	const Fl_PNG_Image icon("logo32.png", logo32_png, 1374);
 	stracciatellaLauncher->icon(&icon);
@@ -109,6 +123,11 @@ void Launcher::initializeInputsFromDefaults() {
 }
 
 int Launcher::writeJsonFile() {
+	if (is_game_running(this->engine_options)) {
+		SLOGW(LAUNCHER_TOPIC, "Not writing config file while the game is running");
+		return 1;
+	}
+
 	set_start_in_fullscreen(this->engine_options, fullscreenCheckbox->value());
 	set_start_without_sound(this->engine_options, !playSoundsCheckbox->value());
 
@@ -186,7 +205,9 @@ void Launcher::openDataDirectorySelector(Fl_Widget *btn, void *userdata) {
 		case  1:
 			break; // CANCEL
 		default:
-			window->dataDirectoryInput->value(fnfc.filename());
+			char* path = translate_chosen_path(fnfc.filename());
+			window->dataDirectoryInput->value(path);
+			free_rust_string(path);
 			break; // FILE CHOSEN
 	}
 }
@@ -201,14 +222,46 @@ void Launcher::enableCustomResolutionSelection(Fl_Widget* btn, void* userdata) {
 	window->enableCustomResolutions();
 }
 
+bool Launcher::runPreflightChecks() {
+	preflight_report_t* report = run_preflight_checks(this->engine_options);
+	preflight_status_t status = get_preflight_status(report);
+
+	std::string problems;
+	for (UINT32 i = 0; i < get_number_of_preflight_checks(report); i++) {
+		if (get_preflight_check_status(report, i) != PREFLIGHT_OK) {
+			char* message = get_preflight_check_message(report, i);
+			SLOGW(LAUNCHER_TOPIC, "Preflight check failed: %s", message);
+			problems += std::string(message) + "\n";
+			free_rust_string(message);
+		}
+	}
+	free_preflight_report(report);
+
+	if (status == PREFLIGHT_ERROR) {
+		fl_alert("JA2 Stracciatella can not be started:\n%s", problems.c_str());
+		return false;
+	}
+	if (status == PREFLIGHT_WARNING) {
+		return fl_choice("There might be problems when running JA2 Stracciatella:\n%s", "Cancel", "Start anyway", NULL, problems.c_str()) == 1;
+	}
+	return true;
+}
+
 void Launcher::startExecutable(bool asEditor) {
-	// check minimal resolution:
-	if (customResolutionButton->value() &&
-		(customResolutionXInput->value() < 640 ||
-		customResolutionYInput->value() < 480)) {
-		fl_alert("Invalid custom resolution %dx%d.\nJA2 Stracciatella needs a resolution of at least 640x480.",
-			(int) customResolutionXInput->value(),
-			(int) customResolutionYInput->value());
+	// check the resolution with the rules of the engine:
+	std::string resolution = predefinedResolutionInput->value();
+	if (customResolutionButton->value()) {
+		resolution = std::to_string((int)customResolutionXInput->value()) + RESOLUTION_SEPARATOR + std::to_string((int)customResolutionYInput->value());
+	}
+	UINT16 width, height;
+	if (!parse_and_validate_resolution(resolution.c_str(), &width, &height)) {
+		char* error = get_last_error();
+		fl_alert("%s", error);
+		free_rust_string(error);
+		return;
+	}
+
+	if (!runPreflightChecks()) {
 		return;
 	}
 
@@ -221,9 +274,36 @@ void Launcher::startExecutable(bool asEditor) {
 	system(cmd.c_str());
 }
 
+void Launcher::updateRunningGame(void* userdata) {
+	Launcher* window = static_cast< Launcher* >( userdata );
+
+	if (is_game_running(window->engine_options)) {
+		window->playButton->deactivate();
+		window->editorButton->deactivate();
+		window->moveHomeButton->deactivate();
+	} else {
+		window->playButton->activate();
+		window->editorButton->activate();
+		window->moveHomeButton->activate();
+	}
+	Fl::repeat_timeout(1.0, updateRunningGame, userdata);
+}
+
+void Launcher::updateMods(void* userdata) {
+	// Callbacks of ENGINE_EVENT_MODS_CHANGED are called from here
+	if (mods_changed()) {
+		SLOGI(LAUNCHER_TOPIC, "Mods were installed or removed");
+	}
+	Fl::repeat_timeout(1.0, updateMods, userdata);
+}
+
 void Launcher::startGame(Fl_Widget* btn, void* userdata) {
 	Launcher* window = static_cast< Launcher* >( userdata );
 
+	if (is_game_running(window->engine_options)) {
+		fl_alert("JA2 Stracciatella is already running.");
+		return;
+	}
 	window->writeJsonFile();
 	window->startExecutable(false);
 }
@@ -231,6 +311,44 @@ void Launcher::startGame(Fl_Widget* btn, void* userdata) {
 void Launcher::startEditor(Fl_Widget* btn, void* userdata) {
 	Launcher* window = static_cast< Launcher* >( userdata );
 
+	if (is_game_running(window->engine_options)) {
+		fl_alert("JA2 Stracciatella is already running.");
+		return;
+	}
 	window->writeJsonFile();
 	window->startExecutable(true);
 }
+
+void Launcher::moveHomeDirectory(Fl_Widget* btn, void* userdata) {
+	Launcher* window = static_cast< Launcher* >( userdata );
+
+	if (is_game_running(window->engine_options)) {
+		fl_alert("JA2 Stracciatella is already running.");
+		return;
+	}
+
+	Fl_Native_File_Chooser fnfc;
+	fnfc.title("Select the new home folder for the config, saved games and mods");
+	fnfc.type(Fl_Native_File_Chooser::BROWSE_SAVE_DIRECTORY);
+	if (fnfc.show() != 0) {
+		return;
+	}
+
+	char* newHome = translate_chosen_path(fnfc.filename());
+	char* oldHome = get_stracciatella_home(window->engine_options);
+	if (fl_choice("Move the config, saved games and mods from\n%s\nto\n%s?", "Cancel", "Move", NULL, oldHome, newHome) == 1) {
+		// Save the current settings, so they are moved as well
+		window->writeJsonFile();
+		if (migrate_stracciatella_home(window->engine_options, newHome)) {
+			SLOGI(LAUNCHER_TOPIC, "Moved the home folder from %s to %s", oldHome, newHome);
+			// The old home points to the new one, so the launcher and the game find it on the next start
+			fl_message("The home folder was moved to\n%s", newHome);
+		} else {
+			char* error = get_last_error();
+			fl_alert("%s", error);
+			free_rust_string(error);
+		}
+	}
+	free_rust_string(oldHome);
+	free_rust_string(newHome);
+}
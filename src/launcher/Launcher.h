@@ -21,12 +21,16 @@ private:
 	void populateChoices();
 	void enablePredefinedResolutions();
 	void enableCustomResolutions();
+	bool runPreflightChecks();
 	void startExecutable(bool asEditor);
 	static void openDataDirectorySelector(Fl_Widget *btn, void *userdata);
 	static void enablePredefinedResolutionSelection(Fl_Widget* btn, void* userdata);
 	static void enableCustomResolutionSelection(Fl_Widget* btn, void* userdata);
 	static void startGame(Fl_Widget* btn, void* userdata);
 	static void startEditor(Fl_Widget* btn, void* userdata);
+	static void moveHomeDirectory(Fl_Widget* btn, void* userdata);
+	static void updateRunningGame(void* userdata);
+	static void updateMods(void* userdata);
 };
 
 #endif //JA2_LAUNCHER_H_H
@@ -60,6 +60,8 @@ StracciatellaLauncher::StracciatellaLauncher() {
         } // Fl_Button* playButton
         { editorButton = new Fl_Button(270, 229, 185, 25, "Start Map Editor");
         } // Fl_Button* editorButton
+        { moveHomeButton = new Fl_Button(270, 195, 185, 25, "Move Home Folder...");
+        } // Fl_Button* moveHomeButton
         o->end();
       } // Fl_Group* o
       o->end();
@@ -1,3 +1,4 @@
+#include <cstdlib>
 #include <string>
 #include <FL/Fl.H>
 #include <slog/slog.h>
@@ -9,6 +10,13 @@ int main(int argc, char* argv[]) {
 	SLOG_Init(SLOG_STDERR, "stracciatella-launcher.log");
 	SLOG_SetLevel(SLOG_WARNING, SLOG_WARNING);
 
+	if (!negotiate_ffi_api_version(RUST_FFI_API_VERSION)) {
+		char* rustError = get_last_error();
+		SLOGE(DEBUG_TAG_LAUNCHER, "%s", rustError);
+		free_rust_string(rustError);
+		return EXIT_FAILURE;
+	}
+
 	char* rustExePath = find_ja2_executable(argv[0]);
 	std::string exePath = std::string(rustExePath);
 	free_rust_string(rustExePath);
@@ -16,10 +24,10 @@ int main(int argc, char* argv[]) {
 	engine_options_t* params = create_engine_options(argv, argc);
 
 	if (params == NULL) {
-		return EXIT_FAILURE;
+		return get_options_exit_code();
 	}
 	if (should_show_help(params)) {
-		return EXIT_SUCCESS;
+		return OPTIONS_HELP_SHOWN;
 	}
 
 	Launcher launcher(exePath, params);
@@ -8,6 +8,8 @@
 #include "Video.h"
 #include "Local.h"
 #include "UILayout.h"
+#include "RustInterface.h"
+#include "slog/slog.h"
 
 
 // The gfKeyState table is used to track which of the keys is up or down at any one time. This is used while polling
@@ -38,6 +40,14 @@ static UINT16    gusQueueCount;
 static UINT16    gusHeadIndex;
 static UINT16    gusTailIndex;
 
+// The replay structures are used to record the dequeued events or to play them instead of the queued ones
+
+static BOOLEAN        gfRecordingReplay = FALSE;
+static BOOLEAN        gfPlayingReplay   = FALSE;
+static UINT32         guiReplayStartTime;
+static replay_event_t gPendingReplayEvent;
+static BOOLEAN        gfReplayEventPending = FALSE;
+
 
 static void QueueMouseEvent(UINT16 ubInputEvent)
 {
@@ -71,7 +81,8 @@ static void QueueKeyEvent(UINT16 ubInputEvent, SDL_Keycode Key, SDL_Keymod Mod,
 	gusTailIndex = (gusTailIndex + 1) % lengthof(gEventQueue);
 }
 
-void SetSafeMousePosition(int x, int y) {
+static void ClampMousePosition(int x, int y)
+{
 	if (x < 0) x = 0;
 	if (y < 0) y = 0;
 	if (x > SCREEN_WIDTH) x = SCREEN_WIDTH;
@@ -81,9 +92,21 @@ void SetSafeMousePosition(int x, int y) {
 	gusMouseYPos = y;
 }
 
+void SetSafeMousePosition(int x, int y) {
+	// The mouse of the player is ignored while a replay is played
+	if (gfPlayingReplay) return;
+	ClampMousePosition(x, y);
+}
+
 
 BOOLEAN DequeueSpecificEvent(InputAtom* Event, UINT32 uiMaskFlags)
 {
+	if (gfPlayingReplay)
+	{
+		// Only the next event of the replay can be dequeued
+		return gfReplayEventPending && gPendingReplayEvent.event & uiMaskFlags && DequeueEvent(Event);
+	}
+
 	// Is there an event to dequeue
 	if (gusQueueCount > 0)
 	{
@@ -101,15 +124,91 @@ BOOLEAN DequeueSpecificEvent(InputAtom* Event, UINT32 uiMaskFlags)
 static void HandleSingleClicksAndButtonRepeats(void);
 
 
+void StartReplayRecording(void)
+{
+	gfRecordingReplay  = TRUE;
+	guiReplayStartTime = GetClock();
+}
+
+
+void StopReplayRecording(void)
+{
+	if (!gfRecordingReplay) return;
+	gfRecordingReplay = FALSE;
+	stop_replay_recording();
+}
+
+
+void StartReplayPlayback(void)
+{
+	gfPlayingReplay      = TRUE;
+	gfReplayEventPending = FALSE;
+	guiReplayStartTime   = GetClock();
+}
+
+
+static void RecordReplayEvent(const InputAtom* Event)
+{
+	replay_event_t e;
+	e.time      = GetClock() - guiReplayStartTime;
+	e.key_state = Event->usKeyState;
+	e.event     = Event->usEvent;
+	// Mouse events have no parameter, the position of the mouse is recorded instead
+	e.param     = Event->usEvent & MOUSE_EVENTS ? gusMouseXPos | gusMouseYPos << 16 : Event->usParam;
+	e.character = Event->Char;
+	if (!record_replay_event(&e))
+	{
+		SLOGE(DEBUG_TAG_SGP, "Failed to record the input event, the replay is stopped");
+		StopReplayRecording();
+	}
+}
+
+
+static BOOLEAN DequeueReplayEvent(InputAtom* Event)
+{
+	// The input of the player is ignored while the replay is played
+	gusHeadIndex  = gusTailIndex;
+	gusQueueCount = 0;
+
+	if (!gfReplayEventPending)
+	{
+		if (!read_replay_event(&gPendingReplayEvent))
+		{
+			SLOGI(DEBUG_TAG_SGP, "The replay has ended");
+			gfPlayingReplay = FALSE;
+			return FALSE;
+		}
+		gfReplayEventPending = TRUE;
+	}
+	if (GetClock() - guiReplayStartTime < gPendingReplayEvent.time) return FALSE;
+
+	gfReplayEventPending = FALSE;
+	Event->usKeyState = gPendingReplayEvent.key_state;
+	Event->usEvent    = gPendingReplayEvent.event;
+	Event->usParam    = gPendingReplayEvent.param;
+	Event->Char       = gPendingReplayEvent.character;
+	if (Event->usEvent & MOUSE_EVENTS)
+	{
+		ClampMousePosition(gPendingReplayEvent.param & 0xFFFF, gPendingReplayEvent.param >> 16);
+		Event->usParam = 0;
+	}
+	return TRUE;
+}
+
+
 BOOLEAN DequeueEvent(InputAtom* Event)
 {
 	HandleSingleClicksAndButtonRepeats();
 
+	if (gfPlayingReplay) return DequeueReplayEvent(Event);
+
 	if (gusQueueCount == 0) return FALSE;
 
 	*Event = gEventQueue[gusHeadIndex];
 	gusHeadIndex = (gusHeadIndex + 1) % lengthof(gEventQueue);
 	gusQueueCount--;
+
+	if (gfRecordingReplay) RecordReplayEvent(Event);
 	return TRUE;
 }
 
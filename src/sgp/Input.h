@@ -44,6 +44,14 @@ struct InputAtom
 
 extern BOOLEAN			DequeueEvent(InputAtom *Event);
 
+/* Records the dequeued input events to the replay started with
+ * start_replay_recording() of the library. */
+void StartReplayRecording(void);
+void StopReplayRecording(void);
+/* Plays the input events of the replay opened with open_replay() of the
+ * library instead of the input of the player, until the replay ends. */
+void StartReplayPlayback(void);
+
 void MouseButtonDown(const SDL_MouseButtonEvent*);
 void MouseButtonUp(const SDL_MouseButtonEvent*);
 void MouseWheelScroll(const SDL_MouseWheelEvent*);
@@ -5,11 +5,11 @@
 UINT32 guiPreRandomIndex = 0;
 UINT32 guiPreRandomNums[ MAX_PREGENERATED_NUMS ];
 
-void InitializeRandom(void)
+void InitializeRandom(UINT32 seed)
 {
-	// Seed the random-number generator with current time so that
-	// the numbers will be different every time we run.
-	srand( (unsigned) time(NULL) );
+	// Seed the random-number generator, the seed is the current time
+	// unless a fixed seed is used to reproduce a game.
+	srand( (unsigned) seed );
 	//Pregenerate all of the random numbers.
 	for( guiPreRandomIndex = 0; guiPreRandomIndex < MAX_PREGENERATED_NUMS; guiPreRandomIndex++ )
 	{
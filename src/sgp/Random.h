@@ -4,7 +4,7 @@
 #include "Types.h"
 
 
-extern void InitializeRandom(void);
+extern void InitializeRandom(UINT32 seed);
 extern UINT32 Random( UINT32 uiRange );
 
 //Chance( 74 ) returns TRUE 74% of the time.  If uiChance >= 100, then it will always return TRUE.
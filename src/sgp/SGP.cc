@@ -11,6 +11,7 @@
 
 #include <exception>
 #include <new>
+#include <time.h>
 
 #include "Button_System.h"
 #include "Cheats.h"
@@ -18,6 +19,7 @@
 #include "FileMan.h"
 #include "Font.h"
 #include "GameLoop.h"
+#include "GameVersion.h"
 #include "Init.h" // XXX should not be used in SGP
 #include "Input.h"
 #include "Intro.h"
@@ -27,6 +29,7 @@
 #include "SGP.h"
 #include "SaveLoadGame.h" // XXX should not be used in SGP
 #include "SoundMan.h"
+#include "Strategic_AI.h" // XXX should not be used in SGP
 #include "VObject.h"
 #include "Video.h"
 #include "VSurface.h"
@@ -147,6 +150,8 @@ static void deinitGameAndExit()
 
 	SoundServiceStreams();
 
+	StopReplayRecording();
+
 	if (gfGameInitialized)
 	{
 		ShutdownGame();
@@ -177,6 +182,8 @@ static void deinitGameAndExit()
 	SLOGD(DEBUG_TAG_SGP, "Shutting Down SDL");
 	SDL_Quit();
 
+	unlock_game();
+
 	exit(0);
 }
 
@@ -190,7 +197,7 @@ void requestGameExit()
 	SDL_PushEvent(&event);
 }
 
-static void MainLoop(int msPerGameCycle)
+static void MainLoop(int msPerGameCycle, bool pauseOnFocusLoss)
 {
 	BOOLEAN s_doGameCycles = TRUE;
 
@@ -211,6 +218,17 @@ static void MainLoop(int msPerGameCycle)
 					s_doGameCycles = true;
 					break;
 
+				case SDL_WINDOWEVENT:
+					if (event.window.event == SDL_WINDOWEVENT_FOCUS_LOST && pauseOnFocusLoss)
+					{
+						s_doGameCycles = false;
+					}
+					else if (event.window.event == SDL_WINDOWEVENT_FOCUS_GAINED)
+					{
+						s_doGameCycles = true;
+					}
+					break;
+
 				case SDL_KEYDOWN: KeyDown(&event.key.keysym); break;
 				case SDL_KEYUP:   KeyUp(  &event.key.keysym); break;
 				case SDL_TEXTINPUT: TextInput(&event.text); break;
@@ -262,10 +280,112 @@ ContentManager *GCM = NULL;
 
 ////////////////////////////////////////////////////////////
 
+/* Passes the messages of SLOG to the logger of the library, which applies
+ * log_format and log_filters of the engine options. */
+static void LogToRust(SLOGLevel level, const char* tag, const char* message)
+{
+	log_level_t rustLevel;
+	switch (level)
+	{
+		case SLOG_ERROR:   rustLevel = LOG_ERROR;   break;
+		case SLOG_WARNING: rustLevel = LOG_WARNING; break;
+		case SLOG_INFO:    rustLevel = LOG_INFO;    break;
+		default:           rustLevel = LOG_DEBUG;   break;
+	}
+	log_message(rustLevel, tag, message);
+}
+
+/* Gives the tracks of the selected music pack to the content manager. The
+ * tracks are resolved in the virtual file system, where the pack is mounted. */
+static void LoadMusicPack(DefaultContentManager* cm, const engine_options_t* params, const vfs_t* vfs)
+{
+	static const struct { MusicMode mode; const char* name; } musicModes[] =
+	{
+		{ MUSIC_MAIN_MENU,                      "main_menu" },
+		{ MUSIC_LAPTOP,                         "laptop" },
+		{ MUSIC_TACTICAL_NOTHING,               "tactical" },
+		{ MUSIC_TACTICAL_ENEMYPRESENT,          "tactical_enemypresent" },
+		{ MUSIC_TACTICAL_BATTLE,                "tactical_battle" },
+		{ MUSIC_TACTICAL_CREATURE_NOTHING,      "tactical_creature" },
+		{ MUSIC_TACTICAL_CREATURE_ENEMYPRESENT, "tactical_creature_enemypresent" },
+		{ MUSIC_TACTICAL_CREATURE_BATTLE,       "tactical_creature_battle" },
+		{ MUSIC_TACTICAL_VICTORY,               "tactical_victory" },
+		{ MUSIC_TACTICAL_DEFEAT,                "tactical_defeat" }
+	};
+
+	for (size_t i = 0; i < lengthof(musicModes); i++)
+	{
+		std::vector<std::string> tracks;
+		for (UINT32 j = 0; j < get_number_of_music_pack_tracks(params, musicModes[i].name); j++)
+		{
+			char* rustTrack = get_music_pack_track(params, musicModes[i].name, j);
+			char* rustPath = vfs_resolve(vfs, rustTrack);
+			if (rustPath != NULL)
+			{
+				tracks.push_back(std::string(rustPath));
+				free_rust_string(rustPath);
+			}
+			else
+			{
+				SLOGW(DEBUG_TAG_SGP, "Music pack track %s does not exist", rustTrack);
+			}
+			free_rust_string(rustTrack);
+		}
+		cm->setMusicPackTracks(musicModes[i].mode, tracks);
+	}
+}
+
+/* Returns the name of the configured audio device among the output devices of
+ * SDL, or an empty string for the default device of the OS. */
+static std::string FindAudioDevice(const engine_options_t* params)
+{
+	char* rustAudioDevice = get_audio_device(params);
+	std::string const audioDevice(rustAudioDevice);
+	free_rust_string(rustAudioDevice);
+	if (audioDevice.empty()) return std::string();
+
+	// The devices can only be enumerated while the audio subsystem is initialized
+	if (SDL_InitSubSystem(SDL_INIT_AUDIO) != 0)
+	{
+		SLOGW(DEBUG_TAG_SGP, "Failed to enumerate the audio devices: %s", SDL_GetError());
+		return std::string();
+	}
+	std::vector<std::string> deviceNames;
+	for (int i = 0; i < SDL_GetNumAudioDevices(0); i++)
+	{
+		const char* name = SDL_GetAudioDeviceName(i, 0);
+		deviceNames.push_back(name != NULL ? std::string(name) : std::string());
+	}
+	SDL_QuitSubSystem(SDL_INIT_AUDIO);
+
+	std::vector<const char*> deviceNamePtrs;
+	for (size_t i = 0; i < deviceNames.size(); i++)
+	{
+		deviceNamePtrs.push_back(deviceNames[i].c_str());
+	}
+	INT32 index = find_audio_device_index(params, deviceNamePtrs.data(), deviceNamePtrs.size());
+	if (index < 0)
+	{
+		SLOGW(DEBUG_TAG_SGP, "Audio device %s not found, using the default device", audioDevice.c_str());
+		return std::string();
+	}
+	SLOGI(DEBUG_TAG_SGP, "Audio device: %s", deviceNames[index].c_str());
+	return deviceNames[index];
+}
+
+////////////////////////////////////////////////////////////
+
 int main(int argc, char* argv[])
 {
 	std::string exeFolder = FileMan::getParentPath(argv[0], true);
 
+	std::string extraDataDir = EXTRA_DATA_DIR;
+	if(extraDataDir.empty())
+	{
+		// use location of the exe file
+		extraDataDir = exeFolder;
+	}
+
 #if defined BROKEN_SWPRINTF
 	if (setlocale(LC_CTYPE, "UTF-8") == NULL)
 	{
@@ -277,13 +397,45 @@ int main(int argc, char* argv[])
 	SLOG_Init(SLOG_STDERR, "ja2.log");
 	SLOG_SetLevel(SLOG_WARNING, SLOG_WARNING);
 
+	// The layout of the shared types is only known to match if the library is from the same build
+	if (!negotiate_ffi_api_version(RUST_FFI_API_VERSION)) {
+		char* rustError = get_last_error();
+		SLOGE(DEBUG_TAG_SGP, "%s", rustError);
+		SDL_ShowSimpleMessageBox(SDL_MESSAGEBOX_ERROR, "JA2 Stracciatella", rustError, NULL);
+		free_rust_string(rustError);
+		return EXIT_FAILURE;
+	}
+
 	engine_options_t* params = create_engine_options(argv, argc);
 	if (params == NULL) {
-		return EXIT_FAILURE;
+		return get_options_exit_code();
 	}
 
 	if (should_show_help(params)) {
-		return EXIT_SUCCESS;
+		return OPTIONS_HELP_SHOWN;
+	}
+
+	if (init_logger(params, "ja2.log")) {
+		SLOG_SetHandler(LogToRust);
+	} else {
+		SLOGW(DEBUG_TAG_SGP, "Could not initialize the logger, log_format and log_filters are ignored");
+	}
+
+	// game_dir of ja2.json or the command line replaces the folder of the installation
+	char* rustGameDir = get_game_dir(params, extraDataDir.c_str());
+	extraDataDir = std::string(rustGameDir);
+	free_rust_string(rustGameDir);
+
+	if (!lock_game(params)) {
+		char* rustRunningGameMessage = get_running_game_message(params);
+		std::string runningGameMessage = rustRunningGameMessage != NULL ? std::string(rustRunningGameMessage) : "Could not write the lock file";
+		free_rust_string(rustRunningGameMessage);
+		if (should_enforce_single_instance(params)) {
+			SLOGE(DEBUG_TAG_SGP, "%s", runningGameMessage.c_str());
+			SDL_ShowSimpleMessageBox(SDL_MESSAGEBOX_ERROR, "JA2 Stracciatella", runningGameMessage.c_str(), NULL);
+			return EXIT_FAILURE;
+		}
+		SLOGW(DEBUG_TAG_SGP, "%s", runningGameMessage.c_str());
 	}
 
 	if (should_start_in_fullscreen(params)) {
@@ -291,11 +443,25 @@ int main(int argc, char* argv[])
 	} else if (should_start_in_window(params)) {
 		VideoSetFullScreen(FALSE);
 	}
+	VideoSetRefreshRate(get_refresh_rate(params));
+	VideoSetWindowPosition(get_display_index(params), has_window_position(params), get_window_x(params), get_window_y(params));
+	VideoSetAlwaysOnTop(should_keep_window_on_top(params));
+	VideoSetMouseGrab(should_grab_mouse(params));
 
 	if (should_start_without_sound(params)) {
 		SoundEnableSound(FALSE);
 	}
 
+	// SDL picks up the audio driver from the environment when the sound system is initialized
+	char* rustAudioDriver = get_audio_driver(params);
+	if (rustAudioDriver[0] != '\0') {
+		SDL_setenv("SDL_AUDIODRIVER", rustAudioDriver, 1);
+	}
+	free_rust_string(rustAudioDriver);
+
+	SoundSetAudioDevice(FindAudioDevice(params).c_str());
+	SoundSetSampleRate(get_audio_sample_rate(params));
+
 	// Disable sound when using SDL2 2.0.6:
 	SDL_version sdl_version_linked;
 	SDL_GetVersion(&sdl_version_linked);
@@ -311,6 +477,10 @@ int main(int argc, char* argv[])
 		GameState::getInstance()->setDebugging(true);
 	}
 
+	if (should_enable_cheats(params)) {
+		GameState::getInstance()->setCheatsEnabled(true);
+	}
+
 	if (should_run_editor(params)) {
 		GameState::getInstance()->setEditorMode(false);
 	}
@@ -325,7 +495,13 @@ int main(int argc, char* argv[])
 	if (should_run_unittests(params)) {
 #ifdef WITH_UNITTESTS
 		testing::InitGoogleTest(&argc, argv);
-		return RUN_ALL_TESTS();
+		int result = RUN_ALL_TESTS();
+		// The library tests are merged into the report that googletest has just written
+		if (!run_library_unittests(params, extraDataDir.c_str()))
+		{
+			result = EXIT_FAILURE;
+		}
+		return result;
 #else
 		SLOGW(DEBUG_TAG_SGP, "This executable does not include unit tests.");
 #endif
@@ -333,9 +509,17 @@ int main(int argc, char* argv[])
 
 	GameVersion version = get_resource_version(params);
 	setGameVersion(version);
+	setGameTextLanguage(get_game_text_language(params));
 
 	VideoScaleQuality scalingQuality = get_scaling_quality(params);
 
+	bool runSmoketest = should_run_smoketest(params);
+	bool pauseOnFocusLoss = should_pause_on_focus_loss(params);
+
+	// The seed of the command line has 64 bits, both halves are folded into the 32-bit seed of the engine
+	uint64_t const seed = get_random_seed(params);
+	UINT32 randomSeed = has_random_seed(params) ? (UINT32)(seed ^ (seed >> 32)) : (UINT32)time(NULL);
+
 	////////////////////////////////////////////////////////////
 
 	SDL_Init(SDL_INIT_VIDEO);
@@ -357,21 +541,23 @@ int main(int argc, char* argv[])
 
 	SLOGD(DEBUG_TAG_SGP, "Initializing Game Resources");
 	char* rustConfigFolderPath = get_stracciatella_home(params);
+	char* rustSavesFolderPath = get_save_dir(params);
 	char* rustResRootPath = get_vanilla_data_dir(params);
 	std::string configFolderPath = std::string(rustConfigFolderPath);
+	std::string savesFolderPath = std::string(rustSavesFolderPath);
 	std::string gameResRootPath = std::string(rustResRootPath);
 	free_rust_string(rustConfigFolderPath);
+	free_rust_string(rustSavesFolderPath);
 	free_rust_string(rustResRootPath);
 
-	std::string extraDataDir = EXTRA_DATA_DIR;
-	if(extraDataDir.empty())
-	{
-		// use location of the exe file
-		extraDataDir = exeFolder;
-	}
-
 	std::string externalizedDataPath = FileMan::joinPaths(extraDataDir, "externalized");
 
+	if (!create_user_dirs(params)) {
+		char* rustError = get_last_error();
+		SLOGE(DEBUG_TAG_SGP, "%s", rustError);
+		free_rust_string(rustError);
+	}
+
 	FileMan::switchTmpFolder(configFolderPath);
 
 	DefaultContentManager *cm;
@@ -381,9 +567,11 @@ int main(int argc, char* argv[])
 		char* rustModName = get_mod(params, 0);
 		std::string modName = std::string(rustModName);
 		free_rust_string(rustModName);
-		std::string modResFolder = FileMan::joinPaths(FileMan::joinPaths(FileMan::joinPaths(extraDataDir, "mods"), modName), "data");
+		char* rustModResFolder = get_mod_data_dir(params, extraDataDir.c_str(), 0);
+		std::string modResFolder = std::string(rustModResFolder);
+		free_rust_string(rustModResFolder);
 		cm = new ModPackContentManager(version,
-						modName, modResFolder, configFolderPath,
+						modName, modResFolder, configFolderPath, savesFolderPath,
 						gameResRootPath, externalizedDataPath);
 		SLOGI(DEBUG_TAG_SGP,"------------------------------------------------------------------------------");
 		SLOGI(DEBUG_TAG_SGP,"JA2 Home Dir:                  '%s'", configFolderPath.c_str());
@@ -400,7 +588,7 @@ int main(int argc, char* argv[])
 	else
 	{
 		cm = new DefaultContentManager(version,
-						configFolderPath,
+						configFolderPath, savesFolderPath,
 						gameResRootPath, externalizedDataPath);
 		SLOGI(DEBUG_TAG_SGP,"------------------------------------------------------------------------------");
 		SLOGI(DEBUG_TAG_SGP,"JA2 Home Dir:                  '%s'", configFolderPath.c_str());
@@ -412,6 +600,79 @@ int main(int argc, char* argv[])
 		SLOGI(DEBUG_TAG_SGP,"------------------------------------------------------------------------------");
 	}
 
+	std::vector<std::string> extraDataDirs;
+	for (UINT32 i = 0; i < get_number_of_extra_data_dirs(params); i++)
+	{
+		char* rustExtraDataDir = get_extra_data_dir(params, i);
+		extraDataDirs.push_back(std::string(rustExtraDataDir));
+		free_rust_string(rustExtraDataDir);
+	}
+	cm->setExtraDataDirs(extraDataDirs);
+	// Mount order from the lowest to the highest precedence, mods take precedence over all of them
+	for (size_t i = 0; i < extraDataDirs.size(); i++)
+	{
+		SLOGI(DEBUG_TAG_SGP,"Extra data dir %d:              '%s'", (int)i, extraDataDirs[i].c_str());
+	}
+
+	vfs_t* vfs = create_vfs(params, extraDataDir.c_str());
+	LoadMusicPack(cm, params, vfs);
+	free_vfs(vfs);
+
+	if (!load_translations(params, extraDataDir.c_str()))
+	{
+		SLOGW(DEBUG_TAG_SGP, "Failed to load the translations");
+	}
+
+	if (!LoadDifficultySettings(params, extraDataDir.c_str()))
+	{
+		char* rustError = get_last_error();
+		SLOGE(DEBUG_TAG_SGP, "Failed to load the difficulty settings: %s", rustError);
+		free_rust_string(rustError);
+		return EXIT_FAILURE;
+	}
+
+	preflight_report_t* startupReport = run_startup_checks(params, extraDataDir.c_str());
+	for (UINT32 i = 0; i < get_number_of_preflight_checks(startupReport); i++)
+	{
+		if (get_preflight_check_status(startupReport, i) != PREFLIGHT_OK)
+		{
+			char* name = get_preflight_check_name(startupReport, i);
+			char* message = get_preflight_check_message(startupReport, i);
+			SLOGW(DEBUG_TAG_SGP, "Startup check %s failed: %s", name, message);
+			free_rust_string(message);
+			free_rust_string(name);
+		}
+	}
+	free_preflight_report(startupReport);
+
+	// A replay is played with the seed it was recorded with
+	char* rustReplayFile = get_replay_file(params);
+	bool const playReplay = rustReplayFile != NULL;
+	if (playReplay)
+	{
+		bool const opened = open_replay(rustReplayFile) && get_replay_seed(&randomSeed);
+		free_rust_string(rustReplayFile);
+		if (!opened)
+		{
+			char* rustError = get_last_error();
+			SLOGE(DEBUG_TAG_SGP, "Failed to open the replay: %s", rustError);
+			free_rust_string(rustError);
+			return EXIT_FAILURE;
+		}
+	}
+
+	bool recordReplay = false;
+	if (should_record_replay(params))
+	{
+		recordReplay = start_replay_recording(params, g_version_label, randomSeed);
+		if (!recordReplay)
+		{
+			char* rustError = get_last_error();
+			SLOGE(DEBUG_TAG_SGP, "Failed to start recording the replay: %s", rustError);
+			free_rust_string(rustError);
+		}
+	}
+
 		free_engine_options(params);
 
 	std::vector<std::string> libraries = cm->getListOfGameResources();
@@ -426,6 +687,20 @@ int main(int argc, char* argv[])
 
 		GCM = cm;
 
+		if (runSmoketest)
+		{
+			char saveFile[512];
+			CreateSavedGameFileNameFromNumber(SAVE__SMOKETEST_NUM, saveFile);
+			if (!install_smoketest_save(extraDataDir.c_str(), saveFile))
+			{
+				char* rustError = get_last_error();
+				SLOGE(DEBUG_TAG_SGP, "Failed to install the smoke test save: %s", rustError);
+				free_rust_string(rustError);
+				return EXIT_FAILURE;
+			}
+			GameState::getInstance()->setRunningSmoketest(true);
+		}
+
 		SLOGD(DEBUG_TAG_SGP, "Initializing Video Manager");
 		InitializeVideoManager(scalingQuality);
 
@@ -449,7 +724,8 @@ int main(int argc, char* argv[])
 
 		SLOGD(DEBUG_TAG_SGP, "Initializing Random");
 		// Initialize random number generator
-		InitializeRandom(); // no Shutdown
+		SLOGI(DEBUG_TAG_SGP, "Random seed: %u", randomSeed);
+		InitializeRandom(randomSeed); // no Shutdown
 
 		SLOGD(DEBUG_TAG_SGP, "Initializing Game Manager");
 		// Initialize the Game
@@ -457,6 +733,9 @@ int main(int argc, char* argv[])
 
 		gfGameInitialized = TRUE;
 
+		if (playReplay) StartReplayPlayback();
+		if (recordReplay) StartReplayRecording();
+
 		////////////////////////////////////////////////////////////
 
 		// some data convertion
@@ -482,7 +761,7 @@ int main(int argc, char* argv[])
 		/* At this point the SGP is set up, which means all I/O, Memory, tools, etc.
 		 * are available. All we need to do is attend to the gaming mechanics
 		 * themselves */
-		MainLoop(gamepolicy(ms_per_game_cycle));
+		MainLoop(gamepolicy(ms_per_game_cycle), pauseOnFocusLoss);
 	}
 
 	SLOG_Deinit();
@@ -15,9 +15,11 @@
 #include <SDL.h>
 #include <assert.h>
 #include <stdexcept>
+#include <string>
 
 #include "ContentManager.h"
 #include "GameInstance.h"
+#include "RustInterface.h"
 #include "slog/slog.h"
 
 
@@ -68,7 +70,7 @@ enum
 // Holds the regular sample data, as well as the data for the random samples
 struct SAMPLETAG
 {
-	CHAR8   pName[128];  // Path to sample data
+	CHAR8   pName[512];  // Path to sample data
 	UINT32  n_samples;
 	UINT32  uiFlags;     // Status flags
 	PTR     pData;       // pointer to sample data memory
@@ -117,6 +119,10 @@ static void DecreaseSoundMemoryUsedBySample(SAMPLETAG *sample) { guiSoundMemoryU
 static BOOLEAN fSoundSystemInit = FALSE; // Startup called
 static BOOLEAN gfEnableStartup  = TRUE;  // Allow hardware to start up
 
+static std::string       gAudioDeviceName;           // Empty for the default device of the OS
+static int               giAudioSampleRate = 44100;
+static SDL_AudioDeviceID gAudioDevice      = 0;
+
 SDL_AudioSpec gTargetAudioSpec;
 
 // Sample cache list for files loaded
@@ -131,6 +137,18 @@ void SoundEnableSound(BOOLEAN fEnable)
 }
 
 
+void SoundSetAudioDevice(const char* device_name)
+{
+	gAudioDeviceName = device_name;
+}
+
+
+void SoundSetSampleRate(UINT32 sample_rate)
+{
+	giAudioSampleRate = sample_rate;
+}
+
+
 static void    SoundInitCache(void);
 static BOOLEAN SoundInitHardware(void);
 
@@ -330,7 +348,7 @@ void SoundStopAll(void)
 {
 	if (!fSoundSystemInit) return;
 
-	SDL_PauseAudio(1);
+	SDL_PauseAudioDevice(gAudioDevice, 1);
 	FOR_EACH(SOUNDTAG, i, pSoundList)
 	{
 		if (SoundStopChannel(i))
@@ -342,7 +360,7 @@ void SoundStopAll(void)
 			i->State                 = CHANNEL_FREE;
 		}
 	}
-	SDL_PauseAudio(0);
+	SDL_PauseAudioDevice(gAudioDevice, 0);
 }
 
 
@@ -540,6 +558,38 @@ static size_t GetSampleSize(const SAMPLETAG* const s)
 	return 2u * (s->uiFlags & SAMPLE_STEREO ? 2 : 1);
 }
 
+static bool IsOggFile(const char* pFilename)
+{
+	size_t length = strlen(pFilename);
+	return length > 4 && strcasecmp(pFilename + length - 4, ".ogg") == 0;
+}
+
+/* Decodes an ogg vorbis file into 16 bit samples, which have to be freed with
+ * free_decoded_audio. */
+static bool LoadOggFile(SGPFile* hFile, SDL_AudioSpec* spec, Uint8** samples, size_t* samplesLength)
+{
+	UINT32 size = FileGetSize(hFile);
+	SGP::Buffer<UINT8> data(size);
+	FileRead(hFile, data, size);
+
+	UINT16 channels;
+	UINT32 sampleRate;
+	INT16* decoded = decode_ogg_vorbis(data, size, &channels, &sampleRate, samplesLength);
+	if (decoded == NULL) {
+		char* error = get_last_error();
+		SLOGE(DEBUG_TAG_SOUND, "Error loading sound file: %s", error);
+		free_rust_string(error);
+		return false;
+	}
+
+	SDL_zerop(spec);
+	spec->format   = AUDIO_S16SYS;
+	spec->channels = (Uint8)channels;
+	spec->freq     = (int)sampleRate;
+	*samples = (Uint8*)decoded;
+	return true;
+}
+
 /* Loads a sound file from disk into the cache, allocating memory and a slot
  * for storage.
  *
@@ -553,6 +603,10 @@ static SAMPLETAG* SoundLoadDisk(const char* pFilename)
 		SLOGE(DEBUG_TAG_ASSERTS, "SoundLoadDisk Error: pFilename is an empty string.");
 		return NULL;
 	}
+	if (strlen(pFilename) >= lengthof(((SAMPLETAG*)NULL)->pName)) {
+		SLOGE(DEBUG_TAG_SOUND, "SoundLoadDisk Error: path %s is too long", pFilename);
+		return NULL;
+	}
 
 	AutoSGPFile hFile;
 
@@ -598,23 +652,38 @@ static SAMPLETAG* SoundLoadDisk(const char* pFilename)
 
 	memset(s, 0, sizeof(*s));
 
-	SDL_RWops* rwOps = FileGetRWOps(hFile);
 	SDL_AudioSpec wavSpec;
 	Uint32 wavLength;
 	Uint8 *wavBuffer;
 	SDL_AudioCVT cvt;
 
-	if (SDL_LoadWAV_RW(rwOps, 0,  &wavSpec, &wavBuffer, &wavLength) == NULL) {
-		SLOGE(DEBUG_TAG_SOUND, "Error loading sound file: %s", SDL_GetError());
-		return NULL;
+	// Tracks of music packs are ogg vorbis files, which are decoded by the library
+	bool isOgg = IsOggFile(pFilename);
+	size_t oggSamplesLength = 0;
+	SDL_RWops* rwOps = NULL;
+	if (isOgg) {
+		if (!LoadOggFile(hFile, &wavSpec, &wavBuffer, &oggSamplesLength)) {
+			return NULL;
+		}
+		wavLength = (Uint32)(oggSamplesLength * sizeof(INT16));
+	} else {
+		rwOps = FileGetRWOps(hFile);
+		if (SDL_LoadWAV_RW(rwOps, 0,  &wavSpec, &wavBuffer, &wavLength) == NULL) {
+			SLOGE(DEBUG_TAG_SOUND, "Error loading sound file: %s", SDL_GetError());
+			return NULL;
+		}
 	}
 
 	SDL_BuildAudioCVT(&cvt, wavSpec.format, wavSpec.channels, wavSpec.freq, gTargetAudioSpec.format, wavSpec.channels, gTargetAudioSpec.freq);
 	cvt.len = wavLength;
 	cvt.buf = MALLOCN(UINT8, cvt.len * cvt.len_mult);
 	memcpy(cvt.buf, wavBuffer, wavLength);
-	SDL_FreeWAV(wavBuffer);
-	SDL_FreeRW(rwOps);
+	if (isOgg) {
+		free_decoded_audio((INT16*)wavBuffer, oggSamplesLength);
+	} else {
+		SDL_FreeWAV(wavBuffer);
+		SDL_FreeRW(rwOps);
+	}
 
 	if (cvt.needed) {
 		if (SDL_ConvertAudio(&cvt) != 0) {
@@ -798,23 +867,34 @@ static BOOLEAN SoundInitHardware(void)
 {
 	SDL_InitSubSystem(SDL_INIT_AUDIO);
 
-	gTargetAudioSpec.freq     = 44100;
+	gTargetAudioSpec.freq     = giAudioSampleRate;
 	gTargetAudioSpec.format   = AUDIO_S16SYS;
 	gTargetAudioSpec.channels = 2;
 	gTargetAudioSpec.samples  = 1024;
 	gTargetAudioSpec.callback = SoundCallback;
 	gTargetAudioSpec.userdata = NULL;
 
-	if (SDL_OpenAudio(&gTargetAudioSpec, NULL) != 0) return FALSE;
+	const char* const device = gAudioDeviceName.empty() ? NULL : gAudioDeviceName.c_str();
+	gAudioDevice = SDL_OpenAudioDevice(device, 0, &gTargetAudioSpec, NULL, 0);
+	if (gAudioDevice == 0)
+	{
+		SLOGE(DEBUG_TAG_SOUND, "Failed to open the audio device: %s", SDL_GetError());
+		return FALSE;
+	}
 
 	memset(pSoundList, 0, sizeof(pSoundList));
-	SDL_PauseAudio(0);
+	SDL_PauseAudioDevice(gAudioDevice, 0);
 	return TRUE;
 }
 
 
 static void SoundShutdownHardware(void)
 {
+	if (gAudioDevice != 0)
+	{
+		SDL_CloseAudioDevice(gAudioDevice);
+		gAudioDevice = 0;
+	}
 	SDL_QuitSubSystem(SDL_INIT_AUDIO);
 }
 
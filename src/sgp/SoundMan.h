@@ -107,4 +107,10 @@ UINT32 SoundGetPosition(UINT32 uiSoundID);
 // Allows or disallows the startup of the sound hardware.
 void SoundEnableSound(BOOLEAN fEnable);
 
+/* Sets the audio device that is opened when the sound manager is initialized,
+ * an empty name selects the default device of the OS. */
+void SoundSetAudioDevice(const char* device_name);
+// Sets the sample rate of the audio output in Hz.
+void SoundSetSampleRate(UINT32 sample_rate);
+
 #endif
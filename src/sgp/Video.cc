@@ -92,6 +92,11 @@ static SDL_Surface* ScreenBuffer;
 static SDL_Texture* ScreenTexture;
 static SDL_Texture* ScaledScreenTexture;
 static Uint32       g_window_flags = 0;
+static UINT16       g_refresh_rate = 0;
+static UINT16       g_display_index = 0;
+static BOOLEAN      g_has_window_position = FALSE;
+static INT32        g_window_x = 0;
+static INT32        g_window_y = 0;
 static VideoScaleQuality ScaleQuality = VIDEO_SCALE_QUALITY_LINEAR;
 
 static void RecreateBackBuffer();
@@ -109,6 +114,76 @@ void VideoSetFullScreen(const BOOLEAN enable)
 	}
 }
 
+void VideoSetAlwaysOnTop(const BOOLEAN enable)
+{
+#if SDL_VERSION_ATLEAST(2,0,5)
+	if (enable)
+	{
+		g_window_flags |= SDL_WINDOW_ALWAYS_ON_TOP;
+	}
+	else
+	{
+		g_window_flags &= ~SDL_WINDOW_ALWAYS_ON_TOP;
+	}
+#else
+	if (enable)
+	{
+		SLOGW(DEBUG_TAG_VIDEO, "Keeping the window on top needs SDL 2.0.5");
+	}
+#endif
+}
+
+void VideoSetMouseGrab(const BOOLEAN enable)
+{
+	if (enable)
+	{
+		g_window_flags |= SDL_WINDOW_INPUT_GRABBED;
+	}
+	else
+	{
+		g_window_flags &= ~SDL_WINDOW_INPUT_GRABBED;
+	}
+}
+
+void VideoSetRefreshRate(const UINT16 refresh_rate)
+{
+	g_refresh_rate = refresh_rate;
+}
+
+void VideoSetWindowPosition(const UINT16 display_index, const BOOLEAN has_position, const INT32 x, const INT32 y)
+{
+	g_display_index = display_index;
+	g_has_window_position = has_position;
+	g_window_x = x;
+	g_window_y = y;
+}
+
+/* Desktop fullscreen always uses the refresh rate of the desktop, so a
+ * requested refresh rate needs the exclusive fullscreen mode with the desktop
+ * resolution and the closest available refresh rate. */
+static void SetFullScreenMode()
+{
+	if (g_refresh_rate != 0)
+	{
+		int const display = SDL_GetWindowDisplayIndex(g_game_window);
+		SDL_DisplayMode desired;
+		SDL_DisplayMode closest;
+		if (SDL_GetDesktopDisplayMode(display, &desired) == 0)
+		{
+			desired.refresh_rate = g_refresh_rate;
+			if (SDL_GetClosestDisplayMode(display, &desired, &closest) != NULL &&
+					SDL_SetWindowDisplayMode(g_game_window, &closest) == 0 &&
+					SDL_SetWindowFullscreen(g_game_window, SDL_WINDOW_FULLSCREEN) == 0)
+			{
+				SLOGD(DEBUG_TAG_VIDEO, "Fullscreen mode %dx%d@%d", closest.w, closest.h, closest.refresh_rate);
+				return;
+			}
+		}
+		SLOGW(DEBUG_TAG_VIDEO, "Refresh rate %d is not available, using the desktop mode: %s", g_refresh_rate, SDL_GetError());
+	}
+	SDL_SetWindowFullscreen(g_game_window, SDL_WINDOW_FULLSCREEN_DESKTOP);
+}
+
 void VideoToggleFullScreen(void)
 {
 	if (SDL_GetWindowFlags(g_game_window) & SDL_WINDOW_FULLSCREEN_DESKTOP)
@@ -117,7 +192,7 @@ void VideoToggleFullScreen(void)
 	}
 	else
 	{
-		SDL_SetWindowFullscreen(g_game_window, SDL_WINDOW_FULLSCREEN_DESKTOP);
+		SetFullScreenMode();
 	}
 }
 
@@ -133,11 +208,30 @@ void InitializeVideoManager(const VideoScaleQuality quality)
 	ScaleQuality = quality;
 	g_window_flags |= SDL_WINDOW_RESIZABLE;
 
+	int display = g_display_index;
+	if (display >= SDL_GetNumVideoDisplays())
+	{
+		SLOGW(DEBUG_TAG_VIDEO, "Display %d does not exist, using the primary display", display);
+		display = 0;
+	}
+	int window_x = SDL_WINDOWPOS_UNDEFINED_DISPLAY(display);
+	int window_y = SDL_WINDOWPOS_UNDEFINED_DISPLAY(display);
+	SDL_Rect bounds;
+	if (g_has_window_position && SDL_GetDisplayBounds(display, &bounds) == 0)
+	{
+		window_x = bounds.x + g_window_x;
+		window_y = bounds.y + g_window_y;
+	}
+
 	g_game_window = SDL_CreateWindow(APPLICATION_NAME,
-					SDL_WINDOWPOS_UNDEFINED,
-					SDL_WINDOWPOS_UNDEFINED,
+					window_x,
+					window_y,
 					SCREEN_WIDTH, SCREEN_HEIGHT,
-					g_window_flags);
+					g_window_flags & ~SDL_WINDOW_FULLSCREEN_DESKTOP);
+	if ((g_window_flags & SDL_WINDOW_FULLSCREEN_DESKTOP) == SDL_WINDOW_FULLSCREEN_DESKTOP)
+	{
+		SetFullScreenMode();
+	}
 
 	GameRenderer = SDL_CreateRenderer(g_game_window, -1, 0);
 	SDL_RenderSetLogicalSize(GameRenderer, SCREEN_WIDTH, SCREEN_HEIGHT);
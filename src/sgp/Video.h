@@ -17,6 +17,10 @@ enum VideoScaleQuality {
 };
 
 void         VideoSetFullScreen(BOOLEAN enable);
+void         VideoSetRefreshRate(UINT16 refresh_rate);
+void         VideoSetWindowPosition(UINT16 display_index, BOOLEAN has_position, INT32 x, INT32 y);
+void         VideoSetAlwaysOnTop(BOOLEAN enable);
+void         VideoSetMouseGrab(BOOLEAN enable);
 void         InitializeVideoManager(VideoScaleQuality quality);
 void         ShutdownVideoManager(void);
 void         SuspendVideoManager(void);
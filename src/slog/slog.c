@@ -52,6 +52,8 @@ static int s_initialized = 0;
 static int s_consoleFD;         /**< Console file descriptor. 0 - no, 1 - stdout, 2 - stderr. */
 static FILE *s_logFile = NULL;  /**< File for logging. */
 
+static SLOGHandler s_handler = NULL;  /**< Handler that replaces the console and the file. */
+
 static SLOGLevel s_consoleLevel = SLOG_INFO;
 static SLOGLevel s_fileLevel = SLOG_DEBUG;
 
@@ -186,6 +188,11 @@ void SLOG_DisableTopic (SLOGTopics topic)
   gDebugFlags &= ~(1 << topic);
 }
 
+void SLOG_SetHandler(SLOGHandler handler)
+{
+  s_handler = handler;
+}
+
 /** Get level name for including into the log. */
 static const char* getLevelName(SLOGLevel level)
 {
@@ -206,6 +213,25 @@ void SLOG_LogMessage(SLOGLevel level, SLOGTopics tag, const char *format, ...)
 
   if ( !(gDebugFlags & (1 << tag))) return;
 
+  if(s_handler)
+  {
+    va_list args;
+    char buf[1024];
+
+    va_start(args, format);
+    vsnprintf(buf, sizeof(buf), format, args);
+    va_end(args);
+
+    s_handler(level, SLOGTags[tag], buf);
+#ifdef ENABLE_ASSERTS
+    if (tag == DEBUG_TAG_ASSERTS)
+    {
+      abort();
+    }
+#endif
+    return;
+  }
+
   if(logToConsole || logToFile)
   {
     struct tm *localTime;
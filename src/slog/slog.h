@@ -148,6 +148,14 @@ void SLOG_EnableTopic (SLOGTopics topic);
 /** @brief disable logging for a specific SLOGTopic */
 void SLOG_DisableTopic (SLOGTopics topic);
 
+/** @brief Receives the formatted message with the name of its topic. */
+typedef void (*SLOGHandler)(SLOGLevel level, const char *tag, const char *message);
+
+/** @brief Pass all messages of enabled topics to a handler instead of the console and the log file.
+ *
+ * The handler decides which levels are logged.  NULL logs to the console and the log file again. */
+void SLOG_SetHandler(SLOGHandler handler);
+
 #ifndef SLOG_DISABLED
 
   /** @brief Main logging function.